@@ -0,0 +1,9 @@
+//! plaque as a shared library: the C API with the `ffi` feature, built with
+//! `cargo build -p plaque-bindings --features ffi`, and the JavaScript
+//! bindings with `wasm-bindgen`, built with
+//! `wasm-pack build bindings -- --features wasm-bindgen`.
+
+#[cfg(feature = "ffi")]
+pub use plaque::ffi::*;
+#[cfg(feature = "wasm-bindgen")]
+pub use plaque::web::*;
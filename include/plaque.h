@@ -0,0 +1,153 @@
+#ifndef PLAQUE_H
+#define PLAQUE_H
+
+/* Generated with cbindgen from src/ffi.rs, don't edit by hand. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+// The instruction pointer before the first instruction
+#define PLAQUE_AT_START -1
+
+// The instruction pointer after the last instruction
+#define PLAQUE_AT_END -2
+
+// What a call did, or why it stopped
+typedef enum PlaqueStatus {
+  // It did what it was asked and the program can go on
+  PLAQUE_OK = 0,
+  // The program stopped at a breakpoint
+  PLAQUE_BREAKPOINT = 1,
+  // The program needs input, given with `plaque_push_input`
+  PLAQUE_NEEDS_INPUT = 2,
+  // The program ran past its last instruction
+  PLAQUE_FINISHED = 3,
+  // A run took as many steps as it was allowed
+  PLAQUE_OUT_OF_STEPS = 4,
+  // It failed, with a message from `plaque_last_error`
+  PLAQUE_ERROR = 5,
+} PlaqueStatus;
+
+// An engine running the overflow flavor, with breakpoints at instruction
+// indexes and the last error, opaque to C
+typedef struct PlaqueEngine PlaqueEngine;
+
+// Parse a program, with input instructions storing zero at the end of
+// input rather than asking for more if `zero_eof` isn't 0. Returns null if
+// `code` isn't UTF-8 or has a bracket nothing matches.
+//
+// # Safety
+//
+// `code` must be a nul-terminated string.
+PlaqueEngine *plaque_engine_new(const char *code, int32_t zero_eof);
+
+// Free an engine. Null is ignored.
+//
+// # Safety
+//
+// `engine` must be null or from `plaque_engine_new`, and not used again.
+void plaque_engine_free(PlaqueEngine *engine);
+
+// Run one instruction
+//
+// # Safety
+//
+// `engine` must be from `plaque_engine_new` and not yet freed.
+PlaqueStatus plaque_step(PlaqueEngine *engine);
+
+// Undo the last step
+//
+// # Safety
+//
+// `engine` must be from `plaque_engine_new` and not yet freed.
+PlaqueStatus plaque_undo(PlaqueEngine *engine);
+
+// Step until the program finishes, stops or has taken `max_steps` steps,
+// or with no limit if `max_steps` is 0. Getting past a breakpoint it's
+// stopped at first.
+//
+// # Safety
+//
+// `engine` must be from `plaque_engine_new` and not yet freed.
+PlaqueStatus plaque_run(PlaqueEngine *engine, size_t max_steps);
+
+// Start the program over, keeping the breakpoints
+//
+// # Safety
+//
+// `engine` must be from `plaque_engine_new` and not yet freed.
+void plaque_reset(PlaqueEngine *engine);
+
+// The value of a cell, with cells the program hasn't reached yet 0
+//
+// # Safety
+//
+// `engine` must be from `plaque_engine_new` and not yet freed.
+uint8_t plaque_cell_get(const PlaqueEngine *engine, size_t index);
+
+// Change a cell, which can be undone like a step
+//
+// # Safety
+//
+// `engine` must be from `plaque_engine_new` and not yet freed.
+PlaqueStatus plaque_cell_set(PlaqueEngine *engine, size_t index, uint8_t value);
+
+// # Safety
+//
+// `engine` must be from `plaque_engine_new` and not yet freed.
+size_t plaque_tape_pointer(const PlaqueEngine *engine);
+
+// The index of the next instruction to run, or `PLAQUE_AT_START` or
+// `PLAQUE_AT_END`
+//
+// # Safety
+//
+// `engine` must be from `plaque_engine_new` and not yet freed.
+intptr_t plaque_instruction_pointer(const PlaqueEngine *engine);
+
+// How many steps the program has taken
+//
+// # Safety
+//
+// `engine` must be from `plaque_engine_new` and not yet freed.
+size_t plaque_steps(const PlaqueEngine *engine);
+
+// The output so far, with its length stored in `len`. The bytes are only
+// valid until the engine is next changed.
+//
+// # Safety
+//
+// `engine` must be from `plaque_engine_new` and not yet freed, and `len`
+// must point to somewhere to store a `size_t`.
+const uint8_t *plaque_output(const PlaqueEngine *engine, size_t *len);
+
+// Give the program input, after any it has yet to read
+//
+// # Safety
+//
+// `engine` must be from `plaque_engine_new` and not yet freed, and `bytes`
+// must point to `len` bytes.
+void plaque_push_input(PlaqueEngine *engine, const uint8_t *bytes, size_t len);
+
+// # Safety
+//
+// `engine` must be from `plaque_engine_new` and not yet freed.
+void plaque_set_breakpoint(PlaqueEngine *engine, size_t index);
+
+// # Safety
+//
+// `engine` must be from `plaque_engine_new` and not yet freed.
+void plaque_clear_breakpoint(PlaqueEngine *engine, size_t index);
+
+// Why the last call returning `PlaqueError` failed, or null if the last
+// call didn't. The message is only valid until the engine is next changed.
+//
+// # Safety
+//
+// `engine` must be from `plaque_engine_new` and not yet freed.
+const char *plaque_last_error(const PlaqueEngine *engine);
+
+// The version of the crate, as a static nul-terminated string
+const char *plaque_version(void);
+
+#endif /* PLAQUE_H */
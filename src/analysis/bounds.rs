@@ -0,0 +1,236 @@
+use crate::analysis::{Warning, WarningKind};
+use crate::engine::TapeModel;
+use crate::instruction::Instruction;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// The furthest cells a program may reach either way, relative to the first
+/// cell. Either is `None` where no limit can be worked out, such as after a
+/// loop like `[>]` that moves a different distance each time.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TapeBounds {
+    pub min: Option<isize>,
+    pub max: Option<isize>,
+}
+
+/// The offsets the tape pointer may be at, each end `None` if unlimited
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Offsets {
+    low: Option<isize>,
+    high: Option<isize>,
+}
+
+impl Offsets {
+    fn shift(self, by: isize) -> Offsets {
+        Offsets {
+            low: self.low.map(|low| low + by),
+            high: self.high.map(|high| high + by),
+        }
+    }
+
+    fn union(self, other: Offsets) -> Offsets {
+        Offsets {
+            low: self.low.zip(other.low).map(|(a, b)| a.min(b)),
+            high: self.high.zip(other.high).map(|(a, b)| a.max(b)),
+        }
+    }
+}
+
+/// How far the pointer has been seen to go, with the first instruction to go
+/// past each limit that's checked for
+struct Reach {
+    bounds: TapeBounds,
+    size: Option<usize>,
+    first_underflow: Option<usize>,
+    first_overflow: Option<usize>,
+}
+
+impl Reach {
+    fn see(&mut self, offsets: Offsets, index: usize) {
+        self.bounds.min = self.bounds.min.zip(offsets.low).map(|(a, b)| a.min(b));
+        self.bounds.max = self.bounds.max.zip(offsets.high).map(|(a, b)| a.max(b));
+
+        if offsets.low.is_some_and(|low| low < 0) {
+            self.first_underflow.get_or_insert(index);
+        }
+        if let Some((high, size)) = offsets.high.zip(self.size) {
+            if high >= size as isize {
+                self.first_overflow.get_or_insert(index);
+            }
+        }
+    }
+}
+
+/// Work out how far left and right of the first cell a program may move the
+/// tape pointer, assuming every loop may run any number of times
+pub fn tape_bounds(instructions: &[Instruction]) -> TapeBounds {
+    reach(instructions, None).bounds
+}
+
+/// Warn where a program may move off the start of the tape, or past the end
+/// of a fixed size one. Moves that can't be placed, like any after a scan
+/// such as `[<]`, are never flagged.
+pub fn tape_warnings(instructions: &[Instruction], tape_model: TapeModel) -> Vec<Warning> {
+    let size = match tape_model {
+        TapeModel::Unbounded | TapeModel::Sparse => None,
+        TapeModel::Fixed(size) => Some(size),
+    };
+    let reach = reach(instructions, size);
+
+    let mut warnings = vec![];
+    if let Some(index) = reach.first_underflow {
+        warnings.push(Warning::at(WarningKind::TapeUnderflow, index));
+    }
+    if let Some(index) = reach.first_overflow {
+        warnings.push(Warning::at(WarningKind::TapeOverflow, index));
+    }
+    warnings.sort_by_key(|warning| warning.index);
+    warnings
+}
+
+fn reach(instructions: &[Instruction], size: Option<usize>) -> Reach {
+    let matching = super::match_brackets(instructions);
+    let mut reach = Reach {
+        bounds: TapeBounds {
+            min: Some(0),
+            max: Some(0),
+        },
+        size,
+        first_underflow: None,
+        first_overflow: None,
+    };
+    let start = Offsets {
+        low: Some(0),
+        high: Some(0),
+    };
+    walk(
+        instructions,
+        &matching,
+        0..instructions.len(),
+        start,
+        &mut reach,
+    );
+    reach
+}
+
+/// Follow the pointer through some instructions, starting at some offsets,
+/// giving the offsets it may end up at
+fn walk(
+    instructions: &[Instruction],
+    matching: &[Option<usize>],
+    range: Range<usize>,
+    mut offsets: Offsets,
+    reach: &mut Reach,
+) -> Offsets {
+    let mut index = range.start;
+    while index < range.end {
+        let instruction = &instructions[index];
+        match instruction.symbol {
+            _ if instruction.opens_loop() => {
+                let close = matching[index].unwrap_or(instructions.len());
+                let body = index + 1..close;
+                // a body that doesn't come back to where it started could
+                // leave the pointer anywhere that way after enough times round
+                match net_shift(instructions, matching, body.clone()) {
+                    Some(0) => {}
+                    Some(shift) if shift > 0 => offsets.high = None,
+                    Some(_) => offsets.low = None,
+                    None => {
+                        offsets.low = None;
+                        offsets.high = None;
+                    }
+                }
+                let after_body = walk(instructions, matching, body, offsets, reach);
+                offsets = offsets.union(after_body);
+                index = close;
+            }
+            '>' => {
+                offsets = offsets.shift(1);
+                reach.see(offsets, index);
+            }
+            '<' => {
+                offsets = offsets.shift(-1);
+                reach.see(offsets, index);
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+    offsets
+}
+
+/// How far some instructions always move the pointer, if it's always the
+/// same distance
+fn net_shift(
+    instructions: &[Instruction],
+    matching: &[Option<usize>],
+    range: Range<usize>,
+) -> Option<isize> {
+    let mut shift = 0;
+    let mut index = range.start;
+    while index < range.end {
+        let instruction = &instructions[index];
+        match instruction.symbol {
+            _ if instruction.opens_loop() => {
+                let close = matching[index].unwrap_or(instructions.len());
+                if net_shift(instructions, matching, index + 1..close)? != 0 {
+                    return None;
+                }
+                index = close;
+            }
+            '>' => shift += 1,
+            '<' => shift -= 1,
+            _ => {}
+        }
+        index += 1;
+    }
+    Some(shift)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    fn bounds(code: &str) -> (Option<isize>, Option<isize>) {
+        let bounds = tape_bounds(&overflow::instruction_set().parse(code));
+        (bounds.min, bounds.max)
+    }
+
+    fn warnings(code: &str, tape_model: TapeModel) -> Vec<(WarningKind, usize)> {
+        tape_warnings(&overflow::instruction_set().parse(code), tape_model)
+            .into_iter()
+            .map(|warning| (warning.kind, warning.index))
+            .collect()
+    }
+
+    #[test]
+    fn balanced_loops_have_bounds() {
+        assert_eq!(bounds(">>+<<"), (Some(0), Some(2)));
+        assert_eq!(bounds("++[>+++[>+<-]<-]>>."), (Some(0), Some(2)));
+        assert_eq!(bounds("<"), (Some(-1), Some(0)));
+    }
+
+    #[test]
+    fn unbalanced_loops_are_unbounded_their_way() {
+        assert_eq!(bounds(">>+[<]>>>"), (None, Some(5)));
+        assert_eq!(bounds("+[>+]"), (Some(0), None));
+        assert_eq!(bounds("+[[>]<<]"), (None, None));
+    }
+
+    #[test]
+    fn moves_off_the_tape_are_flagged() {
+        assert_eq!(
+            warnings("+>[<<+>>-]", TapeModel::Unbounded),
+            vec![(WarningKind::TapeUnderflow, 4)]
+        );
+        assert_eq!(
+            warnings(">>>", TapeModel::Fixed(3)),
+            vec![(WarningKind::TapeOverflow, 2)]
+        );
+        assert_eq!(warnings(">>+[<]", TapeModel::Fixed(3)), vec![]);
+        assert_eq!(warnings("+[>]", TapeModel::Fixed(1)), vec![]);
+    }
+}
@@ -0,0 +1,313 @@
+use crate::instruction::{Instruction, InstructionKind, InstructionSet};
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::ops::Range;
+
+/// How many symbols of a block its label shows
+const LABEL_SYMBOLS: usize = 16;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NodeKind {
+    /// Instructions that run one after the other, with no brackets
+    Block,
+    /// An instruction opening a loop, such as `[`, skipping it when the cell
+    /// is zero
+    LoopStart,
+    /// An instruction closing a loop, such as `]`, going round it again when
+    /// the cell is nonzero
+    LoopEnd,
+    /// Past the last instruction
+    Exit,
+}
+
+/// Where a node's instructions are in the source, with the line and column
+/// of the first counting from 1
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SourceSpan {
+    pub bytes: Range<usize>,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub instructions: Range<usize>,
+    /// The node's instructions' symbols
+    pub code: String,
+    /// Set by `Cfg::with_source`
+    pub source: Option<SourceSpan>,
+}
+
+/// When an edge is taken, by the current cell
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Condition {
+    Always,
+    Zero,
+    Nonzero,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub condition: Condition,
+}
+
+/// A program's control flow: runs of instructions, the brackets between
+/// them and where each can go next. Nodes are in program order, ending with
+/// the exit, and edges refer to them by index.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Cfg {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+/// Split a program into a control flow graph. Unmatched brackets can't jump
+/// anywhere, so they're left in blocks like any other instruction.
+pub fn cfg(instructions: &[Instruction]) -> Cfg {
+    let matching = super::match_brackets(instructions);
+    let mut nodes = vec![];
+    // the node each instruction is in
+    let mut node_of = vec![0; instructions.len() + 1];
+
+    let mut i = 0;
+    while i < instructions.len() {
+        let kind = match (instructions[i].kind, matching[i]) {
+            (InstructionKind::OpenLoop, Some(_)) => NodeKind::LoopStart,
+            (InstructionKind::CloseLoop, Some(_)) => NodeKind::LoopEnd,
+            _ => NodeKind::Block,
+        };
+        let end = match kind {
+            NodeKind::Block => (i..instructions.len())
+                .find(|&j| matching[j].is_some())
+                .unwrap_or(instructions.len()),
+            _ => i + 1,
+        };
+        node_of[i..end].fill(nodes.len());
+        nodes.push(Node {
+            kind,
+            instructions: i..end,
+            code: instructions[i..end].iter().map(|i| i.symbol).collect(),
+            source: None,
+        });
+        i = end;
+    }
+    node_of[instructions.len()] = nodes.len();
+    nodes.push(Node {
+        kind: NodeKind::Exit,
+        instructions: instructions.len()..instructions.len(),
+        code: String::new(),
+        source: None,
+    });
+
+    let mut edges = vec![];
+    for (from, node) in nodes.iter().enumerate() {
+        let next = node_of[node.instructions.end];
+        let edge = |to, condition| Edge {
+            from,
+            to,
+            condition,
+        };
+        match node.kind {
+            NodeKind::Block => edges.push(edge(next, Condition::Always)),
+            NodeKind::LoopStart | NodeKind::LoopEnd => {
+                let jump = node_of[matching[node.instructions.start].unwrap_or(0) + 1];
+                let (zero, nonzero) = match node.kind {
+                    NodeKind::LoopStart => (jump, next),
+                    _ => (next, jump),
+                };
+                edges.push(edge(nonzero, Condition::Nonzero));
+                edges.push(edge(zero, Condition::Zero));
+            }
+            NodeKind::Exit => {}
+        }
+    }
+
+    Cfg { nodes, edges }
+}
+
+impl Cfg {
+    /// Note where each node is in the source it was parsed from
+    pub fn with_source(mut self, source: &str, instruction_set: &InstructionSet) -> Cfg {
+        // the byte offset, line and column of each instruction
+        let mut positions = vec![];
+        let (mut line, mut column) = (1, 1);
+        for (offset, symbol) in source.char_indices() {
+            if instruction_set.get(symbol).is_some() {
+                positions.push((offset, symbol.len_utf8(), line, column));
+            }
+            match symbol {
+                '\n' => (line, column) = (line + 1, 1),
+                _ => column += 1,
+            }
+        }
+
+        for node in &mut self.nodes {
+            let Range { start, end } = node.instructions;
+            let (Some(first), Some(last)) = (positions.get(start), positions.get(end.max(1) - 1))
+            else {
+                continue;
+            };
+            if start < end {
+                node.source = Some(SourceSpan {
+                    bytes: first.0..last.0 + last.1,
+                    line: first.2,
+                    column: first.3,
+                });
+            }
+        }
+        self
+    }
+
+    /// The graph in Graphviz's DOT language, with each loop drawn as a box
+    /// around its body
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+        let mut depth = 1;
+        for (i, node) in self.nodes.iter().enumerate() {
+            if node.kind == NodeKind::LoopStart {
+                let _ = writeln!(
+                    dot,
+                    "{:indent$}subgraph cluster_{i} {{",
+                    "",
+                    indent = depth * 4
+                );
+                depth += 1;
+                let _ = writeln!(
+                    dot,
+                    "{:indent$}label=\"loop {}\";",
+                    "",
+                    node.instructions.start,
+                    indent = depth * 4
+                );
+            }
+
+            let code = match node.kind {
+                NodeKind::Exit => String::from("exit"),
+                _ if node.code.chars().count() > LABEL_SYMBOLS => {
+                    let code = node.code.chars().take(LABEL_SYMBOLS).collect::<String>();
+                    format!("{}...", escape(&code))
+                }
+                _ => escape(&node.code),
+            };
+            let mut label = format!("{code}\\n{:?}", node.instructions);
+            if let Some(source) = &node.source {
+                let _ = write!(label, "\\nline {}:{}", source.line, source.column);
+            }
+            let shape = match node.kind {
+                NodeKind::LoopStart | NodeKind::LoopEnd => ", shape=diamond",
+                NodeKind::Exit => ", shape=oval",
+                NodeKind::Block => "",
+            };
+            let _ = writeln!(
+                dot,
+                "{:indent$}n{i} [label=\"{label}\"{shape}];",
+                "",
+                indent = depth * 4
+            );
+
+            if node.kind == NodeKind::LoopEnd {
+                depth -= 1;
+                let _ = writeln!(dot, "{:indent$}}}", "", indent = depth * 4);
+            }
+        }
+
+        for edge in &self.edges {
+            let label = match edge.condition {
+                Condition::Always => "",
+                Condition::Zero => " [label=\"0\"]",
+                Condition::Nonzero => " [label=\"not 0\"]",
+            };
+            let _ = writeln!(dot, "    n{} -> n{}{label};", edge.from, edge.to);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn escape(code: &str) -> String {
+    code.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    fn graph(code: &str) -> Cfg {
+        let instruction_set = overflow::instruction_set();
+        cfg(&instruction_set.parse(code)).with_source(code, &instruction_set)
+    }
+
+    #[test]
+    fn loops_split_the_program() {
+        let cfg = graph("++[->+<]>.");
+        let nodes = cfg
+            .nodes
+            .iter()
+            .map(|node| (node.kind, node.code.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            nodes,
+            [
+                (NodeKind::Block, "++"),
+                (NodeKind::LoopStart, "["),
+                (NodeKind::Block, "->+<"),
+                (NodeKind::LoopEnd, "]"),
+                (NodeKind::Block, ">."),
+                (NodeKind::Exit, ""),
+            ]
+        );
+        let edges = cfg
+            .edges
+            .iter()
+            .map(|edge| (edge.from, edge.to, edge.condition))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            edges,
+            [
+                (0, 1, Condition::Always),
+                (1, 2, Condition::Nonzero),
+                (1, 4, Condition::Zero),
+                (2, 3, Condition::Always),
+                (3, 2, Condition::Nonzero),
+                (3, 4, Condition::Zero),
+                (4, 5, Condition::Always),
+            ]
+        );
+    }
+
+    #[test]
+    fn nodes_know_where_they_are_in_the_source() {
+        let cfg = graph("+ add one\n[-]");
+        assert_eq!(
+            cfg.nodes[2].source,
+            Some(SourceSpan {
+                bytes: 11..12,
+                line: 2,
+                column: 2
+            })
+        );
+        assert_eq!(cfg.nodes[4].source, None);
+
+        let unmatched = graph("+]");
+        assert_eq!(unmatched.nodes.len(), 2);
+        assert_eq!(unmatched.nodes[0].kind, NodeKind::Block);
+    }
+
+    #[test]
+    fn dot_draws_loops_as_clusters() {
+        let dot = graph("+[[-]>]").to_dot();
+        assert!(dot.starts_with("digraph cfg {"));
+        assert!(dot.contains("subgraph cluster_1 {"));
+        assert!(dot.contains("        subgraph cluster_2 {"));
+        assert!(dot.contains("n2 [label=\"[\\n2..3\\nline 1:3\", shape=diamond];"));
+        assert!(dot.contains("n1 -> n7 [label=\"0\"];"));
+        assert_eq!(dot.matches('{').count(), dot.matches('}').count());
+    }
+}
@@ -0,0 +1,336 @@
+pub mod bounds;
+pub mod cfg;
+pub mod stats;
+pub mod values;
+
+use crate::instruction::{Instruction, InstructionKind};
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Severity {
+    /// The program can't run as written
+    Error,
+    /// The program runs, but probably not as meant
+    Warning,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WarningKind {
+    /// A bracket with nothing to match it
+    UnmatchedBracket,
+    /// A loop whose cell is provably zero whenever it's reached
+    DeadLoop,
+    /// A `[]` loop at a cell that may be nonzero, which never terminates
+    /// once entered
+    EmptyLoop,
+    /// An input instruction reached after all provided input has been read
+    InputExhausted,
+    /// A move left of the first cell
+    TapeUnderflow,
+    /// A move past the end of a fixed size tape
+    TapeOverflow,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub index: usize,
+    /// The instructions the warning covers, such as the whole of a dead loop
+    pub span: Range<usize>,
+}
+
+impl Warning {
+    fn at(kind: WarningKind, index: usize) -> Warning {
+        Warning {
+            kind,
+            index,
+            span: index..index + 1,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self.kind {
+            WarningKind::UnmatchedBracket => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self.kind {
+            WarningKind::UnmatchedBracket => "unmatched bracket",
+            WarningKind::DeadLoop => "loop starts at a cell that is always zero, so never runs",
+            WarningKind::EmptyLoop => "empty loop never terminates if entered",
+            WarningKind::InputExhausted => "input is read after all provided input is used up",
+            WarningKind::TapeUnderflow => "may move the tape pointer before the first cell",
+            WarningKind::TapeOverflow => "may move the tape pointer past the end of the tape",
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "instruction {}: {}", self.index, self.message())
+    }
+}
+
+/// What is statically known about the tape at some point in the program,
+/// relative to wherever the pointer was when the knowledge was last reset.
+struct KnownTape {
+    offset: isize,
+    cells: BTreeMap<isize, Option<u8>>,
+    untouched: Option<u8>,
+}
+
+impl KnownTape {
+    fn start() -> KnownTape {
+        KnownTape {
+            offset: 0,
+            cells: BTreeMap::new(),
+            untouched: Some(0),
+        }
+    }
+
+    fn unknown() -> KnownTape {
+        KnownTape {
+            offset: 0,
+            cells: BTreeMap::new(),
+            untouched: None,
+        }
+    }
+
+    fn cell(&self) -> Option<u8> {
+        self.cells
+            .get(&self.offset)
+            .copied()
+            .unwrap_or(self.untouched)
+    }
+
+    fn set_cell(&mut self, value: Option<u8>) {
+        self.cells.insert(self.offset, value);
+    }
+}
+
+/// Warn about unmatched brackets, and patterns that almost always indicate a
+/// mistake. The patterns aren't errors: the program still runs exactly as
+/// written. Warnings are in order of where they are.
+pub fn sanity_warnings(instructions: &[Instruction], input_length: Option<usize>) -> Vec<Warning> {
+    let mut warnings = unmatched_brackets(instructions)
+        .into_iter()
+        .map(|index| Warning::at(WarningKind::UnmatchedBracket, index))
+        .collect::<Vec<_>>();
+    let matching = match_brackets(instructions);
+    let mut tape = KnownTape::start();
+    let mut depth = 0;
+    let mut inputs = 0;
+    let mut index = 0;
+
+    while index < instructions.len() {
+        let instruction = &instructions[index];
+
+        if instruction.opens_loop()
+            && tape.cell() != Some(0)
+            && instructions.get(index + 1).is_some_and(Instruction::closes_loop)
+        {
+            warnings.push(Warning::at(WarningKind::EmptyLoop, index));
+        }
+
+        match instruction.kind {
+            InstructionKind::OpenLoop if tape.cell() == Some(0) => {
+                match matching[index] {
+                    // the body is skipped entirely, so what's known still holds
+                    Some(close) => {
+                        warnings.push(Warning {
+                            kind: WarningKind::DeadLoop,
+                            index,
+                            span: index..close + 1,
+                        });
+                        index = close;
+                    }
+                    None => break,
+                }
+            }
+            InstructionKind::OpenLoop => {
+                depth += 1;
+                tape = KnownTape::unknown();
+            }
+            InstructionKind::CloseLoop => {
+                depth = core::cmp::max(depth, 1) - 1;
+                tape = KnownTape::unknown();
+                tape.set_cell(Some(0));
+            }
+            InstructionKind::Debug => {}
+            _ => match instruction.symbol {
+                '>' => tape.offset += 1,
+                '<' => tape.offset -= 1,
+                '+' => tape.set_cell(tape.cell().map(|cell| cell.wrapping_add(1))),
+                '-' => tape.set_cell(tape.cell().map(|cell| cell.wrapping_sub(1))),
+                '.' => {}
+                ',' => {
+                    if depth == 0 {
+                        if input_length == Some(inputs) {
+                            warnings.push(Warning::at(WarningKind::InputExhausted, index));
+                        }
+                        inputs += 1;
+                    }
+                    tape.set_cell(None);
+                }
+                _ => tape = KnownTape::unknown(),
+            },
+        }
+
+        index += 1;
+    }
+
+    warnings.sort_by_key(|warning| warning.index);
+    warnings
+}
+
+/// Add the dead loops a deeper analysis found, like `CellValues::dead_loops`,
+/// to warnings from `sanity_warnings`. A loop that never runs can't hang, so
+/// any empty loop warning for the same loop goes.
+pub fn add_dead_loops(warnings: &mut Vec<Warning>, dead_loops: Vec<Warning>) {
+    for dead_loop in dead_loops {
+        warnings.retain(|warning| {
+            warning.index != dead_loop.index || warning.kind != WarningKind::EmptyLoop
+        });
+        if !warnings.contains(&dead_loop) {
+            warnings.push(dead_loop);
+        }
+    }
+    warnings.sort_by_key(|warning| warning.index);
+}
+
+/// The index of the bracket matching each bracket that has one, and `None`
+/// for everything else
+pub fn match_brackets(instructions: &[Instruction]) -> Vec<Option<usize>> {
+    match_kinds(instructions.iter().map(|instruction| instruction.kind))
+}
+
+/// `match_brackets` for anything made of instructions, such as nodes the
+/// optimizer has built from them
+pub(crate) fn match_kinds(kinds: impl IntoIterator<Item = InstructionKind>) -> Vec<Option<usize>> {
+    let mut matching = vec![];
+    let mut open = vec![];
+    for (i, kind) in kinds.into_iter().enumerate() {
+        matching.push(None);
+        match kind {
+            InstructionKind::OpenLoop => open.push(i),
+            InstructionKind::CloseLoop => {
+                if let Some(start) = open.pop() {
+                    matching[start] = Some(i);
+                    matching[i] = Some(start);
+                }
+            }
+            _ => {}
+        }
+    }
+    matching
+}
+
+/// The brackets with nothing to match them, in order
+pub fn unmatched_brackets(instructions: &[Instruction]) -> Vec<usize> {
+    let matching = match_brackets(instructions);
+    (instructions.iter().enumerate())
+        .filter(|&(i, instruction)| {
+            (instruction.opens_loop() || instruction.closes_loop()) && matching[i].is_none()
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    fn warnings(code: &str, input_length: Option<usize>) -> Vec<(WarningKind, usize)> {
+        let instructions = overflow::instruction_set().parse(code);
+        sanity_warnings(&instructions, input_length)
+            .into_iter()
+            .map(|warning| (warning.kind, warning.index))
+            .collect()
+    }
+
+    #[test]
+    fn loop_at_program_start_is_dead() {
+        assert_eq!(warnings("[-]+", None), vec![(WarningKind::DeadLoop, 0)]);
+    }
+
+    #[test]
+    fn loop_after_clear_is_dead() {
+        assert_eq!(warnings(",[-][.]", None), vec![(WarningKind::DeadLoop, 4)]);
+    }
+
+    #[test]
+    fn loop_at_nonzero_cell_is_fine() {
+        assert_eq!(warnings("++[->+<]>.", None), vec![]);
+        assert_eq!(warnings("+[-]>[-]", None), vec![]);
+    }
+
+    #[test]
+    fn empty_loop_is_flagged() {
+        assert_eq!(warnings("+[]", None), vec![(WarningKind::EmptyLoop, 1)]);
+    }
+
+    #[test]
+    fn empty_loop_at_zero_is_only_dead() {
+        assert_eq!(warnings("[]+", None), vec![(WarningKind::DeadLoop, 0)]);
+        assert_eq!(warnings(",[]", None), vec![(WarningKind::EmptyLoop, 1)]);
+    }
+
+    #[test]
+    fn deeper_dead_loops_replace_empty_loops() {
+        let instructions = overflow::instruction_set().parse("+>+[-<->]<[]");
+        let mut warnings = sanity_warnings(&instructions, None);
+        assert_eq!(warnings[0].kind, WarningKind::EmptyLoop);
+        let dead_loops = values::CellValues::analyze(&instructions).dead_loops(&instructions);
+        add_dead_loops(&mut warnings, dead_loops);
+        assert_eq!(
+            warnings,
+            vec![Warning {
+                kind: WarningKind::DeadLoop,
+                index: 10,
+                span: 10..12,
+            }]
+        );
+    }
+
+    #[test]
+    fn unmatched_brackets_are_errors() {
+        assert_eq!(
+            warnings("]+[[-]", None),
+            vec![
+                (WarningKind::UnmatchedBracket, 0),
+                (WarningKind::UnmatchedBracket, 2)
+            ]
+        );
+        let instructions = overflow::instruction_set().parse("[");
+        assert_eq!(
+            sanity_warnings(&instructions, None)[0].severity(),
+            Severity::Error
+        );
+    }
+
+    #[test]
+    fn dead_loops_span_the_whole_loop() {
+        let instructions = overflow::instruction_set().parse("+[-][>[-]<]");
+        let warning = &sanity_warnings(&instructions, None)[0];
+        assert_eq!(warning.kind, WarningKind::DeadLoop);
+        assert_eq!(warning.span, 4..11);
+    }
+
+    #[test]
+    fn input_past_provided_length_is_flagged() {
+        assert_eq!(
+            warnings(",.,.,.", Some(2)),
+            vec![(WarningKind::InputExhausted, 4)]
+        );
+        assert_eq!(warnings(",.,.,.", None), vec![]);
+        assert_eq!(warnings("+[,.]", Some(0)), vec![]);
+    }
+}
@@ -0,0 +1,76 @@
+use crate::instruction::{Instruction, InstructionKind};
+
+use alloc::collections::BTreeMap;
+
+/// Counts describing a program's shape, for comparing programs or versions
+/// of one
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Stats {
+    pub instructions: usize,
+    /// How many of each symbol there are
+    pub counts: BTreeMap<char, usize>,
+    /// The longest run of each symbol in a row
+    pub longest_runs: BTreeMap<char, usize>,
+    /// How many loops there are, counting each `[`
+    pub loops: usize,
+    /// How deeply the most nested loop is nested, 1 for a loop on its own
+    pub max_depth: usize,
+    /// How many instructions there would be with each run of a symbol
+    /// collapsed into one, as run-length encoding would
+    pub rle_size: usize,
+}
+
+pub fn stats(instructions: &[Instruction]) -> Stats {
+    let mut stats = Stats {
+        instructions: instructions.len(),
+        ..Stats::default()
+    };
+    let mut depth: usize = 0;
+    let mut run = (None, 0);
+
+    for instruction in instructions {
+        let symbol = instruction.symbol;
+        *stats.counts.entry(symbol).or_insert(0) += 1;
+
+        run = match run {
+            (Some(previous), length) if previous == symbol => (Some(symbol), length + 1),
+            _ => {
+                stats.rle_size += 1;
+                (Some(symbol), 1)
+            }
+        };
+        let longest = stats.longest_runs.entry(symbol).or_insert(0);
+        *longest = (*longest).max(run.1);
+
+        match instruction.kind {
+            InstructionKind::OpenLoop => {
+                stats.loops += 1;
+                depth += 1;
+                stats.max_depth = stats.max_depth.max(depth);
+            }
+            InstructionKind::CloseLoop => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    #[test]
+    fn stats_count_symbols_runs_and_loops() {
+        let instructions = overflow::instruction_set().parse("+++[>++[-]<-]>>>.+.");
+        let stats = stats(&instructions);
+
+        assert_eq!(stats.instructions, 19);
+        assert_eq!(stats.counts[&'+'], 6);
+        assert_eq!(stats.longest_runs[&'+'], 3);
+        assert_eq!(stats.longest_runs[&'>'], 3);
+        assert_eq!((stats.loops, stats.max_depth), (2, 2));
+        assert_eq!(stats.rle_size, 14);
+    }
+}
@@ -0,0 +1,311 @@
+use crate::analysis::{match_brackets, match_kinds, Warning, WarningKind};
+use crate::instruction::{Instruction, InstructionKind};
+use crate::ir::{self, Node, Op};
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// How many times round a loop its cells are worked out before giving up on
+/// knowing any of them, for loops that never settle like `[>+]`
+const LOOP_ROUNDS: usize = 8;
+
+/// What's known about the tape at one point in the program, each cell either
+/// a constant or `None` if it could be anything
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct State {
+    /// Whether `offset` and the keys of `cells` are from the first cell, or
+    /// only from some earlier point the pointer was at, such as after a scan
+    anchored: bool,
+    offset: isize,
+    cells: BTreeMap<isize, Option<u8>>,
+    untouched: Option<u8>,
+}
+
+impl State {
+    fn start() -> State {
+        State {
+            anchored: true,
+            offset: 0,
+            cells: BTreeMap::new(),
+            untouched: Some(0),
+        }
+    }
+
+    /// Knowing nothing at all, not even where the pointer is
+    fn unknown() -> State {
+        State {
+            anchored: false,
+            offset: 0,
+            cells: BTreeMap::new(),
+            untouched: None,
+        }
+    }
+
+    fn get(&self, offset: isize) -> Option<u8> {
+        self.cells.get(&offset).copied().unwrap_or(self.untouched)
+    }
+
+    fn cell(&self) -> Option<u8> {
+        self.get(self.offset)
+    }
+
+    fn set_cell(&mut self, value: Option<u8>) {
+        self.cells.insert(self.offset, value);
+    }
+
+    /// What's known either way. Where the two have the pointer in different
+    /// places, only what's known relative to the pointer is kept.
+    fn join(&self, other: &State) -> State {
+        if self.anchored && other.anchored && self.offset == other.offset {
+            return self.join_aligned(other);
+        }
+        let relative = |state: &State| State {
+            anchored: false,
+            offset: 0,
+            cells: state
+                .cells
+                .iter()
+                .map(|(&offset, &value)| (offset - state.offset, value))
+                .collect(),
+            untouched: state.untouched,
+        };
+        relative(self).join_aligned(&relative(other))
+    }
+
+    /// Join two states with their cells keyed the same way
+    fn join_aligned(&self, other: &State) -> State {
+        let join = |a: Option<u8>, b: Option<u8>| a.filter(|_| a == b);
+        let untouched = join(self.untouched, other.untouched);
+        let mut cells = self
+            .cells
+            .keys()
+            .chain(other.cells.keys())
+            .map(|&offset| (offset, join(self.get(offset), other.get(offset))))
+            .collect::<BTreeMap<_, _>>();
+        // cells the same as untouched ones are left out, so states that know
+        // the same compare equal
+        cells.retain(|_, value| *value != untouched);
+        State {
+            anchored: self.anchored,
+            offset: self.offset,
+            cells,
+            untouched,
+        }
+    }
+}
+
+/// Which constant each cell holds wherever in a program it's knowable,
+/// worked out by running the program over abstract values, with loops run
+/// until what's known at their start stops changing
+#[derive(Clone, Debug, Default)]
+pub struct CellValues {
+    /// What's known just before each instruction that starts a node, for
+    /// every instruction that can be reached
+    states: BTreeMap<usize, State>,
+}
+
+impl CellValues {
+    pub fn analyze(instructions: &[Instruction]) -> CellValues {
+        let nodes = ir::build(instructions);
+        let matching = match_kinds(nodes.iter().map(|node| match &node.op {
+            Op::Instruction(instruction) => instruction.kind,
+            _ => InstructionKind::Plain,
+        }));
+        let mut values = CellValues::default();
+        values.run(&nodes, &matching, 0..nodes.len(), State::start());
+        values
+    }
+
+    /// Whether the instruction at an index can ever run
+    pub fn reached(&self, index: usize) -> bool {
+        self.states.contains_key(&index)
+    }
+
+    /// The value a cell always has when the instruction at an index is
+    /// about to run, if it's the same every time
+    pub fn value(&self, index: usize, cell: usize) -> Option<u8> {
+        let state = self.states.get(&index)?;
+        state.anchored.then(|| state.get(cell as isize)).flatten()
+    }
+
+    /// The value the current cell always has when the instruction at an
+    /// index is about to run, wherever the pointer is
+    pub fn current_value(&self, index: usize) -> Option<u8> {
+        self.states.get(&index)?.cell()
+    }
+
+    /// Whether a cell could be nonzero when the instruction at an index is
+    /// about to run, such as to tell whether a loop there can be entered
+    pub fn may_be_nonzero(&self, index: usize, cell: usize) -> bool {
+        self.reached(index) && self.value(index, cell) != Some(0)
+    }
+
+    /// Every loop that can be reached but never entered, as the current
+    /// cell is always zero there
+    pub fn dead_loops(&self, instructions: &[Instruction]) -> Vec<Warning> {
+        let matching = match_brackets(instructions);
+        instructions
+            .iter()
+            .enumerate()
+            .filter(|&(index, instruction)| {
+                instruction.opens_loop() && self.current_value(index) == Some(0)
+            })
+            .filter_map(|(index, _)| {
+                Some(Warning {
+                    kind: WarningKind::DeadLoop,
+                    index,
+                    span: index..matching[index]? + 1,
+                })
+            })
+            .collect()
+    }
+
+    fn record(&mut self, index: usize, state: &State) {
+        let recorded = match self.states.get(&index) {
+            Some(recorded) => recorded.join(state),
+            None => state.clone(),
+        };
+        self.states.insert(index, recorded);
+    }
+
+    fn run(
+        &mut self,
+        nodes: &[Node],
+        matching: &[Option<usize>],
+        range: Range<usize>,
+        mut state: State,
+    ) -> State {
+        let mut index = range.start;
+        while index < range.end {
+            let node = &nodes[index];
+            self.record(node.origin, &state);
+            match &node.op {
+                Op::Add(amount) => {
+                    let amount = amount.rem_euclid(256) as u8;
+                    state.set_cell(state.cell().map(|cell| cell.wrapping_add(amount)));
+                }
+                Op::Move(offset) => state.offset += offset,
+                Op::Clear => state.set_cell(Some(0)),
+                Op::Transfer { targets, .. } => {
+                    let cell = state.cell();
+                    if cell != Some(0) {
+                        for &(offset, factor) in targets {
+                            let target = state.offset + offset;
+                            let value = state.get(target).zip(cell).map(|(target, cell)| {
+                                target.wrapping_add(cell.wrapping_mul(factor as u8))
+                            });
+                            state.cells.insert(target, value);
+                        }
+                        state.set_cell(Some(0));
+                    }
+                }
+                Op::Scan(_) => {
+                    if state.cell() != Some(0) {
+                        state = State::unknown();
+                        state.set_cell(Some(0));
+                    }
+                }
+                Op::Instruction(instruction) => match instruction.kind {
+                    InstructionKind::OpenLoop => {
+                        // loops that are never closed go on to the end
+                        let close = matching[index].unwrap_or(nodes.len());
+                        state = self.run_loop(nodes, matching, index + 1..close, state);
+                        index = close;
+                    }
+                    InstructionKind::CloseLoop | InstructionKind::Debug => {}
+                    _ => match instruction.symbol {
+                        ',' => state.set_cell(None),
+                        '.' => {}
+                        _ => state = State::unknown(),
+                    },
+                },
+            }
+            index += 1;
+        }
+        state
+    }
+
+    /// Run a loop's body until what's known at its start settles, giving
+    /// what's known once it's done
+    fn run_loop(
+        &mut self,
+        nodes: &[Node],
+        matching: &[Option<usize>],
+        body: Range<usize>,
+        entry: State,
+    ) -> State {
+        if entry.cell() == Some(0) {
+            return entry;
+        }
+
+        // the loop can only finish without going round at all if it might
+        // be skipped, so otherwise what's known after it is only what's
+        // known at the end of its body
+        let skippable = entry.cell().is_none();
+        let mut start = entry;
+        let mut rounds = 0;
+        loop {
+            let end = self.run(nodes, matching, body.clone(), start.clone());
+            if let Some(close) = nodes.get(body.end) {
+                self.record(close.origin, &end);
+            }
+            let joined = start.join(&end);
+            if joined == start {
+                let mut exit = match skippable {
+                    true => joined,
+                    false => end,
+                };
+                exit.set_cell(Some(0));
+                return exit;
+            }
+            rounds += 1;
+            start = match rounds < LOOP_ROUNDS {
+                true => joined,
+                false => State::unknown(),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    fn analyze(code: &str) -> (Vec<Instruction>, CellValues) {
+        let instructions = overflow::instruction_set().parse(code);
+        let values = CellValues::analyze(&instructions);
+        (instructions, values)
+    }
+
+    #[test]
+    fn constants_carry_through_loops() {
+        // cell 1 ends up 10 however many times round, and cell 2 is never
+        // touched
+        let (_, values) = analyze("++[>[-]++++++++++<-]>>[<]");
+        assert_eq!(values.value(22, 1), Some(10));
+        assert_eq!(values.value(22, 0), Some(0));
+        assert!(!values.may_be_nonzero(22, 2));
+        assert!(values.may_be_nonzero(3, 1));
+    }
+
+    #[test]
+    fn loops_that_move_lose_the_position() {
+        let (_, values) = analyze("+[>+]+[-]");
+        assert_eq!(values.value(5, 0), None);
+        assert_eq!(values.current_value(6), Some(1));
+    }
+
+    #[test]
+    fn dead_loops_are_found_past_other_loops() {
+        let (instructions, values) = analyze("+[>+<-]>>[.]<[-]");
+        let dead = values
+            .dead_loops(&instructions)
+            .into_iter()
+            .map(|warning| (warning.index, warning.span))
+            .collect::<Vec<_>>();
+        assert_eq!(dead, vec![(9, 9..12)]);
+        assert!(!values.reached(10));
+    }
+}
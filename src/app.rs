@@ -1,5 +1,7 @@
 use crate::editor;
-use crate::program::{Mode, Program};
+use crate::program::Mode;
+use crate::session;
+use crate::tabs::Tabs;
 use crate::ui;
 
 use anyhow::Result;
@@ -8,17 +10,20 @@ use crossterm::execute;
 use crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen};
 use std::sync::{
     mpsc,
-    mpsc::{Receiver, Sender},
+    mpsc::{Receiver, RecvTimeoutError, Sender},
     Arc, Mutex,
 };
 use std::thread;
 use std::time::Duration;
 use tui::{backend::CrosstermBackend, Terminal};
 
-type SharedState = Arc<Mutex<Program>>;
+type SharedState = Arc<Mutex<Tabs>>;
 
-pub fn run(program: Program) -> Result<()> {
-    let shared_state = Arc::new(Mutex::new(program));
+/// How long playing programs wait between steps
+const PLAY_TICK: Duration = Duration::from_millis(10);
+
+pub fn run(tabs: Tabs) -> Result<()> {
+    let shared_state = Arc::new(Mutex::new(tabs));
     let (tx_program, rx_program) = mpsc::channel::<KeyEvent>();
     let (tx_ui, rx_ui) = mpsc::channel::<()>();
 
@@ -44,12 +49,56 @@ pub fn spawn_program_thread(
     tx_ui: Sender<()>,
 ) {
     thread::spawn(move || loop {
-        if let Ok(event) = rx_program.recv() {
+        let received = rx_program.recv_timeout(PLAY_TICK);
+        if let Err(RecvTimeoutError::Timeout) = received {
+            let mut guard = shared_state.lock().unwrap();
+            for program in guard.programs.iter_mut() {
+                program.play();
+            }
+        }
+
+        if let Ok(event) = received {
             let mut guard = shared_state.lock().unwrap();
-            let program = &mut guard;
+            let tabs = &mut guard;
             let control = event.modifiers.contains(KeyModifiers::CONTROL);
             let shift = event.modifiers.contains(KeyModifiers::SHIFT);
 
+            if tabs.overview {
+                match event.code {
+                    KeyCode::Up | KeyCode::BackTab => tabs.prev(),
+                    KeyCode::Down | KeyCode::Tab => tabs.next(),
+                    KeyCode::Enter | KeyCode::Esc | KeyCode::Char('o') => tabs.toggle_overview(),
+                    KeyCode::Char('q') => {
+                        tx_ui.send(()).unwrap();
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            if tabs.active().is_interactive_mode() {
+                match event.code {
+                    KeyCode::Tab => {
+                        tabs.next();
+                        continue;
+                    }
+                    KeyCode::BackTab => {
+                        tabs.prev();
+                        continue;
+                    }
+                    KeyCode::Char('o') => {
+                        tabs.toggle_overview();
+                        continue;
+                    }
+                    KeyCode::Char(c @ '1'..='9') => {
+                        tabs.select(c as usize - '1' as usize);
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+
+            let program = tabs.active_mut();
             match program.mode {
                 Mode::Interactive => match event.code {
                     KeyCode::Char('e') => {
@@ -58,6 +107,41 @@ pub fn spawn_program_thread(
                     KeyCode::Char('x') => {
                         program.reset();
                     }
+                    KeyCode::Char(' ') => {
+                        program.toggle_playing();
+                    }
+                    KeyCode::Char('b') => {
+                        program.toggle_breakpoint();
+                    }
+                    KeyCode::Char('f') => {
+                        program.tape_view.paged = !program.tape_view.paged;
+                    }
+                    KeyCode::Char('c') => {
+                        program.tape_view.format = program.tape_view.format.next();
+                    }
+                    KeyCode::Char('l') => {
+                        program.jump_to_enclosing_loop();
+                    }
+                    KeyCode::Char('%') => {
+                        if let Err(e) = program.engine.goto_matching_bracket() {
+                            program.debug_messages.push(e.to_string());
+                        }
+                    }
+                    KeyCode::Char('/') => {
+                        program.enter_search_mode();
+                    }
+                    KeyCode::Char('p') => {
+                        let tape_pointer = program.engine.tape_pointer;
+                        program.tape_view.toggle_pin(tape_pointer);
+                    }
+                    KeyCode::Char('s') => {
+                        let path = session::default_path(program);
+                        let message = match session::save(program, &path) {
+                            Ok(()) => format!("session saved to {}", path.display()),
+                            Err(e) => format!("couldn't save session: {e}"),
+                        };
+                        program.debug_messages.push(message);
+                    }
                     KeyCode::Esc | KeyCode::Char('q') => {
                         tx_ui.send(()).unwrap();
                     }
@@ -70,6 +154,20 @@ pub fn spawn_program_thread(
                     KeyCode::Down => {
                         program.step_until_exception();
                     }
+                    KeyCode::Char('.') => {
+                        program.step_until_output();
+                    }
+                    KeyCode::Char('w') => {
+                        let tape_pointer = program.engine.tape_pointer;
+                        program.step_until_cell_changes(tape_pointer);
+                    }
+                    KeyCode::Char(',') => {
+                        program.undo_until_output_removed();
+                    }
+                    KeyCode::Char('W') => {
+                        let tape_pointer = program.engine.tape_pointer;
+                        program.undo_until_cell_changes(tape_pointer);
+                    }
                     KeyCode::Up => {
                         program.undo_until_exception();
                     }
@@ -119,6 +217,21 @@ pub fn spawn_program_thread(
                     }
                     _ => {}
                 },
+                Mode::Search => match event.code {
+                    KeyCode::Char(c) => {
+                        program.search_buffer.push(c);
+                    }
+                    KeyCode::Backspace => {
+                        program.search_buffer.pop();
+                    }
+                    KeyCode::Enter => {
+                        program.exit_search_mode(true);
+                    }
+                    KeyCode::Esc => {
+                        program.exit_search_mode(false);
+                    }
+                    _ => {}
+                },
                 Mode::Input => match event.code {
                     KeyCode::Char(c) => {
                         program.add_input(c);
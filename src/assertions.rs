@@ -0,0 +1,234 @@
+use crate::engine::{Engine, EngineError};
+use crate::instruction::{Instruction, InstructionKind, InstructionSet};
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// The symbol assertion instructions carry, which no flavor uses
+pub const SYMBOL: char = '?';
+
+/// What an assertion checks
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Subject {
+    /// The current cell
+    Cell,
+    /// The cell at an index
+    CellAt(usize),
+    /// The tape pointer
+    Pointer,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn holds(self, actual: usize, expected: usize) -> bool {
+        match self {
+            Comparison::Eq => actual == expected,
+            Comparison::Ne => actual != expected,
+            Comparison::Lt => actual < expected,
+            Comparison::Le => actual <= expected,
+            Comparison::Gt => actual > expected,
+            Comparison::Ge => actual >= expected,
+        }
+    }
+}
+
+/// A condition a program expects to hold when it reaches a point, such as
+/// `cell==72`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Assertion {
+    pub subject: Subject,
+    pub comparison: Comparison,
+    pub value: usize,
+    /// The assertion as written, for saying which one failed
+    pub text: String,
+}
+
+/// Parses `subject op value`, where the subject is `cell`, `cell[index]` or
+/// `ptr`, the op is one of `== != < <= > >=` and the value is a number, a
+/// `0x` hex number or a character in single quotes
+impl core::str::FromStr for Assertion {
+    type Err = String;
+
+    fn from_str(assertion: &str) -> Result<Assertion, String> {
+        let text = assertion.trim();
+        let invalid = || format!("invalid assertion {text}, expected e.g. cell==72");
+        let operators = [
+            ("==", Comparison::Eq),
+            ("!=", Comparison::Ne),
+            ("<=", Comparison::Le),
+            (">=", Comparison::Ge),
+            ("<", Comparison::Lt),
+            (">", Comparison::Gt),
+        ];
+        let (subject, comparison, value) = operators
+            .iter()
+            .find_map(|&(operator, comparison)| {
+                let (subject, value) = text.split_once(operator)?;
+                Some((subject.trim(), comparison, value.trim()))
+            })
+            .ok_or_else(invalid)?;
+
+        let subject = match subject {
+            "cell" => Subject::Cell,
+            "ptr" => Subject::Pointer,
+            _ => subject
+                .strip_prefix("cell[")
+                .and_then(|index| index.strip_suffix(']'))
+                .and_then(|index| index.trim().parse().ok())
+                .map(Subject::CellAt)
+                .ok_or_else(invalid)?,
+        };
+        let value = if let Some(hex) = value.strip_prefix("0x") {
+            usize::from_str_radix(hex, 16).ok()
+        } else if let Some(character) = value.strip_prefix('\'') {
+            let mut chars = character.strip_suffix('\'').unwrap_or("").chars();
+            match (chars.next(), chars.next()) {
+                (Some(character), None) => Some(character as usize),
+                _ => None,
+            }
+        } else {
+            value.parse().ok()
+        }
+        .ok_or_else(invalid)?;
+
+        Ok(Assertion {
+            subject,
+            comparison,
+            value,
+            text: String::from(text),
+        })
+    }
+}
+
+impl Assertion {
+    pub fn check(&self, engine: &Engine) -> Result<(), EngineError> {
+        let cell = |index| engine.tape.get(index).copied().unwrap_or(0) as usize;
+        let actual = match self.subject {
+            Subject::Cell => cell(engine.tape_pointer),
+            Subject::CellAt(index) => cell(index),
+            Subject::Pointer => engine.tape_pointer,
+        };
+        match self.comparison.holds(actual, self.value) {
+            true => Ok(()),
+            false => Err(EngineError::AssertionFailed {
+                assertion: self.text.clone(),
+                actual,
+            }),
+        }
+    }
+
+    /// An instruction that fails with `EngineError::AssertionFailed` when
+    /// the assertion doesn't hold, and otherwise does nothing
+    pub fn instruction(self) -> Instruction {
+        Instruction::new(
+            SYMBOL,
+            move |program| {
+                self.check(program)?;
+                program.next_instruction()
+            },
+            |program| program.prev_instruction(),
+        )
+        .with_kind(InstructionKind::Debug)
+    }
+}
+
+/// Parse a program like `InstructionSet::parse`, but with each
+/// `{assert ...}` in its comments an assertion instruction where it is
+pub fn parse(source: &str, instruction_set: &InstructionSet) -> Result<Vec<Instruction>, String> {
+    let mut instructions = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find("{assert") {
+        instructions.extend(instruction_set.parse(&rest[..start]));
+        let directive = &rest[start + "{assert".len()..];
+        let line = source.len() - rest.len() + start;
+        let line = source[..line].matches('\n').count() + 1;
+        let end = directive
+            .find('}')
+            .ok_or_else(|| format!("unclosed assertion on line {line}"))?;
+        let assertion = directive[..end]
+            .parse::<Assertion>()
+            .map_err(|error| format!("{error} on line {line}"))?;
+        instructions.push(assertion.instruction());
+        rest = &directive[end + 1..];
+    }
+    instructions.extend(instruction_set.parse(rest));
+    Ok(instructions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Exception;
+    use crate::flavor::overflow;
+    use alloc::vec;
+
+    fn run(code: &str) -> Result<(), Exception> {
+        let instructions = parse(code, &overflow::instruction_set()).unwrap();
+        let mut engine = Engine::new(instructions);
+        loop {
+            engine.step()?;
+            if engine.instruction_pointer == crate::engine::InstructionPointer::End {
+                return Ok(());
+            }
+        }
+    }
+
+    #[test]
+    fn assertions_parse_from_their_text() {
+        let assertion = "cell[3] >= 'A'".parse::<Assertion>().unwrap();
+        assert_eq!(assertion.subject, Subject::CellAt(3));
+        assert_eq!(assertion.comparison, Comparison::Ge);
+        assert_eq!(assertion.value, 65);
+        assert_eq!("ptr!=0x10".parse::<Assertion>().unwrap().value, 16);
+        assert!("tape==1".parse::<Assertion>().is_err());
+        assert!("cell=1".parse::<Assertion>().is_err());
+    }
+
+    #[test]
+    fn assertions_are_comments_that_check() {
+        let symbols = parse("+{assert cell==1}.", &overflow::instruction_set())
+            .unwrap()
+            .iter()
+            .map(|instruction| instruction.symbol)
+            .collect::<String>();
+        assert_eq!(symbols, "+?.");
+
+        assert_eq!(run("+++ {assert cell==3} > {assert ptr==1}"), Ok(()));
+        assert_eq!(
+            run("+{assert cell[0] == 1}\n+ {assert cell<2}"),
+            Err(Exception::Error(EngineError::AssertionFailed {
+                assertion: String::from("cell<2"),
+                actual: 2
+            }))
+        );
+        assert_eq!(
+            parse("+\n{assert cell", &overflow::instruction_set()).map(|_| ()),
+            Err(String::from("unclosed assertion on line 2"))
+        );
+    }
+
+    #[test]
+    fn failed_assertions_stop_where_they_are() {
+        let instructions = parse("+{assert cell==2}", &overflow::instruction_set()).unwrap();
+        let mut engine = Engine::new(instructions);
+        engine.step().unwrap();
+        engine.step().unwrap();
+        assert!(engine.step().is_err());
+        assert_eq!(
+            engine.instruction_pointer,
+            crate::engine::InstructionPointer::Index(1)
+        );
+        engine.undo().unwrap();
+        assert_eq!(engine.tape, vec![0]);
+    }
+}
@@ -0,0 +1,240 @@
+use crate::engine::{Engine, EngineError, Exception, InstructionPointer};
+use crate::instruction::Instruction;
+
+use alloc::vec::Vec;
+use core::fmt;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    Completed,
+    AwaitingInput,
+    OutOfSteps,
+    Error(EngineError),
+}
+
+impl Outcome {
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Outcome::OutOfSteps | Outcome::Error(_))
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Completed => write!(fmt, "completed"),
+            Outcome::AwaitingInput => write!(fmt, "ran out of input"),
+            Outcome::OutOfSteps => write!(fmt, "ran out of steps"),
+            Outcome::Error(error) => write!(fmt, "error: {error}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Budget {
+    /// Steps each run may take before it counts as a failure
+    pub steps: usize,
+    /// Runs the whole bisection may use before settling for what it has
+    pub runs: usize,
+}
+
+impl Default for Budget {
+    fn default() -> Budget {
+        Budget {
+            steps: 1_000_000,
+            runs: 1_000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BisectError {
+    FailingInputPasses,
+    PassingInputFails(Outcome),
+}
+
+impl fmt::Display for BisectError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BisectError::FailingInputPasses => write!(fmt, "the failing input doesn't fail"),
+            BisectError::PassingInputFails(outcome) => {
+                write!(fmt, "the passing input fails ({outcome})")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BisectError {}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Bisection {
+    /// The smallest failing input found
+    pub input: Vec<u8>,
+    pub outcome: Outcome,
+    /// The first byte of the failing input which, copied over the passing
+    /// input, turns it into a failing one
+    pub critical_index: Option<usize>,
+    pub runs: usize,
+    pub exhausted: bool,
+}
+
+/// Run a program on the given input until it completes, fails or the step
+/// budget runs out.
+pub fn run_outcome(instructions: &[Instruction], input: &[u8], steps: usize) -> Outcome {
+    let mut engine = Engine::new(instructions.to_vec());
+    engine.input = input.to_vec();
+
+    for _ in 0..steps {
+        if engine.instruction_pointer == InstructionPointer::End {
+            return Outcome::Completed;
+        }
+        match engine.step() {
+            Ok(()) | Err(Exception::Breakpoint) => {}
+            Err(Exception::RequestingInput) => return Outcome::AwaitingInput,
+            Err(Exception::Error(error)) => return Outcome::Error(error),
+        }
+    }
+
+    Outcome::OutOfSteps
+}
+
+struct Bisector<'a> {
+    instructions: &'a [Instruction],
+    budget: Budget,
+    runs: usize,
+}
+
+impl Bisector<'_> {
+    fn exhausted(&self) -> bool {
+        self.runs >= self.budget.runs
+    }
+
+    fn outcome(&mut self, input: &[u8]) -> Outcome {
+        self.runs += 1;
+        run_outcome(self.instructions, input, self.budget.steps)
+    }
+
+    fn fails(&mut self, input: &[u8]) -> bool {
+        self.outcome(input).is_failure()
+    }
+}
+
+/// Find a small input that still makes a program fail, given one input that
+/// fails (with an error, or by running out of steps) and one that doesn't.
+pub fn bisect(
+    instructions: &[Instruction],
+    failing: &[u8],
+    passing: &[u8],
+    budget: Budget,
+) -> Result<Bisection, BisectError> {
+    let mut bisector = Bisector {
+        instructions,
+        budget,
+        runs: 0,
+    };
+
+    if !bisector.fails(failing) {
+        return Err(BisectError::FailingInputPasses);
+    }
+    let passing_outcome = bisector.outcome(passing);
+    if passing_outcome.is_failure() {
+        return Err(BisectError::PassingInputFails(passing_outcome));
+    }
+
+    // splice ever more of the failing input over the passing input to find
+    // the first differing byte that's responsible for the failure
+    let common = failing
+        .iter()
+        .zip(passing)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let hybrid = |k: usize| {
+        let mut input = failing[..k].to_vec();
+        input.extend(passing.iter().skip(k));
+        input
+    };
+    let (mut low, mut high) = (common, failing.len());
+    while low < high && !bisector.exhausted() {
+        let mid = low + (high - low) / 2;
+        if bisector.fails(&hybrid(mid + 1)) {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    let critical_index = (low < failing.len()).then_some(low);
+
+    // then shrink the failing input, dropping ever smaller chunks of it
+    let mut input = failing.to_vec();
+    let mut chunk = core::cmp::max(input.len() / 2, 1);
+    while !input.is_empty() && !bisector.exhausted() {
+        let mut removed = false;
+        let mut start = 0;
+        while start < input.len() && !bisector.exhausted() {
+            let end = core::cmp::min(start + chunk, input.len());
+            let mut candidate = input[..start].to_vec();
+            candidate.extend_from_slice(&input[end..]);
+            if bisector.fails(&candidate) {
+                input = candidate;
+                removed = true;
+            } else {
+                start = end;
+            }
+        }
+        if chunk == 1 && !removed {
+            break;
+        }
+        if !removed {
+            chunk = core::cmp::max(chunk / 2, 1);
+        }
+    }
+
+    let exhausted = bisector.exhausted();
+    let outcome = run_outcome(instructions, &input, budget.steps);
+
+    Ok(Bisection {
+        input,
+        outcome,
+        critical_index,
+        runs: bisector.runs,
+        exhausted,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    // moves left once per input byte, so underflows on four or more bytes
+    fn instructions() -> Vec<Instruction> {
+        overflow::instruction_set().parse(">>>,[<,]")
+    }
+
+    #[test]
+    fn rejects_inputs_that_dont_fail() {
+        let result = bisect(&instructions(), b"ab", b"ab", Budget::default());
+        assert_eq!(result, Err(BisectError::FailingInputPasses));
+    }
+
+    #[test]
+    fn finds_small_failing_input() {
+        let bisection = bisect(&instructions(), b"abcdef", b"abc", Budget::default()).unwrap();
+
+        assert_eq!(bisection.input.len(), 4);
+        assert_eq!(bisection.critical_index, Some(3));
+        assert!(matches!(bisection.outcome, Outcome::Error(_)));
+    }
+
+    #[test]
+    fn stops_when_out_of_runs() {
+        let budget = Budget {
+            steps: 1_000,
+            runs: 3,
+        };
+        let bisection = bisect(&instructions(), b"abcdef", b"abc", budget).unwrap();
+
+        assert!(bisection.exhausted);
+        assert!(bisection.outcome.is_failure());
+    }
+}
@@ -0,0 +1,271 @@
+use crate::cli::Args;
+use crate::engine::history::HistoryPolicy;
+use crate::engine::{Exception, InstructionPointer};
+use crate::flavor::{overflow, Eof};
+use crate::instruction::InstructionSet;
+use crate::program::Program;
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+const DEFAULT_CONTROL_ADDRESS: &str = "127.0.0.1:0";
+const TAPE_WINDOW: usize = 16;
+
+struct Session {
+    program: Program,
+    paused: bool,
+    attached: usize,
+    pending_steps: Option<usize>,
+    finished: bool,
+    /// Steps taken, as no history is kept to count them
+    steps: usize,
+    /// Output bytes written to stdout, which are taken out of the engine
+    written: usize,
+}
+
+type SharedSession = Arc<(Mutex<Session>, Condvar)>;
+
+/// Run a program as a transparent stdin/stdout filter, with a JSON-RPC
+/// control socket that a debugger can attach to while the pipeline is live.
+/// The socket doesn't speak DAP. Once stdin ends, reading goes by `--eof`,
+/// which by default finishes the program rather than wait for input that
+/// won't come.
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    let [filepath] = args.positional() else {
+        return Err(anyhow!(
+            "usage: plaque attach-run <program> [--control <address>] \
+            [--eof request|zero|unchanged|max]\n\
+            the control socket takes JSON-RPC (pause, continue, step, state), not DAP"
+        ));
+    };
+    let eof: Eof = args.parsed("eof")?.unwrap_or_default();
+    let control_address = args.value("control").unwrap_or(DEFAULT_CONTROL_ADDRESS);
+    let mut program = Program::load(filepath, instruction_set)?;
+    // nothing over the control socket undoes steps, and a pipeline can run
+    // for as long as its input lasts
+    program.engine.history.set_policy(HistoryPolicy::Off);
+
+    let listener = TcpListener::bind(control_address)?;
+    eprintln!(
+        "plaque: control socket listening on {}",
+        listener.local_addr()?
+    );
+
+    let session = Arc::new((
+        Mutex::new(Session {
+            program,
+            paused: false,
+            attached: 0,
+            pending_steps: None,
+            finished: false,
+            steps: 0,
+            written: 0,
+        }),
+        Condvar::new(),
+    ));
+
+    spawn_control_thread(listener, session.clone());
+    let rx_stdin = spawn_stdin_thread();
+    filter_loop(session, rx_stdin, eof)
+}
+
+fn spawn_stdin_thread() -> Receiver<Vec<u8>> {
+    let (tx_stdin, rx_stdin) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdin = io::stdin().lock();
+        let mut buffer = [0; 4096];
+        loop {
+            match stdin.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if tx_stdin.send(buffer[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+    rx_stdin
+}
+
+fn spawn_control_thread(listener: TcpListener, session: SharedSession) {
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let session = session.clone();
+            thread::spawn(move || serve_client(stream, session));
+        }
+    });
+}
+
+fn filter_loop(session: SharedSession, rx_stdin: Receiver<Vec<u8>>, eof: Eof) -> Result<()> {
+    let (lock, resume) = &*session;
+    let mut stdout = io::stdout().lock();
+
+    loop {
+        let mut guard = lock.lock().unwrap();
+        while guard.paused {
+            guard = resume.wait(guard).unwrap();
+        }
+
+        if guard.program.engine.instruction_pointer == InstructionPointer::End {
+            guard.finished = true;
+            return Ok(());
+        }
+
+        let result = guard.program.engine.step();
+
+        // output is taken out of the engine as it's written, so it doesn't
+        // pile up over a long pipeline
+        let output = guard.program.engine.take_output();
+        if !output.is_empty() {
+            stdout.write_all(&output)?;
+            stdout.flush()?;
+            guard.written += output.len();
+        }
+
+        match result {
+            Ok(()) => guard.count_step(),
+            Err(Exception::Breakpoint) => {
+                guard.count_step();
+                if guard.attached > 0 {
+                    guard.paused = true;
+                }
+            }
+            Err(Exception::RequestingInput) => {
+                drop(guard);
+                match rx_stdin.recv() {
+                    Ok(bytes) => lock.lock().unwrap().program.engine.input.extend(bytes),
+                    Err(_) if eof == Eof::RequestInput => {
+                        lock.lock().unwrap().finished = true;
+                        return Ok(());
+                    }
+                    // stdin has ended, so reading from now on gets what the
+                    // EOF policy gives instead of waiting
+                    Err(_) => {
+                        let program = &mut lock.lock().unwrap().program;
+                        program.instruction_set.insert(overflow::input(eof));
+                        let inputs = (program.engine.instructions.iter_mut())
+                            .filter(|instruction| instruction.written_symbol() == Some(','));
+                        for instruction in inputs {
+                            *instruction = overflow::input(eof);
+                        }
+                    }
+                }
+            }
+            Err(Exception::Error(error)) => {
+                guard.finished = true;
+                return Err(error.into());
+            }
+        }
+    }
+}
+
+fn serve_client(stream: TcpStream, session: SharedSession) -> io::Result<()> {
+    let (lock, resume) = &*session;
+    lock.lock().unwrap().attached += 1;
+
+    let result = exchange_messages(stream, &session);
+
+    // never leave the pipeline stalled once the last debugger has gone
+    let mut guard = lock.lock().unwrap();
+    guard.attached -= 1;
+    if guard.attached == 0 {
+        guard.paused = false;
+        guard.pending_steps = None;
+        resume.notify_all();
+    }
+
+    result
+}
+
+fn exchange_messages(stream: TcpStream, session: &SharedSession) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let response = match serde_json::from_str::<Value>(&line?) {
+            Ok(request) => handle_request(&request, session),
+            Err(e) => error_response(Value::Null, -32700, format!("parse error: {e}")),
+        };
+        writeln!(writer, "{response}")?;
+    }
+    Ok(())
+}
+
+fn handle_request(request: &Value, session: &SharedSession) -> Value {
+    let (lock, resume) = &**session;
+    let id = request["id"].clone();
+    let mut guard = lock.lock().unwrap();
+
+    match request["method"].as_str() {
+        Some("pause") => {
+            guard.paused = true;
+            result_response(id, guard.state())
+        }
+        Some("continue") => {
+            guard.paused = false;
+            guard.pending_steps = None;
+            resume.notify_all();
+            result_response(id, guard.state())
+        }
+        Some("step") => {
+            let count = request["params"]["count"].as_u64().unwrap_or(1).max(1) as usize;
+            guard.paused = false;
+            guard.pending_steps = Some(count);
+            resume.notify_all();
+            result_response(id, json!({ "stepping": count }))
+        }
+        Some("state") => result_response(id, guard.state()),
+        Some(method) => error_response(id, -32601, format!("unknown method {method}")),
+        None => error_response(id, -32600, "missing method".to_string()),
+    }
+}
+
+fn result_response(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error_response(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+impl Session {
+    fn count_step(&mut self) {
+        self.steps += 1;
+        if let Some(remaining) = self.pending_steps {
+            if remaining <= 1 {
+                self.pending_steps = None;
+                self.paused = true;
+            } else {
+                self.pending_steps = Some(remaining - 1);
+            }
+        }
+    }
+
+    fn state(&self) -> Value {
+        let engine = &self.program.engine;
+        let instruction_pointer = match engine.instruction_pointer {
+            InstructionPointer::Start => json!("start"),
+            InstructionPointer::End => json!("end"),
+            InstructionPointer::Index(i) => json!(i),
+        };
+        let tape = engine.tape_window(engine.tape_pointer, TAPE_WINDOW / 2);
+
+        json!({
+            "paused": self.paused,
+            "finished": self.finished,
+            "steps": self.steps,
+            "instruction_pointer": instruction_pointer,
+            "instruction": engine.current_instruction().map(|i| i.symbol.to_string()),
+            "tape_pointer": engine.tape_pointer,
+            "tape_offset": tape.start,
+            "tape": tape.iter().map(|(_, cell)| cell).collect::<Vec<_>>(),
+            "output_length": self.written,
+            "input_buffered": engine.input.len(),
+        })
+    }
+}
@@ -0,0 +1,183 @@
+use crate::cli::Args;
+use crate::engine::history::HistoryPolicy;
+use crate::engine::input::InputSource;
+use crate::engine::{Engine, Exception, InstructionPointer};
+use crate::instruction::{Instruction, InstructionSet};
+use crate::ir;
+
+use anyhow::{anyhow, Result};
+use std::io::{self, Read};
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: plaque bench <program> [--runs <n>] [--steps <n>] \
+    [--backend interpret|optimized|jit|all] [--input <source>]";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Backend {
+    /// Stepping through the instructions as written
+    Interpret,
+    /// Stepping through the instructions the optimizer lowers them to
+    Optimized,
+    #[cfg(feature = "jit")]
+    Jit,
+}
+
+impl Backend {
+    const ALL: &'static [Backend] = &[
+        Backend::Interpret,
+        Backend::Optimized,
+        #[cfg(feature = "jit")]
+        Backend::Jit,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Backend::Interpret => "interpret",
+            Backend::Optimized => "optimized",
+            #[cfg(feature = "jit")]
+            Backend::Jit => "jit",
+        }
+    }
+}
+
+/// How one backend did over every run
+struct Measurement {
+    backend: Backend,
+    runs: usize,
+    elapsed: Duration,
+    /// Steps taken over every run, which the JIT doesn't count
+    steps: Option<usize>,
+    peak_tape: usize,
+    /// Whether a run was cut short by the step budget
+    budget_reached: bool,
+}
+
+/// Time running a program to the end, or to a step budget, several times
+/// over with each backend, for spotting performance regressions
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    let [filepath] = args.positional() else {
+        return Err(anyhow!(USAGE));
+    };
+    let runs = args.parsed::<usize>("runs")?.unwrap_or(10).max(1);
+    let budget = args.parsed::<usize>("steps")?;
+    let backends = match args.value("backend").unwrap_or("all") {
+        "all" => Backend::ALL.to_vec(),
+        name => vec![*Backend::ALL
+            .iter()
+            .find(|backend| backend.name() == name)
+            .ok_or_else(|| anyhow!("unknown backend {name}\n{USAGE}"))?],
+    };
+
+    let mut input = vec![];
+    match args.value("input") {
+        Some(source) => {
+            let source = source.parse::<InputSource>().map_err(anyhow::Error::msg)?;
+            input = source.read().map_err(anyhow::Error::msg)?;
+        }
+        None if atty::isnt(atty::Stream::Stdin) => {
+            io::stdin().read_to_end(&mut input)?;
+        }
+        None => {}
+    }
+
+    let instructions = instruction_set.parse(&std::fs::read_to_string(filepath)?);
+    println!(
+        "{:<10} {:>5} {:>12} {:>12} {:>14} {:>10}",
+        "backend", "runs", "total", "per run", "steps/second", "peak tape"
+    );
+    for backend in backends {
+        let measurement = measure(backend, &instructions, &input, runs, budget)?;
+        report(&measurement);
+    }
+
+    Ok(())
+}
+
+fn measure(
+    backend: Backend,
+    instructions: &[Instruction],
+    input: &[u8],
+    runs: usize,
+    budget: Option<usize>,
+) -> Result<Measurement> {
+    let instructions = match backend {
+        Backend::Optimized => ir::compile(instructions).instructions,
+        _ => instructions.to_vec(),
+    };
+    let mut measurement = Measurement {
+        backend,
+        runs,
+        elapsed: Duration::ZERO,
+        steps: Some(0),
+        peak_tape: 0,
+        budget_reached: false,
+    };
+
+    for _ in 0..runs {
+        let mut engine = Engine::new(instructions.clone());
+        engine.history.set_policy(HistoryPolicy::Off);
+        engine.input = input.to_vec();
+
+        let start = Instant::now();
+        let result = match backend {
+            #[cfg(feature = "jit")]
+            Backend::Jit => {
+                measurement.steps = None;
+                crate::jit::run(&mut engine, crate::flavor::Eof::default())
+            }
+            _ => step(&mut engine, budget),
+        };
+        measurement.elapsed += start.elapsed();
+
+        match result {
+            Ok(()) | Err(Exception::Breakpoint) => {}
+            Err(Exception::RequestingInput) => {
+                return Err(anyhow!("{}: the program needs more input", backend.name()))
+            }
+            Err(Exception::Error(error)) => return Err(anyhow!("{}: {error}", backend.name())),
+        }
+        measurement.steps = measurement.steps.map(|steps| steps + engine.history.len());
+        measurement.peak_tape = measurement.peak_tape.max(engine.tape.len());
+        measurement.budget_reached |= engine.instruction_pointer != InstructionPointer::End;
+    }
+
+    Ok(measurement)
+}
+
+/// Step to the end, or until a budget of steps is used up
+fn step(engine: &mut Engine, budget: Option<usize>) -> Result<(), Exception> {
+    while engine.instruction_pointer != InstructionPointer::End {
+        if budget.is_some_and(|budget| engine.history.len() >= budget) {
+            break;
+        }
+        match engine.step() {
+            Ok(()) | Err(Exception::Breakpoint) => {}
+            Err(exception) => return Err(exception),
+        }
+    }
+    Ok(())
+}
+
+fn report(measurement: &Measurement) {
+    let per_run = measurement.elapsed / measurement.runs as u32;
+    let steps_per_second = match measurement.steps {
+        Some(steps) if !measurement.elapsed.is_zero() => {
+            format!("{:.0}", steps as f64 / measurement.elapsed.as_secs_f64())
+        }
+        _ => String::from("-"),
+    };
+    println!(
+        "{:<10} {:>5} {:>12} {:>12} {:>14} {:>10}{}",
+        measurement.backend.name(),
+        measurement.runs,
+        format!("{:.2?}", measurement.elapsed),
+        format!("{:.2?}", per_run),
+        steps_per_second,
+        measurement.peak_tape,
+        match measurement.budget_reached {
+            true => "  (step budget reached)",
+            false => "",
+        }
+    );
+}
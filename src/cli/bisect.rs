@@ -0,0 +1,55 @@
+use crate::bisect::{self, Budget};
+use crate::cli::Args;
+use crate::instruction::InstructionSet;
+
+use anyhow::{anyhow, Result};
+
+const USAGE: &str =
+    "usage: plaque bisect <program> --fail <input> --pass <input> [--steps <n>] [--runs <n>]";
+
+/// Shrink an input that makes a program fail down to a minimal one
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    let [filepath] = args.positional() else {
+        return Err(anyhow!(USAGE));
+    };
+    let failing = std::fs::read(args.value("fail").ok_or_else(|| anyhow!(USAGE))?)?;
+    let passing = std::fs::read(args.value("pass").ok_or_else(|| anyhow!(USAGE))?)?;
+
+    let default = Budget::default();
+    let budget = Budget {
+        steps: args.parsed("steps")?.unwrap_or(default.steps),
+        runs: args.parsed("runs")?.unwrap_or(default.runs),
+    };
+
+    let instructions = instruction_set.parse(&std::fs::read_to_string(filepath)?);
+    let bisection = bisect::bisect(&instructions, &failing, &passing, budget)?;
+
+    println!(
+        "minimal failing input ({} bytes): \"{}\"",
+        bisection.input.len(),
+        escape(&bisection.input)
+    );
+    println!("outcome: {}", bisection.outcome);
+    if let Some(index) = bisection.critical_index {
+        println!(
+            "first responsible byte: {} (\"{}\")",
+            index,
+            escape(&failing[index..index + 1])
+        );
+    }
+    println!("runs: {}", bisection.runs);
+    if bisection.exhausted {
+        println!("run budget exhausted: the input may shrink further with more runs");
+    }
+
+    Ok(())
+}
+
+fn escape(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .flat_map(|&byte| std::ascii::escape_default(byte))
+        .map(char::from)
+        .collect()
+}
@@ -0,0 +1,16 @@
+use crate::analysis::cfg;
+use crate::instruction::InstructionSet;
+
+use anyhow::{anyhow, Result};
+
+/// Print a program's control flow graph as DOT, for Graphviz
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    let [filepath] = args else {
+        return Err(anyhow!("usage: plaque cfg <program>"));
+    };
+
+    let source = std::fs::read_to_string(filepath)?;
+    let graph = cfg::cfg(&instruction_set.parse(&source)).with_source(&source, &instruction_set);
+    print!("{}", graph.to_dot());
+    Ok(())
+}
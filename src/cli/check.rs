@@ -0,0 +1,71 @@
+use crate::analysis::values::CellValues;
+use crate::analysis::{self, Severity};
+use crate::instruction::InstructionSet;
+use crate::preprocess::SourceManager;
+use crate::program::Program;
+
+use anyhow::{anyhow, Result};
+
+/// Report load-time warnings for each program, reading any piped stdin as the
+/// input the programs will be given. With `--deep`, also works out cell values
+/// through loops to find dead loops the quick checks miss. With `--macros`,
+/// macros and includes are expanded first, and warnings point to where
+/// macros are invoked in whichever file that is.
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    let deep = args.iter().any(|arg| arg == "--deep");
+    let macros = args.iter().any(|arg| arg == "--macros");
+    let filepaths = args
+        .iter()
+        .filter(|arg| *arg != "--deep" && *arg != "--macros")
+        .collect::<Vec<_>>();
+    if filepaths.is_empty() {
+        return Err(anyhow!(
+            "usage: plaque check [--deep] [--macros] <program>..."
+        ));
+    }
+
+    let mut stdin = None;
+    for (i, filepath) in filepaths.into_iter().enumerate() {
+        let mut program = Program::load(filepath, instruction_set.clone())?;
+        if macros {
+            let mut sources = SourceManager::new();
+            let file = sources.load(filepath)?;
+            sources.expand(file).map_err(anyhow::Error::msg)?;
+            program.macros = true;
+            program.index_instructions();
+        }
+        if i == 0 {
+            program.read_stdin();
+            stdin = program.stdin.clone();
+        } else {
+            program.set_stdin(stdin.clone());
+        }
+        program.check();
+        if deep {
+            let values = CellValues::analyze(&program.engine.instructions);
+            let dead_loops = values.dead_loops(&program.engine.instructions);
+            analysis::add_dead_loops(&mut program.warnings, dead_loops);
+        }
+
+        for warning in &program.warnings {
+            let origin = program.instruction_origins[warning.index];
+            let file = match origin.file {
+                0 => filepath.as_str(),
+                file => program.sources.files()[file].name.as_str(),
+            };
+            println!(
+                "{}:{}:{}: {}: {}",
+                file,
+                origin.line + 1,
+                origin.column + 1,
+                match warning.severity() {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                },
+                warning.message()
+            );
+        }
+    }
+
+    Ok(())
+}
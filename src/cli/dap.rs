@@ -0,0 +1,546 @@
+use crate::cli::framed::{read_message, write_message};
+use crate::cli::Args;
+use crate::engine::labels::Label;
+use crate::engine::{Exception, InstructionPointer};
+use crate::instruction::InstructionSet;
+use crate::output::OutputDecoder;
+use crate::program::Program;
+use crate::watch::Expr;
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, VecDeque};
+use std::io::{self, StdoutLock};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+const THREAD_ID: u64 = 1;
+const TAPE_REFERENCE: u64 = 1;
+/// How many steps a running program takes between checks for a pause request
+const POLL_INTERVAL: usize = 4096;
+
+enum Stop {
+    Entry,
+    Step,
+    Pause,
+    Breakpoint,
+    Input,
+    Error(String),
+    Finished,
+}
+
+struct Connection {
+    writer: StdoutLock<'static>,
+    seq: u64,
+    requests: Receiver<Value>,
+    pending: VecDeque<Value>,
+}
+
+struct Session {
+    program: Program,
+    /// Zero-based lines to stop on
+    breakpoints: BTreeSet<usize>,
+    stop_on_entry: bool,
+    written: usize,
+}
+
+/// Serve the Debug Adapter Protocol over stdin and stdout, so that editors
+/// like VS Code can drive the debugger. There's a single thread, whose stack
+/// frames are the loops enclosing the current instruction, and the tape is
+/// its only scope.
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    if !args.positional().is_empty() {
+        return Err(anyhow!("usage: plaque dap"));
+    }
+
+    let mut connection = Connection {
+        writer: io::stdout().lock(),
+        seq: 0,
+        requests: spawn_request_thread(),
+        pending: VecDeque::new(),
+    };
+    let mut session = None;
+
+    while let Some(request) = connection.next_request() {
+        if !handle_request(&request, &mut connection, &mut session, &instruction_set)? {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+fn spawn_request_thread() -> Receiver<Value> {
+    let (tx_requests, rx_requests) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdin = io::stdin().lock();
+        loop {
+            match read_message(&mut stdin) {
+                Ok(Some(request)) => {
+                    if tx_requests.send(request).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    eprintln!("plaque: {e}");
+                    break;
+                }
+            }
+        }
+    });
+    rx_requests
+}
+
+/// Answer a request, returning whether to keep serving
+fn handle_request(
+    request: &Value,
+    connection: &mut Connection,
+    session: &mut Option<Session>,
+    instruction_set: &InstructionSet,
+) -> Result<bool> {
+    let arguments = &request["arguments"];
+    let command = request["command"].as_str().unwrap_or_default();
+
+    match (command, session.as_mut()) {
+        ("initialize", _) => connection.respond(
+            request,
+            json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsStepBack": true,
+                "supportsEvaluateForHovers": true,
+                "supportsSetVariable": true,
+            }),
+        )?,
+        ("launch", _) => {
+            let Some(path) = arguments["program"].as_str() else {
+                connection.fail(request, "launch needs a program")?;
+                return Ok(true);
+            };
+            let mut program = match Program::load(path, instruction_set.clone()) {
+                Ok(program) => program,
+                Err(e) => {
+                    connection.fail(request, &format!("can't load {path}: {e}"))?;
+                    return Ok(true);
+                }
+            };
+            if let Some(input) = arguments["input"].as_str() {
+                program.set_stdin(Some(input.as_bytes().to_vec()));
+            }
+            let labels = arguments["labels"].as_array().into_iter().flatten();
+            for label in labels.filter_map(Value::as_str) {
+                match label.parse::<Label>() {
+                    Ok(label) => program.engine.label_range(label.cells, label.name),
+                    Err(e) => {
+                        connection.fail(request, &e)?;
+                        return Ok(true);
+                    }
+                }
+            }
+
+            *session = Some(Session {
+                program,
+                breakpoints: BTreeSet::new(),
+                stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or(false),
+                written: 0,
+            });
+            connection.respond(request, Value::Null)?;
+            connection.event("initialized", Value::Null)?;
+        }
+        ("disconnect", _) => {
+            connection.respond(request, Value::Null)?;
+            return Ok(false);
+        }
+        ("threads", _) => connection.respond(
+            request,
+            json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }),
+        )?,
+        ("setBreakpoints", Some(session)) => {
+            let lines = arguments["breakpoints"]
+                .as_array()
+                .map(|breakpoints| {
+                    breakpoints
+                        .iter()
+                        .filter_map(|breakpoint| breakpoint["line"].as_u64())
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+
+            session.breakpoints = lines
+                .iter()
+                .filter(|&&line| line > 0)
+                .map(|&line| line as usize - 1)
+                .collect();
+            let breakpoints = lines
+                .iter()
+                .map(|&line| {
+                    let verified = line > 0 && session.has_instructions_on(line as usize - 1);
+                    json!({ "verified": verified, "line": line })
+                })
+                .collect::<Vec<_>>();
+            connection.respond(request, json!({ "breakpoints": breakpoints }))?;
+        }
+        ("configurationDone", Some(session)) => {
+            connection.respond(request, Value::Null)?;
+            if session.stop_on_entry {
+                session.report(connection, Stop::Entry)?;
+            } else if session.on_breakpoint_line() {
+                session.report(connection, Stop::Breakpoint)?;
+            } else {
+                session.resume(connection)?;
+            }
+        }
+        ("continue", Some(session)) => {
+            connection.respond(request, json!({ "allThreadsContinued": true }))?;
+            session.resume(connection)?;
+        }
+        ("next" | "stepIn", Some(session)) => {
+            connection.respond(request, Value::Null)?;
+            let stop = session.step().unwrap_or(Stop::Step);
+            session.report(connection, stop)?;
+        }
+        ("stepBack", Some(session)) => {
+            connection.respond(request, Value::Null)?;
+            let stop = session.undo().unwrap_or(Stop::Step);
+            session.report(connection, stop)?;
+        }
+        ("reverseContinue", Some(session)) => {
+            connection.respond(request, Value::Null)?;
+            session.rewind(connection)?;
+        }
+        ("pause", Some(session)) => {
+            // requests are only read between runs, so there's nothing to
+            // interrupt by now
+            connection.respond(request, Value::Null)?;
+            session.report(connection, Stop::Pause)?;
+        }
+        ("stackTrace", Some(session)) => connection.respond(request, session.stack_trace())?,
+        ("scopes", Some(_)) => connection.respond(
+            request,
+            json!({
+                "scopes": [{
+                    "name": "Tape",
+                    "variablesReference": TAPE_REFERENCE,
+                    "expensive": false,
+                }]
+            }),
+        )?,
+        ("variables", Some(session)) => {
+            let variables = match arguments["variablesReference"].as_u64() {
+                Some(TAPE_REFERENCE) => session.tape_variables(),
+                _ => vec![],
+            };
+            connection.respond(request, json!({ "variables": variables }))?;
+        }
+        ("setVariable", Some(session)) => {
+            let name = arguments["name"].as_str().unwrap_or_default();
+            let value = arguments["value"].as_str().unwrap_or_default();
+            match session.set_cell(name, value) {
+                Ok(value) => connection.respond(request, json!({ "value": value }))?,
+                Err(message) => connection.fail(request, &message)?,
+            }
+        }
+        ("evaluate", Some(session)) => {
+            let expression = arguments["expression"].as_str().unwrap_or_default();
+            match Expr::parse(expression).and_then(|expr| expr.eval(&session.program.engine)) {
+                Ok(value) => connection.respond(
+                    request,
+                    json!({ "result": value.to_string(), "variablesReference": 0 }),
+                )?,
+                Err(message) => connection.fail(request, &message)?,
+            }
+        }
+        (_, None) if !command.is_empty() => {
+            connection.fail(request, &format!("{command} needs a launched program"))?
+        }
+        _ => connection.fail(request, &format!("unsupported request {command}"))?,
+    }
+
+    Ok(true)
+}
+
+impl Connection {
+    fn next_request(&mut self) -> Option<Value> {
+        self.pending
+            .pop_front()
+            .or_else(|| self.requests.recv().ok())
+    }
+
+    /// Whether a pause request has arrived, keeping any other requests for
+    /// later
+    fn pause_requested(&mut self) -> bool {
+        while let Ok(request) = self.requests.try_recv() {
+            if request["command"].as_str() == Some("pause") {
+                self.respond(&request, Value::Null).ok();
+                return true;
+            }
+            self.pending.push_back(request);
+        }
+        false
+    }
+
+    fn respond(&mut self, request: &Value, body: Value) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"].clone(),
+            "command": request["command"].clone(),
+            "success": true,
+            "body": body,
+        }))
+    }
+
+    fn fail(&mut self, request: &Value, message: &str) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"].clone(),
+            "command": request["command"].clone(),
+            "success": false,
+            "message": message,
+        }))
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        if let Some(message) = message.as_object_mut() {
+            message.insert("seq".to_string(), json!(self.seq));
+        }
+
+        write_message(&mut self.writer, &message)
+    }
+}
+
+impl Session {
+    fn index(&self) -> Option<usize> {
+        match self.program.engine.instruction_pointer {
+            InstructionPointer::Index(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    fn line(&self) -> Option<usize> {
+        self.index()
+            .map(|i| self.program.instruction_positions[i].0)
+    }
+
+    fn has_instructions_on(&self, line: usize) -> bool {
+        self.program
+            .instruction_positions
+            .iter()
+            .any(|&(instruction_line, _)| instruction_line == line)
+    }
+
+    fn on_breakpoint_line(&self) -> bool {
+        self.line()
+            .is_some_and(|line| self.breakpoints.contains(&line))
+    }
+
+    /// Whether moving from an instruction to the current one should stop on a
+    /// breakpoint, which is when entering a breakpoint's line or jumping back
+    /// within it, so that a loop on a single line stops once per iteration
+    fn hits_breakpoint(&self, before: Option<usize>) -> bool {
+        let (Some(before), Some(after)) = (before, self.index()) else {
+            return false;
+        };
+        let positions = &self.program.instruction_positions;
+        self.on_breakpoint_line() && (positions[before].0 != positions[after].0 || after < before)
+    }
+
+    fn step(&mut self) -> Option<Stop> {
+        if self.program.engine.instruction_pointer == InstructionPointer::End {
+            return Some(Stop::Finished);
+        }
+
+        match self.program.engine.step() {
+            Ok(()) if self.program.engine.instruction_pointer == InstructionPointer::End => {
+                Some(Stop::Finished)
+            }
+            Ok(()) => None,
+            Err(Exception::Breakpoint) => Some(Stop::Breakpoint),
+            Err(Exception::RequestingInput) => Some(Stop::Input),
+            Err(Exception::Error(error)) => Some(Stop::Error(error.message())),
+        }
+    }
+
+    fn undo(&mut self) -> Option<Stop> {
+        if self.program.engine.history.is_empty() {
+            return Some(Stop::Entry);
+        }
+
+        match self.program.engine.undo() {
+            Ok(()) | Err(Exception::RequestingInput) => None,
+            Err(Exception::Breakpoint) => Some(Stop::Breakpoint),
+            Err(Exception::Error(error)) => Some(Stop::Error(error.message())),
+        }
+    }
+
+    fn resume(&mut self, connection: &mut Connection) -> Result<()> {
+        for steps in 1.. {
+            let before = self.index();
+            if let Some(stop) = self.step() {
+                return self.report(connection, stop);
+            }
+            if self.hits_breakpoint(before) {
+                return self.report(connection, Stop::Breakpoint);
+            }
+            if steps % POLL_INTERVAL == 0 && connection.pause_requested() {
+                return self.report(connection, Stop::Pause);
+            }
+        }
+        unreachable!()
+    }
+
+    fn rewind(&mut self, connection: &mut Connection) -> Result<()> {
+        for steps in 1.. {
+            let before = self.index();
+            if let Some(stop) = self.undo() {
+                return self.report(connection, stop);
+            }
+            if self.hits_breakpoint(before) {
+                return self.report(connection, Stop::Breakpoint);
+            }
+            if steps % POLL_INTERVAL == 0 && connection.pause_requested() {
+                return self.report(connection, Stop::Pause);
+            }
+        }
+        unreachable!()
+    }
+
+    fn report(&mut self, connection: &mut Connection, stop: Stop) -> Result<()> {
+        // output can't be taken back from the client, so only ever send the
+        // bytes past what has been sent so far
+        // output can't be taken back from the client, so only ever send the
+        // bytes past what has been sent so far, holding back a character
+        // that's cut off until the rest of it is written
+        let output = &self.program.engine.output;
+        let mut pending = 0;
+        if output.len() > self.written {
+            let decoded = OutputDecoder::Utf8.decode(&output[self.written..]);
+            if !decoded.text.is_empty() {
+                connection.event(
+                    "output",
+                    json!({ "category": "stdout", "output": decoded.text }),
+                )?;
+            }
+            pending = decoded.pending;
+        }
+        self.written = output.len() - pending;
+
+        let (reason, text) = match stop {
+            Stop::Finished => {
+                connection.event("exited", json!({ "exitCode": 0 }))?;
+                connection.event("terminated", Value::Null)?;
+                return Ok(());
+            }
+            Stop::Entry => ("entry", None),
+            Stop::Step => ("step", None),
+            Stop::Pause => ("pause", None),
+            Stop::Breakpoint => ("breakpoint", None),
+            Stop::Input => (
+                "exception",
+                Some("the program needs more input".to_string()),
+            ),
+            Stop::Error(message) => ("exception", Some(message)),
+        };
+        connection.event(
+            "stopped",
+            json!({
+                "reason": reason,
+                "text": text,
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+            }),
+        )?;
+        Ok(())
+    }
+
+    fn stack_trace(&self) -> Value {
+        let Some(index) = self.index() else {
+            return json!({ "stackFrames": [], "totalFrames": 0 });
+        };
+        let loops = self.program.engine.loop_stack();
+        let source = self.program.editor.filepath.as_ref().map(|path| {
+            json!({
+                "name": path.file_name().map(|name| name.to_string_lossy().into_owned()),
+                "path": path.to_string_lossy().into_owned(),
+            })
+        });
+
+        // each frame is inside the loop started by the next one down
+        let positions =
+            std::iter::once(index).chain(loops.iter().rev().map(|frame| frame.open_idx));
+        let frames = positions
+            .enumerate()
+            .map(|(depth, position)| {
+                let (line, column) = self.program.instruction_positions[position];
+                let name = match loops.len().checked_sub(depth + 1) {
+                    Some(enclosing) => {
+                        let frame = loops[enclosing];
+                        let (line, column) = self.program.instruction_positions[frame.open_idx];
+                        match frame.iteration_count {
+                            1 => format!("loop at {}:{}", line + 1, column + 1),
+                            n => format!("loop at {}:{} (iteration {n})", line + 1, column + 1),
+                        }
+                    }
+                    None => "program".to_string(),
+                };
+                json!({
+                    "id": depth,
+                    "name": name,
+                    "source": source.clone(),
+                    "line": line + 1,
+                    "column": column + 1,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({ "totalFrames": frames.len(), "stackFrames": frames })
+    }
+
+    /// Patch a tape cell from its variable's name, taking a number or a
+    /// quoted character, as a step that can be stepped back over
+    fn set_cell(&mut self, name: &str, value: &str) -> std::result::Result<String, String> {
+        let index = name
+            .strip_prefix('[')
+            .and_then(|name| name.split_once(']'))
+            .and_then(|(index, _)| index.parse::<usize>().ok())
+            .ok_or_else(|| format!("{name} isn't a tape cell"))?;
+        let value = value.trim();
+        let cell = match value.as_bytes() {
+            [b'\'', cell, b'\''] => *cell,
+            _ => value
+                .parse()
+                .map_err(|_| format!("{value} isn't a cell value, expected 0 to 255"))?,
+        };
+        match self.program.engine.write_tape(index, &[cell], true) {
+            Err(Exception::Error(error)) => Err(error.message()),
+            _ => Ok(cell.to_string()),
+        }
+    }
+
+    fn tape_variables(&self) -> Vec<Value> {
+        let engine = &self.program.engine;
+        let pointer = json!({
+            "name": "pointer",
+            "value": engine.tape_pointer.to_string(),
+            "variablesReference": 0,
+        });
+        let cells = engine.tape.iter().enumerate().map(|(i, &cell)| {
+            let value = if cell.is_ascii_graphic() {
+                format!("{cell} '{}'", cell as char)
+            } else {
+                cell.to_string()
+            };
+            let name = match engine.cell_name(i) {
+                Some(name) => format!("[{i}] {name}"),
+                None => format!("[{i}]"),
+            };
+            json!({ "name": name, "value": value, "variablesReference": 0 })
+        });
+        std::iter::once(pointer).chain(cells).collect()
+    }
+}
@@ -0,0 +1,74 @@
+use crate::cli::Args;
+use crate::engine::history::HistoryPolicy;
+use crate::engine::input::InputSource;
+use crate::engine::run::{StopReason, Stops};
+use crate::engine::Engine;
+use crate::instruction::InstructionSet;
+use crate::tape::CellFormat;
+
+use anyhow::{anyhow, Result};
+use std::io::{self, Read};
+
+const USAGE: &str = "usage: plaque dump <program> [--input <source>] [--range <start>..<end>] \
+    [--format <dec|hex|ascii|bin>] [--raw <file>]";
+
+/// Run a program to completion and print its tape like `xxd`, or with
+/// `--raw` write the cells as they are to a file for other tools. The tape
+/// is dumped even if the program fails, to see what it had done.
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    let [filepath] = args.positional() else {
+        return Err(anyhow!(USAGE));
+    };
+    let format = args
+        .value("format")
+        .map(str::parse::<CellFormat>)
+        .transpose()
+        .map_err(anyhow::Error::msg)?
+        .unwrap_or(CellFormat::Hex);
+
+    let mut engine = Engine::new(instruction_set.parse(&std::fs::read_to_string(filepath)?));
+    engine.history.set_policy(HistoryPolicy::Off);
+    match args.value("input") {
+        Some(input) => {
+            let input = input.parse::<InputSource>().map_err(anyhow::Error::msg)?;
+            engine.set_input(&input).map_err(anyhow::Error::msg)?;
+        }
+        None if !atty::is(atty::Stream::Stdin) => {
+            io::stdin().read_to_end(&mut engine.input)?;
+        }
+        None => {}
+    }
+
+    let stop = loop {
+        match engine.continue_(&Stops::default()) {
+            StopReason::Breakpoint(_) => {}
+            stop => break stop,
+        }
+    };
+
+    let range = match args.value("range") {
+        Some(range) => range
+            .split_once("..")
+            .and_then(|(start, end)| Some(start.parse().ok()?..end.parse().ok()?))
+            .ok_or_else(|| anyhow!("invalid value for --range: {range}"))?,
+        None => 0..engine.tape.len(),
+    };
+    match args.value("raw") {
+        Some(path) => {
+            let cells = range
+                .clone()
+                .map(|i| engine.tape.get(i).copied().unwrap_or(0))
+                .collect::<Vec<_>>();
+            std::fs::write(path, cells)?;
+            eprintln!("plaque: wrote {} cells to {path}", range.len());
+        }
+        None => print!("{}", engine.dump_tape(range, format)),
+    }
+
+    match stop {
+        StopReason::InputRequested => Err(anyhow!("the program needs more input")),
+        StopReason::Error(error) => Err(error.into()),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,45 @@
+use crate::cli::Args;
+use crate::format::{self, Style};
+use crate::instruction::InstructionSet;
+
+use anyhow::{anyhow, Result};
+
+const USAGE: &str = "usage: plaque fmt <program> [--style minify|reflow|indent] \
+    [--width <n>] [--indent <n>] [--write]";
+
+/// Print a program's code laid out in a style, without its comments, or
+/// write it back over the program with `--write`
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    let args = Args::parse(args, &["write"])?;
+    let [filepath] = args.positional() else {
+        return Err(anyhow!(USAGE));
+    };
+
+    let mut style = args
+        .value("style")
+        .map(|style| style.parse::<Style>())
+        .transpose()
+        .map_err(anyhow::Error::msg)?
+        .unwrap_or_default();
+    match &mut style {
+        Style::Minified => {}
+        Style::Reflowed { width } => *width = args.parsed("width")?.unwrap_or(*width),
+        Style::Indented { width, indent } => {
+            *width = args.parsed("width")?.unwrap_or(*width);
+            *indent = args.parsed("indent")?.unwrap_or(*indent);
+        }
+    }
+
+    let source = std::fs::read_to_string(filepath)?;
+    let mut formatted = format::format(&source, &instruction_set, style);
+    if !formatted.ends_with('\n') {
+        formatted.push('\n');
+    }
+    if args.switch("write") {
+        std::fs::write(filepath, formatted)?;
+    } else {
+        print!("{formatted}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+
+/// The next of a stream of JSON messages, each preceded by a
+/// `Content-Length` header as in the Debug Adapter and Language Server
+/// protocols, or `None` at the end of the stream
+pub fn read_message(reader: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = Some(value.trim().parse::<usize>()?);
+        }
+    }
+
+    let length = length.ok_or_else(|| anyhow!("message without a Content-Length header"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let message = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{message}", message.len())?;
+    writer.flush()
+}
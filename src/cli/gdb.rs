@@ -0,0 +1,384 @@
+use crate::cli::Args;
+use crate::engine::{Exception, InstructionPointer};
+use crate::instruction::InstructionSet;
+use crate::program::Program;
+
+use anyhow::{anyhow, Result};
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+const DEFAULT_LISTEN_ADDRESS: &str = "127.0.0.1:1234";
+/// How many steps a continuing program takes between checks for an interrupt
+const POLL_INTERVAL: usize = 4096;
+const INTERRUPT: u8 = 0x03;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?><!DOCTYPE target SYSTEM "gdb-target.dtd"><target version="1.0"><feature name="org.plaque.core"><reg name="pc" bitsize="64" type="code_ptr"/><reg name="tp" bitsize="64" type="uint64"/></feature></target>"#;
+
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+enum Incoming {
+    Packet(String),
+    Interrupt,
+}
+
+enum Stop {
+    Signal(u8),
+    Exited,
+}
+
+struct Stub {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    program: Program,
+    breakpoints: BTreeSet<usize>,
+    acknowledge: bool,
+    written: usize,
+}
+
+/// Serve a single gdb client over the remote serial protocol. The program
+/// counter (`pc`) is the instruction index and the only other register
+/// (`tp`) is the tape pointer; memory is the tape, and software breakpoints
+/// are set at instruction indexes.
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    let [filepath] = args.positional() else {
+        return Err(anyhow!("usage: plaque gdb <program> [--listen <address>]"));
+    };
+    let listen_address = args.value("listen").unwrap_or(DEFAULT_LISTEN_ADDRESS);
+    let program = Program::load(filepath, instruction_set)?;
+
+    let listener = TcpListener::bind(listen_address)?;
+    eprintln!("plaque: gdb stub listening on {}", listener.local_addr()?);
+    let (stream, _) = listener.accept()?;
+
+    let mut stub = Stub {
+        reader: BufReader::new(stream.try_clone()?),
+        writer: stream,
+        program,
+        breakpoints: BTreeSet::new(),
+        acknowledge: true,
+        written: 0,
+    };
+    stub.serve()?;
+    Ok(())
+}
+
+fn checksum(data: &str) -> u8 {
+    data.bytes().fold(0, u8::wrapping_add)
+}
+
+/// A register's value as gdb expects it, in target (little endian) byte order
+fn encode_register(value: usize) -> String {
+    (value as u64)
+        .to_le_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn stop_reply(stop: Stop) -> String {
+    match stop {
+        Stop::Signal(signal) => format!("S{signal:02x}"),
+        Stop::Exited => "W00".to_string(),
+    }
+}
+
+/// An `address,length` pair, as used by memory and breakpoint packets
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let (address, length) = range.split_once(',')?;
+    Some((
+        usize::from_str_radix(address, 16).ok()?,
+        usize::from_str_radix(length, 16).ok()?,
+    ))
+}
+
+impl Stub {
+    fn serve(&mut self) -> io::Result<()> {
+        while let Some(incoming) = self.receive()? {
+            let packet = match incoming {
+                Incoming::Packet(packet) => packet,
+                // nothing is running between packets, so there's nothing to
+                // interrupt
+                Incoming::Interrupt => continue,
+            };
+
+            let reply = match packet.as_str() {
+                "?" => stop_reply(Stop::Signal(SIGTRAP)),
+                "g" => {
+                    let registers = self.registers();
+                    registers.iter().map(|&value| encode_register(value)).collect()
+                }
+                "c" => {
+                    let stop = self.resume();
+                    stop_reply(stop)
+                }
+                "s" => {
+                    let stop = self.step().unwrap_or(Stop::Signal(SIGTRAP));
+                    stop_reply(stop)
+                }
+                "bc" => {
+                    let stop = self.rewind();
+                    stop_reply(stop)
+                }
+                "bs" => {
+                    let stop = self.undo().unwrap_or(Stop::Signal(SIGTRAP));
+                    stop_reply(stop)
+                }
+                "k" => return Ok(()),
+                "D" => {
+                    self.send("OK")?;
+                    return Ok(());
+                }
+                "qAttached" => "1".to_string(),
+                "qC" => "QC1".to_string(),
+                "qfThreadInfo" => "m1".to_string(),
+                "qsThreadInfo" => "l".to_string(),
+                "QStartNoAckMode" => {
+                    self.send("OK")?;
+                    self.acknowledge = false;
+                    continue;
+                }
+                packet if packet.starts_with("qSupported") => {
+                    "PacketSize=4000;qXfer:features:read+;QStartNoAckMode+;ReverseStep+;ReverseContinue+"
+                        .to_string()
+                }
+                packet if packet.starts_with('H') => "OK".to_string(),
+                packet => self.reply_with_arguments(packet),
+            };
+            self.send(&reply)?;
+        }
+        Ok(())
+    }
+
+    /// Replies to the packets that carry arguments, or an empty reply for
+    /// anything unsupported
+    fn reply_with_arguments(&mut self, packet: &str) -> String {
+        let reply = if let Some(register) = packet.strip_prefix('p') {
+            usize::from_str_radix(register, 16)
+                .ok()
+                .and_then(|register| self.registers().get(register).copied())
+                .map(encode_register)
+        } else if let Some(range) = packet.strip_prefix('m') {
+            parse_range(range).map(|(address, length)| self.read_memory(address, length))
+        } else if let Some(write) = packet.strip_prefix('M') {
+            write
+                .split_once(':')
+                .and_then(|(range, data)| Some((parse_range(range)?, decode_hex(data)?)))
+                .and_then(|((address, _), bytes)| self.write_memory(address, &bytes))
+        } else if let Some(breakpoint) = packet.strip_prefix("Z0,") {
+            parse_range(breakpoint).map(|(index, _)| {
+                self.breakpoints.insert(index);
+                "OK".to_string()
+            })
+        } else if let Some(breakpoint) = packet.strip_prefix("z0,") {
+            parse_range(breakpoint).map(|(index, _)| {
+                self.breakpoints.remove(&index);
+                "OK".to_string()
+            })
+        } else if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            parse_range(range).map(|(offset, length)| {
+                let rest = TARGET_XML.get(offset..).unwrap_or_default();
+                match rest.get(..length) {
+                    Some(chunk) if chunk.len() < rest.len() => format!("m{chunk}"),
+                    _ => format!("l{rest}"),
+                }
+            })
+        } else {
+            return String::new();
+        };
+
+        reply.unwrap_or_else(|| "E01".to_string())
+    }
+
+    fn registers(&self) -> [usize; 2] {
+        let engine = &self.program.engine;
+        let pc = match engine.instruction_pointer {
+            InstructionPointer::Start => 0,
+            InstructionPointer::End => engine.instructions.len(),
+            InstructionPointer::Index(i) => i,
+        };
+        [pc, engine.tape_pointer]
+    }
+
+    /// Cells past the end of the tape haven't been reached yet, so read as 0
+    fn read_memory(&self, address: usize, length: usize) -> String {
+        (address..address.saturating_add(length))
+            .map(|i| self.program.engine.tape.get(i).copied().unwrap_or(0))
+            .map(|cell| format!("{cell:02x}"))
+            .collect()
+    }
+
+    fn write_memory(&mut self, address: usize, bytes: &[u8]) -> Option<String> {
+        let tape = &mut self.program.engine.tape;
+        let end = address.checked_add(bytes.len())?;
+        if end > tape.len() {
+            tape.resize(end);
+        }
+        tape.write(address, bytes);
+        Some("OK".to_string())
+    }
+
+    fn step(&mut self) -> Option<Stop> {
+        if self.program.engine.instruction_pointer == InstructionPointer::End {
+            return Some(Stop::Exited);
+        }
+
+        let result = self.program.engine.step();
+        self.flush_output();
+        match result {
+            Ok(()) if self.program.engine.instruction_pointer == InstructionPointer::End => {
+                Some(Stop::Exited)
+            }
+            Ok(()) => None,
+            Err(Exception::Breakpoint) => Some(Stop::Signal(SIGTRAP)),
+            Err(Exception::RequestingInput) => match self.read_input() {
+                Ok(true) => None,
+                _ => Some(Stop::Exited),
+            },
+            Err(Exception::Error(error)) => {
+                eprintln!("plaque: {error}");
+                Some(Stop::Signal(SIGSEGV))
+            }
+        }
+    }
+
+    fn undo(&mut self) -> Option<Stop> {
+        if self.program.engine.history.is_empty() {
+            return Some(Stop::Signal(SIGTRAP));
+        }
+
+        match self.program.engine.undo() {
+            Ok(()) | Err(Exception::RequestingInput) => None,
+            Err(Exception::Breakpoint) => Some(Stop::Signal(SIGTRAP)),
+            Err(Exception::Error(error)) => {
+                eprintln!("plaque: {error}");
+                Some(Stop::Signal(SIGSEGV))
+            }
+        }
+    }
+
+    fn at_breakpoint(&self) -> bool {
+        matches!(
+            self.program.engine.instruction_pointer,
+            InstructionPointer::Index(i) if self.breakpoints.contains(&i)
+        )
+    }
+
+    fn resume(&mut self) -> Stop {
+        for steps in 1.. {
+            if let Some(stop) = self.step() {
+                return stop;
+            }
+            if self.at_breakpoint() {
+                return Stop::Signal(SIGTRAP);
+            }
+            if steps % POLL_INTERVAL == 0 && self.interrupted() {
+                return Stop::Signal(SIGINT);
+            }
+        }
+        unreachable!()
+    }
+
+    fn rewind(&mut self) -> Stop {
+        for steps in 1.. {
+            if let Some(stop) = self.undo() {
+                return stop;
+            }
+            if self.at_breakpoint() {
+                return Stop::Signal(SIGTRAP);
+            }
+            if steps % POLL_INTERVAL == 0 && self.interrupted() {
+                return Stop::Signal(SIGINT);
+            }
+        }
+        unreachable!()
+    }
+
+    /// Whether gdb has sent an interrupt, without waiting for one
+    fn interrupted(&mut self) -> bool {
+        if self.reader.buffer().contains(&INTERRUPT) {
+            self.reader.consume(self.reader.buffer().len());
+            return true;
+        }
+
+        let mut byte = [0];
+        let stream = self.reader.get_mut();
+        if stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let read = stream.read(&mut byte);
+        stream.set_nonblocking(false).ok();
+        matches!(read, Ok(1) if byte[0] == INTERRUPT)
+    }
+
+    /// Wait for more of stdin, returning whether there was any
+    fn read_input(&mut self) -> io::Result<bool> {
+        let mut buffer = [0; 4096];
+        let n = io::stdin().lock().read(&mut buffer)?;
+        self.program.engine.input.extend(&buffer[..n]);
+        Ok(n > 0)
+    }
+
+    // output already written to stdout can't be taken back, so only ever
+    // write the bytes past what has been written so far
+    fn flush_output(&mut self) {
+        let output = &self.program.engine.output;
+        if output.len() > self.written {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&output[self.written..]).ok();
+            stdout.flush().ok();
+            self.written = output.len();
+        }
+    }
+
+    fn receive(&mut self) -> io::Result<Option<Incoming>> {
+        loop {
+            let mut byte = [0];
+            if self.reader.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            match byte[0] {
+                INTERRUPT => return Ok(Some(Incoming::Interrupt)),
+                b'$' => {}
+                // acknowledgements, and anything else between packets
+                _ => continue,
+            }
+
+            let mut packet = vec![];
+            self.reader.read_until(b'#', &mut packet)?;
+            packet.pop();
+            let mut sum = [0; 2];
+            self.reader.read_exact(&mut sum)?;
+
+            let packet = String::from_utf8_lossy(&packet).into_owned();
+            let valid = std::str::from_utf8(&sum)
+                .ok()
+                .and_then(|sum| u8::from_str_radix(sum, 16).ok())
+                == Some(checksum(&packet));
+            if self.acknowledge {
+                self.writer.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid {
+                return Ok(Some(Incoming::Packet(packet)));
+            }
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        write!(self.writer, "${data}#{:02x}", checksum(data))?;
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,32 @@
+use crate::cli::Args;
+use crate::codegen;
+use crate::format::{self, Style};
+use crate::instruction::InstructionSet;
+
+use anyhow::{anyhow, Result};
+use std::io::{self, Read};
+
+const USAGE: &str = "usage: plaque gen-text [<text>] [--width <n>]";
+
+/// Print a program that prints some text, or what's on stdin if none is
+/// given, for making test programs and fixtures
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    let text = match args.positional() {
+        [text] => text.clone(),
+        [] => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            text
+        }
+        _ => return Err(anyhow!(USAGE)),
+    };
+
+    let mut code = codegen::text_to_bf(&text);
+    if let Some(width) = args.parsed::<usize>("width")? {
+        code = format::format(&code, &instruction_set, Style::Reflowed { width });
+    }
+    println!("{code}");
+
+    Ok(())
+}
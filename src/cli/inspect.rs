@@ -0,0 +1,73 @@
+use crate::cli::Args;
+use crate::engine::input::InputSource;
+use crate::engine::run::{StopReason, Stops};
+use crate::engine::summary::SUMMARY_RADIUS;
+use crate::engine::{Engine, InstructionPointer};
+use crate::instruction::InstructionSet;
+use crate::output::OutputDecoder;
+use crate::tape::CellFormat;
+
+use anyhow::{anyhow, Result};
+use std::io::{self, Read};
+
+const USAGE: &str = "usage: plaque inspect <program> [--input <source>] [--steps <count>] [--json]";
+
+/// Run a program until it stops, at the end, a breakpoint or after
+/// `--steps` steps, and show where it got to. With `--json`, the state is
+/// printed as one line of JSON for other tools to read.
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    let args = Args::parse(args, &["json"])?;
+    let [filepath] = args.positional() else {
+        return Err(anyhow!(USAGE));
+    };
+
+    let mut engine = Engine::new(instruction_set.parse(&std::fs::read_to_string(filepath)?));
+    match args.value("input") {
+        Some(input) => {
+            let input = input.parse::<InputSource>().map_err(anyhow::Error::msg)?;
+            engine.set_input(&input).map_err(anyhow::Error::msg)?;
+        }
+        None if !atty::is(atty::Stream::Stdin) => {
+            io::stdin().read_to_end(&mut engine.input)?;
+        }
+        None => {}
+    }
+    let stops = Stops {
+        fuel: args.parsed("steps")?,
+        ..Stops::default()
+    };
+    let stop = engine.run(&stops);
+
+    if args.switch("json") {
+        println!("{}", engine.to_json_summary(Some(&stop)));
+        return Ok(());
+    }
+
+    let position = match engine.instruction_pointer {
+        InstructionPointer::Start => String::from("at the start"),
+        InstructionPointer::End => String::from("at the end"),
+        InstructionPointer::Index(i) => {
+            format!("at instruction {i} ({})", engine.instructions[i].symbol)
+        }
+    };
+    let stopped = match &stop {
+        StopReason::Completed => String::from("finished"),
+        StopReason::Breakpoint(index) => format!("stopped at the breakpoint at {index}"),
+        StopReason::Watchpoint(cell) => format!("stopped as cell {cell} changed"),
+        StopReason::InputRequested => String::from("stopped for more input"),
+        StopReason::FuelExhausted => String::from("stopped after the steps allowed"),
+        StopReason::TimedOut => String::from("ran out of time"),
+        StopReason::InfiniteLoopDetected => String::from("stuck in a loop"),
+        StopReason::Error(error) => format!("failed: {error}"),
+    };
+    println!("{stopped}, {position}, step {}", engine.history.len());
+    println!("tape pointer {}", engine.tape_pointer);
+
+    let window = engine.tape_window(engine.tape_pointer, SUMMARY_RADIUS);
+    print!("{}", engine.dump_tape(window.range(), CellFormat::Hex));
+    println!(
+        "output: {:?}",
+        OutputDecoder::Utf8.decode_all(&engine.output)
+    );
+    Ok(())
+}
@@ -0,0 +1,123 @@
+"""A Jupyter kernel for Brainfuck, installed by `plaque kernel install`.
+
+ipykernel speaks Jupyter's protocol, and each cell is passed on to
+`plaque kernel serve`, which keeps the tape from cell to cell and sends back
+output as the program writes it, as JSON messages with Content-Length
+headers.
+
+Magics on the first lines of a cell:
+
+    %step [count]     load the cell's code, if any, and step through it
+    %tape [radius]    show the cells around the tape pointer
+    %input <source>   give the program input, as text:, hex:, cycle: or file:
+    %reset            start over with a blank tape
+"""
+
+import argparse
+import json
+import subprocess
+
+from ipykernel.kernelapp import IPKernelApp
+from ipykernel.kernelbase import Kernel
+
+
+class PlaqueKernel(Kernel):
+    implementation = "plaque"
+    implementation_version = "1.0"
+    language = "brainfuck"
+    language_version = "1.0"
+    language_info = {
+        "name": "brainfuck",
+        "mimetype": "text/x-brainfuck",
+        "file_extension": ".bf",
+    }
+    banner = "Brainfuck, run by plaque, with %step, %tape, %input and %reset"
+    plaque = "plaque"
+
+    def __init__(self, **kwargs):
+        super().__init__(**kwargs)
+        self.server = subprocess.Popen(
+            [self.plaque, "kernel", "serve"],
+            stdin=subprocess.PIPE,
+            stdout=subprocess.PIPE,
+        )
+        self.next_id = 0
+
+    def send(self, message):
+        body = json.dumps(message).encode()
+        self.server.stdin.write(b"Content-Length: %d\r\n\r\n" % len(body) + body)
+        self.server.stdin.flush()
+
+    def receive(self):
+        length = None
+        while True:
+            header = self.server.stdout.readline()
+            if not header:
+                raise EOFError("plaque kernel serve exited")
+            header = header.strip()
+            if not header:
+                break
+            name, _, value = header.partition(b":")
+            if name.strip().lower() == b"content-length":
+                length = int(value)
+        return json.loads(self.server.stdout.read(length))
+
+    def do_execute(
+        self, code, silent, store_history=True, user_expressions=None, allow_stdin=False
+    ):
+        self.next_id += 1
+        self.send({"id": self.next_id, "code": code})
+        while True:
+            message = self.receive()
+            if "stream" in message:
+                if not silent:
+                    self.send_response(
+                        self.iopub_socket,
+                        "stream",
+                        {"name": "stdout", "text": message["stream"]},
+                    )
+            elif message.get("input_requested"):
+                line = self.raw_input("") + "\n" if allow_stdin else None
+                self.send({"id": self.next_id, "input": line})
+            else:
+                break
+
+        if message["status"] == "error":
+            error = {
+                "ename": "Error",
+                "evalue": message["error"],
+                "traceback": [message["error"]],
+            }
+            if not silent:
+                self.send_response(self.iopub_socket, "error", error)
+            return {"status": "error", "execution_count": self.execution_count, **error}
+
+        if message["result"] is not None and not silent:
+            self.send_response(
+                self.iopub_socket,
+                "execute_result",
+                {
+                    "execution_count": self.execution_count,
+                    "data": {"text/plain": message["result"]},
+                    "metadata": {},
+                },
+            )
+        return {
+            "status": "ok",
+            "execution_count": self.execution_count,
+            "payload": [],
+            "user_expressions": {},
+        }
+
+    def do_shutdown(self, restart):
+        self.server.stdin.close()
+        self.server.wait()
+        return {"status": "ok", "restart": restart}
+
+
+if __name__ == "__main__":
+    parser = argparse.ArgumentParser()
+    parser.add_argument("--plaque", default="plaque")
+    known, rest = parser.parse_known_args()
+    PlaqueKernel.plaque = known.plaque
+    IPKernelApp.launch_instance(argv=rest, kernel_class=PlaqueKernel)
@@ -0,0 +1,342 @@
+use crate::cli::framed::{read_message, write_message};
+use crate::cli::Args;
+use crate::engine::input::InputSource;
+use crate::engine::run::{StopReason, Stops};
+use crate::engine::summary::SUMMARY_RADIUS;
+use crate::engine::{Engine, Exception, InstructionPointer};
+use crate::instruction::{Instruction, InstructionSet};
+use crate::tape::CellFormat;
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::io::{self, StdinLock, StdoutLock};
+use std::path::PathBuf;
+
+const USAGE: &str =
+    "usage: plaque kernel install [--prefix <dir>] [--python <interpreter>] | plaque kernel serve";
+const KERNEL_NAME: &str = "plaque";
+/// The half of the kernel Jupyter starts, which speaks its ZeroMQ protocol
+/// through ipykernel and passes each cell on to `plaque kernel serve`
+const WRAPPER: &str = include_str!("kernel.py");
+/// How many steps a cell may take, so a runaway program can't hang the
+/// notebook
+const RUN_LIMIT: usize = 10_000_000;
+/// How many steps to run between sending on output, so long cells show
+/// what they've printed as they go
+const STREAM_INTERVAL: usize = 10_000;
+const LOOP_CHECK: usize = 1000;
+
+/// The machine a notebook runs its cells on. Each cell of code is a program
+/// of its own, starting on the tape and input the last one left.
+struct Kernel {
+    instruction_set: InstructionSet,
+    engine: Engine,
+    /// How much of the output has been sent to the notebook
+    sent: usize,
+}
+
+/// The way back to the notebook for the cell being run
+struct Channel<'a> {
+    id: Value,
+    reader: StdinLock<'a>,
+    writer: StdoutLock<'a>,
+}
+
+/// Install a Jupyter kernel running Brainfuck cells, or serve the cells of
+/// one over stdin and stdout.
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    match args.first().map(String::as_str) {
+        Some("install") => install(&args[1..]),
+        Some("serve") if args.len() == 1 => serve(instruction_set),
+        _ => Err(anyhow!(USAGE)),
+    }
+}
+
+/// Write the kernel spec, for the user or under `--prefix`, pointing
+/// Jupyter at the wrapper and the wrapper at this executable
+fn install(args: &[String]) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    if !args.positional().is_empty() {
+        return Err(anyhow!(USAGE));
+    }
+    let directory = match args.value("prefix") {
+        Some(prefix) => PathBuf::from(prefix).join("share").join("jupyter"),
+        None => user_data_dir()?,
+    }
+    .join("kernels")
+    .join(KERNEL_NAME);
+    std::fs::create_dir_all(&directory)?;
+
+    let wrapper = directory.join("plaque_kernel.py");
+    std::fs::write(&wrapper, WRAPPER)?;
+    let spec = json!({
+        "argv": [
+            args.value("python").unwrap_or("python3"),
+            wrapper.display().to_string(),
+            "--plaque",
+            std::env::current_exe()?.display().to_string(),
+            "-f",
+            "{connection_file}",
+        ],
+        "display_name": "Brainfuck (plaque)",
+        "language": "brainfuck",
+    });
+    std::fs::write(
+        directory.join("kernel.json"),
+        serde_json::to_string_pretty(&spec)?,
+    )?;
+    println!("installed the kernel in {}", directory.display());
+    Ok(())
+}
+
+/// Where Jupyter looks for a user's kernels
+fn user_data_dir() -> Result<PathBuf> {
+    if let Some(directory) = std::env::var_os("JUPYTER_DATA_DIR") {
+        return Ok(directory.into());
+    }
+    let var = |name| {
+        std::env::var_os(name)
+            .map(PathBuf::from)
+            .ok_or_else(|| anyhow!("${name} isn't set, give a --prefix to install under"))
+    };
+    if cfg!(windows) {
+        Ok(var("APPDATA")?.join("jupyter"))
+    } else if cfg!(target_os = "macos") {
+        Ok(var("HOME")?.join("Library").join("Jupyter"))
+    } else {
+        let data = var("XDG_DATA_HOME")
+            .or_else(|_| Ok::<_, anyhow::Error>(var("HOME")?.join(".local").join("share")))?;
+        Ok(data.join("jupyter"))
+    }
+}
+
+/// Run cells sent as `{"id", "code"}` messages with `Content-Length`
+/// headers. Output is sent back as `{"id", "stream"}` while the cell runs,
+/// `{"id", "input_requested": true}` when it needs input, to be answered
+/// with `{"input"}`, and the cell ends with `{"id", "status"}` and its
+/// `result` or `error`.
+fn serve(instruction_set: InstructionSet) -> Result<()> {
+    let mut kernel = Kernel {
+        instruction_set,
+        engine: Engine::new(vec![]),
+        sent: 0,
+    };
+    let mut channel = Channel {
+        id: Value::Null,
+        reader: io::stdin().lock(),
+        writer: io::stdout().lock(),
+    };
+
+    while let Some(message) = read_message(&mut channel.reader)? {
+        channel.id = message["id"].clone();
+        let reply = match kernel.execute(message["code"].as_str().unwrap_or_default(), &mut channel)
+        {
+            Ok(result) => json!({ "id": channel.id, "status": "ok", "result": result }),
+            Err(e) => json!({ "id": channel.id, "status": "error", "error": e.to_string() }),
+        };
+        kernel.stream(&mut channel, true)?;
+        channel.send(reply)?;
+    }
+
+    Ok(())
+}
+
+impl Kernel {
+    /// Run a cell: the magics on its first lines in order, then its code,
+    /// unless a `%step` has already loaded the code to step through
+    fn execute(&mut self, cell: &str, channel: &mut Channel) -> Result<Option<String>> {
+        let mut magics = vec![];
+        let mut code = cell.trim_start();
+        while let Some(magic) = code.strip_prefix('%') {
+            let (line, rest) = magic.split_once('\n').unwrap_or((magic, ""));
+            magics.push(line.trim());
+            code = rest.trim_start();
+        }
+
+        let instructions = self.instruction_set.parse(code);
+        let mut pending = (!instructions.is_empty()).then_some(instructions);
+        let mut shown = vec![];
+        for magic in magics {
+            let (name, argument) = magic
+                .split_once(char::is_whitespace)
+                .map_or((magic, ""), |(name, argument)| (name, argument.trim()));
+            match name {
+                "step" => {
+                    if let Some(instructions) = pending.take() {
+                        self.load(instructions)?;
+                    }
+                    let count = match argument {
+                        "" => 1,
+                        count => count
+                            .parse()
+                            .map_err(|_| anyhow!("%step takes a number of steps, not {count}"))?,
+                    };
+                    self.step(count, channel)?;
+                    shown.push(self.position());
+                }
+                "tape" => {
+                    let radius = match argument {
+                        "" => SUMMARY_RADIUS,
+                        radius => radius
+                            .parse()
+                            .map_err(|_| anyhow!("%tape takes a number of cells, not {radius}"))?,
+                    };
+                    shown.push(self.tape(radius));
+                }
+                "input" => {
+                    let source = argument
+                        .parse::<InputSource>()
+                        .map_err(anyhow::Error::msg)?;
+                    self.engine.set_input(&source).map_err(anyhow::Error::msg)?;
+                }
+                "reset" => {
+                    self.engine = Engine::new(vec![]);
+                    self.sent = 0;
+                }
+                _ => {
+                    return Err(anyhow!(
+                        "unknown magic %{name}, expected %step, %tape, %input or %reset"
+                    ))
+                }
+            }
+        }
+
+        if let Some(instructions) = pending {
+            self.load(instructions)?;
+            shown.extend(self.run(channel)?);
+        }
+        Ok((!shown.is_empty()).then(|| shown.join("\n")))
+    }
+
+    /// Start a new program on the tape and input the last one left
+    fn load(&mut self, instructions: Vec<Instruction>) -> Result<()> {
+        let mut engine = Engine::new(vec![]);
+        engine
+            .load_instructions(instructions)
+            .map_err(|e| anyhow!("{e}"))?;
+        engine.tape = std::mem::take(&mut self.engine.tape);
+        engine.tape_pointer = self.engine.tape_pointer;
+        engine.input = std::mem::take(&mut self.engine.input);
+        engine.input_cycle = std::mem::take(&mut self.engine.input_cycle);
+        self.engine = engine;
+        self.sent = 0;
+        Ok(())
+    }
+
+    /// Run the program until it finishes or stops, saying why if it stopped
+    /// somewhere it can go on from
+    fn run(&mut self, channel: &mut Channel) -> Result<Option<String>> {
+        let stops = Stops {
+            fuel: Some(STREAM_INTERVAL),
+            loop_check: Some(LOOP_CHECK),
+            ..Stops::default()
+        };
+        loop {
+            let stop = self.engine.continue_(&stops);
+            self.stream(channel, false)?;
+            match stop {
+                StopReason::Completed => return Ok(None),
+                StopReason::FuelExhausted if self.engine.history.len() < RUN_LIMIT => {}
+                StopReason::FuelExhausted => {
+                    return Err(anyhow!("gave up after {RUN_LIMIT} steps"))
+                }
+                StopReason::InputRequested => self.give_input(channel)?,
+                StopReason::Breakpoint(index) => {
+                    return Ok(Some(format!(
+                        "stopped at the breakpoint at instruction {index}, %step to go on"
+                    )))
+                }
+                StopReason::InfiniteLoopDetected => {
+                    return Err(anyhow!("stuck in a loop, back in a state it had been in"))
+                }
+                StopReason::Error(error) => return Err(anyhow!("{error}")),
+                StopReason::Watchpoint(_) | StopReason::TimedOut => unreachable!(),
+            }
+        }
+    }
+
+    /// Take `count` steps, or fewer if the program finishes first, running
+    /// on past breakpoints
+    fn step(&mut self, count: usize, channel: &mut Channel) -> Result<()> {
+        let target = self.engine.history.len() + count;
+        while self.engine.history.len() < target
+            && self.engine.instruction_pointer != InstructionPointer::End
+        {
+            match self.engine.step() {
+                Ok(()) | Err(Exception::Breakpoint) => {}
+                Err(Exception::RequestingInput) => self.give_input(channel)?,
+                Err(Exception::Error(error)) => return Err(anyhow!("{error}")),
+            }
+        }
+        self.stream(channel, false)?;
+        Ok(())
+    }
+
+    fn give_input(&mut self, channel: &mut Channel) -> Result<()> {
+        match channel.ask_input()? {
+            Some(input) => {
+                self.engine.input.extend(input);
+                Ok(())
+            }
+            None => Err(anyhow!("the program needs input, give it with %input")),
+        }
+    }
+
+    /// Where the program is, and the cells around the tape pointer
+    fn position(&self) -> String {
+        let position = match self.engine.instruction_pointer {
+            InstructionPointer::Start => String::from("at the start"),
+            InstructionPointer::End => String::from("at the end"),
+            InstructionPointer::Index(i) => {
+                format!(
+                    "at instruction {i} ({})",
+                    self.engine.instructions[i].symbol
+                )
+            }
+        };
+        format!(
+            "{position}, step {}, tape pointer {}\n{}",
+            self.engine.history.len(),
+            self.engine.tape_pointer,
+            self.tape(SUMMARY_RADIUS)
+        )
+    }
+
+    fn tape(&self, radius: usize) -> String {
+        let window = self.engine.tape_window(self.engine.tape_pointer, radius);
+        let dump = self.engine.dump_tape(window.range(), CellFormat::Hex);
+        dump.trim_end().to_string()
+    }
+
+    /// Send the output written since last time, holding back a character
+    /// that's only partly written unless it's the end of the cell
+    fn stream(&mut self, channel: &mut Channel, finished: bool) -> io::Result<()> {
+        let unsent = &self.engine.output[self.sent.min(self.engine.output.len())..];
+        let complete = match std::str::from_utf8(unsent) {
+            Err(e) if !finished && e.error_len().is_none() => e.valid_up_to(),
+            _ => unsent.len(),
+        };
+        if complete == 0 {
+            return Ok(());
+        }
+        let text = String::from_utf8_lossy(&unsent[..complete]).into_owned();
+        self.sent += complete;
+        channel.send(json!({ "id": channel.id, "stream": text }))
+    }
+}
+
+impl Channel<'_> {
+    fn send(&mut self, message: Value) -> io::Result<()> {
+        write_message(&mut self.writer, &message)
+    }
+
+    /// Ask the notebook for a line of input, which it may not be able to give
+    fn ask_input(&mut self) -> Result<Option<Vec<u8>>> {
+        self.send(json!({ "id": self.id, "input_requested": true }))?;
+        let reply = read_message(&mut self.reader)?
+            .ok_or_else(|| anyhow!("the notebook went away while the program waited for input"))?;
+        Ok(reply["input"]
+            .as_str()
+            .map(|input| input.as_bytes().to_vec()))
+    }
+}
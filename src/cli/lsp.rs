@@ -0,0 +1,247 @@
+use crate::analysis::{self, Severity, WarningKind};
+use crate::cli::framed::{read_message, write_message};
+use crate::engine::TapeModel;
+use crate::instruction::{Instruction, InstructionKind, InstructionSet};
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, StdoutLock};
+use std::ops::Range;
+
+const SEVERITY_ERROR: u64 = 1;
+const SEVERITY_WARNING: u64 = 2;
+const SEVERITY_HINT: u64 = 4;
+const TAG_UNNECESSARY: u64 = 1;
+
+/// A source file's instructions, with where each one is as a zero-based line
+/// and UTF-16 column, as the protocol counts them
+struct Document {
+    instructions: Vec<Instruction>,
+    positions: Vec<(usize, usize)>,
+    /// The index of the bracket matching each bracket that has one
+    matches: Vec<Option<usize>>,
+}
+
+/// Serve the Language Server Protocol over stdin and stdout, with
+/// diagnostics for unmatched brackets and the load-time warnings, matching
+/// brackets as definitions of each other and loop depth on hover.
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    if !args.is_empty() {
+        return Err(anyhow!("usage: plaque lsp"));
+    }
+
+    let mut reader = io::stdin().lock();
+    let mut writer = io::stdout().lock();
+    let mut documents = HashMap::new();
+
+    while let Some(message) = read_message(&mut reader)? {
+        let params = &message["params"];
+        let method = message["method"].as_str().unwrap_or_default();
+
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                },
+                "serverInfo": { "name": "plaque" },
+            }),
+            "shutdown" => Value::Null,
+            "exit" => break,
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                let text = match method {
+                    "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+                    // only full syncs are asked for, so the last change is
+                    // the whole text
+                    _ => params["contentChanges"]
+                        .as_array()
+                        .and_then(|changes| changes.last())
+                        .and_then(|change| change["text"].as_str()),
+                };
+                let document = Document::parse(text.unwrap_or_default(), &instruction_set);
+                publish(&mut writer, uri, document.diagnostics())?;
+                documents.insert(uri.to_string(), document);
+                continue;
+            }
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                documents.remove(uri);
+                publish(&mut writer, uri, vec![])?;
+                continue;
+            }
+            "textDocument/hover" | "textDocument/definition" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                let position = (
+                    params["position"]["line"].as_u64().unwrap_or_default() as usize,
+                    params["position"]["character"].as_u64().unwrap_or_default() as usize,
+                );
+                documents
+                    .get(uri)
+                    .and_then(|document: &Document| {
+                        let index = document.instruction_at(position)?;
+                        match method {
+                            "textDocument/hover" => Some(document.hover(index)),
+                            _ => document.definition(uri, index),
+                        }
+                    })
+                    .unwrap_or(Value::Null)
+            }
+            _ => {
+                if !message["id"].is_null() {
+                    write_message(
+                        &mut writer,
+                        &json!({
+                            "jsonrpc": "2.0",
+                            "id": message["id"].clone(),
+                            "error": { "code": -32601, "message": format!("unknown method {method}") },
+                        }),
+                    )?;
+                }
+                continue;
+            }
+        };
+
+        write_message(
+            &mut writer,
+            &json!({ "jsonrpc": "2.0", "id": message["id"].clone(), "result": result }),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn publish(writer: &mut StdoutLock, uri: &str, diagnostics: Vec<Value>) -> io::Result<()> {
+    write_message(
+        writer,
+        &json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }),
+    )
+}
+
+fn range(start: (usize, usize), end: (usize, usize)) -> Value {
+    json!({
+        "start": { "line": start.0, "character": start.1 },
+        "end": { "line": end.0, "character": end.1 },
+    })
+}
+
+impl Document {
+    fn parse(text: &str, instruction_set: &InstructionSet) -> Document {
+        let mut instructions = vec![];
+        let mut positions = vec![];
+        for (line_number, line) in text.lines().enumerate() {
+            let mut column = 0;
+            for character in line.chars() {
+                if let Some(instruction) = instruction_set.get(character) {
+                    instructions.push(instruction.clone());
+                    positions.push((line_number, column));
+                }
+                column += character.len_utf16();
+            }
+        }
+
+        let matches = analysis::match_brackets(&instructions);
+
+        Document {
+            instructions,
+            positions,
+            matches,
+        }
+    }
+
+    /// The range covering the instruction at an index
+    fn range(&self, index: usize) -> Value {
+        self.span(index..index + 1)
+    }
+
+    /// The range covering a run of instructions
+    fn span(&self, span: Range<usize>) -> Value {
+        let (line, column) = self.positions[span.end - 1];
+        let width = self.instructions[span.end - 1].symbol.len_utf16();
+        range(self.positions[span.start], (line, column + width))
+    }
+
+    /// The instruction under a cursor, or else the one just before it
+    fn instruction_at(&self, (line, character): (usize, usize)) -> Option<usize> {
+        let exact = self.positions.binary_search(&(line, character));
+        match exact {
+            Ok(index) => Some(index),
+            Err(_) if character > 0 => self.positions.binary_search(&(line, character - 1)).ok(),
+            Err(_) => None,
+        }
+    }
+
+    fn diagnostics(&self) -> Vec<Value> {
+        let mut warnings = analysis::sanity_warnings(&self.instructions, None);
+        warnings.extend(analysis::bounds::tape_warnings(
+            &self.instructions,
+            TapeModel::Unbounded,
+        ));
+        warnings
+            .into_iter()
+            .map(|warning| {
+                let range = self.span(warning.span.clone());
+                let message = match warning.kind {
+                    WarningKind::UnmatchedBracket => {
+                        format!("unmatched {}", self.instructions[warning.index].symbol)
+                    }
+                    _ => warning.message().to_string(),
+                };
+                match (warning.kind, warning.severity()) {
+                    // the whole loop is dead, so fade out all of it
+                    (WarningKind::DeadLoop, _) => json!({
+                        "range": range,
+                        "severity": SEVERITY_HINT,
+                        "tags": [TAG_UNNECESSARY],
+                        "source": "plaque",
+                        "message": message,
+                    }),
+                    (_, severity) => json!({
+                        "range": range,
+                        "severity": match severity {
+                            Severity::Error => SEVERITY_ERROR,
+                            Severity::Warning => SEVERITY_WARNING,
+                        },
+                        "source": "plaque",
+                        "message": message,
+                    }),
+                }
+            })
+            .collect()
+    }
+
+    /// How many loops an instruction is part of, counting a loop's own
+    /// brackets as inside it
+    fn depth(&self, index: usize) -> usize {
+        let mut depth: usize = 0;
+        for instruction in &self.instructions[..index] {
+            match instruction.kind {
+                InstructionKind::OpenLoop => depth += 1,
+                InstructionKind::CloseLoop => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        match self.instructions[index].opens_loop() {
+            true => depth + 1,
+            false => depth,
+        }
+    }
+
+    fn hover(&self, index: usize) -> Value {
+        json!({
+            "contents": { "kind": "markdown", "value": format!("loop depth {}", self.depth(index)) },
+            "range": self.range(index),
+        })
+    }
+
+    fn definition(&self, uri: &str, index: usize) -> Option<Value> {
+        let matching = self.matches.get(index).copied().flatten()?;
+        Some(json!({ "uri": uri, "range": self.range(matching) }))
+    }
+}
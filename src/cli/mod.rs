@@ -0,0 +1,103 @@
+#[cfg(feature = "server")]
+pub mod attach;
+pub mod bench;
+pub mod bisect;
+pub mod cfg;
+pub mod check;
+pub mod fmt;
+#[cfg(feature = "server")]
+pub mod dap;
+pub mod dump;
+#[cfg(feature = "server")]
+mod framed;
+pub mod gen_text;
+pub mod inspect;
+#[cfg(feature = "server")]
+pub mod kernel;
+#[cfg(feature = "server")]
+pub mod gdb;
+#[cfg(feature = "server")]
+pub mod lsp;
+pub mod record;
+pub mod replay;
+pub mod run;
+pub mod script;
+#[cfg(feature = "server")]
+pub mod serve;
+pub mod stat;
+pub mod test;
+pub mod transpile;
+#[cfg(feature = "server")]
+mod websocket;
+
+use anyhow::{anyhow, Result};
+use std::str::FromStr;
+
+/// Command line arguments split into positional arguments, `--name value`
+/// options and bare `--name` switches.
+#[derive(Debug)]
+pub struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, String)>,
+    switches: Vec<String>,
+}
+
+impl Args {
+    pub fn parse(args: &[String], switches: &[&str]) -> Result<Args> {
+        let mut parsed = Args {
+            positional: vec![],
+            options: vec![],
+            switches: vec![],
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) if switches.contains(&name) => parsed.switches.push(name.to_string()),
+                Some(name) => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--{name} requires a value"))?;
+                    parsed.options.push((name.to_string(), value.clone()));
+                }
+                None => parsed.positional.push(arg.clone()),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    pub fn positional(&self) -> &[String] {
+        &self.positional
+    }
+
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(option, _)| option == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Every value given for an option that can be repeated, in order
+    pub fn values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.options
+            .iter()
+            .filter(move |(option, _)| option == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn switch(&self, name: &str) -> bool {
+        self.switches.iter().any(|switch| switch == name)
+    }
+
+    pub fn parsed<T: FromStr>(&self, name: &str) -> Result<Option<T>> {
+        self.value(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| anyhow!("invalid value for --{name}: {value}"))
+            })
+            .transpose()
+    }
+}
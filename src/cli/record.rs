@@ -0,0 +1,57 @@
+use crate::cli::Args;
+use crate::engine::history::HistoryPolicy;
+use crate::engine::input::InputSource;
+use crate::engine::{Engine, Exception};
+use crate::instruction::InstructionSet;
+
+use anyhow::{anyhow, Result};
+use std::io::{self, Read, Write};
+
+const USAGE: &str = "usage: plaque record <program> -o <trace> [--input <source>]";
+
+/// Run a program to completion like `run`, writing a trace of every step to
+/// look back through with `replay`. Input is piped stdin read up front
+/// unless `--input` gives another source. The trace is written even if the
+/// program fails, as that's when it's wanted most.
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    // -o is short for --output
+    let args = args
+        .iter()
+        .map(|arg| match arg.as_str() {
+            "-o" => String::from("--output"),
+            _ => arg.clone(),
+        })
+        .collect::<Vec<_>>();
+    let args = Args::parse(&args, &[])?;
+    let ([filepath], Some(trace_path)) = (args.positional(), args.value("output")) else {
+        return Err(anyhow!(USAGE));
+    };
+
+    let source = std::fs::read_to_string(filepath)?;
+    let mut engine = Engine::new(instruction_set.parse(&source));
+    engine.history.set_policy(HistoryPolicy::Off);
+    match args.value("input") {
+        Some(input) => {
+            let input = input.parse::<InputSource>().map_err(anyhow::Error::msg)?;
+            engine.set_input(&input).map_err(anyhow::Error::msg)?;
+        }
+        None if !atty::is(atty::Stream::Stdin) => {
+            io::stdin().read_to_end(&mut engine.input)?;
+        }
+        None => {}
+    }
+
+    let (trace, result) = engine.record(source);
+    std::fs::write(trace_path, trace.to_bytes())?;
+    io::stdout().write_all(&engine.output)?;
+    eprintln!(
+        "plaque: recorded {} steps to {trace_path}",
+        trace.steps.len()
+    );
+
+    match result {
+        Ok(()) | Err(Exception::Breakpoint) => Ok(()),
+        Err(Exception::RequestingInput) => Err(anyhow!("the program needs more input")),
+        Err(Exception::Error(error)) => Err(error.into()),
+    }
+}
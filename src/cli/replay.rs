@@ -0,0 +1,53 @@
+use crate::cli::Args;
+use crate::engine::trace::Trace;
+use crate::engine::InstructionPointer;
+use crate::instruction::InstructionSet;
+use crate::profile::{self, Profile};
+use crate::tape::{self, CellFormat};
+
+use anyhow::{anyhow, Result};
+
+const USAGE: &str = "usage: plaque replay <trace> [--step <n>] [--chrome-trace <file>]";
+
+/// How many cells either side of the pointer are shown
+const TAPE_RADIUS: usize = 8;
+
+/// Rebuild the state a recorded run was in after some number of steps, by
+/// default at the end, and describe it. With `--chrome-trace`, the run's
+/// loops are also written out for Perfetto.
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    let [trace_path] = args.positional() else {
+        return Err(anyhow!(USAGE));
+    };
+
+    let trace = Trace::from_bytes(&std::fs::read(trace_path)?).map_err(anyhow::Error::msg)?;
+    if let Some(path) = args.value("chrome-trace") {
+        profile::export_chrome_trace(&Profile::from_trace(&trace, &instruction_set), path)?;
+    }
+    let step = args.parsed("step")?.unwrap_or(trace.steps.len());
+    let engine = trace
+        .state_at(&instruction_set, step)
+        .map_err(anyhow::Error::msg)?;
+
+    println!("step {step} of {}", trace.steps.len());
+    match engine.instruction_pointer {
+        InstructionPointer::Index(index) => {
+            let symbol = engine.instructions[index].symbol;
+            println!("next instruction: {index} {symbol}");
+        }
+        InstructionPointer::Start => println!("next instruction: start"),
+        InstructionPointer::End => println!("next instruction: end"),
+    }
+    let window = engine.tape_window(engine.tape_pointer, TAPE_RADIUS);
+    let cells = window.iter().map(|(_, cell)| cell).collect::<Vec<_>>();
+    println!(
+        "tape from {}: {}",
+        window.start,
+        tape::render(&cells, 0..cells.len(), CellFormat::Decimal)
+    );
+    println!("tape pointer: {}", engine.tape_pointer);
+    println!("output: {:?}", String::from_utf8_lossy(&engine.output));
+
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use crate::assertions;
+use crate::cli::Args;
+use crate::engine::history::HistoryPolicy;
+use crate::engine::input::InputSource;
+use crate::engine::{Engine, EngineResult, Exception, InstructionPointer};
+use crate::flavor::{overflow, Eof};
+use crate::instruction::InstructionSet;
+use crate::ir;
+use crate::preprocess::SourceManager;
+
+use anyhow::{anyhow, Result};
+use std::io::{self, Read, Write};
+
+/// Run a program to completion without any debugging or history, as fast as
+/// possible, streaming its output to stdout. Input is piped stdin read up
+/// front, or read from the terminal whenever the program needs more, unless
+/// `--input` gives another source. With `--expect-output`, the output is
+/// checked against a file as it's written instead, stopping at the first
+/// byte that's wrong. With `--macros`, macros and includes are
+/// expanded before the program is parsed. With `--coverage`, the program is
+/// interpreted as written and its source printed to stderr after, marking
+/// the instructions that never ran. With `--assertions`, each
+/// `{assert ...}` in the program's comments is checked when it's reached,
+/// failing the run if it doesn't hold. With `--tape`, a file's bytes are
+/// loaded onto the tape before the program starts. With `--events ndjson`,
+/// the program is interpreted as written and each step, byte of input or
+/// output, breakpoint and stop is written as a line of JSON to stderr, or
+/// to `--events-to`, which can be a pipe like `/dev/fd/3`. With `--eof`,
+/// reading past the end of the input does as it says, however the program
+/// is run.
+pub fn run(args: &[String], mut instruction_set: InstructionSet) -> Result<()> {
+    let args = Args::parse(args, &["assertions", "coverage", "interpret", "macros"])?;
+    let [filepath] = args.positional() else {
+        return Err(anyhow!(
+            "usage: plaque run <program> [--interpret] [--macros] [--coverage] [--assertions] \
+            [--input <source>] [--tape <file>] [--expect-output <file>] [--events ndjson] \
+            [--events-to <file>] [--eof request|zero|unchanged|max]"
+        ));
+    };
+    let eof: Option<Eof> = args.parsed("eof")?;
+    if let Some(eof) = eof {
+        instruction_set.insert(overflow::input(eof));
+    }
+
+    let mut sources = SourceManager::new();
+    let file = sources.load(filepath)?;
+    let source = match args.switch("macros") {
+        true => sources.expand(file).map_err(anyhow::Error::msg)?.text,
+        false => sources.files()[file].text.clone(),
+    };
+    let instructions = match args.switch("assertions") {
+        true => assertions::parse(&source, &instruction_set).map_err(anyhow::Error::msg)?,
+        false => instruction_set.parse(&source),
+    };
+    let mut engine = Engine::new(instructions);
+    engine.history.set_policy(HistoryPolicy::Off);
+    if let Some(image) = args.value("tape") {
+        engine.load_tape(&std::fs::read(image)?, 0)?;
+    }
+    let input = args
+        .value("input")
+        .map(|input| input.parse::<InputSource>())
+        .transpose()
+        .map_err(anyhow::Error::msg)?;
+    let interactive = input.is_none() && atty::is(atty::Stream::Stdin);
+    match input {
+        Some(source) => engine.set_input(&source).map_err(anyhow::Error::msg)?,
+        None if !interactive => {
+            io::stdin().read_to_end(&mut engine.input)?;
+        }
+        None => {}
+    }
+
+    if let Some(expected) = args.value("expect-output") {
+        let expected = std::fs::read(expected)?;
+        // unoptimized, so a mismatch is pinned on an instruction in the source
+        let result = loop {
+            match engine.expect_output(&expected) {
+                Err(Exception::Breakpoint) => {}
+                result => break result,
+            }
+        };
+        io::stdout().write_all(&engine.output)?;
+        return match result {
+            Ok(None) => Ok(()),
+            Ok(Some(mismatch)) => Err(anyhow!("{mismatch}")),
+            Err(exception) => finished(Err(exception)),
+        };
+    }
+
+    let mut events: Option<Box<dyn Write>> = match args.value("events") {
+        None => None,
+        Some("ndjson") => match args.value("events-to") {
+            Some(path) => Some(Box::new(io::LineWriter::new(std::fs::File::create(path)?))),
+            None => Some(Box::new(io::stderr())),
+        },
+        Some(format) => return Err(anyhow!("unknown event format {format}, expected ndjson")),
+    };
+
+    let mut stdout = io::stdout().lock();
+    let mut result = if args.switch("coverage") || events.is_some() {
+        interpret(&mut engine, &mut stdout, &mut events)?
+    } else if args.switch("interpret") {
+        engine.load_instructions(ir::compile(&engine.instructions).instructions)?;
+        interpret(&mut engine, &mut stdout, &mut events)?
+    } else {
+        compiled(&mut engine, eof.unwrap_or_default(), &mut stdout)?
+    };
+    // wherever the program stopped for input, it carries on interpreted
+    while interactive && result == Err(Exception::RequestingInput) {
+        stdout.flush()?;
+        let mut buffer = [0; 1024];
+        let read = io::stdin().lock().read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        engine.input.extend_from_slice(&buffer[..read]);
+        result = interpret(&mut engine, &mut stdout, &mut events)?;
+    }
+    stdout.flush()?;
+    if args.switch("coverage") {
+        let coverage = engine.coverage();
+        eprint!("{}", coverage.annotate(&source, &instruction_set));
+        eprintln!(
+            "coverage: {} of {} instructions ran",
+            coverage.executed_count(),
+            coverage.len()
+        );
+    }
+    finished(result)
+}
+
+fn finished(result: EngineResult) -> Result<()> {
+    match result {
+        Ok(()) | Err(Exception::Breakpoint) => Ok(()),
+        Err(Exception::RequestingInput) => Err(anyhow!("the program needs more input")),
+        Err(Exception::Error(error)) => Err(error.into()),
+    }
+}
+
+/// Step to the end, writing output as it's written rather than keeping it,
+/// and each step's events if they're wanted
+fn interpret(
+    engine: &mut Engine,
+    stdout: &mut impl Write,
+    events: &mut Option<Box<dyn Write>>,
+) -> Result<EngineResult> {
+    while engine.instruction_pointer != InstructionPointer::End {
+        let result = match events {
+            Some(events) => {
+                let (result, happened) = engine.step_events();
+                for event in happened {
+                    writeln!(events, "{}", event.to_json())?;
+                }
+                result
+            }
+            None => engine.step(),
+        };
+        if !engine.output.is_empty() {
+            stdout.write_all(&engine.take_output())?;
+        }
+        match result {
+            Ok(()) | Err(Exception::Breakpoint) => {}
+            Err(exception) => return Ok(Err(exception)),
+        }
+    }
+    Ok(Ok(()))
+}
+
+#[cfg(feature = "jit")]
+fn compiled(engine: &mut Engine, eof: Eof, stdout: &mut impl Write) -> Result<EngineResult> {
+    match crate::jit::compile(&engine.instructions) {
+        Ok(compiled) => {
+            let result = compiled.run(engine, eof);
+            stdout.write_all(&engine.take_output())?;
+            Ok(result)
+        }
+        // instructions the JIT doesn't know can still be interpreted
+        Err(_) => {
+            engine.load_instructions(ir::compile(&engine.instructions).instructions)?;
+            interpret(engine, stdout, &mut None)
+        }
+    }
+}
+
+/// The instructions already read input with the EOF policy
+#[cfg(not(feature = "jit"))]
+fn compiled(engine: &mut Engine, _eof: Eof, stdout: &mut impl Write) -> Result<EngineResult> {
+    engine.load_instructions(ir::compile(&engine.instructions).instructions)?;
+    interpret(engine, stdout, &mut None)
+}
@@ -0,0 +1,27 @@
+use crate::cli::Args;
+use crate::engine::Engine;
+use crate::instruction::InstructionSet;
+use crate::script::Script;
+
+use anyhow::{anyhow, Result};
+use std::io::{self, Read};
+
+/// Drive a program with a script of debugger commands, reading piped stdin
+/// as its input, and fail on the first failed expectation.
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    let [filepath, script_path] = args.positional() else {
+        return Err(anyhow!("usage: plaque script <program> <script>"));
+    };
+
+    let script = Script::parse(&std::fs::read_to_string(script_path)?)
+        .map_err(|e| anyhow!("{script_path}: {e}"))?;
+    let mut engine = Engine::new(instruction_set.parse(&std::fs::read_to_string(filepath)?));
+    if atty::isnt(atty::Stream::Stdin) {
+        io::stdin().read_to_end(&mut engine.input)?;
+    }
+
+    script
+        .run(&mut engine, &mut io::stdout().lock())
+        .map_err(|e| anyhow!("{script_path}: {e}"))
+}
@@ -0,0 +1,330 @@
+use crate::cli::websocket;
+use crate::cli::Args;
+use crate::engine::{Engine, Exception, InstructionPointer};
+use crate::instruction::InstructionSet;
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const DEFAULT_LISTEN_ADDRESS: &str = "127.0.0.1:8080";
+/// How many steps a single run may take, so a runaway program can't hang
+/// the server
+const RUN_LIMIT: usize = 10_000_000;
+/// The most bytes a request body or WebSocket message may have
+const BODY_LIMIT: usize = 1 << 20;
+/// How long a client may take over sending a request
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Playground {
+    instruction_set: InstructionSet,
+    engine: Engine,
+    /// What stopped the last run or step, if anything
+    stopped: Option<String>,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    body: Vec<u8>,
+    /// The key a client asking to switch to a WebSocket sent
+    websocket_key: Option<String>,
+}
+
+/// Serve a small HTTP API for driving one program from a web page: load
+/// source, step, undo, run, give input and look at the state. Every
+/// response is the state as JSON, which frontends poll, or which a
+/// WebSocket at any path gets back for each request it sends as a JSON
+/// message like `{"method": "POST", "path": "/step?count=10", "body": ""}`.
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    let source = match args.positional() {
+        [] => String::new(),
+        [filepath] => std::fs::read_to_string(filepath)?,
+        _ => {
+            return Err(anyhow!(
+                "usage: plaque serve [program] [--listen <address>]"
+            ))
+        }
+    };
+    let listen_address = args.value("listen").unwrap_or(DEFAULT_LISTEN_ADDRESS);
+
+    let listener = TcpListener::bind(listen_address)?;
+    eprintln!(
+        "plaque: playground listening on http://{}",
+        listener.local_addr()?
+    );
+
+    let playground = Arc::new(Mutex::new(Playground {
+        engine: Engine::new(instruction_set.parse(&source)),
+        instruction_set,
+        stopped: None,
+    }));
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let playground = playground.clone();
+        // a client of its own for each thread, so a slow one can't hold up
+        // the rest, and one bad client shouldn't take the playground down
+        thread::spawn(move || {
+            if let Err(e) = serve(&playground, stream) {
+                eprintln!("plaque: {e}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn serve(playground: &Mutex<Playground>, stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let request = match Request::read(&mut reader) {
+        Ok(Some(request)) => request,
+        Ok(None) => return Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            let error = json!({ "error": e.to_string() });
+            return respond(&mut writer, "400 Bad Request", Some(error));
+        }
+        Err(e) => return Err(e),
+    };
+
+    match &request.websocket_key {
+        // clients wait to hear the switch is made before sending anything,
+        // so nothing's left in the reader
+        Some(key) => serve_websocket(playground, reader.into_inner(), key),
+        None => {
+            let (status, body) = playground.lock().unwrap().handle(&request);
+            respond(&mut writer, status, body)
+        }
+    }
+}
+
+fn serve_websocket(
+    playground: &Mutex<Playground>,
+    mut stream: TcpStream,
+    key: &str,
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        websocket::accept_key(key)
+    )?;
+    stream.flush()?;
+    // frontends can sit watching for as long as they like between requests
+    stream.set_read_timeout(None)?;
+
+    while let Some(message) = websocket::read_message(&mut stream, BODY_LIMIT)? {
+        let body = match Request::from_message(&message) {
+            Some(request) => playground.lock().unwrap().handle(&request).1,
+            None => Some(json!({ "error": "not a request" })),
+        };
+        websocket::write_text(&mut stream, &body.unwrap_or_default().to_string())?;
+    }
+    Ok(())
+}
+
+fn respond(writer: &mut impl Write, status: &str, body: Option<Value>) -> io::Result<()> {
+    let body = body.map(|body| body.to_string()).unwrap_or_default();
+    write!(
+        writer,
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Access-Control-Allow-Methods: GET, POST, OPTIONS\r\n\
+         Access-Control-Allow-Headers: Content-Type\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    writer.flush()
+}
+
+impl Playground {
+    fn handle(&mut self, request: &Request) -> (&'static str, Option<Value>) {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("OPTIONS", _) => ("204 No Content", None),
+            ("GET", "/state") => ("200 OK", Some(self.state())),
+            ("POST", "/load") => {
+                let source = String::from_utf8_lossy(&request.body);
+                self.engine = Engine::new(self.instruction_set.parse(&source));
+                self.stopped = None;
+                ("200 OK", Some(self.state()))
+            }
+            ("POST", "/input") => {
+                self.engine.input.extend_from_slice(&request.body);
+                ("200 OK", Some(self.state()))
+            }
+            ("POST", "/step") => {
+                let count = request.count().unwrap_or(1);
+                self.step(count);
+                ("200 OK", Some(self.state()))
+            }
+            ("POST", "/run") => {
+                self.step(RUN_LIMIT);
+                ("200 OK", Some(self.state()))
+            }
+            ("POST", "/undo") => {
+                let count = request.count().unwrap_or(1);
+                self.stopped = None;
+                for _ in 0..count {
+                    if let Err(Exception::Error(error)) = self.engine.undo() {
+                        self.stopped = Some(error.message());
+                        break;
+                    }
+                }
+                ("200 OK", Some(self.state()))
+            }
+            ("POST", "/reset") => {
+                self.engine.reset();
+                self.stopped = None;
+                ("200 OK", Some(self.state()))
+            }
+            _ => (
+                "404 Not Found",
+                Some(
+                    json!({ "error": format!("no route for {} {}", request.method, request.path) }),
+                ),
+            ),
+        }
+    }
+
+    /// Step up to a number of times, stopping early on any exception
+    fn step(&mut self, count: usize) {
+        self.stopped = None;
+        for _ in 0..count {
+            if self.engine.instruction_pointer == InstructionPointer::End {
+                break;
+            }
+            match self.engine.step() {
+                Ok(()) => {}
+                Err(Exception::Breakpoint) => {
+                    self.stopped = Some("breakpoint".to_string());
+                    break;
+                }
+                Err(Exception::RequestingInput) => {
+                    self.stopped = Some("input".to_string());
+                    break;
+                }
+                Err(Exception::Error(error)) => {
+                    self.stopped = Some(error.message());
+                    break;
+                }
+            }
+        }
+    }
+
+    fn state(&self) -> Value {
+        let engine = &self.engine;
+        let instruction_pointer = match engine.instruction_pointer {
+            InstructionPointer::Start => json!("start"),
+            InstructionPointer::End => json!("end"),
+            InstructionPointer::Index(i) => json!(i),
+        };
+
+        json!({
+            "finished": engine.instruction_pointer == InstructionPointer::End,
+            "stopped": self.stopped,
+            "steps": engine.history.len(),
+            "instruction_pointer": instruction_pointer,
+            "instructions": engine.instructions.iter().map(|i| i.symbol).collect::<String>(),
+            "tape_pointer": engine.tape_pointer,
+            "tape": engine.tape.to_vec(),
+            "output": String::from_utf8_lossy(&engine.output),
+            "input_buffered": engine.input.len(),
+        })
+    }
+}
+
+impl Request {
+    fn read(reader: &mut impl BufRead) -> io::Result<Option<Request>> {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let mut parts = line.split_whitespace().map(str::to_string);
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "malformed request line",
+            ));
+        };
+        let (path, query) = parse_target(&target);
+
+        let mut content_length = 0;
+        let mut websocket_key = None;
+        loop {
+            line.clear();
+            reader.read_line(&mut line)?;
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap_or(0);
+                } else if name.eq_ignore_ascii_case("sec-websocket-key") {
+                    websocket_key = Some(value.trim().to_string());
+                }
+            }
+        }
+
+        if content_length > BODY_LIMIT {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("request body over {BODY_LIMIT} bytes"),
+            ));
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+
+        Ok(Some(Request {
+            method,
+            path,
+            query,
+            body,
+            websocket_key,
+        }))
+    }
+
+    /// A request sent over a WebSocket, as a JSON object with its method,
+    /// path and query, and body as text
+    fn from_message(message: &[u8]) -> Option<Request> {
+        let message: Value = serde_json::from_slice(message).ok()?;
+        let field = |name| message.get(name).and_then(Value::as_str);
+        let (path, query) = parse_target(field("path")?);
+        Some(Request {
+            method: field("method").unwrap_or("GET").to_string(),
+            path,
+            query,
+            body: field("body").unwrap_or_default().as_bytes().to_vec(),
+            websocket_key: None,
+        })
+    }
+
+    fn count(&self) -> Option<usize> {
+        self.query
+            .iter()
+            .find(|(name, _)| name == "count")
+            .and_then(|(_, value)| value.parse().ok())
+    }
+}
+
+/// The path of a request target, and the pairs in its query
+fn parse_target(target: &str) -> (String, Vec<(String, String)>) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    (path.to_string(), query)
+}
@@ -0,0 +1,36 @@
+use crate::analysis::stats;
+use crate::instruction::InstructionSet;
+
+use anyhow::{anyhow, Result};
+
+/// Print counts describing each program's shape
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    if args.is_empty() {
+        return Err(anyhow!("usage: plaque stat <program>..."));
+    }
+
+    for (i, filepath) in args.iter().enumerate() {
+        let instructions = instruction_set.parse(&std::fs::read_to_string(filepath)?);
+        let stats = stats::stats(&instructions);
+
+        if i > 0 {
+            println!();
+        }
+        println!("{filepath}");
+        println!("instructions: {}", stats.instructions);
+        println!(
+            "loops: {} (nested up to {} deep)",
+            stats.loops, stats.max_depth
+        );
+        println!("run-length encoded: {}", stats.rle_size);
+        println!("symbol  count  longest run");
+        for (symbol, count) in &stats.counts {
+            println!(
+                "{symbol:<6}  {count:>5}  {:>11}",
+                stats.longest_runs[symbol]
+            );
+        }
+    }
+
+    Ok(())
+}
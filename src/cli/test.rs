@@ -0,0 +1,81 @@
+use crate::cli::Args;
+use crate::engine::history::HistoryPolicy;
+use crate::engine::Engine;
+use crate::flavor::overflow;
+use crate::grade::{self, Outcome, Suite};
+use crate::instruction::InstructionSet;
+
+use anyhow::{anyhow, Result};
+use std::path::Path;
+
+const USAGE: &str = "usage: plaque test <suite.toml> [<program>...] [--json]";
+
+/// Grade programs against a suite of cases, each giving a program input
+/// and saying what it should output, for marking exercises. Programs are
+/// the ones named after the suite, or the suite's own `program`. Prints
+/// whether each case passed, or with `--json`, a report for other tools,
+/// and fails if any case did.
+pub fn run(args: &[String], mut instruction_set: InstructionSet) -> Result<()> {
+    let args = Args::parse(args, &["json"])?;
+    let [suite_path, programs @ ..] = args.positional() else {
+        return Err(anyhow!(USAGE));
+    };
+    let suite = std::fs::read_to_string(suite_path)?
+        .parse::<Suite>()
+        .map_err(|error| anyhow!("{suite_path}: {error}"))?;
+
+    if let Some(eof) = suite.eof {
+        instruction_set.insert(overflow::input(eof));
+    }
+
+    let programs = match (programs, &suite.program) {
+        ([], Some(program)) => {
+            // relative to the suite, like paths in a manifest
+            let directory = Path::new(suite_path).parent().unwrap_or(Path::new(""));
+            vec![directory.join(program).display().to_string()]
+        }
+        ([], None) => return Err(anyhow!("{suite_path} names no program to test\n{USAGE}")),
+        (programs, _) => programs.to_vec(),
+    };
+
+    let mut graded = vec![];
+    for program in programs {
+        let code = std::fs::read_to_string(&program)?;
+        let mut engine = Engine::new(instruction_set.parse(&code));
+        engine.history.set_policy(HistoryPolicy::Off);
+        graded.push((program, suite.grade(&engine)));
+    }
+
+    if args.switch("json") {
+        print!("{}", grade::json_report(&graded));
+    } else {
+        for (program, outcomes) in &graded {
+            if graded.len() > 1 {
+                println!("{program}:");
+            }
+            report(outcomes);
+        }
+    }
+
+    let failed = graded
+        .iter()
+        .flat_map(|(_, outcomes)| outcomes)
+        .filter(|outcome| !outcome.passed())
+        .count();
+    match failed {
+        0 => Ok(()),
+        1 => Err(anyhow!("1 case failed")),
+        failed => Err(anyhow!("{failed} cases failed")),
+    }
+}
+
+fn report(outcomes: &[Outcome]) {
+    for outcome in outcomes {
+        match outcome.passed() {
+            true => println!("pass  {} ({} steps)", outcome.name, outcome.steps),
+            false => println!("FAIL  {}: {}", outcome.name, outcome.verdict),
+        }
+    }
+    let passed = outcomes.iter().filter(|outcome| outcome.passed()).count();
+    println!("{passed} of {} passed", outcomes.len());
+}
@@ -0,0 +1,57 @@
+use crate::cli::Args;
+use crate::engine::TapeModel;
+use crate::flavor::Eof;
+use crate::instruction::InstructionSet;
+use crate::transpile::{self, COptions, WasmOptions};
+
+use anyhow::{anyhow, Result};
+
+const USAGE: &str = "usage: plaque transpile <program> [--to rust|c|wasm] \
+    [--eof request|zero|unchanged|max] [--tape unbounded|sparse|<cells>] \
+    [--cell-width 8|16|32] [--tape-size <cells>]";
+
+/// Print a standalone program in another language equivalent to a loaded one.
+/// Rust programs can have any tape, while C and WebAssembly ones have a
+/// fixed size tape, `--tape-size` cells long. `--cell-width` is only for C.
+pub fn run(args: &[String], instruction_set: InstructionSet) -> Result<()> {
+    let args = Args::parse(args, &[])?;
+    let [filepath] = args.positional() else {
+        return Err(anyhow!(USAGE));
+    };
+    let eof: Eof = args.parsed("eof")?.unwrap_or_default();
+    let tape: Option<TapeModel> = args.parsed("tape")?;
+    let tape_size = |default| match (tape, args.parsed("tape-size")?) {
+        (None, size) => Ok(size.unwrap_or(default)),
+        (Some(TapeModel::Fixed(cells)), None) => Ok(cells),
+        (Some(TapeModel::Fixed(_)), Some(_)) => {
+            Err(anyhow!("give --tape or --tape-size, not both"))
+        }
+        (Some(_), _) => Err(anyhow!("only Rust programs can have a tape that grows")),
+    };
+
+    let instructions = instruction_set.parse(&std::fs::read_to_string(filepath)?);
+    let code = match args.value("to").unwrap_or("rust") {
+        "rust" => transpile::to_rust(&instructions, eof, tape.unwrap_or_default())?,
+        "c" => {
+            let default = COptions::default();
+            let options = COptions {
+                cell_width: args.parsed("cell-width")?.unwrap_or(default.cell_width),
+                tape_size: tape_size(default.tape_size)?,
+                eof,
+            };
+            transpile::to_c(&instructions, &options)?
+        }
+        "wasm" => {
+            let default = WasmOptions::default();
+            let options = WasmOptions {
+                tape_size: tape_size(default.tape_size)?,
+                eof,
+            };
+            transpile::to_wasm(&instructions, &options)?
+        }
+        language => return Err(anyhow!("can't transpile to {language}\n{USAGE}")),
+    };
+    print!("{code}");
+
+    Ok(())
+}
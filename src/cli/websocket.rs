@@ -0,0 +1,154 @@
+use base64::Engine as _;
+use std::io::{self, Read, Write};
+
+/// What's appended to a client's key before hashing it to accept the
+/// connection, from RFC 6455
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const CONTINUATION: u8 = 0x0;
+const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xA;
+
+/// The `Sec-WebSocket-Accept` header answering a client's
+/// `Sec-WebSocket-Key`
+pub fn accept_key(key: &str) -> String {
+    let digest = sha1_smol::Sha1::from(format!("{}{ACCEPT_GUID}", key.trim())).digest();
+    base64::engine::general_purpose::STANDARD.encode(digest.bytes())
+}
+
+/// The next text or binary message from the client, or `None` once it
+/// closes the connection. Pings are answered along the way, and messages
+/// over `limit` bytes are an error.
+pub fn read_message(stream: &mut (impl Read + Write), limit: usize) -> io::Result<Option<Vec<u8>>> {
+    let mut message = vec![];
+    loop {
+        let mut header = [0; 2];
+        match stream.read_exact(&mut header) {
+            Ok(()) => {}
+            // hanging up between messages ends the connection as well as
+            // closing it does
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof && message.is_empty() => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e),
+        }
+        let (fin, opcode) = (header[0] & 0x80 != 0, header[0] & 0x0F);
+        let masked = header[1] & 0x80 != 0;
+        let length = match header[1] & 0x7F {
+            126 => {
+                let mut length = [0; 2];
+                stream.read_exact(&mut length)?;
+                u64::from(u16::from_be_bytes(length))
+            }
+            127 => {
+                let mut length = [0; 8];
+                stream.read_exact(&mut length)?;
+                u64::from_be_bytes(length)
+            }
+            length => u64::from(length),
+        };
+        if length > (limit - message.len()) as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("message over {limit} bytes"),
+            ));
+        }
+        let mut mask = [0; 4];
+        if masked {
+            stream.read_exact(&mut mask)?;
+        }
+        let mut payload = vec![0; length as usize];
+        stream.read_exact(&mut payload)?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+
+        match opcode {
+            CLOSE => {
+                write_frame(stream, CLOSE, &[])?;
+                return Ok(None);
+            }
+            PING => write_frame(stream, PONG, &payload)?,
+            PONG => {}
+            TEXT | BINARY | CONTINUATION => {
+                message.extend(payload);
+                if fin {
+                    return Ok(Some(message));
+                }
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown opcode {opcode:#x}"),
+                ))
+            }
+        }
+    }
+}
+
+/// Send a text message to the client
+pub fn write_text(stream: &mut impl Write, text: &str) -> io::Result<()> {
+    write_frame(stream, TEXT, text.as_bytes())
+}
+
+fn write_frame(stream: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        length @ 0..=125 => frame.push(length as u8),
+        length @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend((length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(127);
+            frame.extend((length as u64).to_be_bytes());
+        }
+    }
+    frame.extend(payload);
+    stream.write_all(&frame)?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn keys_are_accepted_as_the_rfc_says() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn masked_fragments_make_one_message() {
+        let mask = [1, 2, 3, 4];
+        let masked = |bytes: &[u8]| -> Vec<u8> {
+            bytes
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ mask[i % 4])
+                .collect()
+        };
+        let mut frames = vec![TEXT, 0x80 | 3];
+        frames.extend(mask);
+        frames.extend(masked(b"ste"));
+        frames.extend([0x80 | CONTINUATION, 0x80 | 1]);
+        frames.extend(mask);
+        frames.extend(masked(b"p"));
+        frames.extend([0x80 | CLOSE, 0x80]);
+        frames.extend(mask);
+
+        let mut stream = Cursor::new(frames);
+        assert_eq!(
+            read_message(&mut stream, 16).unwrap(),
+            Some(b"step".to_vec())
+        );
+        assert_eq!(read_message(&mut stream, 16).unwrap(), None);
+    }
+}
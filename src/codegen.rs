@@ -0,0 +1,73 @@
+use alloc::string::String;
+
+/// Code that prints some text, using the first cell for each byte in turn
+/// and the second as a loop counter for big jumps between them. Cells wrap
+/// at 256 like the engine's, so each jump goes whichever way round is
+/// shorter.
+pub fn text_to_bf(text: &str) -> String {
+    let mut code = String::new();
+    let mut cell = 0u8;
+    for &byte in text.as_bytes() {
+        code.push_str(&shortest_change(byte.wrapping_sub(cell) as i8 as isize));
+        code.push('.');
+        cell = byte;
+    }
+    code
+}
+
+/// The shortest code changing the first cell by an amount, either directly
+/// or as a multiple counted down in the second cell plus the rest
+fn shortest_change(delta: isize) -> String {
+    let symbol = if delta < 0 { '-' } else { '+' };
+    let direct = repeat(symbol, delta.unsigned_abs());
+
+    (2..=delta.unsigned_abs())
+        .filter_map(|times| {
+            let (per, rest) = (delta.unsigned_abs() / times, delta.unsigned_abs() % times);
+            (per > 1).then(|| {
+                let mut code = String::from(">");
+                code.push_str(&repeat('+', times));
+                code.push_str("[<");
+                code.push_str(&repeat(symbol, per));
+                code.push_str(">-]<");
+                code.push_str(&repeat(symbol, rest));
+                code
+            })
+        })
+        .chain([direct])
+        .min_by_key(String::len)
+        .unwrap_or_default()
+}
+
+fn repeat(symbol: char, times: usize) -> String {
+    core::iter::repeat_n(symbol, times).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::flavor::overflow;
+
+    fn run(code: &str) -> alloc::vec::Vec<u8> {
+        let mut engine = Engine::new(overflow::instruction_set().parse(code));
+        while engine.step().is_ok() {}
+        engine.output
+    }
+
+    #[test]
+    fn generated_code_prints_the_text() {
+        for text in ["Hello, World!\n", "", "\0\u{ff}☃ snow", "zzzzaaaa"] {
+            assert_eq!(run(&text_to_bf(text)), text.as_bytes());
+        }
+    }
+
+    #[test]
+    fn generated_code_is_short() {
+        assert_eq!(text_to_bf("\u{2}\u{1}"), "++.-.");
+        // wrapping round is shorter than counting up to 255
+        assert_eq!(shortest_change(255u8 as i8 as isize), "-");
+        // against 366 changing the cell one at a time
+        assert_eq!(text_to_bf("Hello, World!").len(), 185);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::engine::builder::EngineBuilder;
+use crate::engine::{Engine, TapeModel};
+use crate::flavor::Dialect;
+
+use alloc::format;
+use alloc::string::String;
+
+/// The manifest a project's directory is looked in for
+pub const MANIFEST: &str = "plaque.toml";
+
+/// How to debug a project, read from the `key = value` lines of a
+/// `plaque.toml`:
+///
+/// ```toml
+/// entry = "src/main.bf"
+/// dialect = "brainfork"
+/// tape = 30000         # or "unbounded" or "sparse"
+/// cell-width = 8
+/// input = "tests/input.txt"
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Config {
+    /// The program to debug
+    pub entry: Option<String>,
+    pub dialect: Dialect,
+    pub tape: TapeModel,
+    /// Bits in each cell, of which the engine only has 8, though programs
+    /// can be transpiled with wider ones
+    pub cell_width: u32,
+    /// A file to read the program's input from
+    pub input: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            entry: None,
+            dialect: Dialect::default(),
+            tape: TapeModel::default(),
+            cell_width: 8,
+            input: None,
+        }
+    }
+}
+
+pub(crate) enum Value {
+    String(String),
+    Integer(usize),
+}
+
+impl Value {
+    pub(crate) fn string(self, key: &str) -> Result<String, String> {
+        match self {
+            Value::String(string) => Ok(string),
+            Value::Integer(_) => Err(format!("{key} should be a string")),
+        }
+    }
+
+    pub(crate) fn integer(self, key: &str) -> Result<usize, String> {
+        match self {
+            Value::Integer(integer) => Ok(integer),
+            Value::String(_) => Err(format!("{key} should be a number")),
+        }
+    }
+}
+
+impl core::str::FromStr for Config {
+    type Err = String;
+
+    fn from_str(manifest: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+        for (line_number, line) in manifest.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| format!("{MANIFEST}:{}: {message}", line_number + 1);
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("expected key = value, found {line}")))?;
+            let key = key.trim();
+            let value = parse_value(value.trim()).map_err(error)?;
+            match key {
+                "entry" => config.entry = Some(value.string(key).map_err(error)?),
+                "input" => config.input = Some(value.string(key).map_err(error)?),
+                "dialect" => {
+                    config.dialect = value.string(key).map_err(error)?.parse().map_err(error)?
+                }
+                "tape" => {
+                    config.tape = match value {
+                        Value::Integer(size) => TapeModel::Fixed(size),
+                        Value::String(model) if model == "unbounded" => TapeModel::Unbounded,
+                        Value::String(model) if model == "sparse" => TapeModel::Sparse,
+                        Value::String(model) => {
+                            return Err(error(format!(
+                                "invalid tape {model}, expected a size, \"unbounded\" or \"sparse\""
+                            )))
+                        }
+                    }
+                }
+                "cell-width" => {
+                    config.cell_width = match value {
+                        Value::Integer(bits @ (8 | 16 | 32)) => bits as u32,
+                        _ => return Err(error(String::from("cell-width should be 8, 16 or 32"))),
+                    }
+                }
+                _ => return Err(error(format!("unknown key {key}"))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// A quoted string or a whole number, with any comment after it
+pub(crate) fn parse_value(text: &str) -> Result<Value, String> {
+    let Some(quoted) = text.strip_prefix('"') else {
+        let number = text.split('#').next().unwrap_or_default().trim();
+        return number
+            .replace('_', "")
+            .parse()
+            .map(Value::Integer)
+            .map_err(|_| format!("invalid value {number}"));
+    };
+
+    let mut string = String::new();
+    let mut characters = quoted.chars();
+    loop {
+        match characters.next() {
+            None => return Err(String::from("unclosed string")),
+            Some('"') => break,
+            Some('\\') => match characters.next() {
+                Some('n') => string.push('\n'),
+                Some('t') => string.push('\t'),
+                Some(escaped @ ('"' | '\\')) => string.push(escaped),
+                _ => return Err(String::from("invalid escape in string")),
+            },
+            Some(character) => string.push(character),
+        }
+    }
+    match characters.as_str().trim() {
+        rest if rest.is_empty() || rest.starts_with('#') => Ok(Value::String(string)),
+        rest => Err(format!("unexpected {rest} after string")),
+    }
+}
+
+impl Config {
+    /// Read a manifest, with its paths made relative to the directory it's
+    /// in rather than to it
+    #[cfg(feature = "std")]
+    pub fn load(path: &std::path::Path) -> Result<Config, String> {
+        let manifest = std::fs::read_to_string(path)
+            .map_err(|error| format!("couldn't read {}: {error}", path.display()))?;
+        let mut config = manifest.parse::<Config>()?;
+        let directory = path.parent().unwrap_or(std::path::Path::new(""));
+        for path in [&mut config.entry, &mut config.input].into_iter().flatten() {
+            *path = directory.join(&*path).display().to_string();
+        }
+        Ok(config)
+    }
+
+    /// A builder for engines with the dialect and tape this describes
+    pub fn builder(&self) -> EngineBuilder {
+        Engine::builder()
+            .dialect(self.dialect.instruction_set())
+            .tape(self.tape)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifests_set_what_they_mention() {
+        let manifest = "# a project\n\
+            entry = \"src/main.bf\"\n\
+            dialect = \"brainfork\"  # with forks\n\
+            tape = 30_000\n";
+        let config = manifest.parse::<Config>().unwrap();
+        assert_eq!(
+            config,
+            Config {
+                entry: Some(String::from("src/main.bf")),
+                dialect: Dialect::Brainfork,
+                tape: TapeModel::Fixed(30_000),
+                ..Config::default()
+            }
+        );
+
+        let engine = config.builder().code("Y>").build().unwrap();
+        assert_eq!(engine.instructions.len(), 2);
+        assert_eq!(engine.tape_model, TapeModel::Fixed(30_000));
+    }
+
+    #[test]
+    fn bad_manifests_say_where() {
+        let error = |manifest: &str| manifest.parse::<Config>().unwrap_err();
+        assert_eq!(
+            error("\nentry = 3"),
+            "plaque.toml:2: entry should be a string"
+        );
+        assert_eq!(error("tape = \"infinite"), "plaque.toml:1: unclosed string");
+        assert_eq!(
+            error("cell-width = 12"),
+            "plaque.toml:1: cell-width should be 8, 16 or 32"
+        );
+        assert_eq!(error("colour = 1"), "plaque.toml:1: unknown key colour");
+    }
+}
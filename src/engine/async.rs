@@ -0,0 +1,162 @@
+use crate::engine::{Engine, Exception, InstructionPointer};
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// How many steps a run takes before letting other tasks have a turn
+pub const DEFAULT_YIELD_EVERY: usize = 4096;
+
+/// Asks a run to stop at its next step. Clones share the same flag, so keep
+/// one to cancel with and hand another to the run.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    Finished,
+    Breakpoint,
+    Cancelled,
+}
+
+/// Drives an engine from async code, reading its input from an `AsyncRead`
+/// as the program asks for it.
+#[derive(Debug)]
+pub struct AsyncEngine<R> {
+    pub engine: Engine,
+    pub input: R,
+    pub yield_every: usize,
+}
+
+impl<R: AsyncRead + Unpin> AsyncEngine<R> {
+    pub fn new(engine: Engine, input: R) -> AsyncEngine<R> {
+        AsyncEngine {
+            engine,
+            input,
+            yield_every: DEFAULT_YIELD_EVERY,
+        }
+    }
+
+    /// Run until the program finishes, hits a breakpoint or is cancelled.
+    /// Running out of input fails with `RequestingInput`, as the engine does.
+    pub async fn run(&mut self, cancel: &CancellationToken) -> Result<Outcome, Exception> {
+        let mut steps: usize = 0;
+        loop {
+            if cancel.is_cancelled() {
+                return Ok(Outcome::Cancelled);
+            }
+            if self.engine.instruction_pointer == InstructionPointer::End {
+                return Ok(Outcome::Finished);
+            }
+
+            match self.engine.step() {
+                Ok(()) => {}
+                Err(Exception::Breakpoint) => return Ok(Outcome::Breakpoint),
+                Err(Exception::RequestingInput) => self.read_input().await?,
+                Err(e) => return Err(e),
+            }
+
+            steps += 1;
+            if steps.is_multiple_of(self.yield_every.max(1)) {
+                YieldNow(false).await;
+            }
+        }
+    }
+
+    async fn read_input(&mut self) -> Result<(), Exception> {
+        let mut buffer = [0; 1024];
+        let read = self
+            .input
+            .read(&mut buffer)
+            .await
+            .map_err(|e| Exception::error(e.to_string()))?;
+        if read == 0 {
+            return Exception::RequestingInput.result();
+        }
+        self.engine.input.extend_from_slice(&buffer[..read]);
+        Ok(())
+    }
+}
+
+/// Pending once, so whatever's polling gets to run something else first
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    use std::task::{Wake, Waker};
+    use std::thread::{self, Thread};
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut context = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn run_reads_input_as_it_goes() {
+        let engine = Engine::new(overflow::instruction_set().parse(",[.,]"));
+        let mut engine = AsyncEngine::new(engine, &b"hi"[..]);
+        engine.yield_every = 1;
+
+        let result = block_on(engine.run(&CancellationToken::new()));
+        assert_eq!(result, Err(Exception::RequestingInput));
+        assert_eq!(engine.engine.output, b"hi");
+    }
+
+    #[test]
+    fn cancelled_run_stops() {
+        let engine = Engine::new(overflow::instruction_set().parse("+[]"));
+        let mut engine = AsyncEngine::new(engine, &b""[..]);
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+
+        assert_eq!(block_on(engine.run(&cancel)), Ok(Outcome::Cancelled));
+    }
+}
@@ -0,0 +1,270 @@
+use crate::engine::history::{History, HistoryPolicy};
+use crate::engine::input::InputSource;
+use crate::engine::{Engine, EngineError, Exception, TapeModel};
+use crate::flavor::{overflow, Eof};
+use crate::instruction::{Instruction, InstructionSet};
+use crate::tape::Tape;
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[derive(Clone, Debug)]
+enum Source {
+    Code(String),
+    Instructions(Vec<Instruction>),
+}
+
+/// Configures an engine one setting at a time, for when `Engine::new` with
+/// the defaults isn't enough. Cells are always bytes, and output always
+/// collects in `Engine::output` for the host to take with `take_output`.
+#[derive(Clone, Debug)]
+pub struct EngineBuilder {
+    dialect: InstructionSet,
+    eof: Option<Eof>,
+    source: Source,
+    tape_model: TapeModel,
+    history: HistoryPolicy,
+    verify_undo: bool,
+    log_cell_writes: bool,
+    tape_image: Vec<u8>,
+    seed: u64,
+    step_budget: Option<usize>,
+    input: InputSource,
+}
+
+impl Default for EngineBuilder {
+    fn default() -> EngineBuilder {
+        EngineBuilder {
+            dialect: overflow::instruction_set(),
+            eof: None,
+            source: Source::Instructions(vec![]),
+            tape_model: TapeModel::default(),
+            history: HistoryPolicy::default(),
+            verify_undo: false,
+            log_cell_writes: false,
+            tape_image: vec![],
+            seed: crate::engine::random::DEFAULT_SEED,
+            step_budget: None,
+            input: InputSource::Bytes(vec![]),
+        }
+    }
+}
+
+impl Engine {
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::default()
+    }
+}
+
+impl EngineBuilder {
+    /// The instruction set code is parsed with, overflow by default
+    pub fn dialect(mut self, dialect: InstructionSet) -> EngineBuilder {
+        self.dialect = dialect;
+        self
+    }
+
+    /// What `,` does once the input runs out, replacing the dialect's own `,`
+    /// when parsing code
+    pub fn eof(mut self, eof: Eof) -> EngineBuilder {
+        self.eof = Some(eof);
+        self
+    }
+
+    pub fn code(mut self, code: &str) -> EngineBuilder {
+        self.source = Source::Code(code.to_string());
+        self
+    }
+
+    /// Instructions that are already parsed, which the dialect and EOF
+    /// policy don't apply to
+    pub fn instructions(mut self, instructions: Vec<Instruction>) -> EngineBuilder {
+        self.source = Source::Instructions(instructions);
+        self
+    }
+
+    pub fn tape(mut self, tape_model: TapeModel) -> EngineBuilder {
+        self.tape_model = tape_model;
+        self
+    }
+
+    /// How much history to keep for undoing, all of it by default
+    pub fn history(mut self, policy: HistoryPolicy) -> EngineBuilder {
+        self.history = policy;
+        self
+    }
+
+    /// Cells for the program to start with, from the first on. `build` fails
+    /// with `TapeOverflow` if they run past the end of a fixed size tape.
+    pub fn tape_image(mut self, image: Vec<u8>) -> EngineBuilder {
+        self.tape_image = image;
+        self
+    }
+
+    /// Start with the contents of a file as the tape image
+    #[cfg(feature = "std")]
+    pub fn tape_from_file<P: AsRef<std::path::Path>>(
+        self,
+        path: P,
+    ) -> std::io::Result<EngineBuilder> {
+        Ok(self.tape_image(std::fs::read(path)?))
+    }
+
+    /// Check each undo restores the state from before its step, off by
+    /// default
+    pub fn verify_undo(mut self, verify: bool) -> EngineBuilder {
+        self.verify_undo = verify;
+        self
+    }
+
+    /// Log every change to every cell, off by default
+    pub fn log_cell_writes(mut self, log: bool) -> EngineBuilder {
+        self.log_cell_writes = log;
+        self
+    }
+
+    /// The seed for everything nondeterministic, see `Engine::set_seed`
+    pub fn seed(mut self, seed: u64) -> EngineBuilder {
+        self.seed = seed;
+        self
+    }
+
+    /// The most steps any run or command like `run_until_output` takes,
+    /// see `Engine::set_step_budget`
+    pub fn step_budget(mut self, steps: usize) -> EngineBuilder {
+        self.step_budget = Some(steps);
+        self
+    }
+
+    /// Where the input comes from, bytes given up front or a source like
+    /// `InputSource::Stdin`, read when the engine's built
+    pub fn input(mut self, input: impl Into<InputSource>) -> EngineBuilder {
+        self.input = input.into();
+        self
+    }
+
+    /// The engine, unless the dialect doesn't hang together, a bracket in
+    /// the program has no match, the input can't be read or the tape image
+    /// doesn't fit on the tape
+    pub fn build(mut self) -> Result<Engine, EngineError> {
+        let instructions = match self.take_source() {
+            Source::Code(code) => self.dialect_with_eof().try_parse(&code)?,
+            Source::Instructions(instructions) => instructions,
+        };
+        let input = self.input.clone();
+        let mut engine = self.build_with()?;
+        engine.load_instructions(instructions)?;
+        engine.set_input(&input).map_err(EngineError::Other)?;
+        Ok(engine)
+    }
+
+    fn take_source(&mut self) -> Source {
+        core::mem::replace(&mut self.source, Source::Instructions(vec![]))
+    }
+
+    /// The dialect with the EOF policy asked for
+    fn dialect_with_eof(&self) -> InstructionSet {
+        let mut dialect = self.dialect.clone();
+        if let Some(eof) = self.eof {
+            dialect.insert(overflow::input(eof));
+        }
+        dialect
+    }
+
+    fn build_with(self) -> Result<Engine, EngineError> {
+        let mut engine = Engine::new(vec![]);
+        engine.tape_model = self.tape_model;
+        engine.tape = Tape::new(self.tape_model);
+        engine.history = History::new(self.history);
+        engine.set_verify_undo(self.verify_undo);
+        engine.set_log_cell_writes(self.log_cell_writes);
+        engine.set_seed(self.seed);
+        engine.set_step_budget(self.step_budget);
+        if !self.tape_image.is_empty() {
+            engine
+                .load_tape(&self.tape_image, 0)
+                .map_err(|exception| match exception {
+                    Exception::Error(error) => error,
+                    other => EngineError::Other(other.to_string()),
+                })?;
+        }
+        Ok(engine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_applies_the_eof_policy() {
+        let mut engine = Engine::builder()
+            .code("+++,")
+            .eof(Eof::Max)
+            .input(vec![])
+            .build()
+            .unwrap();
+        while engine.step().is_ok() {}
+        assert_eq!(engine.tape, vec![255]);
+    }
+
+    #[test]
+    fn builds_refuse_unmatched_brackets() {
+        let built = Engine::builder()
+            .code("+[-")
+            .tape(TapeModel::Sparse)
+            .build();
+        assert_eq!(built, Err(EngineError::UnmatchedBracket { index: 1 }));
+        let engine = Engine::builder()
+            .code("+[-]")
+            .tape(TapeModel::Sparse)
+            .build();
+        assert_eq!(
+            engine.map(|engine| engine.tape_model),
+            Ok(TapeModel::Sparse)
+        );
+    }
+
+    #[test]
+    fn input_comes_from_any_source() {
+        let mut engine = Engine::builder()
+            .code(",.,.,.")
+            .input(InputSource::Cycle(b"ab".to_vec()))
+            .build()
+            .unwrap();
+        while engine.step().is_ok() {}
+        assert_eq!(engine.output, b"aba".to_vec());
+
+        let built = Engine::builder()
+            .input(InputSource::Hex("4".into()))
+            .build();
+        assert!(matches!(built, Err(EngineError::Other(_))));
+    }
+
+    #[test]
+    fn fixed_tape_stops_at_its_end() {
+        let mut engine = Engine::builder()
+            .code(">>")
+            .tape(TapeModel::Fixed(2))
+            .build()
+            .unwrap();
+        engine.step().unwrap();
+        engine.step().unwrap();
+        assert_eq!(
+            engine.step(),
+            Err(Exception::Error(crate::engine::EngineError::TapeOverflow))
+        );
+        assert_eq!(engine.tape_pointer, 1);
+    }
+
+    #[test]
+    fn tape_images_must_fit_the_tape() {
+        let engine = Engine::builder().tape_image(vec![1, 2, 3]).build().unwrap();
+        assert_eq!(engine.tape, vec![1, 2, 3]);
+        let built = Engine::builder()
+            .tape(TapeModel::Fixed(2))
+            .tape_image(vec![1, 2, 3])
+            .build();
+        assert_eq!(built.map(|_| ()), Err(EngineError::TapeOverflow));
+    }
+}
@@ -0,0 +1,369 @@
+use crate::engine::run::{Run, StopReason, Stops};
+use crate::engine::{Engine, EngineError, Exception, InstructionPointer};
+
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+/// How many steps a run takes at a time between checking for commands and
+/// letting anyone else look at the engine
+const CHUNK: usize = 4096;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Command {
+    Step,
+    Run,
+    Pause,
+    /// Stop before the instruction at an index whenever a run reaches it
+    SetBreakpoint(usize),
+    ClearBreakpoint(usize),
+    Input(Vec<u8>),
+    /// Stop and start the program over, with the input it's given
+    Restart(Vec<u8>),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Stop {
+    Step,
+    Breakpoint,
+    Paused,
+    Finished,
+    /// The run took longer than its stops allow
+    TimedOut,
+    /// The run came back to where it had been, so would never finish
+    InfiniteLoop,
+    Error(EngineError),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Event {
+    Stopped(Stop),
+    /// Output written since the last output event
+    Output(Vec<u8>),
+    InputNeeded,
+    /// The engine was reset, and anything shown from its last run is stale
+    Restarted,
+}
+
+/// Runs an engine on a worker thread, so frontends can keep drawing while a
+/// long run goes on. Commands go in and events come out over channels, and
+/// the engine itself can be looked at between the worker's chunks of steps.
+#[derive(Debug)]
+pub struct EngineController {
+    engine: Arc<Mutex<Engine>>,
+    commands: Option<Sender<Command>>,
+    events: Receiver<Event>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl EngineController {
+    pub fn spawn(engine: Engine) -> EngineController {
+        EngineController::spawn_with(engine, Stops::default())
+    }
+
+    /// Run the engine with a timeout, loop check or fuel for each run, as
+    /// well as the breakpoints commands set
+    pub fn spawn_with(engine: Engine, stops: Stops) -> EngineController {
+        let engine = Arc::new(Mutex::new(engine));
+        let (commands, command_receiver) = mpsc::channel();
+        let (event_sender, events) = mpsc::channel();
+
+        let mut worker = Worker {
+            written: engine.lock().unwrap().output.len(),
+            engine: engine.clone(),
+            commands: command_receiver,
+            events: event_sender,
+            stops,
+            run: None,
+        };
+        let worker = thread::spawn(move || worker.serve());
+
+        EngineController {
+            engine,
+            commands: Some(commands),
+            events,
+            worker: Some(worker),
+        }
+    }
+
+    pub fn send(&self, command: Command) {
+        if let Some(commands) = &self.commands {
+            // the worker only goes away when the controller does
+            commands.send(command).ok();
+        }
+    }
+
+    pub fn events(&self) -> &Receiver<Event> {
+        &self.events
+    }
+
+    /// The engine, once the worker is between steps
+    pub fn engine(&self) -> MutexGuard<'_, Engine> {
+        self.engine.lock().unwrap()
+    }
+}
+
+impl Drop for EngineController {
+    fn drop(&mut self) {
+        // hanging up is what tells the worker to finish
+        self.commands = None;
+        if let Some(worker) = self.worker.take() {
+            worker.join().ok();
+        }
+    }
+}
+
+struct Worker {
+    engine: Arc<Mutex<Engine>>,
+    commands: Receiver<Command>,
+    events: Sender<Event>,
+    stops: Stops,
+    /// The run going on, kept across chunks so the engine's step budget
+    /// counts all of it
+    run: Option<Run>,
+    written: usize,
+}
+
+impl Worker {
+    fn serve(&mut self) {
+        loop {
+            let running = self.run.is_some();
+            let command = if running {
+                match self.commands.try_recv() {
+                    Ok(command) => Some(command),
+                    Err(TryRecvError::Empty) => None,
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            } else {
+                match self.commands.recv() {
+                    Ok(command) => Some(command),
+                    Err(_) => return,
+                }
+            };
+
+            match command {
+                Some(Command::Step) => {
+                    self.run = None;
+                    let event = self.advance().unwrap_or(Event::Stopped(Stop::Step));
+                    self.stop(event);
+                }
+                Some(Command::Run) if !running => {
+                    // a run starting on a breakpoint gets past it first
+                    self.run = Some(Run::continuing(self.stops.clone()));
+                }
+                Some(Command::Pause) if running => {
+                    self.run = None;
+                    self.stop(Event::Stopped(Stop::Paused));
+                }
+                Some(Command::SetBreakpoint(index)) => {
+                    self.stops.breakpoints.insert(index);
+                    self.update_run();
+                }
+                Some(Command::ClearBreakpoint(index)) => {
+                    self.stops.breakpoints.remove(&index);
+                    self.update_run();
+                }
+                Some(Command::Input(input)) => {
+                    self.engine.lock().unwrap().input.extend(input);
+                }
+                Some(Command::Restart(input)) => {
+                    self.run = None;
+                    self.engine.lock().unwrap().restart_with_input(input);
+                    self.written = 0;
+                    self.events.send(Event::Restarted).ok();
+                }
+                _ => {}
+            }
+
+            if self.run.is_some() {
+                match self.run_chunk() {
+                    Some(event) => {
+                        self.run = None;
+                        self.stop(event);
+                    }
+                    None => self.flush(),
+                }
+            }
+        }
+    }
+
+    /// Have the run going on stop where the worker's stops now say
+    fn update_run(&mut self) {
+        if let Some(run) = &mut self.run {
+            run.stops = self.stops.clone();
+        }
+    }
+
+    /// Execute one instruction, skipping over the start of the program
+    fn advance(&mut self) -> Option<Event> {
+        let mut engine = self.engine.lock().unwrap();
+        if engine.instruction_pointer == InstructionPointer::Start {
+            if let Err(e) = engine.step() {
+                return Some(Self::exception(e));
+            }
+        }
+        Self::step(&mut engine)
+    }
+
+    fn run_chunk(&mut self) -> Option<Event> {
+        let mut engine = self.engine.lock().unwrap();
+        let run = self.run.as_mut()?;
+        match engine.run_for(run, CHUNK)? {
+            StopReason::Completed => Some(Event::Stopped(Stop::Finished)),
+            StopReason::Breakpoint(_) | StopReason::Watchpoint(_) => {
+                Some(Event::Stopped(Stop::Breakpoint))
+            }
+            StopReason::InputRequested => Some(Event::InputNeeded),
+            StopReason::FuelExhausted => Some(Event::Stopped(Stop::Error(
+                EngineError::FuelExhausted { steps: run.steps() },
+            ))),
+            StopReason::TimedOut => Some(Event::Stopped(Stop::TimedOut)),
+            StopReason::InfiniteLoopDetected => Some(Event::Stopped(Stop::InfiniteLoop)),
+            StopReason::Error(error) => Some(Event::Stopped(Stop::Error(error))),
+        }
+    }
+
+    /// Execute one instruction, and what that stopped on if anything
+    fn step(engine: &mut Engine) -> Option<Event> {
+        if engine.instruction_pointer == InstructionPointer::End {
+            return Some(Event::Stopped(Stop::Finished));
+        }
+        match engine.step() {
+            Ok(()) if engine.instruction_pointer == InstructionPointer::End => {
+                Some(Event::Stopped(Stop::Finished))
+            }
+            Ok(()) => None,
+            Err(e) => Some(Self::exception(e)),
+        }
+    }
+
+    fn exception(exception: Exception) -> Event {
+        match exception {
+            Exception::Breakpoint => Event::Stopped(Stop::Breakpoint),
+            Exception::RequestingInput => Event::InputNeeded,
+            Exception::Error(error) => Event::Stopped(Stop::Error(error)),
+        }
+    }
+
+    /// Report where the engine stopped, after any output it wrote getting there
+    fn stop(&mut self, event: Event) {
+        self.flush();
+        self.events.send(event).ok();
+    }
+
+    fn flush(&mut self) {
+        let output = {
+            let engine = self.engine.lock().unwrap();
+            let output = engine.output[self.written.min(engine.output.len())..].to_vec();
+            self.written = engine.output.len();
+            output
+        };
+        if !output.is_empty() {
+            self.events.send(Event::Output(output)).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    fn controller(code: &str) -> EngineController {
+        EngineController::spawn(Engine::new(overflow::instruction_set().parse(code)))
+    }
+
+    #[test]
+    fn engine_can_move_between_threads() {
+        fn send<T: Send>() {}
+        send::<Engine>();
+    }
+
+    #[test]
+    fn run_reports_output_then_input_needed() {
+        let controller = controller("+.,.");
+        controller.send(Command::Run);
+        let events = controller.events();
+        assert_eq!(events.recv(), Ok(Event::Output(vec![1])));
+        assert_eq!(events.recv(), Ok(Event::InputNeeded));
+
+        controller.send(Command::Input(b"x".to_vec()));
+        controller.send(Command::Run);
+        assert_eq!(events.recv(), Ok(Event::Output(b"x".to_vec())));
+        assert_eq!(events.recv(), Ok(Event::Stopped(Stop::Finished)));
+    }
+
+    #[test]
+    fn run_stops_at_breakpoints_and_pauses() {
+        let controller = controller("+[>+<]");
+        controller.send(Command::SetBreakpoint(2));
+        controller.send(Command::Run);
+        assert_eq!(
+            controller.events().recv(),
+            Ok(Event::Stopped(Stop::Breakpoint))
+        );
+        assert_eq!(
+            controller.engine().instruction_pointer,
+            InstructionPointer::Index(2)
+        );
+
+        controller.send(Command::ClearBreakpoint(2));
+        controller.send(Command::Run);
+        controller.send(Command::Pause);
+        assert_eq!(controller.events().recv(), Ok(Event::Stopped(Stop::Paused)));
+    }
+
+    #[test]
+    fn runs_keep_to_the_step_budget_across_chunks() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("+[]"));
+        engine.set_step_budget(Some(CHUNK * 2 + 1));
+        let controller = EngineController::spawn(engine);
+        controller.send(Command::Run);
+        assert_eq!(
+            controller.events().recv(),
+            Ok(Event::Stopped(Stop::Error(EngineError::FuelExhausted {
+                steps: CHUNK * 2 + 1
+            })))
+        );
+        assert_eq!(controller.engine().history.len(), CHUNK * 2);
+    }
+
+    #[test]
+    fn runs_say_when_they_time_out_or_loop_forever() {
+        let engine = || Engine::new(overflow::instruction_set().parse("+[]"));
+        let stops = Stops {
+            timeout: Some(std::time::Duration::ZERO),
+            ..Stops::default()
+        };
+        let controller = EngineController::spawn_with(engine(), stops);
+        controller.send(Command::Run);
+        assert_eq!(controller.events().recv(), Ok(Event::Stopped(Stop::TimedOut)));
+
+        let stops = Stops {
+            loop_check: Some(16),
+            ..Stops::default()
+        };
+        let controller = EngineController::spawn_with(engine(), stops);
+        controller.send(Command::Run);
+        assert_eq!(
+            controller.events().recv(),
+            Ok(Event::Stopped(Stop::InfiniteLoop))
+        );
+    }
+
+    #[test]
+    fn restarting_runs_again_with_new_input() {
+        let controller = controller(",.");
+        controller.send(Command::Input(b"a".to_vec()));
+        controller.send(Command::Run);
+        let events = controller.events();
+        assert_eq!(events.recv(), Ok(Event::Output(b"a".to_vec())));
+        assert_eq!(events.recv(), Ok(Event::Stopped(Stop::Finished)));
+
+        controller.send(Command::Restart(b"b".to_vec()));
+        assert_eq!(events.recv(), Ok(Event::Restarted));
+        controller.send(Command::Run);
+        assert_eq!(events.recv(), Ok(Event::Output(b"b".to_vec())));
+        assert_eq!(events.recv(), Ok(Event::Stopped(Stop::Finished)));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::engine::Engine;
+use crate::instruction::InstructionSet;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// Which of a program's instructions have run, for finding code no input
+/// reaches
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Coverage {
+    /// Whether each instruction has run
+    pub executed: Vec<bool>,
+}
+
+impl Coverage {
+    pub fn len(&self) -> usize {
+        self.executed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.executed.is_empty()
+    }
+
+    pub fn executed_count(&self) -> usize {
+        self.executed.iter().filter(|&&executed| executed).count()
+    }
+
+    pub fn is_executed(&self, index: usize) -> bool {
+        self.executed.get(index).copied().unwrap_or(false)
+    }
+
+    /// Each run of instructions that hasn't run, in order
+    pub fn unexecuted(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for (i, _) in (self.executed.iter().enumerate()).filter(|(_, &executed)| !executed) {
+            match ranges.last_mut() {
+                Some(range) if range.end == i => range.end += 1,
+                _ => ranges.push(i..i + 1),
+            }
+        }
+        ranges
+    }
+
+    /// The source the program was parsed from, with a line under each line
+    /// of it marking the instructions that haven't run with `^`
+    pub fn annotate(&self, source: &str, instruction_set: &InstructionSet) -> String {
+        let mut annotated = String::new();
+        let mut index = 0;
+        for line in source.lines() {
+            let mut marks = String::new();
+            for symbol in line.chars() {
+                let mark = match instruction_set.get(symbol) {
+                    Some(_) => {
+                        index += 1;
+                        if self.is_executed(index - 1) {
+                            ' '
+                        } else {
+                            '^'
+                        }
+                    }
+                    // keeps the marks lined up under tabs
+                    None if symbol == '\t' => '\t',
+                    None => ' ',
+                };
+                marks.push(mark);
+            }
+
+            annotated.push_str(line);
+            annotated.push('\n');
+            let marks = marks.trim_end();
+            if marks.contains('^') {
+                annotated.push_str(marks);
+                annotated.push('\n');
+            }
+        }
+        annotated
+    }
+}
+
+impl Engine {
+    /// Which instructions have run since the engine was built or its
+    /// coverage was last cleared, across resets and undos
+    pub fn coverage(&self) -> Coverage {
+        let mut executed = self.executed.clone();
+        executed.resize(self.instructions.len(), false);
+        Coverage { executed }
+    }
+
+    pub fn clear_coverage(&mut self) {
+        self.executed.clear();
+    }
+
+    pub(crate) fn mark_executed(&mut self, index: usize) {
+        if index >= self.executed.len() {
+            self.executed
+                .resize(self.instructions.len().max(index + 1), false);
+        }
+        self.executed[index] = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+    use alloc::vec;
+
+    fn engine(code: &str) -> Engine {
+        Engine::new(overflow::instruction_set().parse(code))
+    }
+
+    #[test]
+    fn coverage_builds_up_across_runs() {
+        let mut engine = engine(",[->+<]>[-<+>]");
+        engine.input = vec![0];
+        while engine.step().is_ok() {}
+        let coverage = engine.coverage();
+        assert_eq!(coverage.executed_count(), 4);
+        assert_eq!(coverage.unexecuted(), vec![2..7, 9..14]);
+
+        engine.restart_with_input(vec![1]);
+        while engine.step().is_ok() {}
+        engine.undo().unwrap();
+        assert!(engine.coverage().unexecuted().is_empty());
+
+        engine.clear_coverage();
+        assert_eq!(engine.coverage().executed_count(), 0);
+    }
+
+    #[test]
+    fn reloading_moves_coverage_with_its_instructions() {
+        let mut engine = engine("+.");
+        while engine.step().is_ok() {}
+        engine.reload_source("-+.", &overflow::instruction_set());
+        assert_eq!(engine.coverage().executed, vec![false, true, true]);
+    }
+
+    #[test]
+    fn annotations_mark_what_never_ran() {
+        let code = ".[\n\t->+<]\tdone\n+";
+        let mut engine = engine(code);
+        while engine.step().is_ok() {}
+        let annotated = engine
+            .coverage()
+            .annotate(code, &overflow::instruction_set());
+        assert_eq!(annotated, ".[\n\t->+<]\tdone\n\t^^^^^\n+\n");
+    }
+}
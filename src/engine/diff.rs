@@ -0,0 +1,88 @@
+use crate::engine::Engine;
+
+use alloc::vec::Vec;
+
+/// What changed from one engine's state to another's, such as before and
+/// after stepping over a loop
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EngineDiff {
+    /// Each changed cell with its value before and after
+    pub cells: Vec<(usize, u8, u8)>,
+    pub pointer: (usize, usize),
+    /// Output the later state has past what the two have in common
+    pub output: Vec<u8>,
+    /// How much of the earlier state's output the later one doesn't have
+    pub output_removed: usize,
+}
+
+impl EngineDiff {
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+            && self.pointer.0 == self.pointer.1
+            && self.output.is_empty()
+            && self.output_removed == 0
+    }
+}
+
+impl Engine {
+    /// Compare this engine's state with a later one, with cells past the end
+    /// of either tape counting as zero
+    pub fn diff(&self, other: &Engine) -> EngineDiff {
+        let cells = self.tape.diff(&other.tape);
+
+        let common = self
+            .output
+            .iter()
+            .zip(&other.output)
+            .take_while(|(before, after)| before == after)
+            .count();
+
+        EngineDiff {
+            cells,
+            pointer: (self.tape_pointer, other.tape_pointer),
+            output: other.output[common..].to_vec(),
+            output_removed: self.output.len() - common,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    #[test]
+    fn diff_shows_what_a_loop_did() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("+++[>++<-]>."));
+        for _ in 0..4 {
+            engine.step().unwrap();
+        }
+        let before = engine.clone();
+        while engine.step().is_ok() {}
+
+        assert_eq!(
+            before.diff(&engine),
+            EngineDiff {
+                cells: vec![(0, 3, 0), (1, 0, 6)],
+                pointer: (0, 1),
+                output: vec![6],
+                output_removed: 0,
+            }
+        );
+        assert!(engine.diff(&engine).is_empty());
+    }
+
+    #[test]
+    fn diff_backwards_removes_output() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("+.+."));
+        while engine.step().is_ok() {}
+        let after = engine.clone();
+        engine.undo().unwrap();
+        engine.undo().unwrap();
+
+        let diff = after.diff(&engine);
+        assert_eq!(diff.cells, vec![(0, 2, 1)]);
+        assert_eq!(diff.output, Vec::<u8>::new());
+        assert_eq!(diff.output_removed, 1);
+    }
+}
@@ -0,0 +1,66 @@
+use crate::engine::Engine;
+use crate::tape::CellFormat;
+
+use alloc::string::String;
+use core::fmt::Write;
+use core::ops::Range;
+
+impl Engine {
+    /// A range of cells laid out like `xxd`, each line starting with the
+    /// offset of its first cell in hex and ending with its cells as text,
+    /// with `.` for anything unprintable. Cells past the end of the tape
+    /// are the zeroes they'd be once reached.
+    pub fn dump_tape(&self, range: Range<usize>, format: CellFormat) -> String {
+        let per_line = match format {
+            CellFormat::Binary => 6,
+            _ => 16,
+        };
+        let cell = |i: usize| self.tape.get(i).copied().unwrap_or(0);
+
+        let mut dump = String::new();
+        let mut start = range.start;
+        while start < range.end {
+            let end = range.end.min(start + per_line);
+            let _ = write!(dump, "{start:08x}:");
+            for i in start..end {
+                let _ = write!(dump, " {}", format.format(cell(i)));
+            }
+            // keeps the text lined up on a short last line
+            let missing = per_line - (end - start);
+            let padding = missing * (format.width() + 1);
+            let _ = write!(dump, "{:padding$}  ", "");
+            for i in start..end {
+                dump.push(match cell(i) {
+                    byte @ b' '..=b'~' => byte as char,
+                    _ => '.',
+                });
+            }
+            dump.push('\n');
+            start = end;
+        }
+        dump
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dumps_look_like_xxd() {
+        let mut engine = Engine::new(alloc::vec![]);
+        engine
+            .write_tape(0, b"Hello,\nworld!\0\xff!!!", false)
+            .unwrap();
+        assert_eq!(
+            engine.dump_tape(0..20, CellFormat::Hex),
+            "00000000: 48 65 6C 6C 6F 2C 0A 77 6F 72 6C 64 21 00 FF 21  Hello,.world!..!\n\
+             00000010: 21 21 00 00                                      !!..\n"
+        );
+        assert_eq!(
+            engine.dump_tape(1..3, CellFormat::Binary),
+            alloc::format!("00000001: 01100101 01101100{}el\n", " ".repeat(38))
+        );
+        assert_eq!(engine.dump_tape(5..5, CellFormat::Decimal), "");
+    }
+}
@@ -0,0 +1,103 @@
+use crate::engine::history::Step;
+use crate::engine::{Engine, EngineError, EngineResult};
+
+use alloc::vec;
+use core::ops::Range;
+
+impl Engine {
+    /// Overwrite cells from an offset on, growing the tape to fit. A
+    /// recorded edit goes in the history, so it's undone like a step.
+    pub fn write_tape(&mut self, offset: usize, bytes: &[u8], record: bool) -> EngineResult {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        let end = offset
+            .checked_add(bytes.len())
+            .ok_or(EngineError::TapeOverflow)?;
+        self.reach(end - 1)?;
+
+        let step = self.history.len();
+        let tape = match record {
+            true => self.tape_for_cell_writes(),
+            false => None,
+        };
+        if record {
+            let before = self.state_hash_for_undo();
+            let stacks = self.undo_stack_lengths();
+            self.tape_edit_history
+                .push((offset, self.tape.read(offset..end)));
+            self.push_step(Step::Edit, stacks);
+            self.record_state_hash(before);
+        }
+        self.tape.write(offset, bytes);
+        self.record_cell_writes(step, tape);
+        Ok(())
+    }
+
+    /// Load cells for the program to start with, such as a memory image it
+    /// expects, from an offset on. They're loaded again whenever the engine
+    /// is reset, so every run starts with them.
+    pub fn load_tape(&mut self, cells: &[u8], offset: usize) -> EngineResult {
+        self.write_tape(offset, cells, false)?;
+        self.tape_image.push((offset, cells.to_vec()));
+        Ok(())
+    }
+
+    pub fn fill(&mut self, cells: Range<usize>, value: u8, record: bool) -> EngineResult {
+        self.write_tape(cells.start, &vec![value; cells.len()], record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TapeModel;
+    use crate::flavor::overflow;
+
+    #[test]
+    fn loaded_images_survive_resets() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("[->+<]"));
+        engine.load_tape(&[3], 0).unwrap();
+        engine.load_tape(b"ab", 4).unwrap();
+        while engine.step().is_ok() {}
+        assert_eq!(engine.tape, vec![0, 3, 0, 0, b'a', b'b']);
+
+        engine.reset();
+        assert_eq!(engine.tape, vec![3, 0, 0, 0, b'a', b'b']);
+        engine.tape_model = TapeModel::Fixed(2);
+        engine.tape = crate::tape::Tape::new(TapeModel::Fixed(2));
+        assert_eq!(
+            engine.load_tape(&[1, 2, 3], 0),
+            Err(EngineError::TapeOverflow.into())
+        );
+    }
+
+    #[test]
+    fn recorded_edits_undo_like_steps() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("+++"));
+        engine.step().unwrap();
+        engine.step().unwrap();
+        engine.write_tape(2, b"hi", true).unwrap();
+        engine.step().unwrap();
+        engine.fill(0..2, 9, false).unwrap();
+
+        assert_eq!(engine.tape, vec![9, 9, b'h', b'i']);
+        // the unrecorded fill stays, with the steps around it undone over it
+        engine.undo().unwrap();
+        assert_eq!(engine.tape, vec![8, 9, b'h', b'i']);
+        engine.undo().unwrap();
+        assert_eq!(engine.tape, vec![8, 9, 0, 0]);
+        engine.undo().unwrap();
+        assert_eq!(engine.tape, vec![7, 9, 0, 0]);
+    }
+
+    #[test]
+    fn edits_stay_on_a_fixed_tape() {
+        let mut engine = Engine::builder().tape(TapeModel::Fixed(4)).build().unwrap();
+        assert!(engine.fill(2..5, 1, true).is_err());
+        assert_eq!(engine.tape, vec![0]);
+        assert!(engine.history.is_empty());
+        engine.fill(2..4, 1, true).unwrap();
+        assert_eq!(engine.tape, vec![0, 0, 1, 1]);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::engine::Exception;
+
+use alloc::string::String;
+use core::fmt;
+
+/// Why the engine couldn't do what it was asked, for hosts to tell apart
+/// without reading the message
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum EngineError {
+    /// Moving the tape pointer before the first cell
+    TapeUnderflow,
+    /// Moving the tape pointer past the end of a fixed size tape
+    TapeOverflow,
+    /// The tape pointer, or a cell an instruction works on, is past the end
+    /// of the tape, which only happens if they're changed by hand
+    OffTape,
+    /// No bracket matching the one at an instruction
+    UnmatchedBracket {
+        index: usize,
+    },
+    /// The instruction at an index neither opens nor closes a loop
+    NotABracket {
+        index: usize,
+    },
+    NoSuchInstruction {
+        requested: usize,
+        max: usize,
+    },
+    NoInstructions,
+    AtStart,
+    AtEnd,
+    /// Nothing left to undo
+    HistoryEmpty,
+    /// Undoing further needs history that was dropped after a step
+    HistoryDropped {
+        step: usize,
+    },
+    /// A step can't be undone, as the instruction it ran has since gone
+    ProgramChanged {
+        step: usize,
+    },
+    /// An instruction's undo found none of the state it saved to undo with,
+    /// such as the cell a `,` overwrote
+    UndoStateMissing(&'static str),
+    /// Nothing was output to take back
+    NoOutput,
+    /// The program hasn't read as many input bytes as asked about
+    InputNotRead {
+        index: usize,
+    },
+    /// Every thread of a multithreaded program has finished
+    ThreadsFinished,
+    /// A command used up its step budget without getting where it was going
+    FuelExhausted {
+        steps: usize,
+    },
+    /// Undoing a step didn't put back the state from before it, with the
+    /// instruction it ran, if it wasn't a tape edit
+    UndoMismatch {
+        step: usize,
+        index: Option<usize>,
+    },
+    /// An assertion in the program didn't hold, with the value it checked
+    AssertionFailed {
+        assertion: String,
+        actual: usize,
+    },
+    /// An instruction was read back without the instruction set that
+    /// defines it, or the set has nothing with its symbol
+    UnboundInstruction {
+        symbol: char,
+    },
+    /// An instruction set gives an instruction a whitespace symbol, which
+    /// programs use for layout
+    WhitespaceInstruction {
+        symbol: char,
+    },
+    /// An instruction set already has an instruction with this symbol
+    DuplicateInstruction {
+        symbol: char,
+    },
+    /// An instruction set has an instruction opening loops but none closing
+    /// them, or the other way round
+    UnpairedLoop {
+        symbol: char,
+        opens: bool,
+    },
+    /// Anything else, such as from instructions defined outside the crate
+    Other(String),
+}
+
+impl EngineError {
+    pub fn message(&self) -> String {
+        alloc::string::ToString::to_string(self)
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::TapeUnderflow => {
+                write!(fmt, "can't move tape pointer before the first cell")
+            }
+            EngineError::TapeOverflow => {
+                write!(fmt, "can't move tape pointer past the end of the tape")
+            }
+            EngineError::OffTape => write!(fmt, "tape pointer is past the end of the tape"),
+            EngineError::UnmatchedBracket { index } => {
+                write!(fmt, "no bracket matching the one at instruction {index}")
+            }
+            EngineError::NotABracket { index } => {
+                write!(fmt, "instruction {index} isn't a bracket")
+            }
+            EngineError::NoSuchInstruction { requested, max } => {
+                write!(fmt, "no instruction at position {requested} (max {max})")
+            }
+            EngineError::NoInstructions => write!(fmt, "no instructions"),
+            EngineError::AtStart => write!(fmt, "already at the start of the instruction list"),
+            EngineError::AtEnd => write!(fmt, "already at the end of the instruction list"),
+            EngineError::HistoryEmpty => write!(fmt, "no previous instruction to undo"),
+            EngineError::HistoryDropped { step } => {
+                write!(
+                    fmt,
+                    "can't undo past step {step}, earlier history was dropped"
+                )
+            }
+            EngineError::ProgramChanged { step } => {
+                write!(fmt, "can't undo step {step}, the program has changed since")
+            }
+            EngineError::UndoStateMissing(what) => write!(fmt, "no {what} to undo"),
+            EngineError::NoOutput => write!(fmt, "no output to take back"),
+            EngineError::InputNotRead { index } => {
+                write!(fmt, "input byte {index} hasn't been read")
+            }
+            EngineError::ThreadsFinished => write!(fmt, "all threads have finished"),
+            EngineError::FuelExhausted { steps } => {
+                write!(fmt, "gave up after {steps} steps")
+            }
+            EngineError::UndoMismatch { step, index } => match index {
+                Some(index) => write!(
+                    fmt,
+                    "undoing step {step} didn't restore the state, its instruction {index} \
+                    doesn't undo cleanly"
+                ),
+                None => write!(fmt, "undoing tape edit {step} didn't restore the state"),
+            },
+            EngineError::AssertionFailed { assertion, actual } => {
+                write!(fmt, "assertion {assertion} failed, it was {actual}")
+            }
+            EngineError::UnboundInstruction { symbol } => {
+                write!(fmt, "no instruction {symbol} in the instruction set")
+            }
+            EngineError::WhitespaceInstruction { symbol } => {
+                write!(fmt, "{symbol:?} is whitespace, so can't be an instruction")
+            }
+            EngineError::DuplicateInstruction { symbol } => {
+                write!(fmt, "{symbol} is already an instruction")
+            }
+            EngineError::UnpairedLoop {
+                symbol,
+                opens: true,
+            } => {
+                write!(fmt, "{symbol} opens loops, but nothing closes them")
+            }
+            EngineError::UnpairedLoop {
+                symbol,
+                opens: false,
+            } => {
+                write!(fmt, "{symbol} closes loops, but nothing opens them")
+            }
+            EngineError::Other(message) => write!(fmt, "{message}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EngineError {}
+
+impl From<EngineError> for Exception {
+    fn from(error: EngineError) -> Exception {
+        Exception::Error(error)
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exception::Error(error) => write!(fmt, "{error}"),
+            Exception::RequestingInput => write!(fmt, "waiting for input"),
+            Exception::Breakpoint => write!(fmt, "stopped at a breakpoint"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Exception {}
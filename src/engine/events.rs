@@ -0,0 +1,177 @@
+use crate::engine::{Engine, EngineError, EngineResult, Exception, InstructionPointer};
+use crate::output::json_string;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Something that happened during a step, for tools following a run live
+/// rather than linking the crate
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RunEvent {
+    /// An instruction ran, leaving the tape pointer on a cell
+    Step {
+        step: usize,
+        index: usize,
+        symbol: char,
+        tape_pointer: usize,
+        cell: u8,
+    },
+    Output {
+        step: usize,
+        byte: u8,
+    },
+    Input {
+        step: usize,
+        byte: u8,
+    },
+    /// A breakpoint instruction ran
+    Breakpoint {
+        index: usize,
+    },
+    /// The program ran past its last instruction
+    Finished {
+        steps: usize,
+    },
+    InputRequested,
+    Error(EngineError),
+}
+
+impl RunEvent {
+    /// The event as a line of JSON, without the newline, naming the kind of
+    /// event in `"event"`
+    pub fn to_json(&self) -> String {
+        match self {
+            RunEvent::Step {
+                step,
+                index,
+                symbol,
+                tape_pointer,
+                cell,
+            } => {
+                let mut buffer = [0; 4];
+                let symbol = json_string(symbol.encode_utf8(&mut buffer).as_bytes());
+                format!(
+                    "{{\"event\":\"step\",\"step\":{step},\"instruction\":{index},\
+                     \"symbol\":{symbol},\"tape_pointer\":{tape_pointer},\"cell\":{cell}}}"
+                )
+            }
+            RunEvent::Output { step, byte } => {
+                format!("{{\"event\":\"output\",\"step\":{step},\"byte\":{byte}}}")
+            }
+            RunEvent::Input { step, byte } => {
+                format!("{{\"event\":\"input\",\"step\":{step},\"byte\":{byte}}}")
+            }
+            RunEvent::Breakpoint { index } => {
+                format!("{{\"event\":\"breakpoint\",\"instruction\":{index}}}")
+            }
+            RunEvent::Finished { steps } => {
+                format!("{{\"event\":\"finished\",\"steps\":{steps}}}")
+            }
+            RunEvent::InputRequested => String::from("{\"event\":\"input-requested\"}"),
+            RunEvent::Error(error) => format!(
+                "{{\"event\":\"error\",\"message\":{}}}",
+                json_string(error.message().as_bytes())
+            ),
+        }
+    }
+}
+
+impl Engine {
+    /// Take a step like `step`, also returning what happened in it, in the
+    /// order it happened
+    pub fn step_events(&mut self) -> (EngineResult, Vec<RunEvent>) {
+        let finished = self.instruction_pointer == InstructionPointer::End;
+        let at = match self.instruction_pointer {
+            InstructionPointer::Index(index) => Some(index),
+            _ => None,
+        };
+        let (step, written) = (self.history.len(), self.output.len());
+        let result = self.step();
+
+        let mut events = Vec::new();
+        let ran = self.history.len() > step;
+        if let Some(index) = at.filter(|_| ran) {
+            let input = self.input_read_by(step);
+            events.extend(input.map(|byte| RunEvent::Input { step, byte }));
+            events.push(RunEvent::Step {
+                step,
+                index,
+                symbol: self.instructions[index].symbol,
+                tape_pointer: self.tape_pointer,
+                cell: self.tape.get(self.tape_pointer).copied().unwrap_or(0),
+            });
+            let output = self.output[written.min(self.output.len())..].iter();
+            events.extend(output.map(|&byte| RunEvent::Output { step, byte }));
+        }
+        match &result {
+            Ok(()) => {}
+            Err(Exception::Breakpoint) => events.push(RunEvent::Breakpoint {
+                index: at.unwrap_or(0),
+            }),
+            Err(Exception::RequestingInput) => events.push(RunEvent::InputRequested),
+            Err(Exception::Error(error)) => events.push(RunEvent::Error(error.clone())),
+        }
+        if !finished && self.instruction_pointer == InstructionPointer::End {
+            events.push(RunEvent::Finished {
+                steps: self.history.len(),
+            });
+        }
+        (result, events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    #[test]
+    fn steps_say_what_happened() {
+        let mut engine = Engine::new(overflow::instruction_set().parse(",+.$"));
+        engine.input = b"a".to_vec();
+        let mut events = Vec::new();
+        loop {
+            let (result, step) = engine.step_events();
+            events.extend(step);
+            if engine.instruction_pointer == InstructionPointer::End {
+                break;
+            }
+            assert!(matches!(result, Ok(()) | Err(Exception::Breakpoint)));
+        }
+
+        let lines = events.iter().map(RunEvent::to_json).collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "{\"event\":\"input\",\"step\":0,\"byte\":97}",
+                "{\"event\":\"step\",\"step\":0,\"instruction\":0,\"symbol\":\",\",\
+                 \"tape_pointer\":0,\"cell\":97}",
+                "{\"event\":\"step\",\"step\":1,\"instruction\":1,\"symbol\":\"+\",\
+                 \"tape_pointer\":0,\"cell\":98}",
+                "{\"event\":\"step\",\"step\":2,\"instruction\":2,\"symbol\":\".\",\
+                 \"tape_pointer\":0,\"cell\":98}",
+                "{\"event\":\"output\",\"step\":2,\"byte\":98}",
+                "{\"event\":\"step\",\"step\":3,\"instruction\":3,\"symbol\":\"$\",\
+                 \"tape_pointer\":0,\"cell\":98}",
+                "{\"event\":\"breakpoint\",\"instruction\":3}",
+                "{\"event\":\"finished\",\"steps\":4}",
+            ]
+        );
+    }
+
+    #[test]
+    fn stopping_is_an_event() {
+        let mut engine = Engine::new(overflow::instruction_set().parse(",<"));
+        let _ = engine.step_events();
+        let (_, events) = engine.step_events();
+        assert_eq!(events, [RunEvent::InputRequested]);
+        engine.input.push(1);
+        let _ = engine.step_events();
+        let (_, events) = engine.step_events();
+        assert_eq!(
+            events.last(),
+            Some(&RunEvent::Error(EngineError::TapeUnderflow))
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use crate::engine::{Engine, Exception, InstructionPointer};
+
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+
+/// Where a program's output first stopped matching what was expected
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Mismatch {
+    /// How many bytes of output matched
+    pub offset: usize,
+    pub expected: Option<u8>,
+    pub actual: Option<u8>,
+    /// The step the engine was on when the output stopped matching
+    pub step: usize,
+    /// The instruction that wrote the wrong byte, if one did
+    pub index: Option<usize>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.expected, self.actual) {
+            (expected, Some(actual)) => {
+                write!(
+                    fmt,
+                    "byte {} of output is {}",
+                    self.offset,
+                    describe(actual)
+                )?;
+                match expected {
+                    Some(expected) => write!(fmt, ", expected {}", describe(expected))?,
+                    None => write!(fmt, ", past the end of what was expected")?,
+                }
+                match self.index {
+                    Some(index) => write!(
+                        fmt,
+                        ", written at step {} by instruction {index}",
+                        self.step
+                    ),
+                    None => Ok(()),
+                }
+            }
+            (Some(expected), None) => write!(
+                fmt,
+                "output ended after {} bytes, expected {} next",
+                self.offset,
+                describe(expected)
+            ),
+            (None, None) => write!(fmt, "output matched"),
+        }
+    }
+}
+
+fn describe(byte: u8) -> String {
+    match byte {
+        b' '..=b'~' => format!("{:?}", byte as char),
+        _ => format!("0x{byte:02X}"),
+    }
+}
+
+impl Engine {
+    /// Run to the end, checking each byte of output against what's expected
+    /// as it's written and stopping at the first that's wrong. Exceptions
+    /// stop the run as usual, after which it can be picked up again.
+    pub fn expect_output(&mut self, expected: &[u8]) -> Result<Option<Mismatch>, Exception> {
+        let mismatch = |engine: &Engine, offset: usize, index| Mismatch {
+            offset,
+            expected: expected.get(offset).copied(),
+            actual: engine.output.get(offset).copied(),
+            step: engine.history.len(),
+            index,
+        };
+
+        // output from before this was called can't be pinned on a step
+        let checked = self
+            .output
+            .iter()
+            .zip(expected)
+            .take_while(|(actual, expected)| actual == expected)
+            .count();
+        if checked < self.output.len() {
+            return Ok(Some(mismatch(self, checked, None)));
+        }
+
+        let mut fuel = self.fuel();
+        loop {
+            if self.instruction_pointer == InstructionPointer::End {
+                return Ok((self.output.len() < expected.len())
+                    .then(|| mismatch(self, self.output.len(), None)));
+            }
+
+            let written = self.output.len();
+            let index = match self.instruction_pointer {
+                InstructionPointer::Index(index) => Some(index),
+                _ => None,
+            };
+            fuel.burn()?;
+            self.step()?;
+            if let Some(offset) = (written..self.output.len())
+                .find(|&offset| expected.get(offset) != Some(&self.output[offset]))
+            {
+                return Ok(Some(mismatch(self, offset, index)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    fn engine(code: &str) -> Engine {
+        Engine::new(overflow::instruction_set().parse(code))
+    }
+
+    #[test]
+    fn expect_output_stops_at_the_wrong_byte() {
+        let mut engine = engine("++++++++[>++++++++<-]>+.+.+.+.");
+        let mismatch = engine.expect_output(b"ABD").unwrap().unwrap();
+        assert_eq!(
+            mismatch,
+            Mismatch {
+                offset: 2,
+                expected: Some(b'D'),
+                actual: Some(b'C'),
+                step: engine.history.len(),
+                index: Some(27),
+            }
+        );
+        assert_eq!(engine.instruction_pointer, InstructionPointer::Index(28));
+        assert!(mismatch.to_string().ends_with("by instruction 27"));
+    }
+
+    #[test]
+    fn expect_output_checks_the_end() {
+        assert_eq!(engine("+.").expect_output(&[1]), Ok(None));
+
+        let mismatch = engine("+.+.").expect_output(&[1, 2, 3]).unwrap().unwrap();
+        assert_eq!((mismatch.offset, mismatch.actual), (2, None));
+        assert_eq!(
+            mismatch.to_string(),
+            "output ended after 2 bytes, expected 0x03 next"
+        );
+    }
+}
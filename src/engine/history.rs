@@ -0,0 +1,474 @@
+use crate::engine::Engine;
+
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// How much of its history an engine keeps for undoing
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HistoryPolicy {
+    #[default]
+    Unbounded,
+    /// Only the most recent steps, dropping the oldest as new ones are taken
+    Last(usize),
+    /// Nothing, so steps can't be undone
+    Off,
+}
+
+/// Parses `all`, `off` or a number of steps to keep
+impl core::str::FromStr for HistoryPolicy {
+    type Err = String;
+
+    fn from_str(policy: &str) -> Result<HistoryPolicy, String> {
+        match policy {
+            "all" => Ok(HistoryPolicy::Unbounded),
+            "off" => Ok(HistoryPolicy::Off),
+            _ => policy.parse().map(HistoryPolicy::Last).map_err(|_| {
+                format!("invalid history {policy}, expected all, off or a number of steps")
+            }),
+        }
+    }
+}
+
+/// One step taken. Any other data a step needs to be undone, like the cell
+/// `,` overwrote, is kept on the engine.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Step {
+    /// An instruction ran: where it was, with its symbol to check it's still
+    /// there when undoing
+    Ran { index: u32, symbol: char },
+    /// The tape was edited directly
+    Edit,
+}
+
+impl Step {
+    pub fn ran(index: usize, symbol: char) -> Step {
+        Step::Ran {
+            index: index as u32,
+            symbol,
+        }
+    }
+
+    /// Where the instruction the step ran was, if it ran one
+    pub fn index(self) -> Option<usize> {
+        match self {
+            Step::Ran { index, .. } => Some(index as usize),
+            Step::Edit => None,
+        }
+    }
+}
+
+/// The steps an engine has taken, for undoing them, kept in a ring buffer
+/// when the policy bounds it. Counts every step taken even once some have
+/// been dropped, so `len` is always the step the engine is on.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct History {
+    policy: HistoryPolicy,
+    steps: usize,
+    kept: VecDeque<Step>,
+    /// Runs of steps to undo together, by step number, oldest first
+    groups: Vec<Range<usize>>,
+    /// Where the open group started, and how deeply groups are nested in it
+    open_group: Option<(usize, usize)>,
+    /// How long the engine's undo stacks were before each step that added
+    /// to them, by step number, so what they keep for a step is dropped
+    /// along with it. Only noted when the policy drops steps.
+    marks: VecDeque<(usize, UndoMarks)>,
+}
+
+/// The lengths of the stacks an engine keeps alongside its history, in the
+/// order `Engine::undo_stack_lengths` gives them
+pub(crate) type UndoMarks = [usize; 9];
+
+impl History {
+    pub fn new(policy: HistoryPolicy) -> History {
+        History {
+            policy,
+            steps: 0,
+            kept: VecDeque::new(),
+            groups: Vec::new(),
+            open_group: None,
+            marks: VecDeque::new(),
+        }
+    }
+
+    pub fn policy(&self) -> HistoryPolicy {
+        self.policy
+    }
+
+    /// Change the policy, dropping any steps it no longer keeps
+    pub fn set_policy(&mut self, policy: HistoryPolicy) {
+        self.policy = policy;
+        self.trim();
+    }
+
+    pub fn push(&mut self, step: Step) {
+        self.steps += 1;
+        if self.policy != HistoryPolicy::Off {
+            self.kept.push_back(step);
+            self.trim();
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<Step> {
+        let step = self.kept.pop_back()?;
+        self.steps -= 1;
+        while self.marks.back().is_some_and(|&(step, _)| step >= self.steps) {
+            self.marks.pop_back();
+        }
+        // a group that's partly undone no longer goes together
+        while self
+            .groups
+            .last()
+            .is_some_and(|group| group.end > self.steps)
+        {
+            self.groups.pop();
+        }
+        Some(step)
+    }
+
+    /// Start a group of steps for undoing as one. Groups begun inside an
+    /// open group are part of it.
+    pub fn begin_group(&mut self) {
+        self.open_group = match self.open_group {
+            Some((start, depth)) => Some((start, depth + 1)),
+            None => Some((self.steps, 0)),
+        };
+    }
+
+    pub fn end_group(&mut self) {
+        match self.open_group {
+            Some((start, 0)) => {
+                self.open_group = None;
+                if self.steps > start {
+                    self.groups.push(start..self.steps);
+                }
+            }
+            Some((start, depth)) => self.open_group = Some((start, depth - 1)),
+            None => {}
+        }
+    }
+
+    /// Where the group ending with the latest step started, if it's in one
+    pub fn group_start(&self) -> Option<usize> {
+        self.groups
+            .last()
+            .filter(|group| group.end == self.steps)
+            .map(|group| group.start)
+    }
+
+    pub fn last(&self) -> Option<Step> {
+        self.kept.back().copied()
+    }
+
+    /// How many steps have been taken
+    pub fn len(&self) -> usize {
+        self.steps
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps == 0
+    }
+
+    /// How many steps can be undone
+    pub fn retained(&self) -> usize {
+        self.kept.len()
+    }
+
+    /// Whether earlier steps have been dropped, so undoing stops short of
+    /// the start
+    pub fn is_truncated(&self) -> bool {
+        self.kept.len() < self.steps
+    }
+
+    /// The kept steps, oldest first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Step> + '_ {
+        self.kept.iter().copied()
+    }
+
+    /// Keep steps pointing at the same instructions after one is inserted
+    /// into the program
+    pub fn instruction_inserted(&mut self, index: usize) {
+        for step in self.kept.iter_mut() {
+            match step {
+                Step::Ran { index: ran, .. } if *ran as usize >= index => *ran += 1,
+                _ => {}
+            }
+        }
+    }
+
+    /// Keep steps pointing at the same instructions after one is removed
+    /// from the program. Steps that ran it can't be undone any more.
+    pub fn instruction_removed(&mut self, index: usize) {
+        for step in self.kept.iter_mut() {
+            match step {
+                Step::Ran { index: ran, .. } if *ran as usize == index => *ran = u32::MAX,
+                Step::Ran { index: ran, .. } if *ran as usize > index => *ran -= 1,
+                _ => {}
+            }
+        }
+    }
+
+    /// Move each kept step to where the instruction it ran is after the
+    /// program is edited, returning how many ran instructions that are gone
+    pub fn reindex(&mut self, moved: impl Fn(usize) -> Option<usize>) -> usize {
+        let mut lost = 0;
+        for step in self.kept.iter_mut() {
+            if let Step::Ran { index, .. } = step {
+                *index = match moved(*index as usize) {
+                    Some(moved) => moved as u32,
+                    None => {
+                        lost += 1;
+                        u32::MAX
+                    }
+                };
+            }
+        }
+        lost
+    }
+
+    /// Like `instruction_removed`, but forgetting the steps that ran the
+    /// instruction. Only safe for instructions that do nothing but move on,
+    /// like breakpoints.
+    pub fn forget_instruction(&mut self, index: usize) {
+        let first = self.steps - self.kept.len();
+        let forgotten = (self.kept.iter().enumerate())
+            .filter(|(_, step)| step.index() == Some(index))
+            .map(|(i, _)| first + i)
+            .collect::<Vec<_>>();
+        self.kept.retain(|step| step.index() != Some(index));
+        self.steps -= forgotten.len();
+        for (step, _) in self.marks.iter_mut() {
+            *step -= forgotten.partition_point(|&forgotten| forgotten < *step);
+        }
+        // step numbers have moved, so the groups no longer line up
+        self.groups.clear();
+        self.instruction_removed(index);
+    }
+
+    pub fn clear(&mut self) {
+        self.steps = 0;
+        self.kept.clear();
+        self.groups.clear();
+        self.open_group = None;
+        self.marks.clear();
+    }
+
+    fn trim(&mut self) {
+        let limit = match self.policy {
+            HistoryPolicy::Unbounded => return,
+            HistoryPolicy::Last(limit) => limit,
+            HistoryPolicy::Off => 0,
+        };
+        while self.kept.len() > limit {
+            self.kept.pop_front();
+        }
+    }
+}
+
+impl Engine {
+    /// Start a group of steps that `undo` takes back as one, for composite
+    /// operations like running to a breakpoint
+    pub fn begin_group(&mut self) {
+        self.history.begin_group();
+    }
+
+    pub fn end_group(&mut self) {
+        self.history.end_group();
+    }
+
+    /// How long each stack of what's kept for undoing or tracing steps is
+    pub(crate) fn undo_stack_lengths(&self) -> UndoMarks {
+        [
+            self.input_cell_history.len(),
+            self.fork_cell_history.len(),
+            self.cleared_cell_history.len(),
+            self.scan_history.len(),
+            self.random_history.len(),
+            self.tape_edit_history.len(),
+            self.consumed_input.len(),
+            self.output_sources.len(),
+            self.loop_history.len(),
+        ]
+    }
+
+    /// Put a step in the history, given how long the undo stacks were
+    /// before it, dropping what they keep for steps the history no longer
+    /// does
+    pub(crate) fn push_step(&mut self, step: Step, before: UndoMarks) {
+        let number = self.history.len();
+        if self.history.policy != HistoryPolicy::Unbounded && self.undo_stack_lengths() != before {
+            self.history.marks.push_back((number, before));
+        }
+        self.history.push(step);
+        self.trim_undo_stacks();
+    }
+
+    fn trim_undo_stacks(&mut self) {
+        // the latest step's entries stay even with history off, so hosts
+        // can still see what it read and wrote
+        let steps = self.history.len();
+        let oldest = (steps - self.history.retained()).min(steps.saturating_sub(1));
+        let marks = &self.history.marks;
+        let dropped = marks.partition_point(|&(step, _)| step < oldest);
+        // draining a stack moves what's left of it, so wait until at least
+        // half of it can go
+        if dropped == 0 || dropped * 2 < marks.len() {
+            return;
+        }
+        let keep = match marks.get(dropped) {
+            Some(&(_, before)) => before,
+            None => self.undo_stack_lengths(),
+        };
+        self.history.marks.drain(..dropped);
+        for (_, lengths) in self.history.marks.iter_mut() {
+            for (length, dropped) in lengths.iter_mut().zip(keep) {
+                *length -= dropped;
+            }
+        }
+
+        self.input_cell_history.drain(..keep[0]);
+        self.fork_cell_history.drain(..keep[1]);
+        self.cleared_cell_history.drain(..keep[2]);
+        self.scan_history.drain(..keep[3]);
+        self.random_history.drain(..keep[4]);
+        self.tape_edit_history.drain(..keep[5]);
+        self.consumed_input.drain(..keep[6]);
+        self.dropped_input += keep[6];
+        self.output_sources.drain(..keep[7]);
+        self.loop_history.drain(..keep[8]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::EngineError;
+    use crate::flavor::overflow;
+
+    #[test]
+    fn bounded_history_keeps_the_latest_steps() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("+>+>+"));
+        engine.history.set_policy(HistoryPolicy::Last(2));
+        while engine.step().is_ok() {}
+
+        assert_eq!(engine.history.len(), 5);
+        assert_eq!(engine.history.retained(), 2);
+        assert!(engine.history.is_truncated());
+
+        engine.undo().unwrap();
+        engine.undo().unwrap();
+        assert_eq!(engine.tape, vec![1, 1, 0]);
+        assert_eq!(
+            engine.undo(),
+            Err(EngineError::HistoryDropped { step: 3 }.into())
+        );
+    }
+
+    #[test]
+    fn steps_are_small_and_checked_when_undone() {
+        assert_eq!(core::mem::size_of::<Step>(), 8);
+
+        let mut engine = Engine::new(overflow::instruction_set().parse("+-"));
+        while engine.step().is_ok() {}
+        engine
+            .load_instructions(overflow::instruction_set().parse("++"))
+            .unwrap();
+        assert_eq!(
+            engine.undo(),
+            Err(EngineError::ProgramChanged { step: 2 }.into())
+        );
+    }
+
+    #[test]
+    fn groups_undo_as_one() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("+++++"));
+        engine.step().unwrap();
+        engine.step().unwrap();
+        engine.begin_group();
+        engine.step().unwrap();
+        engine.begin_group();
+        engine.step().unwrap();
+        engine.end_group();
+        engine.step().unwrap();
+        engine.end_group();
+        engine.step().unwrap();
+
+        engine.undo().unwrap();
+        assert_eq!(engine.tape, vec![4]);
+        engine.undo().unwrap();
+        assert_eq!(engine.tape, vec![1]);
+        engine.undo().unwrap();
+        assert_eq!(engine.tape, vec![0]);
+    }
+
+    #[test]
+    fn partly_undone_groups_split_up() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("+++"));
+        engine.step().unwrap();
+        engine.begin_group();
+        while engine.step().is_ok() {}
+        engine.end_group();
+
+        engine.undo_step().unwrap();
+        assert_eq!(engine.history.group_start(), None);
+        engine.undo().unwrap();
+        assert_eq!(engine.tape, vec![1]);
+    }
+
+    #[test]
+    fn history_can_be_off() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("++"));
+        engine.history.set_policy(HistoryPolicy::Off);
+        while engine.step().is_ok() {}
+
+        assert_eq!(engine.history.len(), 2);
+        assert_eq!(engine.history.retained(), 0);
+        assert!(engine.undo().is_err());
+
+        assert_eq!("off".parse(), Ok(HistoryPolicy::Off));
+        assert_eq!("1000".parse(), Ok(HistoryPolicy::Last(1000)));
+        assert!("some".parse::<HistoryPolicy>().is_err());
+    }
+
+    #[test]
+    fn undo_stacks_are_bounded_by_the_policy() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("+[.,]"));
+        engine.history.set_policy(HistoryPolicy::Off);
+        engine.input = vec![1; 10_000];
+        while engine.step().is_ok() {}
+
+        assert_eq!(engine.output.len(), 10_001);
+        assert!(engine.input_cell_history.len() <= 2);
+        assert!(engine.consumed_input.len() <= 2);
+        assert!(engine.output_sources.len() <= 2);
+        assert!(engine.loop_history.len() <= 2);
+        assert_eq!(engine.dropped_input + engine.consumed_input.len(), 10_000);
+
+        let mut engine = Engine::new(overflow::instruction_set().parse("+[.,]"));
+        engine.history.set_policy(HistoryPolicy::Last(6));
+        engine.input = b"abcdefghij".to_vec();
+        while engine.step().is_ok() {}
+        assert!(engine.input_cell_history.len() <= 4);
+        assert!(engine.output_sources.len() <= 4);
+        for _ in 0..6 {
+            engine.undo().unwrap();
+        }
+        assert_eq!(engine.output, b"\x01abcdefgh");
+        assert_eq!(engine.cell(), b'h');
+        assert_eq!(
+            engine.output_source(8),
+            Some(&crate::engine::provenance::OutputSource {
+                step: 26,
+                index: Some(2),
+            })
+        );
+        assert_eq!(engine.output_source(0), None);
+        assert!(engine.undo().is_err());
+    }
+}
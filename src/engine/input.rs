@@ -0,0 +1,139 @@
+use crate::engine::Engine;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Where a program's input comes from, for feeding it without typing
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InputSource {
+    Bytes(Vec<u8>),
+    /// The whole of a file
+    #[cfg(feature = "std")]
+    File(std::path::PathBuf),
+    /// Everything on standard input, up to its end
+    #[cfg(feature = "std")]
+    Stdin,
+    /// Pairs of hex digits, with any whitespace between them ignored
+    Hex(String),
+    /// The same bytes over and over, so the input never runs out
+    Cycle(Vec<u8>),
+}
+
+/// Parses `stdin`, `file:<path>`, `hex:<digits>`, `cycle:<text>` or
+/// `text:<text>`
+impl core::str::FromStr for InputSource {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<InputSource, String> {
+        #[cfg(feature = "std")]
+        if source == "stdin" {
+            return Ok(InputSource::Stdin);
+        }
+        match source.split_once(':') {
+            #[cfg(feature = "std")]
+            Some(("file", path)) => Ok(InputSource::File(path.into())),
+            Some(("hex", hex)) => Ok(InputSource::Hex(hex.into())),
+            Some(("cycle", text)) => Ok(InputSource::Cycle(text.as_bytes().to_vec())),
+            Some(("text", text)) => Ok(InputSource::Bytes(text.as_bytes().to_vec())),
+            _ => Err(format!(
+                "invalid input {source}, expected stdin, file:<path>, hex:<digits>, cycle:<text> or text:<text>"
+            )),
+        }
+    }
+}
+
+impl InputSource {
+    /// The bytes the source gives, or the bytes it repeats for `Cycle`
+    pub fn read(&self) -> Result<Vec<u8>, String> {
+        match self {
+            InputSource::Bytes(bytes) | InputSource::Cycle(bytes) => Ok(bytes.clone()),
+            #[cfg(feature = "std")]
+            InputSource::File(path) => std::fs::read(path)
+                .map_err(|e| format!("can't read input from {}: {e}", path.display())),
+            #[cfg(feature = "std")]
+            InputSource::Stdin => {
+                let mut bytes = Vec::new();
+                std::io::Read::read_to_end(&mut std::io::stdin(), &mut bytes)
+                    .map_err(|e| format!("can't read input from stdin: {e}"))?;
+                Ok(bytes)
+            }
+            InputSource::Hex(hex) => parse_hex(hex),
+        }
+    }
+}
+
+impl From<Vec<u8>> for InputSource {
+    fn from(bytes: Vec<u8>) -> InputSource {
+        InputSource::Bytes(bytes)
+    }
+}
+
+fn parse_hex(hex: &str) -> Result<Vec<u8>, String> {
+    let digits = hex
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| {
+            c.to_digit(16)
+                .map(|digit| digit as u8)
+                .ok_or_else(|| format!("invalid hex digit {c:?} in input"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if digits.len() % 2 != 0 {
+        return Err(String::from("hex input has an odd number of digits"));
+    }
+    Ok(digits
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair[1])
+        .collect())
+}
+
+impl Engine {
+    /// Replace the input with what a source gives
+    pub fn set_input(&mut self, source: &InputSource) -> Result<(), String> {
+        let bytes = source.read()?;
+        match source {
+            InputSource::Cycle(_) => {
+                self.input = Vec::new();
+                self.input_cycle = bytes;
+            }
+            _ => {
+                self.input = bytes;
+                self.input_cycle = Vec::new();
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    #[test]
+    fn hex_input_ignores_whitespace() {
+        let mut engine = Engine::new(vec![]);
+        engine.set_input(&"hex:48 69\n0a".parse().unwrap()).unwrap();
+        assert_eq!(engine.input, b"Hi\n".to_vec());
+
+        assert!(InputSource::Hex(String::from("4")).read().is_err());
+        assert!(InputSource::Hex(String::from("4g")).read().is_err());
+        assert!("hex".parse::<InputSource>().is_err());
+    }
+
+    #[test]
+    fn cycled_input_never_runs_out() {
+        let mut engine = Engine::new(overflow::instruction_set().parse(",.,.,.,.,."));
+        engine
+            .set_input(&InputSource::Cycle(b"ab".to_vec()))
+            .unwrap();
+        while engine.step().is_ok() {}
+        assert_eq!(engine.output, b"ababa".to_vec());
+
+        while engine.undo().is_ok() {}
+        assert!(engine.output.is_empty());
+        while engine.step().is_ok() {}
+        assert_eq!(engine.output, b"ababa".to_vec());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::engine::Engine;
+
+use alloc::format;
+use alloc::string::String;
+use core::ops::Range;
+
+/// A name for a cell, or for a range of cells used as an array
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Label {
+    pub cells: Range<usize>,
+    pub name: String,
+}
+
+/// Parses `name=index` for a cell or `name=start..end` for a range
+impl core::str::FromStr for Label {
+    type Err = String;
+
+    fn from_str(label: &str) -> Result<Label, String> {
+        let invalid = || format!("invalid label {label}, expected name=index or name=start..end");
+        let (name, cells) = label.split_once('=').ok_or_else(invalid)?;
+        let cells = match cells.split_once("..") {
+            Some((start, end)) => start
+                .parse()
+                .ok()
+                .zip(end.parse().ok())
+                .map(|(start, end)| start..end),
+            None => cells.parse().ok().map(|index: usize| index..index + 1),
+        };
+        match cells {
+            Some(cells) if !name.is_empty() && !cells.is_empty() => Ok(Label {
+                cells,
+                name: name.into(),
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Engine {
+    pub fn label_cell<S: Into<String>>(&mut self, index: usize, name: S) {
+        self.label_range(index..index + 1, name);
+    }
+
+    /// Name a range of cells, replacing any label already using the name.
+    /// Where labels overlap, the latest one names the cells.
+    pub fn label_range<S: Into<String>>(&mut self, cells: Range<usize>, name: S) {
+        let name = name.into();
+        self.unlabel(&name);
+        self.labels.push(Label { cells, name });
+    }
+
+    pub fn unlabel(&mut self, name: &str) {
+        self.labels.retain(|label| label.name != name);
+    }
+
+    pub fn label_at(&self, index: usize) -> Option<&Label> {
+        self.labels
+            .iter()
+            .rev()
+            .find(|label| label.cells.contains(&index))
+    }
+
+    /// What a cell is called, as `name` for a single labelled cell and
+    /// `name[i]` for a cell of a labelled range
+    pub fn cell_name(&self, index: usize) -> Option<String> {
+        let label = self.label_at(index)?;
+        match label.cells.len() {
+            1 => Some(label.name.clone()),
+            _ => Some(format!("{}[{}]", label.name, index - label.cells.start)),
+        }
+    }
+
+    pub fn labelled(&self, name: &str) -> Option<Range<usize>> {
+        self.labels
+            .iter()
+            .find(|label| label.name == name)
+            .map(|label| label.cells.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_are_named_by_their_latest_label() {
+        let mut engine = Engine::new(vec![]);
+        engine.label_cell(0, "counter");
+        engine.label_range(2..10, "buffer");
+        engine.label_cell(4, "flag");
+
+        assert_eq!(engine.cell_name(0).as_deref(), Some("counter"));
+        assert_eq!(engine.cell_name(3).as_deref(), Some("buffer[1]"));
+        assert_eq!(engine.cell_name(4).as_deref(), Some("flag"));
+        assert_eq!(engine.cell_name(1), None);
+
+        engine.label_cell(1, "counter");
+        assert_eq!(engine.cell_name(0), None);
+        assert_eq!(engine.labelled("counter"), Some(1..2));
+    }
+
+    #[test]
+    fn labels_parse_from_name_and_cells() {
+        assert_eq!(
+            "buffer=0..8".parse(),
+            Ok(Label {
+                cells: 0..8,
+                name: "buffer".into()
+            })
+        );
+        assert_eq!(
+            "counter=3".parse::<Label>().map(|label| label.cells),
+            Ok(3..4)
+        );
+        assert!("=3".parse::<Label>().is_err());
+        assert!("empty=4..4".parse::<Label>().is_err());
+    }
+}
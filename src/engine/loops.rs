@@ -0,0 +1,323 @@
+use crate::engine::{Engine, EngineError, EngineResult, InstructionPointer};
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// A loop the instruction pointer is inside, with how many times its body
+/// has started since the loop was last entered, counting the current time
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LoopFrame {
+    pub open_idx: usize,
+    pub close_idx: usize,
+    pub iteration_count: usize,
+}
+
+/// How many times the body of a loop has started
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoopCount {
+    /// In this run, whether by entering the loop or jumping back
+    pub total: usize,
+    /// Since the loop was last entered
+    pub since_entered: usize,
+}
+
+/// A step that started the body of a loop
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoopStart {
+    pub step: usize,
+    pub open_idx: usize,
+    /// How many times the body had started since the loop was last entered,
+    /// if this step entered it afresh rather than jumping back
+    pub entered_after: Option<usize>,
+}
+
+impl Engine {
+    /// Where the bracket at an index is matched, if it's a bracket with a
+    /// match
+    pub fn matching_bracket(&self, index: usize) -> Option<usize> {
+        self.brackets.get(index).copied().flatten()
+    }
+
+    /// Match the brackets up again after changing `instructions` directly
+    pub fn rematch_brackets(&mut self) {
+        self.brackets = crate::analysis::match_brackets(&self.instructions);
+    }
+
+    /// Move from the instruction opening a loop to the one closing it, or
+    /// back, without running either
+    pub fn goto_matching_bracket(&mut self) -> EngineResult {
+        let index = match self.instruction_pointer {
+            InstructionPointer::Start => return Err(EngineError::AtStart.into()),
+            InstructionPointer::End => return Err(EngineError::AtEnd.into()),
+            InstructionPointer::Index(i) => i,
+        };
+        let instruction = &self.instructions[index];
+        if !instruction.opens_loop() && !instruction.closes_loop() {
+            return Err(EngineError::NotABracket { index }.into());
+        }
+        match self.matching_bracket(index) {
+            Some(matching) => {
+                self.instruction_pointer = InstructionPointer::Index(matching);
+                Ok(())
+            }
+            None => Err(EngineError::UnmatchedBracket { index }.into()),
+        }
+    }
+
+    /// The loops enclosing the current instruction, outermost first
+    pub fn loop_stack(&self) -> Vec<LoopFrame> {
+        let InstructionPointer::Index(index) = self.instruction_pointer else {
+            return vec![];
+        };
+        self.instructions[..index]
+            .iter()
+            .enumerate()
+            .filter_map(|(i, instruction)| {
+                let close = self.matching_bracket(i)?;
+                (instruction.opens_loop() && close >= index).then_some((i, close))
+            })
+            .map(|(open_idx, close_idx)| LoopFrame {
+                open_idx,
+                close_idx,
+                iteration_count: self
+                    .loop_counts
+                    .get(&open_idx)
+                    .map_or(1, |count| count.since_entered.max(1)),
+            })
+            .collect()
+    }
+
+    /// How many times the body of the loop opened at an instruction has
+    /// started in this run, whether by entering the loop or jumping back
+    pub fn loop_iterations(&self, open_idx: usize) -> usize {
+        match self.instructions.get(open_idx) {
+            Some(instruction) if instruction.opens_loop() => self
+                .loop_counts
+                .get(&open_idx)
+                .map_or(0, |count| count.total),
+            _ => 0,
+        }
+    }
+
+    /// Count the body of a loop starting, if the step about to go in the
+    /// history ran the instruction at `ran` and so went into one
+    pub(crate) fn record_loop(&mut self, ran: usize) {
+        let InstructionPointer::Index(next) = self.instruction_pointer else {
+            return;
+        };
+        let entered = next == ran + 1 && self.instructions[ran].opens_loop();
+        // going round again lands just past the loop's open
+        let jumped_back = next <= ran
+            && self.instructions[ran].closes_loop()
+            && next
+                .checked_sub(1)
+                .is_some_and(|open| self.instructions[open].opens_loop());
+        if !entered && !jumped_back {
+            return;
+        }
+        let open_idx = next - 1;
+        let count = self.loop_counts.entry(open_idx).or_default();
+        let entered_after = entered.then_some(count.since_entered);
+        count.total += 1;
+        count.since_entered = if entered { 1 } else { count.since_entered + 1 };
+        self.loop_history.push(LoopStart {
+            step: self.history.len(),
+            open_idx,
+            entered_after,
+        });
+    }
+
+    /// Take back the loop body starts of steps an undo took back
+    pub(crate) fn forget_loops(&mut self) {
+        let step = self.history.len();
+        while self
+            .loop_history
+            .last()
+            .is_some_and(|start| start.step >= step)
+        {
+            let start = self.loop_history.pop().unwrap();
+            if let Some(count) = self.loop_counts.get_mut(&start.open_idx) {
+                count.total -= 1;
+                count.since_entered = match start.entered_after {
+                    Some(since_entered) => since_entered,
+                    None => count.since_entered - 1,
+                };
+            }
+        }
+    }
+
+    /// Move counts onto where their loops went when the program changed
+    pub(crate) fn reindex_loops(&mut self, moved: impl Fn(usize) -> Option<usize>) {
+        self.loop_counts = core::mem::take(&mut self.loop_counts)
+            .into_iter()
+            .filter_map(|(open_idx, count)| Some((moved(open_idx)?, count)))
+            .collect::<BTreeMap<_, _>>();
+        self.loop_history.retain_mut(|start| match moved(start.open_idx) {
+            Some(open_idx) => {
+                start.open_idx = open_idx;
+                true
+            }
+            None => false,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::history::HistoryPolicy;
+    use crate::flavor::overflow;
+    use crate::instruction::{Instruction, InstructionKind};
+
+    fn run_until(engine: &mut Engine, steps: usize) {
+        for _ in 0..steps {
+            engine.step().unwrap();
+        }
+    }
+
+    #[test]
+    fn loop_stack_counts_iterations_of_each_entry() {
+        let instructions = overflow::instruction_set().parse("++[>++[-]<-]");
+        let mut engine = Engine::new(instructions);
+        // to the first time round the inner loop
+        run_until(&mut engine, 8);
+        assert_eq!(
+            engine.loop_stack(),
+            vec![
+                LoopFrame {
+                    open_idx: 2,
+                    close_idx: 11,
+                    iteration_count: 1
+                },
+                LoopFrame {
+                    open_idx: 6,
+                    close_idx: 8,
+                    iteration_count: 1
+                },
+            ]
+        );
+
+        // round the inner loop once more, then out of it and round the outer
+        run_until(&mut engine, 2);
+        assert_eq!(engine.loop_stack()[1].iteration_count, 2);
+        run_until(&mut engine, 5);
+        let counts = |engine: &Engine| {
+            engine
+                .loop_stack()
+                .iter()
+                .map(|frame| frame.iteration_count)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(counts(&engine), vec![2]);
+
+        engine.undo().unwrap();
+        assert_eq!(counts(&engine), vec![1]);
+    }
+
+    #[test]
+    fn loop_iterations_count_the_whole_run() {
+        let instructions = overflow::instruction_set().parse("++[>++[-]<-]");
+        let mut engine = Engine::new(instructions);
+        while engine.instruction_pointer != InstructionPointer::End {
+            engine.step().unwrap();
+        }
+        assert_eq!(engine.loop_iterations(2), 2);
+        assert_eq!(engine.loop_iterations(6), 4);
+        assert_eq!(engine.loop_iterations(0), 0);
+
+        engine.reset();
+        assert_eq!(engine.loop_iterations(6), 0);
+    }
+
+    #[test]
+    fn loops_are_counted_past_the_history() {
+        let instructions = overflow::instruction_set().parse("++++++++++[-]");
+        let mut engine = Engine::new(instructions);
+        engine.history.set_policy(HistoryPolicy::Last(5));
+        run_until(&mut engine, 21);
+        assert_eq!(engine.loop_stack()[0].iteration_count, 5);
+        while engine.step().is_ok() {}
+        assert_eq!(engine.loop_iterations(10), 10);
+
+        engine.undo().unwrap();
+        engine.undo().unwrap();
+        assert_eq!(engine.loop_stack()[0].iteration_count, 10);
+        engine.undo().unwrap();
+        assert_eq!(engine.loop_stack()[0].iteration_count, 9);
+        assert_eq!(engine.loop_iterations(10), 9);
+    }
+
+    #[test]
+    fn loop_counts_follow_their_loops() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("+[-]"));
+        while engine.step().is_ok() {}
+        engine
+            .insert_instruction(0, overflow::instruction_set().parse(">").remove(0))
+            .unwrap();
+        assert_eq!(engine.loop_iterations(2), 1);
+        assert_eq!(engine.loop_iterations(1), 0);
+    }
+
+    #[test]
+    fn loops_are_found_by_kind_not_symbol() {
+        let step = |kind| {
+            Instruction::new(
+                'x',
+                |program| program.next_instruction(),
+                |program| program.prev_instruction(),
+            )
+            .with_kind(kind)
+        };
+        let mut instructions = overflow::instruction_set().parse("+");
+        instructions.insert(0, step(InstructionKind::OpenLoop));
+        instructions.push(step(InstructionKind::CloseLoop));
+        let mut engine = Engine::new(instructions);
+        run_until(&mut engine, 2);
+        assert_eq!(
+            engine.loop_stack(),
+            vec![LoopFrame {
+                open_idx: 0,
+                close_idx: 2,
+                iteration_count: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn brackets_go_to_their_match() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("+[[-]>]]"));
+        assert_eq!(
+            engine.goto_matching_bracket(),
+            Err(EngineError::AtStart.into())
+        );
+        run_until(&mut engine, 2);
+        engine.goto_matching_bracket().unwrap();
+        assert_eq!(engine.instruction_pointer, InstructionPointer::Index(6));
+        engine.goto_matching_bracket().unwrap();
+        assert_eq!(engine.instruction_pointer, InstructionPointer::Index(1));
+        assert_eq!((engine.history.len(), engine.cell()), (1, 1));
+
+        engine.goto(5).unwrap();
+        assert_eq!(
+            engine.goto_matching_bracket(),
+            Err(EngineError::NotABracket { index: 5 }.into())
+        );
+        engine.goto(7).unwrap();
+        assert_eq!(
+            engine.goto_matching_bracket(),
+            Err(EngineError::UnmatchedBracket { index: 7 }.into())
+        );
+    }
+
+    #[test]
+    fn no_loops_outside_the_program() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("+[-]"));
+        assert!(engine.loop_stack().is_empty());
+        run_until(&mut engine, 5);
+        assert_eq!(engine.instruction_pointer, InstructionPointer::End);
+        assert!(engine.loop_stack().is_empty());
+    }
+}
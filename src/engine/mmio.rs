@@ -0,0 +1,181 @@
+use crate::engine::Engine;
+
+use alloc::sync::Arc;
+use core::fmt;
+use core::ops::Range;
+
+/// The host side of memory-mapped cells, for programs written for a toy
+/// machine with devices at fixed addresses, such as a cell that prints
+/// whatever's written to it or one that reads a timer. Handlers share the
+/// engine's `&self`, so keep any state they need behind a lock or atomics.
+pub trait IoHandler: Send + Sync {
+    /// What the program reads from a mapped cell, given what's stored there
+    fn read(&self, cell: usize, stored: u8) -> u8 {
+        let _ = cell;
+        stored
+    }
+
+    /// Called after the program writes to a mapped cell, which still stores
+    /// the value
+    fn write(&self, cell: usize, value: u8) {
+        let _ = (cell, value);
+    }
+}
+
+/// A range of cells handled by the host
+#[derive(Clone)]
+pub struct IoMap {
+    pub cells: Range<usize>,
+    pub handler: Arc<dyn IoHandler>,
+}
+
+impl fmt::Debug for IoMap {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "IoMap({:?})", self.cells)
+    }
+}
+
+/// Maps are equal if they cover the same cells, as handlers can't be
+/// compared
+impl PartialEq for IoMap {
+    fn eq(&self, other: &IoMap) -> bool {
+        self.cells == other.cells
+    }
+}
+
+impl Eq for IoMap {}
+
+impl Engine {
+    /// Hand reads and writes of a range of cells by the program to the
+    /// host, through the current cell's accessors such as `try_cell` and
+    /// `set_cell` while an instruction runs. Anything else, such as `peek`
+    /// or an undo, sees and changes only what's stored. Ranges mapped later
+    /// win where they overlap earlier ones. Undoing a step that used a
+    /// mapped cell puts back what it stored, but can't take back what the
+    /// host did.
+    pub fn map_io<H: IoHandler + 'static>(&mut self, cells: Range<usize>, handler: H) {
+        self.io_maps.push(IoMap {
+            cells,
+            handler: Arc::new(handler),
+        });
+    }
+
+    /// Stop handing cells to the host, for every map covering any of them
+    pub fn unmap_io(&mut self, cells: Range<usize>) {
+        self.io_maps
+            .retain(|map| map.cells.end <= cells.start || cells.end <= map.cells.start);
+    }
+
+    fn io_map(&self, cell: usize) -> Option<&IoMap> {
+        self.io_maps
+            .iter()
+            .rev()
+            .find(|map| map.cells.contains(&cell))
+    }
+
+    pub(crate) fn read_mapped(&self, cell: usize, stored: u8) -> u8 {
+        if !self.executing {
+            return stored;
+        }
+        match self.io_map(cell) {
+            Some(map) => map.handler.read(cell, stored),
+            None => stored,
+        }
+    }
+
+    pub(crate) fn write_mapped(&self, cell: usize, value: u8) {
+        if !self.executing {
+            return;
+        }
+        if let Some(map) = self.io_map(cell) {
+            map.handler.write(cell, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use std::sync::Mutex;
+
+    /// Prints what's written to it, into a buffer
+    #[derive(Clone, Default)]
+    struct Console(Arc<Mutex<Vec<u8>>>);
+
+    impl IoHandler for Console {
+        fn write(&self, _: usize, value: u8) {
+            self.0.lock().unwrap().push(value);
+        }
+    }
+
+    /// Reads as how many times it's been read
+    #[derive(Default)]
+    struct Timer(core::sync::atomic::AtomicU8);
+
+    impl IoHandler for Timer {
+        fn read(&self, _: usize, _: u8) -> u8 {
+            let ordering = core::sync::atomic::Ordering::Relaxed;
+            self.0.fetch_add(1, ordering).wrapping_add(1)
+        }
+    }
+
+    #[test]
+    fn mapped_cells_talk_to_the_host() {
+        let console = Console::default();
+        let mut engine = Engine::new(overflow::instruction_set().parse(">>+++>[-<+>]<[-]"));
+        engine.map_io(2..3, console.clone());
+        while engine.step().is_ok() {}
+        assert_eq!(*console.0.lock().unwrap(), vec![1, 2, 3, 2, 1, 0]);
+        assert_eq!(engine.tape, vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn reads_come_from_the_host() {
+        let mut engine = Engine::new(overflow::instruction_set().parse(">."));
+        engine.map_io(0..4, Console::default());
+        engine.map_io(1..2, Timer::default());
+        while engine.step().is_ok() {}
+        assert_eq!(engine.output, vec![1]);
+        // looking at the cell doesn't read the timer again
+        assert_eq!(engine.cell(), 0);
+
+        engine.unmap_io(1..2);
+        assert!(engine.io_maps.is_empty());
+        assert_eq!(engine.cell(), 0);
+    }
+
+    #[test]
+    fn only_running_instructions_reach_the_host() {
+        let console = Console::default();
+        let mut engine = Engine::new(overflow::instruction_set().parse("+++>."));
+        engine.map_io(0..1, console.clone());
+        engine.map_io(1..2, Timer::default());
+        for _ in 0..4 {
+            engine.step().unwrap();
+        }
+        engine.undo().unwrap();
+        assert_eq!(engine.peek(), 2);
+        assert_eq!(engine.cell(), 2);
+        assert_eq!(*console.0.lock().unwrap(), vec![1, 2, 3]);
+
+        engine.step().unwrap();
+        engine.step().unwrap();
+        assert_eq!((engine.peek(), engine.cell()), (0, 0));
+        engine.step().unwrap();
+        assert_eq!(engine.output, vec![1]);
+    }
+
+    #[test]
+    fn optimized_loops_write_to_the_host() {
+        let console = Console::default();
+        let instructions = overflow::instruction_set().parse("+++[->+<]");
+        let mut engine = Engine::new(crate::ir::compile(&instructions).instructions);
+        engine.map_io(1..2, console.clone());
+        while engine.step().is_ok() {}
+        assert_eq!(engine.tape, vec![0, 3]);
+        assert_eq!(*console.0.lock().unwrap(), vec![3]);
+    }
+}
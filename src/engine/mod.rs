@@ -0,0 +1,890 @@
+#[cfg(feature = "async")]
+pub mod r#async;
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod controller;
+pub mod coverage;
+pub mod diff;
+pub mod dump;
+pub mod edit;
+pub mod error;
+pub mod events;
+pub mod expect;
+pub mod history;
+pub mod input;
+pub mod labels;
+pub mod loops;
+pub mod mmio;
+pub mod multi;
+pub mod patch;
+pub mod provenance;
+pub mod random;
+pub mod reload;
+pub mod replay;
+pub mod run;
+pub mod steps;
+pub mod summary;
+pub mod timeline;
+pub mod trace;
+pub mod until;
+pub mod verify;
+pub mod window;
+pub mod writes;
+
+use crate::instruction::{Instruction, InstructionKind};
+use crate::tape::Tape;
+
+pub use error::EngineError;
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use tap::prelude::*;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Exception {
+    Error(EngineError),
+    RequestingInput,
+    Breakpoint,
+}
+
+impl Exception {
+    /// An error described only in words, for instructions defined outside
+    /// the crate
+    pub fn error<S: Into<String>>(message: S) -> Exception {
+        Exception::Error(EngineError::Other(message.into()))
+    }
+
+    pub fn result<T>(self) -> Result<T, Exception> {
+        Err(self)
+    }
+}
+
+pub type EngineResult = Result<(), Exception>;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InstructionPointer {
+    Start,
+    End,
+    Index(usize),
+}
+
+/// How far the tape stretches to the right of the first cell
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TapeModel {
+    /// Growing whenever the pointer moves past the last cell
+    #[default]
+    Unbounded,
+    /// A fixed number of cells, past which moving the pointer is an error
+    Fixed(usize),
+    /// Growing like an unbounded tape, but only storing the cells that have
+    /// been written to, for programs that reach cells far apart
+    Sparse,
+}
+
+/// Parses `unbounded`, `sparse` or a number of cells for a fixed size tape
+impl core::str::FromStr for TapeModel {
+    type Err = String;
+
+    fn from_str(model: &str) -> Result<TapeModel, String> {
+        match model {
+            "unbounded" => Ok(TapeModel::Unbounded),
+            "sparse" => Ok(TapeModel::Sparse),
+            _ => model.parse().map(TapeModel::Fixed).map_err(|_| {
+                alloc::format!("invalid tape {model}, expected unbounded, sparse or a size")
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Engine {
+    pub tape: Tape,
+    pub tape_pointer: usize,
+    pub tape_model: TapeModel,
+    /// The program, changed by `load_instructions`, `reload_source` and the
+    /// patches, which match its brackets up again. Call `rematch_brackets`
+    /// after changing it any other way.
+    pub instructions: Vec<Instruction>,
+    /// The bracket matching each bracket, from `analysis::match_brackets`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    brackets: Vec<Option<usize>>,
+    pub instruction_pointer: InstructionPointer,
+    pub history: history::History,
+    pub output: Vec<u8>,
+    pub input: Vec<u8>,
+    /// Input to start over on whenever `input` runs out, if it's not empty
+    pub input_cycle: Vec<u8>,
+    pub input_cell_history: Vec<(u8, Option<u8>)>,
+    pub fork_cell_history: Vec<u8>,
+    pub cleared_cell_history: Vec<u8>,
+    pub scan_history: Vec<usize>,
+    /// The cell a random number overwrote, with the random state before it
+    pub random_history: Vec<(u8, u64)>,
+    /// Where each recorded tape edit was, with the cells it overwrote
+    pub tape_edit_history: Vec<(usize, Vec<u8>)>,
+    /// Each input byte read, with the step that read it, from when the
+    /// history starts
+    pub consumed_input: Vec<(usize, u8)>,
+    /// How many input bytes were read by steps the history has dropped
+    pub dropped_input: usize,
+    /// Where each of the latest output bytes came from, from when the
+    /// history starts
+    pub output_sources: Vec<provenance::OutputSource>,
+    /// How many times the body of each loop has started, by where it opens
+    pub loop_counts: BTreeMap<usize, loops::LoopCount>,
+    /// Each step that started the body of a loop, from when the history
+    /// starts
+    pub loop_history: Vec<loops::LoopStart>,
+    pub labels: Vec<labels::Label>,
+    pub spawned: Vec<Engine>,
+    /// Whether each instruction has run, kept across resets
+    pub executed: Vec<bool>,
+    /// A hash of the state from before each kept step, when undos are
+    /// being checked
+    pub undo_hashes: Option<VecDeque<u64>>,
+    /// Every change to a cell, when they're being logged
+    pub cell_writes: Option<Vec<writes::CellWrite>>,
+    pub random_seed: u64,
+    pub random_state: u64,
+    /// Cells loaded for the program to start with, and where they go
+    pub tape_image: Vec<(usize, Vec<u8>)>,
+    /// Cells the host handles reads and writes of
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub io_maps: Vec<mmio::IoMap>,
+    /// Whether an instruction is running, the only time the host hears
+    /// about mapped cells
+    #[cfg_attr(feature = "serde", serde(skip))]
+    executing: bool,
+    /// The most steps any command that takes more than one step takes
+    /// before giving up, if they're limited
+    pub step_budget: Option<usize>,
+}
+
+impl Engine {
+    pub fn new(instructions: Vec<Instruction>) -> Engine {
+        Engine {
+            tape: Tape::default(),
+            tape_pointer: 0,
+            tape_model: TapeModel::default(),
+            brackets: crate::analysis::match_brackets(&instructions),
+            instructions,
+            instruction_pointer: InstructionPointer::Start,
+            history: history::History::default(),
+            output: vec![],
+            input: vec![],
+            input_cycle: vec![],
+            input_cell_history: vec![],
+            fork_cell_history: vec![],
+            cleared_cell_history: vec![],
+            scan_history: vec![],
+            random_history: vec![],
+            tape_edit_history: vec![],
+            consumed_input: vec![],
+            dropped_input: 0,
+            output_sources: vec![],
+            loop_counts: BTreeMap::new(),
+            loop_history: vec![],
+            labels: vec![],
+            spawned: vec![],
+            executed: vec![],
+            undo_hashes: None,
+            cell_writes: None,
+            random_seed: random::DEFAULT_SEED,
+            random_state: random::DEFAULT_SEED,
+            tape_image: vec![],
+            io_maps: vec![],
+            executing: false,
+            step_budget: None,
+        }
+    }
+
+    /// Swap in another program, unless it has a bracket nothing matches,
+    /// which would fail whenever it was reached
+    pub fn load_instructions(&mut self, instructions: Vec<Instruction>) -> Result<(), EngineError> {
+        if let Some(&index) = crate::analysis::unmatched_brackets(&instructions).first() {
+            return Err(EngineError::UnmatchedBracket { index });
+        }
+        self.instructions = instructions;
+        self.rematch_brackets();
+        Ok(())
+    }
+
+    pub fn goto(&mut self, instruction_index: usize) -> EngineResult {
+        if instruction_index < self.instructions.len() {
+            self.instruction_pointer = InstructionPointer::Index(instruction_index);
+            Ok(())
+        } else if self.instructions.is_empty() {
+            Err(EngineError::NoInstructions.into())
+        } else {
+            Err(EngineError::NoSuchInstruction {
+                requested: instruction_index,
+                max: self.instructions.len() - 1,
+            }
+            .into())
+        }
+    }
+
+    pub fn step(&mut self) -> EngineResult {
+        match self.instruction_pointer {
+            InstructionPointer::Index(i) => {
+                let before = self.state_hash_for_undo();
+                let instruction = &self.instructions[i];
+                let step = history::Step::ran(i, instruction.symbol);
+                let written = self.output.len();
+                let stacks = self.undo_stack_lengths();
+                let tape = self.tape_for_cell_writes();
+                let exec = instruction.exec.clone();
+                self.executing = true;
+                let result = exec(self);
+                self.executing = false;
+                result.tap(|result| {
+                    if matches!(result, Ok(()) | Err(Exception::Breakpoint)) {
+                        self.record_output(i, written);
+                        self.record_loop(i);
+                        self.record_cell_writes(self.history.len(), tape);
+                        self.push_step(step, stacks);
+                        self.record_state_hash(before);
+                        self.mark_executed(i);
+                    }
+                })
+            }
+            _ => self.next_instruction(),
+        }
+    }
+
+    /// Undo the latest step, or the whole group of steps it ended
+    pub fn undo(&mut self) -> EngineResult {
+        let Some(start) = self.history.group_start() else {
+            return self.undo_step();
+        };
+        while self.history.len() > start {
+            match self.undo_step() {
+                Ok(()) | Err(Exception::Breakpoint) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn undo_step(&mut self) -> EngineResult {
+        let step = match self.history.last() {
+            Some(step) => step,
+            None if self.history.is_truncated() => {
+                return Err(EngineError::HistoryDropped {
+                    step: self.history.len(),
+                }
+                .into())
+            }
+            None => return Err(EngineError::HistoryEmpty.into()),
+        };
+        let history::Step::Ran { index, symbol } = step else {
+            // a tape edit, so put back the cells it overwrote
+            let (offset, cells) = self
+                .tape_edit_history
+                .pop()
+                .ok_or(EngineError::UndoStateMissing("tape edit"))?;
+            self.tape.write(offset, &cells);
+            self.history.pop();
+            self.forget_cell_writes();
+            return self.verify_undone(None);
+        };
+        let unexec = self
+            .instructions
+            .get(index as usize)
+            .filter(|instruction| instruction.symbol == symbol)
+            .map(|instruction| instruction.unexec.clone())
+            .ok_or(EngineError::ProgramChanged {
+                step: self.history.len(),
+            })?;
+        let written = self.output.len();
+
+        let result = unexec(self).tap(|result| {
+            if matches!(result, Ok(()) | Err(Exception::Breakpoint)) {
+                // back to where the step ran, even if instructions around
+                // it have been added or removed since
+                self.instruction_pointer = InstructionPointer::Index(index as usize);
+                self.history.pop();
+                let step = self.history.len();
+                while self
+                    .consumed_input
+                    .last()
+                    .is_some_and(|&(read, _)| read >= step)
+                {
+                    self.consumed_input.pop();
+                }
+                self.forget_output(written);
+                self.forget_loops();
+                self.forget_cell_writes();
+            }
+        });
+        if matches!(result, Ok(()) | Err(Exception::Breakpoint)) {
+            self.verify_undone(Some(index as usize))?;
+        }
+        result
+    }
+
+    /// Go back to before the first step, keeping the program, labels, tape
+    /// model and image, random seed, coverage and whether undos are checked
+    /// but none of what running it did
+    pub fn reset(&mut self) {
+        self.tape = Tape::new(self.tape_model);
+        for (offset, cells) in core::mem::take(&mut self.tape_image) {
+            // it fit when it was loaded, on the same model of tape
+            let _ = self.load_tape(&cells, offset);
+        }
+        self.tape_pointer = 0;
+        self.instruction_pointer = InstructionPointer::Start;
+        self.history.clear();
+        self.output = vec![];
+        self.input = vec![];
+        self.input_cell_history = vec![];
+        self.fork_cell_history = vec![];
+        self.cleared_cell_history = vec![];
+        self.scan_history = vec![];
+        self.random_history = vec![];
+        self.random_state = self.random_seed;
+        self.tape_edit_history = vec![];
+        self.consumed_input = vec![];
+        self.dropped_input = 0;
+        self.output_sources = vec![];
+        self.loop_counts.clear();
+        self.loop_history = vec![];
+        self.spawned = vec![];
+        if let Some(hashes) = &mut self.undo_hashes {
+            hashes.clear();
+        }
+        if let Some(writes) = &mut self.cell_writes {
+            writes.clear();
+        }
+    }
+
+    /// Reset, then give the program new input to run with
+    pub fn restart_with_input(&mut self, input: Vec<u8>) {
+        self.reset();
+        self.input = input;
+    }
+
+    /// Build a new engine sharing this one's program position and a copy of
+    /// its tape, but none of its history, input or output, seeded from this
+    /// one's random numbers.
+    pub fn fork(&self) -> Engine {
+        let mut child = Engine::new(self.instructions.clone());
+        child.set_seed(random::derive_seed(self.random_state, self.spawned.len()));
+        child.tape = self.tape.clone();
+        child.tape_pointer = self.tape_pointer;
+        child.tape_model = self.tape_model;
+        child.history = history::History::new(self.history.policy());
+        child.labels = self.labels.clone();
+        child.instruction_pointer = self.instruction_pointer;
+        child
+    }
+
+    pub fn current_instruction(&self) -> Option<Instruction> {
+        match self.instruction_pointer {
+            InstructionPointer::Start => None,
+            InstructionPointer::End => None,
+            InstructionPointer::Index(i) => Some(self.instructions[i].clone()),
+        }
+    }
+
+    pub fn next_instruction(&mut self) -> EngineResult {
+        match self.instruction_pointer {
+            InstructionPointer::End => Err(EngineError::AtEnd.into()),
+            InstructionPointer::Start => {
+                if self.instructions.is_empty() {
+                    Err(EngineError::NoInstructions.into())
+                } else {
+                    self.instruction_pointer = InstructionPointer::Index(0);
+                    Ok(())
+                }
+            }
+            InstructionPointer::Index(i) if i + 1 == self.instructions.len() => {
+                self.instruction_pointer = InstructionPointer::End;
+                Ok(())
+            }
+            InstructionPointer::Index(i) => {
+                self.instruction_pointer = InstructionPointer::Index(i + 1);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn prev_instruction(&mut self) -> EngineResult {
+        match self.instruction_pointer {
+            InstructionPointer::Start => Err(EngineError::AtStart.into()),
+            InstructionPointer::End => {
+                self.instruction_pointer = InstructionPointer::Index(self.instructions.len() - 1);
+                Ok(())
+            }
+            InstructionPointer::Index(0) => {
+                self.instruction_pointer = InstructionPointer::Start;
+                Ok(())
+            }
+            InstructionPointer::Index(i) => {
+                self.instruction_pointer = InstructionPointer::Index(i - 1);
+                Ok(())
+            }
+        }
+    }
+
+    /// Move to the next instruction of kind `goto`, skipping any that pair
+    /// up with a `matching` instruction in between, such as the `]` that
+    /// closes a loop
+    pub fn goto_next(&mut self, goto: InstructionKind, matching: InstructionKind) -> EngineResult {
+        let start = match self.instruction_pointer {
+            InstructionPointer::End => Err(EngineError::AtEnd),
+            InstructionPointer::Start => Ok(0),
+            InstructionPointer::Index(i) => Ok(i + 1),
+        }?;
+
+        let rest = self.instructions.iter().skip(start);
+        let mut skip = 0;
+        for (i, instruction) in rest.enumerate() {
+            if instruction.kind == goto {
+                if skip == 0 {
+                    self.instruction_pointer = InstructionPointer::Index(start + i);
+                    return Ok(());
+                } else {
+                    skip -= 1;
+                }
+            } else if instruction.kind == matching {
+                skip += 1;
+            }
+        }
+
+        Err(EngineError::UnmatchedBracket {
+            index: start.saturating_sub(1),
+        }
+        .into())
+    }
+
+    /// Move to the previous instruction of kind `goto`, skipping any that
+    /// pair up with a `matching` instruction in between
+    pub fn goto_prev(&mut self, goto: InstructionKind, matching: InstructionKind) -> EngineResult {
+        let end = match self.instruction_pointer {
+            InstructionPointer::Start => Err(EngineError::AtStart),
+            InstructionPointer::End => Ok(self.instructions.len() - 1),
+            InstructionPointer::Index(i) => Ok(i),
+        }?;
+
+        let rest = self.instructions.iter().take(end);
+        let mut skip = 0;
+        for (i, instruction) in rest.rev().enumerate() {
+            if instruction.kind == goto {
+                if skip == 0 {
+                    self.instruction_pointer = InstructionPointer::Index(end - i - 1);
+                    return Ok(());
+                } else {
+                    skip -= 1;
+                }
+            } else if instruction.kind == matching {
+                skip += 1;
+            }
+        }
+
+        Err(EngineError::UnmatchedBracket { index: end }.into())
+    }
+
+    pub fn next_cell(&mut self) -> EngineResult {
+        self.reach(self.tape_pointer + 1)?;
+        self.tape_pointer += 1;
+
+        Ok(())
+    }
+
+    pub fn prev_cell(&mut self) -> EngineResult {
+        self.try_prev_cell()
+    }
+
+    /// Move the tape pointer back a cell, failing at the first cell
+    pub fn try_prev_cell(&mut self) -> EngineResult {
+        self.tape_pointer = self
+            .tape_pointer
+            .checked_sub(1)
+            .ok_or(EngineError::TapeUnderflow)?;
+        Ok(())
+    }
+
+    /// Move the tape pointer by several cells at once, failing without
+    /// moving at all if that would take it off the start of the tape.
+    pub fn move_pointer(&mut self, offset: isize) -> EngineResult {
+        let target = self
+            .tape_pointer
+            .checked_add_signed(offset)
+            .ok_or(EngineError::TapeUnderflow)?;
+
+        self.reach(target)?;
+        self.tape_pointer = target;
+
+        Ok(())
+    }
+
+    /// Expand the tape to include the cell at an index if it's new, failing
+    /// if that's past the end of a fixed size tape.
+    pub fn reach(&mut self, index: usize) -> EngineResult {
+        if let TapeModel::Fixed(size) = self.tape_model {
+            if index >= size {
+                return Err(EngineError::TapeOverflow.into());
+            }
+        }
+        if index >= self.tape.len() {
+            self.tape.resize(index + 1);
+        }
+
+        Ok(())
+    }
+
+    /// What's stored in the current cell, or 0 past the end of the tape,
+    /// without asking the host about mapped cells, for looking at the engine
+    /// rather than running it
+    pub fn peek(&self) -> u8 {
+        self.tape.get(self.tape_pointer).copied().unwrap_or(0)
+    }
+
+    /// The current cell, panicking if the tape pointer is past the end of
+    /// the tape, which only happens if they're changed by hand
+    pub fn cell(&self) -> u8 {
+        self.read_mapped(self.tape_pointer, self.tape[self.tape_pointer])
+    }
+
+    pub fn set_cell(&mut self, value: u8) {
+        self.tape[self.tape_pointer] = value;
+        self.write_mapped(self.tape_pointer, value);
+    }
+
+    pub fn map_cell(&mut self, f: impl FnOnce(u8) -> u8) {
+        let value = self.cell();
+        self.set_cell(f(value));
+    }
+
+    /// The current cell, or an error if the tape pointer is past the end of
+    /// the tape, so a tape or pointer changed by hand can't panic
+    pub fn try_cell(&self) -> Result<u8, Exception> {
+        self.tape
+            .get(self.tape_pointer)
+            .map(|&stored| self.read_mapped(self.tape_pointer, stored))
+            .ok_or(EngineError::OffTape.into())
+    }
+
+    pub fn try_set_cell(&mut self, value: u8) -> EngineResult {
+        if self.tape_pointer >= self.tape.len() {
+            return Err(EngineError::OffTape.into());
+        }
+        self.set_cell(value);
+        Ok(())
+    }
+
+    pub fn try_map_cell(&mut self, f: impl FnOnce(u8) -> u8) -> EngineResult {
+        let value = self.try_cell()?;
+        self.set_cell(f(value));
+        Ok(())
+    }
+
+    /// Where a sequence of bytes starts on the tape, including overlapping
+    /// matches
+    pub fn find_in_tape(&self, pattern: &[u8]) -> Vec<usize> {
+        self.tape.find(pattern)
+    }
+
+    pub fn pop_input(&mut self) -> Option<u8> {
+        if self.input.is_empty() {
+            self.input.clone_from(&self.input_cycle);
+        }
+        let head = self.input.first().cloned();
+        if let Some(head) = head {
+            self.input.remove(0);
+            self.consumed_input.push((self.history.len(), head));
+        }
+        head
+    }
+
+    pub fn push_input(&mut self, head: u8) {
+        self.input.insert(0, head);
+    }
+
+    pub fn input(&mut self, buffered: &mut Vec<u8>) {
+        let mut input = vec![];
+        input.append(buffered);
+        input.append(&mut self.input);
+        self.input = input;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use InstructionKind::{CloseLoop, OpenLoop};
+
+    fn noop(symbol: char) -> Instruction {
+        Instruction::new(symbol, |_| Ok(()), |_| Ok(()))
+    }
+
+    fn noops(symbols: &str) -> Vec<Instruction> {
+        symbols.chars().map(noop).collect()
+    }
+
+    /// Noops where `a` opens loops and `c` closes them
+    fn brackets(symbols: &str) -> Vec<Instruction> {
+        let kind = |symbol| match symbol {
+            'a' => OpenLoop,
+            'c' => CloseLoop,
+            _ => InstructionKind::Plain,
+        };
+        symbols
+            .chars()
+            .map(|symbol| noop(symbol).with_kind(kind(symbol)))
+            .collect()
+    }
+
+    fn ok(result: EngineResult) {
+        assert_eq!(result, Ok(()))
+    }
+
+    #[cfg(feature = "cow-tape")]
+    #[test]
+    fn new_engines_fork_without_copying_the_tape() {
+        let mut engine = Engine::new(noops("a"));
+        engine.tape[0] = 1;
+        let child = engine.fork();
+        let (Tape::Paged { pages: parent, .. }, Tape::Paged { pages: forked, .. }) =
+            (&engine.tape, &child.tape)
+        else {
+            panic!("expected paged tapes");
+        };
+        assert!(alloc::sync::Arc::ptr_eq(&parent[&0], &forked[&0]));
+    }
+
+    #[test]
+    fn new_builds_blank_program() {
+        let program = Engine::new(noops("abc"));
+
+        assert_eq!(
+            program,
+            Engine {
+                tape: Tape::default(),
+                tape_pointer: 0,
+                tape_model: TapeModel::Unbounded,
+                instructions: noops("abc"),
+                brackets: vec![None; 3],
+                instruction_pointer: InstructionPointer::Start,
+                history: history::History::default(),
+                output: vec![],
+                input: vec![],
+                input_cycle: vec![],
+                input_cell_history: vec![],
+                fork_cell_history: vec![],
+                cleared_cell_history: vec![],
+                scan_history: vec![],
+                random_history: vec![],
+                tape_edit_history: vec![],
+                consumed_input: vec![],
+                dropped_input: 0,
+                output_sources: vec![],
+                loop_counts: BTreeMap::new(),
+                loop_history: vec![],
+                labels: vec![],
+                spawned: vec![],
+                executed: vec![],
+                undo_hashes: None,
+                cell_writes: None,
+                random_seed: random::DEFAULT_SEED,
+                random_state: random::DEFAULT_SEED,
+                tape_image: vec![],
+                io_maps: vec![],
+                executing: false,
+                step_budget: None,
+            }
+        );
+    }
+
+    #[test]
+    fn find_in_tape_finds_every_match() {
+        let mut program = Engine::new(noops("abc"));
+        program.tape = b"abababc".to_vec().into();
+
+        assert_eq!(program.find_in_tape(b"aba"), vec![0, 2]);
+        assert_eq!(program.find_in_tape(b"c"), vec![6]);
+        assert!(program.find_in_tape(b"abcd").is_empty());
+        assert!(program.find_in_tape(b"").is_empty());
+    }
+
+    #[test]
+    fn goto_sets_instruction_pointer() {
+        let mut program = Engine::new(noops("abc"));
+
+        ok(program.goto(1));
+
+        assert_eq!(program.current_instruction(), Some(noop('b')));
+        assert_eq!(program.instruction_pointer, InstructionPointer::Index(1));
+    }
+
+    #[test]
+    fn goto_overrun_fails_gracefully() {
+        let mut program = Engine::new(noops("abc"));
+
+        assert!(program.goto(3).is_err());
+        assert_eq!(program.instruction_pointer, InstructionPointer::Start);
+    }
+
+    #[test]
+    fn goto_errors_say_what_went_wrong() {
+        let mut program = Engine::new(noops("abc"));
+        assert_eq!(
+            program.goto(5),
+            Err(Exception::Error(EngineError::NoSuchInstruction {
+                requested: 5,
+                max: 2
+            }))
+        );
+
+        let mut empty = Engine::new(vec![]);
+        assert_eq!(
+            empty.goto(0),
+            Err(Exception::Error(EngineError::NoInstructions))
+        );
+    }
+
+    #[test]
+    fn goto_next_moves_to_next_instruction() {
+        let mut program = Engine::new(brackets("abcbac"));
+
+        ok(program.goto(0));
+        ok(program.goto_next(CloseLoop, OpenLoop));
+
+        assert_eq!(program.current_instruction(), Some(noop('c')));
+        assert_eq!(program.instruction_pointer, InstructionPointer::Index(2));
+    }
+
+    #[test]
+    fn goto_next_matches_nesting() {
+        let mut program = Engine::new(brackets("abacbc"));
+
+        ok(program.goto(0));
+        ok(program.goto_next(CloseLoop, OpenLoop));
+
+        assert_eq!(program.current_instruction(), Some(noop('c')));
+        assert_eq!(program.instruction_pointer, InstructionPointer::Index(5));
+    }
+
+    #[test]
+    fn goto_next_fails_gracefully_on_overrun() {
+        let mut program = Engine::new(brackets("abca"));
+
+        ok(program.goto(0));
+        ok(program.goto_next(CloseLoop, OpenLoop));
+
+        assert!(program.goto_next(CloseLoop, OpenLoop).is_err());
+        assert_eq!(program.current_instruction(), Some(noop('c')));
+        assert_eq!(program.instruction_pointer, InstructionPointer::Index(2));
+    }
+
+    #[test]
+    fn goto_prev_moves_to_prev_instruction() {
+        let mut program = Engine::new(brackets("abcbac"));
+
+        ok(program.goto(5));
+        ok(program.goto_prev(OpenLoop, CloseLoop));
+
+        assert_eq!(program.current_instruction(), Some(noop('a')));
+        assert_eq!(program.instruction_pointer, InstructionPointer::Index(4));
+    }
+
+    #[test]
+    fn goto_prev_nmatches_nesting() {
+        let mut program = Engine::new(brackets("abacbc"));
+
+        ok(program.goto(5));
+        ok(program.goto_prev(OpenLoop, CloseLoop));
+
+        assert_eq!(program.current_instruction(), Some(noop('a')));
+        assert_eq!(program.instruction_pointer, InstructionPointer::Index(0));
+    }
+
+    #[test]
+    fn goto_prev_fails_gracefully_on_underrun() {
+        let mut program = Engine::new(brackets("cabc"));
+
+        ok(program.goto(3));
+        ok(program.goto_prev(OpenLoop, CloseLoop));
+
+        assert!(program.goto_prev(OpenLoop, CloseLoop).is_err());
+        assert_eq!(program.current_instruction(), Some(noop('a')));
+        assert_eq!(program.instruction_pointer, InstructionPointer::Index(1));
+    }
+
+    #[test]
+    fn pointer_off_the_tape_fails_gracefully() {
+        let instructions = crate::flavor::overflow::instruction_set().parse("+.<[]");
+        let mut program = Engine::new(instructions);
+        program.tape_pointer = 3;
+
+        assert!(program.try_cell().is_err());
+        ok(program.step());
+        assert!(program.step().is_err());
+        program.tape_pointer = 0;
+        assert!(program.try_prev_cell().is_err());
+        assert_eq!(program.tape_pointer, 0);
+    }
+
+    #[test]
+    fn restarting_keeps_the_program_and_labels() {
+        let instructions = crate::flavor::overflow::instruction_set().parse(",>+.");
+        let mut program = Engine::new(instructions);
+        program.label_cell(1, "flag");
+        program.input = b"a".to_vec();
+        while program.step().is_ok() {}
+
+        program.restart_with_input(b"b".to_vec());
+        assert_eq!(program.instruction_pointer, InstructionPointer::Start);
+        assert_eq!(program.tape, vec![0]);
+        assert_eq!(program.output, Vec::<u8>::new());
+        assert_eq!(program.instructions.len(), 4);
+        assert_eq!(program.labelled("flag"), Some(1..2));
+        while program.step().is_ok() {}
+        assert_eq!(program.tape, vec![b'b', 1]);
+    }
+
+    #[test]
+    fn loading_unmatched_brackets_is_refused() {
+        let instruction_set = crate::flavor::overflow::instruction_set();
+        let mut program = Engine::new(instruction_set.parse("+"));
+        assert_eq!(
+            program.load_instructions(instruction_set.parse("+[>]]")),
+            Err(EngineError::UnmatchedBracket { index: 4 })
+        );
+        assert_eq!(program.instructions.len(), 1);
+        assert_eq!(program.load_instructions(instruction_set.parse("[-]")), Ok(()));
+        assert_eq!(program.instructions.len(), 3);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialized_engine_resumes_where_it_left_off() {
+        use serde::de::DeserializeSeed;
+
+        let instruction_set = crate::flavor::overflow::instruction_set();
+        let mut program = Engine::new(instruction_set.parse("++[->+<]"));
+        for _ in 0..6 {
+            ok(program.step());
+        }
+
+        let json = serde_json::to_string(&program).unwrap();
+        let mut resumed = crate::instruction::EngineSeed(&instruction_set)
+            .deserialize(&mut serde_json::Deserializer::from_str(&json))
+            .unwrap();
+        assert_eq!(resumed, program);
+
+        ok(program.step());
+        ok(resumed.step());
+        assert_eq!(resumed, program);
+        ok(resumed.undo());
+        ok(resumed.undo());
+        assert_eq!(resumed.tape, vec![1, 0]);
+        assert_eq!(resumed.tape_pointer, 0);
+    }
+}
@@ -0,0 +1,224 @@
+use crate::engine::random;
+use crate::engine::{Engine, EngineError, EngineResult, Exception, InstructionPointer};
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// A record of one scheduled step, kept so it can be undone
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Turn {
+    thread: usize,
+    forked: bool,
+    output_len: usize,
+    /// The scheduler's random state before the turn
+    scheduler: Option<u64>,
+}
+
+/// Steps several engines forked from one program in a deterministic
+/// round-robin order, or a seeded random one, sharing a single input and
+/// output stream between them.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MultiEngine {
+    pub threads: Vec<Engine>,
+    pub current: usize,
+    pub output: Vec<u8>,
+    pub input: Vec<u8>,
+    schedule: Vec<Turn>,
+    /// The random state picking threads, if they're picked at random
+    scheduler: Option<u64>,
+}
+
+impl MultiEngine {
+    pub fn new(mut engine: Engine) -> MultiEngine {
+        if engine.instruction_pointer == InstructionPointer::Start {
+            engine.next_instruction().ok();
+        }
+
+        MultiEngine {
+            threads: vec![engine],
+            current: 0,
+            output: vec![],
+            input: vec![],
+            schedule: vec![],
+            scheduler: None,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.threads.iter().all(Self::is_thread_finished)
+    }
+
+    fn is_thread_finished(engine: &Engine) -> bool {
+        engine.instruction_pointer == InstructionPointer::End
+    }
+
+    fn next_runnable(&self) -> Option<usize> {
+        let count = self.threads.len();
+        (0..count)
+            .map(|offset| (self.current + offset) % count)
+            .find(|&i| !Self::is_thread_finished(&self.threads[i]))
+    }
+
+    /// Seed every thread and pick which runs next at random from the seed
+    /// rather than in turn, so the same seed interleaves the same way every
+    /// run. Threads forked later are seeded from their parents.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.scheduler = Some(seed.max(1));
+        for (i, thread) in self.threads.iter_mut().enumerate() {
+            thread.set_seed(random::derive_seed(seed, i));
+        }
+    }
+
+    /// The thread to step next, and the scheduler's state after picking it
+    fn pick(&self) -> Option<(usize, Option<u64>)> {
+        let Some(state) = self.scheduler else {
+            return self.next_runnable().map(|thread| (thread, None));
+        };
+        let runnable = (0..self.threads.len())
+            .filter(|&i| !Self::is_thread_finished(&self.threads[i]))
+            .collect::<Vec<_>>();
+        let state = random::xorshift(state);
+        let thread = *runnable.get((state % runnable.len().max(1) as u64) as usize)?;
+        Some((thread, Some(state)))
+    }
+
+    pub fn step(&mut self) -> EngineResult {
+        let (thread, scheduler) = self.pick().ok_or(EngineError::ThreadsFinished)?;
+        let output_len = self.output.len();
+        let engine = &mut self.threads[thread];
+        let engine_output_len = engine.output.len();
+
+        let mut result = engine.step();
+        if result == Err(Exception::RequestingInput) && !self.input.is_empty() {
+            engine.push_input(self.input.remove(0));
+            result = engine.step();
+        }
+
+        if let Err(Exception::Error(_) | Exception::RequestingInput) = result {
+            self.current = thread;
+            return result;
+        }
+
+        self.output
+            .extend_from_slice(&engine.output[engine_output_len..]);
+        let forked = !engine.spawned.is_empty();
+        let spawned = core::mem::take(&mut engine.spawned);
+        self.threads.extend(spawned);
+
+        self.schedule.push(Turn {
+            thread,
+            forked,
+            output_len,
+            scheduler: self.scheduler,
+        });
+        self.scheduler = scheduler;
+        self.current = (thread + 1) % self.threads.len();
+
+        result
+    }
+
+    pub fn undo(&mut self) -> EngineResult {
+        let turn = self
+            .schedule
+            .last()
+            .cloned()
+            .ok_or(EngineError::HistoryEmpty)?;
+
+        // turns are undone in reverse, so by the time a fork is reached the
+        // child it spawned is the newest thread and back at its first step
+        let child = if turn.forked {
+            self.threads.pop()
+        } else {
+            None
+        };
+
+        let engine = &mut self.threads[turn.thread];
+        let result = engine.undo();
+        if let Err(Exception::Error(_)) = result {
+            self.threads.extend(child);
+            return result;
+        }
+
+        // give any input the undo handed back to the thread to the shared queue
+        let mut input = core::mem::take(&mut engine.input);
+        input.append(&mut self.input);
+        self.input = input;
+
+        self.output.truncate(turn.output_len);
+        self.schedule.pop();
+        self.current = turn.thread;
+        self.scheduler = turn.scheduler;
+
+        result
+    }
+
+    pub fn run(&mut self) -> EngineResult {
+        while !self.is_finished() {
+            self.step()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::brainfork;
+
+    fn engine(code: &str) -> Engine {
+        Engine::new(brainfork::instruction_set().parse(code))
+    }
+
+    #[test]
+    fn fork_copies_tape_into_new_thread() {
+        let mut multi = MultiEngine::new(engine("+++Y"));
+
+        multi.run().unwrap();
+
+        assert_eq!(multi.threads.len(), 2);
+        assert_eq!(multi.threads[0].tape, vec![0]);
+        assert_eq!(multi.threads[1].tape, vec![3, 1]);
+        assert_eq!(multi.threads[1].tape_pointer, 1);
+    }
+
+    #[test]
+    fn undo_removes_forked_thread() {
+        let mut multi = MultiEngine::new(engine("+Y+"));
+
+        multi.run().unwrap();
+        while multi.undo().is_ok() {}
+
+        assert_eq!(multi.threads.len(), 1);
+        assert_eq!(multi.threads[0].tape, vec![0]);
+        assert_eq!(multi.output, Vec::<u8>::new());
+    }
+
+    #[test]
+    fn input_is_shared_between_threads() {
+        let mut multi = MultiEngine::new(engine("Y,."));
+        multi.input = b"xy".to_vec();
+
+        multi.run().unwrap();
+
+        assert_eq!(multi.output, b"xy".to_vec());
+        assert!(multi.input.is_empty());
+    }
+
+    #[test]
+    fn seeded_schedules_repeat() {
+        let run = |seed| {
+            let mut multi = MultiEngine::new(engine("YY+++[.-]"));
+            multi.set_seed(seed);
+            multi.run().unwrap();
+            multi
+        };
+        let first = run(5);
+        assert_eq!(first, run(5));
+        assert_ne!(first.schedule, run(6).schedule);
+
+        let mut multi = run(5);
+        while multi.undo().is_ok() {}
+        multi.run().unwrap();
+        assert_eq!(multi.schedule, first.schedule);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::engine::{Engine, EngineError, EngineResult, Exception, InstructionPointer};
+use crate::instruction::Instruction;
+
+use alloc::vec::Vec;
+
+impl Engine {
+    /// Swap the instruction at an index for another, returning the old one.
+    /// Steps that ran the old instruction can't be undone afterwards unless
+    /// the new one has the same symbol. Like the other patches, it's refused
+    /// if it would leave a bracket unmatched, so change loops as a whole
+    /// with `reload_source`.
+    pub fn replace_instruction(
+        &mut self,
+        index: usize,
+        instruction: Instruction,
+    ) -> Result<Instruction, Exception> {
+        if index >= self.instructions.len() {
+            return Err(self.no_such_instruction(index));
+        }
+        if self.instructions[index].kind != instruction.kind {
+            self.check_brackets(|patched| patched[index] = instruction.clone())?;
+        }
+        let replaced = core::mem::replace(&mut self.instructions[index], instruction);
+        self.rematch_brackets();
+        Ok(replaced)
+    }
+
+    /// Insert an instruction before the one at an index, or at the end,
+    /// keeping the instruction pointer and history on the instructions they
+    /// were on.
+    pub fn insert_instruction(&mut self, index: usize, instruction: Instruction) -> EngineResult {
+        if index > self.instructions.len() {
+            return Err(EngineError::NoSuchInstruction {
+                requested: index,
+                max: self.instructions.len(),
+            }
+            .into());
+        }
+        if instruction.opens_loop() || instruction.closes_loop() {
+            self.check_brackets(|patched| patched.insert(index, instruction.clone()))?;
+        }
+        self.instructions.insert(index, instruction);
+        self.rematch_brackets();
+        self.history.instruction_inserted(index);
+        self.reindex_output(|i| Some(if i >= index { i + 1 } else { i }));
+        self.reindex_loops(|i| Some(if i >= index { i + 1 } else { i }));
+        if let InstructionPointer::Index(i) = self.instruction_pointer {
+            if i >= index {
+                self.instruction_pointer = InstructionPointer::Index(i + 1);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the instruction at an index, returning it. The instruction
+    /// pointer stays on the instruction it was on, or moves on to the next
+    /// one if that was the one removed.
+    pub fn remove_instruction(&mut self, index: usize) -> Result<Instruction, Exception> {
+        if index >= self.instructions.len() {
+            return Err(self.no_such_instruction(index));
+        }
+        let removed = &self.instructions[index];
+        if removed.opens_loop() || removed.closes_loop() {
+            self.check_brackets(|patched| {
+                patched.remove(index);
+            })?;
+        }
+        let instruction = self.instructions.remove(index);
+        self.rematch_brackets();
+        self.history.instruction_removed(index);
+        let moved = |i: usize| match i.cmp(&index) {
+            core::cmp::Ordering::Less => Some(i),
+            core::cmp::Ordering::Equal => None,
+            core::cmp::Ordering::Greater => Some(i - 1),
+        };
+        self.reindex_output(moved);
+        self.reindex_loops(moved);
+        if let InstructionPointer::Index(i) = self.instruction_pointer {
+            self.instruction_pointer = if i > index {
+                InstructionPointer::Index(i - 1)
+            } else if i == self.instructions.len() {
+                InstructionPointer::End
+            } else {
+                InstructionPointer::Index(i)
+            };
+        }
+        Ok(instruction)
+    }
+
+    /// Refuse a patch to a bracket that would leave one unmatched
+    fn check_brackets(&self, patch: impl FnOnce(&mut Vec<Instruction>)) -> Result<(), EngineError> {
+        let mut patched = self.instructions.clone();
+        patch(&mut patched);
+        match crate::analysis::unmatched_brackets(&patched).first() {
+            Some(&index) => Err(EngineError::UnmatchedBracket { index }),
+            None => Ok(()),
+        }
+    }
+
+    fn no_such_instruction(&self, index: usize) -> Exception {
+        match self.instructions.len() {
+            0 => EngineError::NoInstructions.into(),
+            len => EngineError::NoSuchInstruction {
+                requested: index,
+                max: len - 1,
+            }
+            .into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    fn symbols(engine: &Engine) -> alloc::string::String {
+        engine.instructions.iter().map(|i| i.symbol).collect()
+    }
+
+    #[test]
+    fn patching_keeps_the_current_instruction() {
+        let set = overflow::instruction_set();
+        let mut engine = Engine::new(set.parse("+>+"));
+        engine.step().unwrap();
+        engine.step().unwrap();
+        engine.step().unwrap();
+        assert_eq!(engine.instruction_pointer, InstructionPointer::Index(2));
+
+        engine
+            .insert_instruction(0, set.get('-').cloned().unwrap())
+            .unwrap();
+        assert_eq!(symbols(&engine), "-+>+");
+        assert_eq!(engine.instruction_pointer, InstructionPointer::Index(3));
+
+        engine.remove_instruction(3).unwrap();
+        assert_eq!(engine.instruction_pointer, InstructionPointer::End);
+        engine
+            .replace_instruction(0, set.get('+').cloned().unwrap())
+            .unwrap();
+        assert_eq!(symbols(&engine), "++>");
+
+        // the history followed the instructions it ran
+        engine.undo().unwrap();
+        engine.undo().unwrap();
+        assert_eq!(engine.tape, vec![0, 0]);
+        assert_eq!(engine.instruction_pointer, InstructionPointer::Index(1));
+    }
+
+    #[test]
+    fn patches_cant_leave_brackets_unmatched() {
+        let set = overflow::instruction_set();
+        let mut engine = Engine::new(set.parse("+[-]"));
+        assert_eq!(
+            engine.insert_instruction(0, set.get(']').cloned().unwrap()),
+            Err(EngineError::UnmatchedBracket { index: 0 }.into())
+        );
+        assert_eq!(
+            engine.remove_instruction(3).map(|_| ()),
+            Err(EngineError::UnmatchedBracket { index: 1 }.into())
+        );
+        assert!(engine
+            .replace_instruction(1, set.get('>').cloned().unwrap())
+            .is_err());
+        assert_eq!(symbols(&engine), "+[-]");
+        engine
+            .replace_instruction(2, set.get('+').cloned().unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn brackets_stay_matched_through_patches() {
+        let set = overflow::instruction_set();
+        let mut engine = Engine::new(set.parse("[-]"));
+        engine
+            .insert_instruction(0, set.get('>').cloned().unwrap())
+            .unwrap();
+        assert_eq!(engine.matching_bracket(1), Some(3));
+        engine.remove_instruction(2).unwrap();
+        assert_eq!(engine.matching_bracket(2), Some(1));
+        engine.goto(1).unwrap();
+        engine.goto_matching_bracket().unwrap();
+        assert_eq!(engine.instruction_pointer, InstructionPointer::Index(2));
+    }
+
+    #[test]
+    fn steps_of_removed_instructions_cant_be_undone() {
+        let set = overflow::instruction_set();
+        let mut engine = Engine::new(set.parse("+-"));
+        engine.step().unwrap();
+        engine.step().unwrap();
+        engine.remove_instruction(0).unwrap();
+
+        assert!(engine.undo().is_err());
+        assert!(engine.remove_instruction(1).is_err());
+        assert!(engine
+            .insert_instruction(2, set.get('+').cloned().unwrap())
+            .is_err());
+    }
+}
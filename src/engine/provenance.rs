@@ -0,0 +1,189 @@
+use crate::engine::{Engine, EngineError, EngineResult, Exception};
+
+use alloc::vec::Vec;
+
+/// Where an output byte came from
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutputSource {
+    /// The step that wrote the byte, counting from 0
+    pub step: usize,
+    /// The instruction that wrote it, unless it's since been removed
+    pub index: Option<usize>,
+}
+
+impl Engine {
+    /// Where each output byte came from, in order, for the latest bytes
+    /// written by steps the history still has. Bytes put in the output by
+    /// hand rather than by a step have none.
+    pub fn output_provenance(&self) -> &[OutputSource] {
+        &self.output_sources
+    }
+
+    /// Where an output byte came from, if it's one of the latest and the
+    /// history still has the step that wrote it
+    pub fn output_source(&self, byte: usize) -> Option<&OutputSource> {
+        let first = self.output.len().saturating_sub(self.output_sources.len());
+        self.output_sources.get(byte.checked_sub(first)?)
+    }
+
+    /// Go to just after the step that wrote an output byte
+    pub fn goto_output(&mut self, byte: usize) -> EngineResult {
+        let source = self.output_source(byte).ok_or(EngineError::NoOutput)?;
+        self.goto_step(source.step + 1)
+    }
+
+    /// Take everything output so far, such as to stream it somewhere else,
+    /// forgetting where it came from along with it
+    pub fn take_output(&mut self) -> Vec<u8> {
+        self.output_sources.clear();
+        core::mem::take(&mut self.output)
+    }
+
+    /// Each input byte read since `dropped_input`, with the step that read it
+    pub fn input_provenance(&self) -> &[(usize, u8)] {
+        &self.consumed_input
+    }
+
+    /// The input bytes a step the history still has read, such as the
+    /// latest, which is kept even with history off
+    pub fn input_read_by(&self, step: usize) -> impl Iterator<Item = u8> + '_ {
+        let start = self.consumed_input.partition_point(|&(read, _)| read < step);
+        self.consumed_input[start..]
+            .iter()
+            .take_while(move |&&(read, _)| read == step)
+            .map(|&(_, byte)| byte)
+    }
+
+    /// Go back to just before the program read an input byte, for giving it
+    /// a different one. The byte is taken out of the input and returned,
+    /// leaving whatever was read after it to be read again.
+    pub fn rewind_to_input(&mut self, byte: usize) -> Result<u8, Exception> {
+        let Some(kept) = byte.checked_sub(self.dropped_input) else {
+            return Err(EngineError::HistoryDropped {
+                step: self.history.len() - self.history.retained(),
+            }
+            .into());
+        };
+        let (step, read) = *self
+            .consumed_input
+            .get(kept)
+            .ok_or(EngineError::InputNotRead { index: byte })?;
+        self.goto_step(step)?;
+        // rewinding handed it back to be read first
+        if self.input.first() == Some(&read) {
+            self.input.remove(0);
+        }
+        Ok(read)
+    }
+
+    /// Note that the step about to go in the history ran an instruction
+    /// that wrote everything in the output past `written`
+    pub(crate) fn record_output(&mut self, index: usize, written: usize) {
+        let step = self.history.len();
+        let source = OutputSource {
+            step,
+            index: Some(index),
+        };
+        let wrote = self.output.len().saturating_sub(written);
+        self.output_sources
+            .extend(core::iter::repeat_n(source, wrote));
+    }
+
+    /// Drop the sources of output an undo took back, from when there were
+    /// `written` bytes
+    pub(crate) fn forget_output(&mut self, written: usize) {
+        let removed = written.saturating_sub(self.output.len());
+        let kept = self.output_sources.len().saturating_sub(removed);
+        self.output_sources.truncate(kept);
+    }
+
+    /// Move sources onto where their instructions went when the program
+    /// changed
+    pub(crate) fn reindex_output(&mut self, moved: impl Fn(usize) -> Option<usize>) {
+        for source in &mut self.output_sources {
+            source.index = source.index.and_then(&moved);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+    use alloc::vec;
+
+    fn engine(code: &str) -> Engine {
+        Engine::new(overflow::instruction_set().parse(code))
+    }
+
+    #[test]
+    fn output_knows_what_wrote_it() {
+        let mut engine = engine("+.+[.-]");
+        while engine.step().is_ok() {}
+        assert_eq!(engine.output, vec![1, 2, 1]);
+        let sources = engine
+            .output_provenance()
+            .iter()
+            .map(|source| (source.step, source.index))
+            .collect::<Vec<_>>();
+        assert_eq!(sources, vec![(1, Some(1)), (4, Some(4)), (7, Some(4))]);
+
+        for _ in 0..3 {
+            engine.undo().unwrap();
+        }
+        assert_eq!(engine.output_provenance().len(), 2);
+    }
+
+    #[test]
+    fn output_leads_back_to_when_it_was_written() {
+        let mut engine = engine("+.+[.-]");
+        while engine.step().is_ok() {}
+        engine.goto_output(1).unwrap();
+        assert_eq!(engine.history.len(), 5);
+        assert_eq!(engine.output, vec![1, 2]);
+        assert_eq!(engine.goto_output(2), Err(EngineError::NoOutput.into()));
+    }
+
+    #[test]
+    fn input_can_be_taken_back_and_retyped() {
+        let mut engine = engine(",.,.,.");
+        engine.input = b"abd".to_vec();
+        while engine.step().is_ok() {}
+        assert_eq!(engine.input_provenance(), [(0, b'a'), (2, b'b'), (4, b'd')]);
+
+        assert_eq!(engine.rewind_to_input(1), Ok(b'b'));
+        assert_eq!(engine.output, b"a");
+        assert_eq!(engine.input, b"d");
+        engine.push_input(b'c');
+        while engine.step().is_ok() {}
+        assert_eq!(engine.output, b"acd");
+
+        assert_eq!(
+            engine.rewind_to_input(3),
+            Err(EngineError::InputNotRead { index: 3 }.into())
+        );
+    }
+
+    #[test]
+    fn taking_output_takes_its_sources() {
+        let mut engine = engine("+.+.");
+        for _ in 0..3 {
+            engine.step().unwrap();
+        }
+        assert_eq!(engine.take_output(), vec![1]);
+        while engine.step().is_ok() {}
+        assert_eq!(engine.output, vec![2]);
+        assert_eq!(engine.output_source(0).map(|source| source.step), Some(3));
+    }
+
+    #[test]
+    fn sources_follow_their_instructions() {
+        let mut engine = engine("+.");
+        while engine.step().is_ok() {}
+        engine.reload_source("-+.", &overflow::instruction_set());
+        assert_eq!(engine.output_provenance()[0].index, Some(2));
+        engine.remove_instruction(2).unwrap();
+        assert_eq!(engine.output_provenance()[0].index, None);
+    }
+}
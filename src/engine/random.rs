@@ -0,0 +1,39 @@
+use crate::engine::Engine;
+
+/// The seed engines start with, so runs are repeatable unless seeded
+pub const DEFAULT_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// The next state of a xorshift generator, which is never zero unless the
+/// last one was
+pub(crate) fn xorshift(mut state: u64) -> u64 {
+    state ^= state << 13;
+    state ^= state >> 7;
+    state ^= state << 17;
+    state
+}
+
+/// A seed for the `n`th thing seeded from another, such as a thread, well
+/// mixed so neighbours don't get similar numbers
+pub(crate) fn derive_seed(seed: u64, n: usize) -> u64 {
+    let mut z = seed.wrapping_add((n as u64 + 1).wrapping_mul(DEFAULT_SEED));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+impl Engine {
+    /// Seed everything that isn't otherwise deterministic: the random
+    /// numbers instructions like `flavor::random::random` take, and so the
+    /// seeds of threads forked from this one. It starts them over, as does
+    /// resetting, so every run from the start goes the same way.
+    pub fn set_seed(&mut self, seed: u64) {
+        // xorshift never leaves zero
+        self.random_seed = seed.max(1);
+        self.random_state = self.random_seed;
+    }
+
+    pub fn next_random(&mut self) -> u64 {
+        self.random_state = xorshift(self.random_state);
+        self.random_state
+    }
+}
@@ -0,0 +1,195 @@
+use crate::engine::{Engine, EngineError, InstructionPointer};
+use crate::instruction::{Instruction, InstructionSet};
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The most cells the table matching up changed instructions may have,
+/// past which they're all taken as replaced
+const MATCH_LIMIT: usize = 1 << 22;
+
+/// What reloading an engine's source kept of where it was
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Reload {
+    /// Where each old instruction is in the new program, if it's still there
+    pub moved: Vec<Option<usize>>,
+    /// Whether the current instruction was changed, so the instruction
+    /// pointer moved on to the next one that wasn't
+    pub lost_position: bool,
+    /// How many kept steps ran changed instructions, and so can't be undone
+    pub lost_steps: usize,
+}
+
+impl Reload {
+    pub fn is_lossless(&self) -> bool {
+        !self.lost_position && self.lost_steps == 0
+    }
+}
+
+impl Engine {
+    /// Swap in a new version of the program, keeping the tape, output and
+    /// as much of the position and history as the unchanged instructions
+    /// allow
+    pub fn reload_source(&mut self, source: &str, instruction_set: &InstructionSet) -> Reload {
+        let instructions = instruction_set.parse(source);
+        let moved = match_instructions(&self.instructions, &instructions);
+        self.instructions = instructions;
+        self.rematch_brackets();
+
+        let mut lost_position = false;
+        if let InstructionPointer::Index(i) = self.instruction_pointer {
+            lost_position = moved[i].is_none();
+            self.instruction_pointer = match moved[i..].iter().flatten().next() {
+                Some(&index) => InstructionPointer::Index(index),
+                None => InstructionPointer::End,
+            };
+        }
+
+        let lost_steps = self
+            .history
+            .reindex(|index| moved.get(index).copied().flatten());
+        self.reindex_output(|index| moved.get(index).copied().flatten());
+        self.reindex_loops(|index| moved.get(index).copied().flatten());
+        let executed = core::mem::take(&mut self.executed);
+        for (old, _) in executed
+            .iter()
+            .enumerate()
+            .filter(|(_, &executed)| executed)
+        {
+            if let Some(&Some(new)) = moved.get(old) {
+                self.mark_executed(new);
+            }
+        }
+
+        Reload {
+            moved,
+            lost_position,
+            lost_steps,
+        }
+    }
+
+    /// Take every instruction, and those of any spawned threads, from an
+    /// instruction set by its symbol, such as after reading the engine back
+    /// from a save that only recorded the symbols
+    pub fn bind_instructions(
+        &mut self,
+        instruction_set: &InstructionSet,
+    ) -> Result<(), EngineError> {
+        instruction_set.validate()?;
+        for instruction in &mut self.instructions {
+            // the optimizer's own instructions don't come from any set
+            if instruction.composite.is_some() {
+                continue;
+            }
+            *instruction = instruction_set
+                .get(instruction.symbol)
+                .cloned()
+                .ok_or(EngineError::UnboundInstruction {
+                    symbol: instruction.symbol,
+                })?;
+        }
+        self.rematch_brackets();
+        self.spawned
+            .iter_mut()
+            .try_for_each(|thread| thread.bind_instructions(instruction_set))
+    }
+}
+
+/// Line up the instructions two programs have in common, in order, giving
+/// where each old one is in the new program
+fn match_instructions(old: &[Instruction], new: &[Instruction]) -> Vec<Option<usize>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut moved = (0..old.len())
+        .map(|i| (i < prefix).then_some(i))
+        .collect::<Vec<_>>();
+    for i in 0..suffix {
+        moved[old.len() - 1 - i] = Some(new.len() - 1 - i);
+    }
+
+    // a longest common subsequence of what's left in the middle
+    let (old_middle, new_middle) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    let (rows, columns) = (old_middle.len(), new_middle.len());
+    if rows == 0 || columns == 0 || rows.saturating_mul(columns) > MATCH_LIMIT {
+        return moved;
+    }
+    let mut lengths = vec![0u32; (rows + 1) * (columns + 1)];
+    let cell = |i: usize, j: usize| i * (columns + 1) + j;
+    for i in (0..rows).rev() {
+        for j in (0..columns).rev() {
+            lengths[cell(i, j)] = if old_middle[i] == new_middle[j] {
+                lengths[cell(i + 1, j + 1)] + 1
+            } else {
+                lengths[cell(i + 1, j)].max(lengths[cell(i, j + 1)])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < rows && j < columns {
+        if old_middle[i] == new_middle[j] {
+            moved[prefix + i] = Some(prefix + j);
+            i += 1;
+            j += 1;
+        } else if lengths[cell(i + 1, j)] >= lengths[cell(i, j + 1)] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    moved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    #[test]
+    fn reloading_keeps_the_position_and_history() {
+        let set = overflow::instruction_set();
+        let mut engine = Engine::new(set.parse("+>+.<-"));
+        for _ in 0..4 {
+            engine.step().unwrap();
+        }
+        assert_eq!(engine.instruction_pointer, InstructionPointer::Index(3));
+
+        let reload = engine.reload_source("++>+ some comment .<", &set);
+        assert!(reload.is_lossless());
+        assert_eq!(
+            reload.moved,
+            vec![Some(0), Some(2), Some(3), Some(4), Some(5), None]
+        );
+        assert_eq!(engine.instruction_pointer, InstructionPointer::Index(4));
+
+        while engine.undo().is_ok() {}
+        assert_eq!(engine.tape, vec![0, 0]);
+        assert_eq!(engine.instruction_pointer, InstructionPointer::Index(0));
+    }
+
+    #[test]
+    fn reloading_reports_what_it_lost() {
+        let set = overflow::instruction_set();
+        let mut engine = Engine::new(set.parse("+>+-."));
+        for _ in 0..4 {
+            engine.step().unwrap();
+        }
+
+        let reload = engine.reload_source("+<+.", &set);
+        assert!(reload.lost_position);
+        assert_eq!(reload.lost_steps, 1);
+        assert_eq!(engine.instruction_pointer, InstructionPointer::Index(3));
+
+        engine.undo().unwrap();
+        assert_eq!(engine.tape, vec![1, 0]);
+        assert!(engine.undo().is_err());
+    }
+}
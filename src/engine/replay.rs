@@ -0,0 +1,194 @@
+use crate::engine::{Engine, EngineError, EngineResult, Exception};
+
+use alloc::vec::Vec;
+
+/// Everything an engine read and how far it got, enough to run its program
+/// again to exactly the same state without anyone typing the input back in.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReplayLog {
+    pub steps: usize,
+    /// Each input byte read, with the step that read it
+    pub input: Vec<(usize, u8)>,
+}
+
+impl Engine {
+    pub fn replay_log(&self) -> ReplayLog {
+        ReplayLog {
+            steps: self.history.len(),
+            input: self.consumed_input.clone(),
+        }
+    }
+
+    /// Start the program over and run it for as many steps as the log
+    /// records, giving it each input byte just before the step that read it.
+    /// Breakpoints are run through; anything else that stops the engine
+    /// means the log isn't from this program, and so does a log longer than
+    /// the step budget, before anything is reset.
+    pub fn replay(&mut self, log: &ReplayLog) -> EngineResult {
+        if self.step_budget.is_some_and(|budget| log.steps > budget) {
+            return Err(EngineError::FuelExhausted { steps: 0 }.into());
+        }
+        self.reset();
+
+        let mut input = log.input.iter().peekable();
+        while self.history.len() < log.steps {
+            let step = self.history.len();
+            while let Some(&(_, byte)) = input.next_if(|&&(read, _)| read == step) {
+                self.input.push(byte);
+            }
+
+            match self.step() {
+                Ok(()) | Err(Exception::Breakpoint) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Go to the state after exactly `step` steps, for scrubbing through a
+    /// run: back by undoing while the history reaches, and otherwise by
+    /// replaying from the start, or forward by stepping, through
+    /// breakpoints. Input read after the step is given back to read again.
+    /// Replaying only redoes steps that ran instructions, so rewinding past
+    /// dropped history loses tape edits made along the way, and can't be
+    /// done at all once input read in that history has been dropped. Fails
+    /// without moving if getting there takes more steps than the budget.
+    pub fn goto_step(&mut self, step: usize) -> EngineResult {
+        let rewind = self.history.len().saturating_sub(step);
+        let replaying = rewind > self.history.retained();
+        let cost = match replaying {
+            true => step,
+            false => rewind + step.saturating_sub(self.history.len()),
+        };
+        if self.step_budget.is_some_and(|budget| cost > budget) {
+            return Err(EngineError::FuelExhausted { steps: 0 }.into());
+        }
+        if replaying {
+            // replaying needs every byte read from the start
+            if self.dropped_input > 0 {
+                return Err(EngineError::HistoryDropped {
+                    step: self.history.len() - self.history.retained(),
+                }
+                .into());
+            }
+            let mut log = self.replay_log();
+            let later = log.input.partition_point(|&(read, _)| read < step);
+            let mut input = log
+                .input
+                .drain(later..)
+                .map(|(_, byte)| byte)
+                .collect::<Vec<_>>();
+            input.append(&mut self.input);
+            log.steps = step;
+            self.replay(&log)?;
+            self.input = input;
+            return Ok(());
+        }
+
+        for _ in 0..rewind {
+            self.undo_step()?;
+        }
+        while self.history.len() < step {
+            match self.step() {
+                Ok(()) | Err(Exception::Breakpoint) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    #[test]
+    fn replay_reaches_the_same_state() {
+        let instructions = overflow::instruction_set().parse(",[.,]");
+        let mut engine = Engine::new(instructions.clone());
+
+        // input arrives bit by bit, as it would in an interactive session
+        for byte in b"ab" {
+            while engine.step() != Err(Exception::RequestingInput) {}
+            engine.input.push(*byte);
+        }
+        engine.step().unwrap();
+        engine.step().unwrap();
+
+        let log = engine.replay_log();
+        assert_eq!(
+            log.input.iter().map(|&(_, byte)| byte).collect::<Vec<_>>(),
+            b"ab"
+        );
+
+        let mut replayed = Engine::new(instructions);
+        replayed.replay(&log).unwrap();
+        assert_eq!(replayed, engine);
+    }
+
+    #[test]
+    fn undone_input_leaves_the_log() {
+        let mut engine = Engine::new(overflow::instruction_set().parse(",,"));
+        engine.input = b"xy".to_vec();
+        engine.step().unwrap();
+        engine.step().unwrap();
+        engine.step().unwrap();
+
+        engine.undo().unwrap();
+        assert_eq!(engine.replay_log().input, vec![(0, b'x')]);
+    }
+
+    #[test]
+    fn goto_step_scrubs_both_ways() {
+        let instructions = overflow::instruction_set().parse(",[.-]");
+        let mut engine = Engine::new(instructions.clone());
+        engine.input = vec![3, 9];
+        engine.goto_step(6).unwrap();
+        assert_eq!(engine.output, vec![3, 2]);
+
+        engine.goto_step(2).unwrap();
+        assert_eq!((engine.history.len(), engine.cell()), (2, 3));
+        assert!(engine.output.is_empty());
+        engine.goto_step(0).unwrap();
+        assert_eq!(engine.input, vec![3, 9]);
+        assert_eq!(
+            engine.goto_step(100),
+            Err(crate::engine::EngineError::AtEnd.into())
+        );
+    }
+
+    #[test]
+    fn goto_step_replays_past_dropped_history() {
+        let instructions = overflow::instruction_set().parse("+++[.-]");
+        let mut engine = Engine::builder()
+            .instructions(instructions)
+            .history(crate::engine::history::HistoryPolicy::Last(2))
+            .build()
+            .unwrap();
+        engine.goto_step(10).unwrap();
+        let ahead = engine.clone();
+
+        engine.goto_step(3).unwrap();
+        assert_eq!((engine.history.len(), engine.cell()), (3, 3));
+        engine.goto_step(10).unwrap();
+        assert_eq!(engine.output, ahead.output);
+        assert_eq!(engine.tape, ahead.tape);
+
+        // the input it read went with the history, so it can't be replayed
+        let instructions = overflow::instruction_set().parse(",[.-]");
+        let mut engine = Engine::builder()
+            .instructions(instructions)
+            .input(vec![3, 9])
+            .history(crate::engine::history::HistoryPolicy::Last(2))
+            .build()
+            .unwrap();
+        engine.goto_step(8).unwrap();
+        assert_eq!(
+            engine.goto_step(1),
+            Err(crate::engine::EngineError::HistoryDropped { step: 6 }.into())
+        );
+    }
+}
@@ -0,0 +1,452 @@
+use crate::engine::{Engine, EngineError, Exception, InstructionPointer};
+
+use crate::engine::verify::FutureState;
+
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// How many steps a run with a timeout takes between looking at the clock
+pub const TIMEOUT_CHECK_INTERVAL: usize = 1024;
+
+/// Why a run gave control back
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StopReason {
+    /// The program ran past its last instruction
+    Completed,
+    /// At an instruction with a breakpoint, or having just run a breakpoint
+    /// instruction at that index
+    Breakpoint(usize),
+    /// A watched cell changed, and the step that changed it has run
+    Watchpoint(usize),
+    InputRequested,
+    /// The run took as many steps as it was allowed
+    FuelExhausted,
+    /// The run took longer than it was allowed
+    TimedOut,
+    /// The run came back to exactly where it had been before, so it would
+    /// go round the same way forever
+    InfiniteLoopDetected,
+    Error(EngineError),
+}
+
+/// What should stop a run before the program finishes
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Stops {
+    /// Instruction indexes to stop before running
+    pub breakpoints: BTreeSet<usize>,
+    /// Cells to stop after any change to
+    pub watchpoints: BTreeSet<usize>,
+    /// The most steps a run can take, if it's limited more tightly than
+    /// by the engine's step budget
+    pub fuel: Option<usize>,
+    /// How long a run can take, if it's limited, going by the clock every
+    /// `TIMEOUT_CHECK_INTERVAL` steps. Without `std` there's no clock, so
+    /// it's ignored.
+    pub timeout: Option<Duration>,
+    /// How many steps apart to look at the state, stopping when it repeats,
+    /// if the run's checked for infinite loops. Nothing's caught while
+    /// cells are mapped to the host, which can change them behind the
+    /// program's back. Only one state is kept, replaced after twice as many
+    /// looks each time, so a loop is caught within a few times round once
+    /// it's long enough to span a look or two.
+    pub loop_check: Option<usize>,
+}
+
+/// How much work `run_for` may do before giving control back
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Slice {
+    Steps(usize),
+    /// Going by the clock every `TIMEOUT_CHECK_INTERVAL` steps, so at least
+    /// that many steps are taken unless the run stops first
+    #[cfg(feature = "std")]
+    Time(Duration),
+}
+
+impl From<usize> for Slice {
+    fn from(steps: usize) -> Slice {
+        Slice::Steps(steps)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Duration> for Slice {
+    fn from(time: Duration) -> Slice {
+        Slice::Time(time)
+    }
+}
+
+/// A run carried out a slice at a time by `run_for`, keeping what its stops
+/// count across slices: the steps taken, the deadline and the states noted
+/// for the loop check
+#[derive(Clone, Debug)]
+pub struct Run {
+    /// What should stop the run, which can be changed between slices
+    pub stops: Stops,
+    steps: usize,
+    #[cfg(feature = "std")]
+    deadline: Option<std::time::Instant>,
+    /// The state noted for the loop check and its hash, kept whole so a
+    /// hash two states share isn't taken for a loop
+    seen: Option<(u64, FutureState)>,
+    /// How many looks at the state there have been since it was noted
+    looks: usize,
+    /// How many looks the noted state is kept for, doubling each time it's
+    /// replaced, as in Brent's cycle detection
+    kept_for: usize,
+    resuming: bool,
+}
+
+impl Run {
+    /// A run that stops at a breakpoint on the instruction the engine is
+    /// already at, like `Engine::run`
+    pub fn new(stops: Stops) -> Run {
+        Run {
+            #[cfg(feature = "std")]
+            deadline: stops
+                .timeout
+                .map(|timeout| std::time::Instant::now() + timeout),
+            stops,
+            steps: 0,
+            seen: None,
+            looks: 0,
+            kept_for: 1,
+            resuming: false,
+        }
+    }
+
+    /// A run that gets past a breakpoint the engine is stopped at first,
+    /// like `Engine::continue_`
+    pub fn continuing(stops: Stops) -> Run {
+        Run {
+            resuming: true,
+            ..Run::new(stops)
+        }
+    }
+
+    /// How many steps the run has taken over all its slices
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+}
+
+impl Engine {
+    /// Step until the program finishes or something in `stops` is reached,
+    /// including a breakpoint on the instruction the engine is already at
+    pub fn run(&mut self, stops: &Stops) -> StopReason {
+        self.run_whole(Run::new(stops.clone()))
+    }
+
+    /// Like `run`, but getting past a breakpoint the engine is stopped at
+    /// first, to carry on after stopping there
+    pub fn continue_(&mut self, stops: &Stops) -> StopReason {
+        self.run_whole(Run::continuing(stops.clone()))
+    }
+
+    /// Carry a run on for at most a slice of steps or time, for frontends
+    /// on a single-threaded event loop to draw between slices. Gives `None`
+    /// if the slice was used up first, to be called again with the same
+    /// run. A run stopped at a breakpoint gets past it on the next call.
+    pub fn run_for(&mut self, run: &mut Run, slice: impl Into<Slice>) -> Option<StopReason> {
+        let stop = self.advance(run, Some(slice.into()));
+        if let Some(StopReason::Breakpoint(_)) = stop {
+            run.resuming = true;
+        }
+        stop
+    }
+
+    fn run_whole(&mut self, mut run: Run) -> StopReason {
+        match self.advance(&mut run, None) {
+            Some(stop) => stop,
+            None => unreachable!(),
+        }
+    }
+
+    fn advance(&mut self, run: &mut Run, slice: Option<Slice>) -> Option<StopReason> {
+        let stops = &run.stops;
+        let watched = |engine: &Engine| -> Vec<u8> {
+            stops
+                .watchpoints
+                .iter()
+                .map(|&cell| engine.tape.get(cell).copied().unwrap_or(0))
+                .collect()
+        };
+
+        #[cfg(feature = "std")]
+        let slice_end = match slice {
+            Some(Slice::Time(time)) => Some(std::time::Instant::now() + time),
+            _ => None,
+        };
+
+        let fuel = stops.fuel.into_iter().chain(self.step_budget).min();
+        let loop_check = stops
+            .loop_check
+            .filter(|_| self.io_maps.is_empty())
+            .map(|interval| interval.max(1));
+
+        let mut taken = 0;
+        loop {
+            let at = match self.instruction_pointer {
+                InstructionPointer::End => return Some(StopReason::Completed),
+                InstructionPointer::Index(i) => Some(i),
+                InstructionPointer::Start => None,
+            };
+            // yield before any of the stops are looked at, so the next slice
+            // looks at them afresh from the same step
+            if let Some(Slice::Steps(steps)) = slice {
+                if taken >= steps {
+                    return None;
+                }
+            }
+            #[cfg(feature = "std")]
+            if taken.is_multiple_of(TIMEOUT_CHECK_INTERVAL)
+                && taken > 0
+                && slice_end.is_some_and(|end| std::time::Instant::now() >= end)
+            {
+                return None;
+            }
+
+            if fuel.is_some_and(|fuel| run.steps >= fuel) {
+                return Some(StopReason::FuelExhausted);
+            }
+            #[cfg(feature = "std")]
+            if run.steps.is_multiple_of(TIMEOUT_CHECK_INTERVAL)
+                && run.steps > 0
+                && run
+                    .deadline
+                    .is_some_and(|deadline| std::time::Instant::now() >= deadline)
+            {
+                return Some(StopReason::TimedOut);
+            }
+            if let Some(i) = at.filter(|i| !run.resuming && stops.breakpoints.contains(i)) {
+                return Some(StopReason::Breakpoint(i));
+            }
+            // noted only once nothing else stops the run here, so a run
+            // carried on from here doesn't find the state already seen
+            if loop_check.is_some_and(|interval| run.steps.is_multiple_of(interval)) {
+                let hash = self.future_hash();
+                if (run.seen.as_ref())
+                    .is_some_and(|(seen, state)| *seen == hash && *state == self.future_state())
+                {
+                    return Some(StopReason::InfiniteLoopDetected);
+                }
+                if run.seen.is_none() || run.looks == run.kept_for {
+                    run.seen = Some((hash, self.future_state()));
+                    run.kept_for *= 2;
+                    run.looks = 0;
+                }
+                run.looks += 1;
+            }
+            run.resuming = false;
+
+            let before = watched(self);
+            let result = self.step();
+            run.steps += 1;
+            taken += 1;
+            match result {
+                Ok(()) => {}
+                Err(Exception::Breakpoint) => return Some(StopReason::Breakpoint(at.unwrap_or(0))),
+                Err(Exception::RequestingInput) => return Some(StopReason::InputRequested),
+                Err(Exception::Error(error)) => return Some(StopReason::Error(error)),
+            }
+
+            let changed = (stops.watchpoints.iter())
+                .zip(before.into_iter().zip(watched(self)))
+                .find(|(_, (before, after))| before != after);
+            if let Some((&cell, _)) = changed {
+                return Some(StopReason::Watchpoint(cell));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    fn engine(code: &str) -> Engine {
+        Engine::new(overflow::instruction_set().parse(code))
+    }
+
+    #[test]
+    fn runs_say_why_they_stopped() {
+        let mut engine = engine("+$+,+");
+        let stops = Stops::default();
+
+        assert_eq!(engine.run(&stops), StopReason::Breakpoint(1));
+        assert_eq!(engine.run(&stops), StopReason::InputRequested);
+        engine.input.push(7);
+        assert_eq!(engine.run(&stops), StopReason::Completed);
+        assert_eq!(engine.tape, vec![8]);
+        assert_eq!(engine.run(&stops), StopReason::Completed);
+    }
+
+    #[test]
+    fn continuing_gets_past_the_breakpoint_it_stopped_at() {
+        let mut engine = engine("+>+<+");
+        let stops = Stops {
+            breakpoints: BTreeSet::from([2]),
+            ..Stops::default()
+        };
+
+        assert_eq!(engine.run(&stops), StopReason::Breakpoint(2));
+        assert_eq!(engine.run(&stops), StopReason::Breakpoint(2));
+        assert_eq!(engine.continue_(&stops), StopReason::Completed);
+        assert_eq!(engine.tape, vec![2, 1]);
+    }
+
+    #[test]
+    fn watchpoints_stop_after_the_change() {
+        let mut engine = engine(">>+<+");
+        let stops = Stops {
+            watchpoints: BTreeSet::from([1, 2]),
+            ..Stops::default()
+        };
+
+        assert_eq!(engine.run(&stops), StopReason::Watchpoint(2));
+        assert_eq!(engine.instruction_pointer, InstructionPointer::Index(3));
+        assert_eq!(engine.run(&stops), StopReason::Watchpoint(1));
+        assert_eq!(engine.run(&stops), StopReason::Completed);
+    }
+
+    #[test]
+    fn runs_stop_when_out_of_fuel_or_failing() {
+        let mut looping = engine("+[]");
+        let stops = Stops {
+            fuel: Some(100),
+            ..Stops::default()
+        };
+        assert_eq!(looping.run(&stops), StopReason::FuelExhausted);
+        assert_eq!(looping.history.len(), 99);
+
+        let mut failing = engine("<");
+        assert_eq!(
+            failing.run(&Stops::default()),
+            StopReason::Error(EngineError::TapeUnderflow)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn runs_stop_when_out_of_time() {
+        let mut looping = engine("+[]");
+        let stops = Stops {
+            timeout: Some(Duration::from_millis(10)),
+            ..Stops::default()
+        };
+        assert_eq!(looping.run(&stops), StopReason::TimedOut);
+
+        let mut finishing = engine("+++");
+        assert_eq!(finishing.run(&stops), StopReason::Completed);
+    }
+
+    #[test]
+    fn endless_loops_are_caught() {
+        let stops = Stops {
+            loop_check: Some(10),
+            fuel: Some(20_000),
+            ..Stops::default()
+        };
+        let detected = StopReason::InfiniteLoopDetected;
+        assert_eq!(engine("+[-+]").run(&stops), detected);
+        // round until the cell next door wraps back to where it started
+        assert_eq!(engine("+[>+<]").run(&stops), detected);
+        // never the same twice, as it never stops moving
+        assert_eq!(engine("+[>+]").run(&stops), StopReason::FuelExhausted);
+        assert_eq!(engine("++++[-]").run(&stops), StopReason::Completed);
+
+        // each time round leaves one more thread waiting to run
+        let mut forking = Engine::new(crate::flavor::brainfork::instruction_set().parse("+[Y+]"));
+        let stops = Stops {
+            fuel: Some(1_000),
+            ..stops
+        };
+        assert_eq!(forking.run(&stops), StopReason::FuelExhausted);
+    }
+
+    #[test]
+    fn loop_checks_keep_one_state() {
+        let stops = Stops {
+            loop_check: Some(10),
+            fuel: Some(10_000),
+            ..Stops::default()
+        };
+        let mut run = Run::new(stops.clone());
+        let mut moving = engine("+[>+]");
+        assert_eq!(
+            moving.run_for(&mut run, 20_000),
+            Some(StopReason::FuelExhausted)
+        );
+        // only the state noted last is kept, from no further back than
+        // halfway through the looks
+        assert!(run.seen.is_some());
+        assert!(run.kept_for <= 2 * run.steps() / 10);
+
+        // a loop is still caught after a long way in
+        let mut run = Run::new(stops);
+        let mut counting = engine("++++++++[>++++++++<-]>[-+]");
+        assert_eq!(
+            counting.run_for(&mut run, 20_000),
+            Some(StopReason::InfiniteLoopDetected)
+        );
+        assert!(run.steps() < 1_000);
+    }
+
+    #[test]
+    fn slices_carry_on_where_they_left_off() {
+        let mut whole = engine("++[>+++<-]>$.+");
+        let mut sliced = whole.clone();
+        let stops = Stops {
+            breakpoints: BTreeSet::from([2]),
+            loop_check: Some(1),
+            ..Stops::default()
+        };
+
+        let mut run = Run::new(stops.clone());
+        let mut stopped = vec![];
+        while matches!(stopped.last(), None | Some(StopReason::Breakpoint(_))) {
+            stopped.extend(sliced.run_for(&mut run, 1));
+        }
+        assert_eq!(
+            stopped,
+            [
+                StopReason::Breakpoint(2),
+                StopReason::Breakpoint(11),
+                StopReason::Completed
+            ]
+        );
+
+        assert_eq!(whole.run(&stops), StopReason::Breakpoint(2));
+        assert_eq!(whole.continue_(&stops), StopReason::Breakpoint(11));
+        assert_eq!(whole.continue_(&stops), StopReason::Completed);
+        assert_eq!(sliced.tape, whole.tape);
+        assert_eq!(sliced.output, whole.output);
+        assert_eq!(sliced.history.len(), whole.history.len());
+    }
+
+    #[test]
+    fn slices_share_the_runs_fuel() {
+        let mut looping = engine("+[]");
+        let mut run = Run::new(Stops {
+            fuel: Some(25),
+            ..Stops::default()
+        });
+        assert_eq!(looping.run_for(&mut run, 10), None);
+        assert_eq!(looping.run_for(&mut run, 10), None);
+        assert_eq!(
+            looping.run_for(&mut run, 10),
+            Some(StopReason::FuelExhausted)
+        );
+        assert_eq!(run.steps(), 25);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn slices_can_be_timed() {
+        let mut looping = engine("+[]");
+        let mut run = Run::new(Stops::default());
+        assert_eq!(looping.run_for(&mut run, Duration::ZERO), None);
+        assert_eq!(run.steps(), TIMEOUT_CHECK_INTERVAL);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::engine::{Engine, Exception, InstructionPointer};
+
+/// One executed instruction, with where the tape pointer was left and the
+/// value of the cell it was left on
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StepEvent {
+    pub ip: usize,
+    pub symbol: char,
+    pub tape_ptr: usize,
+    pub cell: u8,
+}
+
+/// Runs an engine one instruction per item, until it reaches the end of the
+/// program or stops on anything but a breakpoint. Whatever stopped it is
+/// kept in `stopped`, so iterate with `by_ref` to check it afterwards.
+#[derive(Debug)]
+pub struct Steps<'a> {
+    engine: &'a mut Engine,
+    pub stopped: Option<Exception>,
+}
+
+impl Engine {
+    pub fn steps(&mut self) -> Steps<'_> {
+        Steps {
+            engine: self,
+            stopped: None,
+        }
+    }
+}
+
+impl Iterator for Steps<'_> {
+    type Item = StepEvent;
+
+    fn next(&mut self) -> Option<StepEvent> {
+        if self.stopped.is_some() {
+            return None;
+        }
+
+        let ip = loop {
+            match self.engine.instruction_pointer {
+                InstructionPointer::End => return None,
+                InstructionPointer::Index(ip) => break ip,
+                // moving off the start doesn't execute anything
+                InstructionPointer::Start => {
+                    if let Err(e) = self.engine.step() {
+                        self.stopped = Some(e);
+                        return None;
+                    }
+                }
+            }
+        };
+
+        match self.engine.step() {
+            Ok(()) | Err(Exception::Breakpoint) => Some(StepEvent {
+                ip,
+                symbol: self.engine.instructions[ip].symbol,
+                tape_ptr: self.engine.tape_pointer,
+                cell: self.engine.peek(),
+            }),
+            Err(e) => {
+                self.stopped = Some(e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    #[test]
+    fn steps_yield_each_executed_instruction() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("++[>+<-]"));
+        let events = engine.steps().collect::<Vec<_>>();
+
+        assert_eq!(events.len(), 2 + 2 * 5 + 1);
+        assert_eq!(
+            events[3],
+            StepEvent {
+                ip: 3,
+                symbol: '>',
+                tape_ptr: 1,
+                cell: 0,
+            }
+        );
+        assert_eq!(engine.instruction_pointer, InstructionPointer::End);
+    }
+
+    #[test]
+    fn steps_stop_when_input_is_needed() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("+$,+"));
+        let mut steps = engine.steps();
+        let symbols = steps.by_ref().map(|event| event.symbol).collect::<String>();
+
+        assert_eq!(symbols, "+$");
+        assert_eq!(steps.stopped, Some(Exception::RequestingInput));
+    }
+}
@@ -0,0 +1,105 @@
+use crate::engine::run::StopReason;
+use crate::engine::{Engine, InstructionPointer};
+use crate::output::json_string;
+
+use alloc::string::String;
+use core::fmt::Write;
+
+/// How many cells either side of the tape pointer a summary shows
+pub const SUMMARY_RADIUS: usize = 16;
+
+impl Engine {
+    /// Where the engine is as one line of JSON, for tools and editors: the
+    /// step, both pointers, the cells around the tape pointer, the output so
+    /// far and, if the caller knows it, why the last run stopped
+    pub fn to_json_summary(&self, stop: Option<&StopReason>) -> String {
+        let mut json = String::new();
+        let _ = write!(json, "{{\"step\":{},", self.history.len());
+        match self.instruction_pointer {
+            InstructionPointer::Start => json.push_str("\"instruction_pointer\":\"start\","),
+            InstructionPointer::End => json.push_str("\"instruction_pointer\":\"end\","),
+            InstructionPointer::Index(i) => {
+                let mut symbol = [0; 4];
+                let symbol = self.instructions[i].symbol.encode_utf8(&mut symbol);
+                let _ = write!(
+                    json,
+                    "\"instruction_pointer\":{i},\"instruction\":{},",
+                    json_string(symbol.as_bytes())
+                );
+            }
+        }
+        let _ = write!(json, "\"tape_pointer\":{},", self.tape_pointer);
+
+        let window = self.tape_window(self.tape_pointer, SUMMARY_RADIUS);
+        let _ = write!(json, "\"tape\":{{\"start\":{},\"cells\":[", window.start);
+        for (i, (_, cell)) in window.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            let _ = write!(json, "{separator}{cell}");
+        }
+        let _ = write!(json, "]}},\"output\":{},", json_string(&self.output));
+
+        json.push_str("\"stop\":");
+        match stop {
+            None => json.push_str("null"),
+            Some(stop) => json.push_str(&stop_json(stop)),
+        }
+        json.push('}');
+        json
+    }
+}
+
+fn stop_json(stop: &StopReason) -> String {
+    let reason = |name: &str| alloc::format!("{{\"reason\":\"{name}\"}}");
+    match stop {
+        StopReason::Completed => reason("completed"),
+        StopReason::Breakpoint(index) => {
+            alloc::format!("{{\"reason\":\"breakpoint\",\"instruction\":{index}}}")
+        }
+        StopReason::Watchpoint(cell) => {
+            alloc::format!("{{\"reason\":\"watchpoint\",\"cell\":{cell}}}")
+        }
+        StopReason::InputRequested => reason("input-requested"),
+        StopReason::FuelExhausted => reason("fuel-exhausted"),
+        StopReason::TimedOut => reason("timed-out"),
+        StopReason::InfiniteLoopDetected => reason("infinite-loop"),
+        StopReason::Error(error) => alloc::format!(
+            "{{\"reason\":\"error\",\"message\":{}}}",
+            json_string(error.message().as_bytes())
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::run::Stops;
+    use crate::engine::EngineError;
+    use crate::flavor::overflow;
+
+    #[test]
+    fn summaries_are_json() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("++.>+$-"));
+        assert_eq!(
+            engine.to_json_summary(None),
+            "{\"step\":0,\"instruction_pointer\":\"start\",\"tape_pointer\":0,\
+             \"tape\":{\"start\":0,\"cells\":[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0]},\
+             \"output\":\"\",\"stop\":null}"
+        );
+
+        let stop = engine.run(&Stops::default());
+        let summary = engine.to_json_summary(Some(&stop));
+        assert!(
+            summary.contains("\"instruction_pointer\":6,\"instruction\":\"-\""),
+            "{summary}"
+        );
+        assert!(summary.contains("\"cells\":[2,1,0,"));
+        assert!(summary.contains("\"output\":\"\\u0002\""));
+        assert!(summary.ends_with("\"stop\":{\"reason\":\"breakpoint\",\"instruction\":5}}"));
+
+        let error = StopReason::Error(EngineError::TapeUnderflow);
+        assert_eq!(
+            stop_json(&error),
+            "{\"reason\":\"error\",\"message\":\"can't move tape pointer before the first cell\"}"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use crate::engine::{Engine, EngineResult, InstructionPointer};
+
+use alloc::vec::Vec;
+
+/// Where a run was after a step
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sample {
+    pub step: usize,
+    pub instruction_pointer: InstructionPointer,
+    pub tape_pointer: usize,
+    pub output_len: usize,
+}
+
+/// A sketch of a run for drawing as a timeline, sampled every `interval`
+/// steps and at every step that wrote output, which a UI can jump back and
+/// forth along with `Timeline::jump`. Samples past a step the engine goes
+/// back to are dropped when it's next observed, so undoing then running
+/// differently redraws the rest.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Timeline {
+    interval: usize,
+    samples: Vec<Sample>,
+    /// The latest step observed
+    steps: usize,
+}
+
+impl Timeline {
+    pub fn new(interval: usize) -> Timeline {
+        Timeline {
+            interval: interval.max(1),
+            samples: Vec::new(),
+            steps: 0,
+        }
+    }
+
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    /// The latest step observed, which is where the timeline ends
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Note where an engine is, after each step for output to be marked at
+    /// the step that wrote it
+    pub fn observe(&mut self, engine: &Engine) {
+        let step = engine.history.len();
+        self.steps = step;
+        let kept = self.samples.partition_point(|sample| sample.step < step);
+        self.samples.truncate(kept);
+
+        let output_len = engine.output.len();
+        let wrote = output_len != self.samples.last().map_or(0, |sample| sample.output_len);
+        if step.is_multiple_of(self.interval) || wrote {
+            self.samples.push(Sample {
+                step,
+                instruction_pointer: engine.instruction_pointer,
+                tape_pointer: engine.tape_pointer,
+                output_len,
+            });
+        }
+    }
+
+    /// The latest sample at or before a step
+    pub fn sample_at(&self, step: usize) -> Option<&Sample> {
+        let after = self.samples.partition_point(|sample| sample.step <= step);
+        after.checked_sub(1).map(|i| &self.samples[i])
+    }
+
+    /// The samples at steps that wrote output, in order
+    pub fn output_markers(&self) -> impl Iterator<Item = &Sample> + '_ {
+        let before = core::iter::once(0).chain(self.samples.iter().map(|sample| sample.output_len));
+        self.samples
+            .iter()
+            .zip(before)
+            .filter(|(sample, before)| sample.output_len != *before)
+            .map(|(sample, _)| sample)
+    }
+
+    /// Take an engine to a step picked from the timeline, noting it there
+    pub fn jump(&mut self, engine: &mut Engine, step: usize) -> EngineResult {
+        let result = engine.goto_step(step);
+        self.observe(engine);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+    use alloc::vec;
+
+    fn run(engine: &mut Engine, timeline: &mut Timeline) {
+        timeline.observe(engine);
+        while engine.step().is_ok() {
+            timeline.observe(engine);
+        }
+    }
+
+    #[test]
+    fn samples_at_intervals_and_outputs() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("++[.-]"));
+        let mut timeline = Timeline::new(4);
+        run(&mut engine, &mut timeline);
+
+        let steps = |samples: &[Sample]| samples.iter().map(|s| s.step).collect::<Vec<_>>();
+        assert_eq!(steps(timeline.samples()), vec![0, 4, 7, 8]);
+        let markers = timeline.output_markers().copied().collect::<Vec<_>>();
+        assert_eq!(steps(&markers), vec![4, 7]);
+        assert_eq!(markers[1].output_len, 2);
+        assert_eq!(timeline.steps(), 9);
+        assert_eq!(timeline.sample_at(7).map(|s| s.tape_pointer), Some(0));
+        assert_eq!(
+            timeline.sample_at(7).map(|s| s.instruction_pointer),
+            Some(InstructionPointer::Index(4))
+        );
+    }
+
+    #[test]
+    fn jumping_back_redraws_what_follows() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("+.>+."));
+        let mut timeline = Timeline::new(10);
+        run(&mut engine, &mut timeline);
+        assert_eq!(timeline.output_markers().count(), 2);
+
+        timeline.jump(&mut engine, 3).unwrap();
+        assert_eq!(timeline.steps(), 3);
+        assert_eq!(timeline.output_markers().count(), 1);
+        assert_eq!(engine.output, vec![1]);
+    }
+}
@@ -0,0 +1,462 @@
+use crate::engine::history::HistoryPolicy;
+use crate::engine::random::DEFAULT_SEED;
+use crate::engine::{Engine, EngineResult, Exception, InstructionPointer, TapeModel};
+use crate::instruction::InstructionSet;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// What trace files start with, before their version
+const MAGIC: &[u8; 4] = b"PLQT";
+const VERSION: u8 = 2;
+
+/// How many steps apart `TraceStates` keeps copies of the engine
+const CHECKPOINT_INTERVAL: usize = 4096;
+
+/// A whole run of a program: its source, how the engine was set up, the
+/// instruction each step ran and the input and output along the way, from
+/// which any state the run passed through can be rebuilt long after it's
+/// over.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Trace {
+    pub source: String,
+    pub tape_model: TapeModel,
+    /// Cells loaded before the run, and where they went
+    pub tape_image: Vec<(usize, Vec<u8>)>,
+    pub seed: u64,
+    /// The index of the instruction each step ran
+    pub steps: Vec<usize>,
+    /// Each input byte read, with the step that read it
+    pub input: Vec<(usize, u8)>,
+    /// Each output byte written, with the step that wrote it
+    pub output: Vec<(usize, u8)>,
+}
+
+impl Default for Trace {
+    fn default() -> Trace {
+        Trace {
+            source: String::new(),
+            tape_model: TapeModel::default(),
+            tape_image: Vec::new(),
+            seed: DEFAULT_SEED,
+            steps: Vec::new(),
+            input: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl Engine {
+    /// Run a program that hasn't started yet, parsed from `source`, to the
+    /// end or until it stops for anything but a breakpoint, tracing it
+    pub fn record<S: Into<String>>(&mut self, source: S) -> (Trace, EngineResult) {
+        let mut trace = Trace {
+            source: source.into(),
+            tape_model: self.tape_model,
+            tape_image: self.tape_image.clone(),
+            seed: self.random_seed,
+            ..Trace::default()
+        };
+        let mut fuel = self.fuel();
+        let result = loop {
+            if self.instruction_pointer == InstructionPointer::End {
+                break Ok(());
+            }
+            if let Err(exception) = fuel.burn() {
+                break Err(exception);
+            }
+            let InstructionPointer::Index(index) = self.instruction_pointer else {
+                if let Err(exception) = self.step() {
+                    break Err(exception);
+                }
+                continue;
+            };
+
+            let (step, written) = (self.history.len(), self.output.len());
+            let result = self.step();
+            if self.history.len() > step {
+                trace.steps.push(index);
+                let input = self.input_read_by(step).map(|byte| (step, byte));
+                trace.input.extend(input);
+                let output = self.output[written..].iter().map(|&byte| (step, byte));
+                trace.output.extend(output);
+            }
+            match result {
+                Ok(()) | Err(Exception::Breakpoint) => {}
+                Err(exception) => break Err(exception),
+            }
+        };
+        (trace, result)
+    }
+}
+
+impl Trace {
+    /// The engine as it was after a number of steps, running the source
+    /// again from the start with the input it read
+    pub fn state_at(
+        &self,
+        instruction_set: &InstructionSet,
+        step: usize,
+    ) -> Result<Engine, String> {
+        self.check_step(step)?;
+        let mut engine = self.start(instruction_set, HistoryPolicy::Unbounded)?;
+        self.run_to(&mut engine, step)?;
+        Ok(engine)
+    }
+
+    /// The states the trace passed through, for looking at more than one
+    pub fn states(&self, instruction_set: &InstructionSet) -> Result<TraceStates<'_>, String> {
+        Ok(TraceStates {
+            trace: self,
+            checkpoints: vec![self.start(instruction_set, HistoryPolicy::Off)?],
+        })
+    }
+
+    fn check_step(&self, step: usize) -> Result<(), String> {
+        match step > self.steps.len() {
+            true => Err(format!("the trace only has {} steps", self.steps.len())),
+            false => Ok(()),
+        }
+    }
+
+    /// The engine set up as it was before the run
+    fn start(
+        &self,
+        instruction_set: &InstructionSet,
+        history: HistoryPolicy,
+    ) -> Result<Engine, String> {
+        let mut engine = Engine::builder()
+            .instructions(instruction_set.parse(&self.source))
+            .tape(self.tape_model)
+            .seed(self.seed)
+            .history(history)
+            .build()
+            .map_err(|error| format!("couldn't set up the trace's engine: {error}"))?;
+        for (offset, cells) in &self.tape_image {
+            engine
+                .load_tape(cells, *offset)
+                .map_err(|exception| format!("couldn't load the trace's tape: {exception}"))?;
+        }
+        Ok(engine)
+    }
+
+    /// Run on from wherever the engine is to a later step, giving it each
+    /// input byte just before the step that read it
+    fn run_to(&self, engine: &mut Engine, step: usize) -> Result<(), String> {
+        let from = engine.history.len();
+        let mut input = self.input[self.input.partition_point(|&(read, _)| read < from)..]
+            .iter()
+            .peekable();
+        while engine.history.len() < step {
+            let at = engine.history.len();
+            while let Some(&(_, byte)) = input.next_if(|&&(read, _)| read == at) {
+                engine.input.push(byte);
+            }
+            match engine.step() {
+                Ok(()) | Err(Exception::Breakpoint) => {}
+                Err(exception) => return Err(format!("couldn't replay the trace: {exception}")),
+            }
+        }
+
+        match (self.steps.get(step), engine.instruction_pointer) {
+            (Some(&expected), InstructionPointer::Index(index)) if index == expected => Ok(()),
+            (None, _) => Ok(()),
+            _ => Err(String::from(
+                "the trace doesn't match its program, so it may be from another flavor",
+            )),
+        }
+    }
+
+    /// The trace as bytes, with each number as a varint and each step as
+    /// the distance from the instruction before, which is mostly 1
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::from(&MAGIC[..]);
+        bytes.push(VERSION);
+        write_varint(&mut bytes, self.source.len() as u64);
+        bytes.extend(self.source.as_bytes());
+
+        match self.tape_model {
+            TapeModel::Unbounded => bytes.push(0),
+            TapeModel::Fixed(len) => {
+                bytes.push(1);
+                write_varint(&mut bytes, len as u64);
+            }
+            TapeModel::Sparse => bytes.push(2),
+        }
+        write_varint(&mut bytes, self.seed);
+        write_varint(&mut bytes, self.tape_image.len() as u64);
+        for (offset, cells) in &self.tape_image {
+            write_varint(&mut bytes, *offset as u64);
+            write_varint(&mut bytes, cells.len() as u64);
+            bytes.extend(cells);
+        }
+
+        write_varint(&mut bytes, self.steps.len() as u64);
+        let mut previous = 0;
+        for &index in &self.steps {
+            write_varint(&mut bytes, zigzag(index as i64 - previous as i64));
+            previous = index;
+        }
+
+        for events in [&self.input, &self.output] {
+            write_varint(&mut bytes, events.len() as u64);
+            let mut previous = 0;
+            for &(step, byte) in events {
+                write_varint(&mut bytes, (step - previous) as u64);
+                bytes.push(byte);
+                previous = step;
+            }
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Trace, String> {
+        let mut reader = Reader { bytes, position: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(String::from("not a trace file"));
+        }
+        let version = match reader.take(1)?[0] {
+            // the first version didn't record how the engine was set up
+            version @ (1 | VERSION) => version,
+            version => return Err(format!("unsupported trace version {version}")),
+        };
+
+        let length = reader.length()?;
+        let source = String::from_utf8(reader.take(length)?.to_vec())
+            .map_err(|_| String::from("the trace's source isn't UTF-8"))?;
+
+        let mut trace = Trace {
+            source,
+            ..Trace::default()
+        };
+        if version == VERSION {
+            trace.tape_model = match reader.take(1)?[0] {
+                0 => TapeModel::Unbounded,
+                1 => TapeModel::Fixed(reader.length()?),
+                2 => TapeModel::Sparse,
+                _ => return Err(corrupt()),
+            };
+            trace.seed = reader.varint()?;
+            for _ in 0..reader.length()? {
+                let offset = reader.length()?;
+                let length = reader.length()?;
+                trace
+                    .tape_image
+                    .push((offset, reader.take(length)?.to_vec()));
+            }
+        }
+
+        let mut steps = Vec::new();
+        let mut previous = 0i64;
+        for _ in 0..reader.length()? {
+            let delta = unzigzag(reader.varint()?);
+            previous = previous.checked_add(delta).ok_or_else(corrupt)?;
+            let index = usize::try_from(previous).map_err(|_| corrupt())?;
+            steps.push(index);
+        }
+
+        let mut events = [Vec::new(), Vec::new()];
+        for events in &mut events {
+            let mut step = 0usize;
+            for _ in 0..reader.length()? {
+                step = step.checked_add(reader.length()?).ok_or_else(corrupt)?;
+                events.push((step, reader.take(1)?[0]));
+            }
+        }
+        if reader.position != bytes.len() {
+            return Err(corrupt());
+        }
+
+        let [input, output] = events;
+        Ok(Trace {
+            steps,
+            input,
+            output,
+            ..trace
+        })
+    }
+}
+
+/// Rebuilds the states a trace passed through, keeping a copy of the engine
+/// every `CHECKPOINT_INTERVAL` steps on the way, so each state only takes
+/// running on from the copy before it. The engines it gives keep no history.
+#[derive(Clone, Debug)]
+pub struct TraceStates<'a> {
+    trace: &'a Trace,
+    /// The engine after each multiple of `CHECKPOINT_INTERVAL` steps reached
+    /// so far
+    checkpoints: Vec<Engine>,
+}
+
+impl TraceStates<'_> {
+    /// The engine as it was after a number of steps
+    pub fn state_at(&mut self, step: usize) -> Result<Engine, String> {
+        self.trace.check_step(step)?;
+        let checkpoint = step / CHECKPOINT_INTERVAL;
+        while self.checkpoints.len() <= checkpoint {
+            let mut engine = self.checkpoints[self.checkpoints.len() - 1].clone();
+            self.trace
+                .run_to(&mut engine, self.checkpoints.len() * CHECKPOINT_INTERVAL)?;
+            self.checkpoints.push(engine);
+        }
+        let mut engine = self.checkpoints[checkpoint].clone();
+        self.trace.run_to(&mut engine, step)?;
+        Ok(engine)
+    }
+}
+
+fn corrupt() -> String {
+    String::from("the trace is corrupt")
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| String::from("the trace is cut short"))?;
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(corrupt())
+    }
+
+    fn length(&mut self) -> Result<usize, String> {
+        usize::try_from(self.varint()?).map_err(|_| corrupt())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    fn record(code: &str, input: &[u8]) -> (Engine, Trace) {
+        let mut engine = Engine::new(overflow::instruction_set().parse(code));
+        engine.input = input.to_vec();
+        let (trace, result) = engine.record(code);
+        assert_eq!(result, Ok(()));
+        (engine, trace)
+    }
+
+    #[test]
+    fn traces_note_each_step_and_byte() {
+        let (_, trace) = record(",[.-]$", b"\x02");
+        assert_eq!(trace.steps, vec![0, 1, 2, 3, 4, 2, 3, 4, 5]);
+        assert_eq!(trace.input, vec![(0, 2)]);
+        assert_eq!(trace.output, vec![(2, 2), (5, 1)]);
+    }
+
+    #[test]
+    fn traces_rebuild_any_state() {
+        let (engine, trace) = record(",[>+<-.]", b"\x03");
+        let instruction_set = overflow::instruction_set();
+
+        let replayed = trace.state_at(&instruction_set, trace.steps.len()).unwrap();
+        assert_eq!(replayed.tape, engine.tape);
+        assert_eq!(replayed.output, engine.output);
+
+        let halfway = trace.state_at(&instruction_set, 7).unwrap();
+        assert_eq!(halfway.tape, vec![2, 1]);
+        assert_eq!(
+            halfway.instruction_pointer,
+            InstructionPointer::Index(trace.steps[7])
+        );
+        assert!(trace.state_at(&instruction_set, 100).is_err());
+
+        let mut tampered = trace.clone();
+        tampered.steps[3] = 0;
+        assert!(tampered.state_at(&instruction_set, 3).is_err());
+    }
+
+    #[test]
+    fn traces_keep_how_the_engine_was_set_up() {
+        let code = ",[>?<-]>.";
+        let mut dialect = overflow::instruction_set();
+        dialect.insert(crate::flavor::random::random());
+        let mut engine = Engine::builder()
+            .dialect(dialect.clone())
+            .code(code)
+            .tape(TapeModel::Fixed(4))
+            .seed(7)
+            .input(vec![3])
+            .build()
+            .unwrap();
+        engine.load_tape(&[0, 5, 9], 0).unwrap();
+        let (trace, result) = engine.record(code);
+        assert_eq!(result, Ok(()));
+        let trace = Trace::from_bytes(&trace.to_bytes()).unwrap();
+        assert_eq!(trace.tape_model, TapeModel::Fixed(4));
+
+        let replayed = trace.state_at(&dialect, trace.steps.len()).unwrap();
+        assert_eq!(replayed.tape, engine.tape);
+        assert_eq!(replayed.output, engine.output);
+        assert_eq!(replayed.tape_model, TapeModel::Fixed(4));
+    }
+
+    #[test]
+    fn checkpoints_give_the_same_states() {
+        let (_, trace) = record("++++[>++++++++[>++++++++[>++++++++[>+<-]<-]<-]<-]", b"");
+        assert!(trace.steps.len() > 2 * CHECKPOINT_INTERVAL);
+        let instruction_set = overflow::instruction_set();
+        let mut states = trace.states(&instruction_set).unwrap();
+        for step in [trace.steps.len(), 5000, CHECKPOINT_INTERVAL, 3] {
+            let expected = trace.state_at(&instruction_set, step).unwrap();
+            let state = states.state_at(step).unwrap();
+            assert_eq!(state.tape, expected.tape);
+            assert_eq!(state.instruction_pointer, expected.instruction_pointer);
+        }
+        assert!(states.state_at(trace.steps.len() + 1).is_err());
+    }
+
+    #[test]
+    fn traces_survive_the_round_trip() {
+        let (_, trace) = record("+[->+[-]>]<,.", b"x");
+        let bytes = trace.to_bytes();
+        assert_eq!(Trace::from_bytes(&bytes), Ok(trace.clone()));
+
+        // consecutive steps cost a byte each, after the setup's dozen or so
+        assert!(bytes.len() < 20 + trace.source.len() + trace.steps.len() + 3 * 2);
+
+        assert!(Trace::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert_eq!(
+            Trace::from_bytes(b"nope"),
+            Err(String::from("not a trace file"))
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use crate::engine::{Engine, EngineError, EngineResult, Exception};
+
+/// Steps taken towards a budget, if there is one
+pub(crate) struct Fuel {
+    budget: Option<usize>,
+    steps: usize,
+}
+
+impl Fuel {
+    /// Count a step, or fail if the budget's used up
+    pub(crate) fn burn(&mut self) -> EngineResult {
+        if self.budget.is_some_and(|budget| self.steps >= budget) {
+            return Err(EngineError::FuelExhausted { steps: self.steps }.into());
+        }
+        self.steps += 1;
+        Ok(())
+    }
+}
+
+impl Engine {
+    /// Limit how many steps any command that takes more than one step
+    /// takes, forwards or back, so one waiting on a loop that never ends
+    /// gives up where it got to instead of hanging. Commands returning a
+    /// result fail with `EngineError::FuelExhausted`, and runs stop with
+    /// `StopReason::FuelExhausted`, taking the tighter of this and their
+    /// own `Stops::fuel`. Unlimited by default.
+    pub fn set_step_budget(&mut self, budget: Option<usize>) {
+        self.step_budget = budget;
+    }
+
+    /// Do something with a different step budget from the engine's, such as
+    /// `engine.with_step_budget(Some(100), Engine::run_until_output)`
+    pub fn with_step_budget<T>(
+        &mut self,
+        budget: Option<usize>,
+        command: impl FnOnce(&mut Engine) -> T,
+    ) -> T {
+        let kept = core::mem::replace(&mut self.step_budget, budget);
+        let result = command(self);
+        self.step_budget = kept;
+        result
+    }
+
+    pub(crate) fn fuel(&self) -> Fuel {
+        Fuel {
+            budget: self.step_budget,
+            steps: 0,
+        }
+    }
+
+    /// Run until an instruction writes a byte of output, returning the byte
+    pub fn run_until_output(&mut self) -> Result<u8, Exception> {
+        let written = self.output.len();
+        let mut fuel = self.fuel();
+        while self.output.len() <= written {
+            fuel.burn()?;
+            self.step()?;
+        }
+        Ok(self.output[written])
+    }
+
+    /// Run until the value of a cell changes, returning its new value
+    pub fn run_until_cell_changes(&mut self, index: usize) -> Result<u8, Exception> {
+        let value = |engine: &Engine| engine.tape.get(index).copied().unwrap_or(0);
+        let before = value(self);
+        let mut fuel = self.fuel();
+        loop {
+            fuel.burn()?;
+            self.step()?;
+            let after = value(self);
+            if after != before {
+                return Ok(after);
+            }
+        }
+    }
+
+    /// Run until the tape pointer moves onto a cell from elsewhere
+    pub fn run_until_pointer(&mut self, index: usize) -> EngineResult {
+        let mut fuel = self.fuel();
+        loop {
+            fuel.burn()?;
+            let before = self.tape_pointer;
+            self.step()?;
+            if self.tape_pointer == index && before != index {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Undo until the last byte of output is taken back, returning the byte
+    pub fn reverse_until_output_removed(&mut self) -> Result<u8, Exception> {
+        let written = self.output.len();
+        let last = *self.output.last().ok_or(EngineError::NoOutput)?;
+        let mut fuel = self.fuel();
+        while self.output.len() >= written {
+            fuel.burn()?;
+            self.undo_step()?;
+        }
+        Ok(last)
+    }
+
+    /// Undo until the value of a cell changes, returning its earlier value
+    pub fn reverse_until_cell_changes(&mut self, index: usize) -> Result<u8, Exception> {
+        let value = |engine: &Engine| engine.tape.get(index).copied().unwrap_or(0);
+        let before = value(self);
+        let mut fuel = self.fuel();
+        loop {
+            fuel.burn()?;
+            self.undo_step()?;
+            let after = value(self);
+            if after != before {
+                return Ok(after);
+            }
+        }
+    }
+
+    /// Undo until the tape pointer moves back onto a cell from elsewhere
+    pub fn reverse_until_pointer(&mut self, index: usize) -> EngineResult {
+        let mut fuel = self.fuel();
+        loop {
+            fuel.burn()?;
+            let before = self.tape_pointer;
+            self.undo_step()?;
+            if self.tape_pointer == index && before != index {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    #[test]
+    fn run_until_output_stops_after_each_byte() {
+        let instructions = overflow::instruction_set().parse("++++[>++++++++<-]>+.+.");
+        let mut engine = Engine::new(instructions);
+
+        assert_eq!(engine.run_until_output(), Ok(b'!'));
+        assert_eq!(engine.output, b"!");
+        assert_eq!(engine.run_until_output(), Ok(b'"'));
+        assert!(matches!(
+            engine.run_until_output(),
+            Err(Exception::Error(_))
+        ));
+    }
+
+    #[test]
+    fn run_until_cell_changes_ignores_other_cells() {
+        let instructions = overflow::instruction_set().parse(">+<++>[-]<");
+        let mut engine = Engine::new(instructions);
+
+        assert_eq!(engine.run_until_cell_changes(0), Ok(1));
+        assert_eq!(engine.history.len(), 4);
+        assert_eq!(engine.run_until_cell_changes(1), Ok(0));
+        assert_eq!(engine.tape, vec![2, 0]);
+        assert!(engine.run_until_cell_changes(5).is_err());
+    }
+
+    #[test]
+    fn run_until_pointer_stops_on_arrival() {
+        let instructions = overflow::instruction_set().parse("+>+>+<<>>+");
+        let mut engine = Engine::new(instructions);
+
+        assert_eq!(engine.run_until_pointer(2), Ok(()));
+        assert_eq!(engine.history.len(), 4);
+        assert_eq!(engine.run_until_pointer(2), Ok(()));
+        assert_eq!(engine.history.len(), 9);
+        assert!(engine.run_until_pointer(2).is_err());
+    }
+
+    #[test]
+    fn reversing_mirrors_running() {
+        let instructions = overflow::instruction_set().parse("+.>++.<+");
+        let mut engine = Engine::new(instructions);
+        while engine.step().is_ok() {}
+
+        assert_eq!(engine.reverse_until_cell_changes(0), Ok(1));
+        assert_eq!(engine.reverse_until_output_removed(), Ok(2));
+        assert_eq!(engine.output, b"\x01");
+        assert_eq!(engine.reverse_until_pointer(0), Ok(()));
+        assert_eq!(engine.reverse_until_output_removed(), Ok(1));
+        assert!(engine.reverse_until_output_removed().is_err());
+        assert_eq!(
+            engine.reverse_until_pointer(1),
+            Err(EngineError::HistoryEmpty.into())
+        );
+    }
+
+    #[test]
+    fn run_until_output_stops_on_exceptions() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("+$."));
+
+        assert_eq!(engine.run_until_output(), Err(Exception::Breakpoint));
+        assert_eq!(engine.run_until_output(), Ok(1));
+    }
+
+    #[test]
+    fn endless_loops_use_up_the_budget() {
+        let mut engine = Engine::new(overflow::instruction_set().parse("+[]."));
+        engine.set_step_budget(Some(50));
+        assert_eq!(
+            engine.run_until_output(),
+            Err(EngineError::FuelExhausted { steps: 50 }.into())
+        );
+        assert_eq!(engine.cell(), 1);
+        assert_eq!(
+            engine.with_step_budget(Some(3), |engine| engine.run_until_pointer(1)),
+            Err(EngineError::FuelExhausted { steps: 3 }.into())
+        );
+        assert_eq!(engine.step_budget, Some(50));
+
+        let mut engine = Engine::builder()
+            .code("+++.")
+            .step_budget(5)
+            .build()
+            .unwrap();
+        assert_eq!(engine.run_until_output(), Ok(3));
+    }
+
+    #[test]
+    fn every_run_keeps_to_the_budget() {
+        use crate::engine::run::{StopReason, Stops};
+
+        let mut engine = Engine::new(overflow::instruction_set().parse("+[]"));
+        engine.set_step_budget(Some(20));
+        assert_eq!(engine.run(&Stops::default()), StopReason::FuelExhausted);
+        assert_eq!(engine.history.len(), 19);
+        let stops = Stops {
+            fuel: Some(5),
+            ..Stops::default()
+        };
+        assert_eq!(engine.continue_(&stops), StopReason::FuelExhausted);
+        assert_eq!(engine.history.len(), 24);
+
+        assert_eq!(
+            engine.reverse_until_cell_changes(0),
+            Err(EngineError::FuelExhausted { steps: 20 }.into())
+        );
+        assert_eq!(engine.history.len(), 4);
+        assert_eq!(
+            engine.goto_step(30),
+            Err(EngineError::FuelExhausted { steps: 0 }.into())
+        );
+        assert_eq!(engine.history.len(), 4);
+        engine.goto_step(1).unwrap();
+        assert_eq!(engine.cell(), 1);
+    }
+}
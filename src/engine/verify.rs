@@ -0,0 +1,216 @@
+use crate::engine::{Engine, EngineError, EngineResult, InstructionPointer};
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::hash::Hasher;
+
+/// FNV-1a, which needs nothing from std and is quick for a byte at a time
+struct Fnv(u64);
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// What the steps to come depend on, as `Engine::future_state` gives it
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct FutureState {
+    cells: Vec<(usize, u8)>,
+    tape_pointer: usize,
+    instruction_pointer: InstructionPointer,
+    input: Vec<u8>,
+    random_state: u64,
+    threads: Vec<FutureState>,
+}
+
+impl Engine {
+    /// Check that undoing puts everything back as it was, by hashing the
+    /// state before each step and comparing it after the step's undone. A
+    /// mismatch fails the undo with `EngineError::UndoMismatch`, leaving
+    /// whatever the undo did. Steps taken before checking was turned on
+    /// aren't checked.
+    pub fn set_verify_undo(&mut self, verify: bool) {
+        self.undo_hashes = verify.then(VecDeque::new);
+    }
+
+    pub fn verifies_undo(&self) -> bool {
+        self.undo_hashes.is_some()
+    }
+
+    /// A hash of the tape, both pointers and the output. Zero cells don't
+    /// count, as undoing a move past the end of the tape leaves it longer.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = Fnv(0xcbf2_9ce4_8422_2325);
+        self.hash_position(&mut hasher);
+        hasher.write(&self.output);
+        hasher.finish()
+    }
+
+    /// A hash of everything the steps to come depend on: the tape, both
+    /// pointers, the input left, the random state and any threads spawned,
+    /// but not the output
+    pub(crate) fn future_hash(&self) -> u64 {
+        let mut hasher = Fnv(0xcbf2_9ce4_8422_2325);
+        self.hash_future(&mut hasher);
+        hasher.finish()
+    }
+
+    fn hash_future(&self, hasher: &mut Fnv) {
+        self.hash_position(hasher);
+        hasher.write_usize(self.input.len());
+        hasher.write(&self.input);
+        hasher.write_u64(self.random_state);
+        hasher.write_usize(self.spawned.len());
+        for thread in &self.spawned {
+            thread.hash_future(hasher);
+        }
+    }
+
+    /// Everything `future_hash` hashes, for telling for certain that two
+    /// states with the same hash are the same
+    pub(crate) fn future_state(&self) -> FutureState {
+        FutureState {
+            cells: (self.tape.regions())
+                .flat_map(|(start, cells)| {
+                    (cells.iter().enumerate())
+                        .filter(|(_, &cell)| cell != 0)
+                        .map(move |(i, &cell)| (start + i, cell))
+                })
+                .collect(),
+            tape_pointer: self.tape_pointer,
+            instruction_pointer: self.instruction_pointer,
+            input: self.input.clone(),
+            random_state: self.random_state,
+            threads: self.spawned.iter().map(Engine::future_state).collect(),
+        }
+    }
+
+    fn hash_position(&self, hasher: &mut Fnv) {
+        for (start, cells) in self.tape.regions() {
+            for (i, &cell) in cells.iter().enumerate().filter(|(_, &cell)| cell != 0) {
+                hasher.write_usize(start + i);
+                hasher.write_u8(cell);
+            }
+        }
+        hasher.write_usize(self.tape_pointer);
+        match self.instruction_pointer {
+            InstructionPointer::Start => hasher.write_u8(0),
+            InstructionPointer::End => hasher.write_u8(1),
+            InstructionPointer::Index(i) => {
+                hasher.write_u8(2);
+                hasher.write_usize(i);
+            }
+        }
+    }
+
+    /// The hash from before a step that's just gone in the history, if
+    /// undos are being checked
+    pub(crate) fn state_hash_for_undo(&self) -> Option<u64> {
+        self.undo_hashes.is_some().then(|| self.state_hash())
+    }
+
+    /// Keep the hash from before the step just taken, for as long as the
+    /// history keeps the step
+    pub(crate) fn record_state_hash(&mut self, hash: Option<u64>) {
+        let (Some(hashes), Some(hash)) = (&mut self.undo_hashes, hash) else {
+            return;
+        };
+        hashes.push_back(hash);
+        while hashes.len() > self.history.retained() {
+            hashes.pop_front();
+        }
+    }
+
+    /// Compare the state to before the step just undone
+    pub(crate) fn verify_undone(&mut self, index: Option<usize>) -> EngineResult {
+        let Some(expected) = self.undo_hashes.as_mut().and_then(VecDeque::pop_back) else {
+            return Ok(());
+        };
+        match self.state_hash() == expected {
+            true => Ok(()),
+            false => Err(EngineError::UndoMismatch {
+                step: self.history.len() + 1,
+                index,
+            }
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Exception;
+    use crate::flavor::overflow;
+    use crate::instruction::Instruction;
+    use alloc::vec;
+
+    #[test]
+    fn sound_undos_pass() {
+        let mut engine = Engine::builder()
+            .code("+[>,.<-]")
+            .input(vec![3])
+            .verify_undo(true)
+            .build()
+            .unwrap();
+        while engine.step().is_ok() {}
+        engine.write_tape(5, &[1, 2], true).unwrap();
+
+        while !engine.history.is_empty() {
+            engine.undo_step().unwrap();
+        }
+        assert_eq!(engine.undo_hashes, Some(VecDeque::new()));
+    }
+
+    #[test]
+    fn unsound_undos_are_caught() {
+        let mut instructions = overflow::instruction_set().parse("+>");
+        // moves right, but "undoes" by adding one
+        instructions.push(Instruction::new(
+            '>',
+            |program| {
+                program.next_cell()?;
+                program.next_instruction()
+            },
+            |program| {
+                program.try_map_cell(|cell| cell.wrapping_add(1))?;
+                program.prev_instruction()
+            },
+        ));
+        let mut engine = Engine::new(instructions);
+        engine.set_verify_undo(true);
+        while engine.step().is_ok() {}
+
+        assert_eq!(
+            engine.undo_step(),
+            Err(Exception::Error(EngineError::UndoMismatch {
+                step: 3,
+                index: Some(2)
+            }))
+        );
+        assert_eq!(engine.history.len(), 2);
+    }
+
+    #[test]
+    fn checks_follow_the_history() {
+        let mut engine = Engine::builder()
+            .code("+++++")
+            .history(crate::engine::history::HistoryPolicy::Last(2))
+            .verify_undo(true)
+            .build()
+            .unwrap();
+        while engine.step().is_ok() {}
+        assert_eq!(engine.undo_hashes.as_ref().map(VecDeque::len), Some(2));
+
+        engine.reset();
+        assert_eq!(engine.undo_hashes, Some(VecDeque::new()));
+    }
+}
@@ -0,0 +1,107 @@
+use crate::engine::Engine;
+use crate::tape::Tape;
+
+use core::ops::Range;
+
+/// The cells around one, borrowed from the tape rather than copied, for
+/// views redrawn too often to copy the whole tape each time
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TapeWindow<'a> {
+    tape: &'a Tape,
+    /// The index on the tape of the first cell in the window
+    pub start: usize,
+    /// The index after the last cell in the window, which can be past the
+    /// end of the tape
+    pub end: usize,
+    pub tape_pointer: usize,
+}
+
+impl<'a> TapeWindow<'a> {
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// The cells in the window the tape has reached so far, if the tape
+    /// stores them in one block
+    pub fn cells(&self) -> Option<&'a [u8]> {
+        let cells = self.tape.as_slice()?;
+        Some(&cells[self.start.min(cells.len())..self.end.min(cells.len())])
+    }
+
+    /// A cell by its index on the tape, with cells past the end of the tape
+    /// as the zeroes they'd be once reached
+    pub fn get(&self, index: usize) -> Option<u8> {
+        self.range()
+            .contains(&index)
+            .then(|| self.tape.get(index).copied().unwrap_or(0))
+    }
+
+    /// Where the tape pointer is in the window, if it's in it
+    pub fn pointer_offset(&self) -> Option<usize> {
+        self.range()
+            .contains(&self.tape_pointer)
+            .then(|| self.tape_pointer - self.start)
+    }
+
+    /// Each cell in the window with its index on the tape
+    pub fn iter(&self) -> impl Iterator<Item = (usize, u8)> + '_ {
+        self.range()
+            .map(|index| (index, self.get(index).unwrap_or(0)))
+    }
+}
+
+impl Engine {
+    /// The cells up to `radius` either side of `center`, stopping at the
+    /// first cell
+    pub fn tape_window(&self, center: usize, radius: usize) -> TapeWindow<'_> {
+        TapeWindow {
+            tape: &self.tape,
+            start: center.saturating_sub(radius),
+            end: center.saturating_add(radius).saturating_add(1),
+            tape_pointer: self.tape_pointer,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TapeModel;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn windows_borrow_what_the_tape_has() {
+        let mut engine = Engine::new(vec![]);
+        engine.tape = vec![1, 2, 3, 4, 5].into();
+        engine.tape_pointer = 3;
+
+        let window = engine.tape_window(1, 2);
+        assert_eq!((window.start, window.end), (0, 4));
+        assert_eq!(window.cells(), Some(&[1, 2, 3, 4][..]));
+        assert_eq!(window.pointer_offset(), Some(3));
+        assert_eq!(window.get(4), None);
+
+        let window = engine.tape_window(5, 1);
+        assert_eq!(window.cells(), Some(&[5][..]));
+        assert_eq!(window.pointer_offset(), None);
+        assert_eq!(window.iter().collect::<Vec<_>>(), [(4, 5), (5, 0), (6, 0)]);
+
+        let window = engine.tape_window(100, 1);
+        assert_eq!(window.cells(), Some(&[][..]));
+        assert_eq!(window.get(100), Some(0));
+    }
+
+    #[test]
+    fn windows_read_sparse_tapes_too() {
+        let mut engine = Engine::builder().tape(TapeModel::Sparse).build().unwrap();
+        engine.write_tape(1_000_000, &[9], false).unwrap();
+
+        let window = engine.tape_window(1_000_000, 1);
+        assert_eq!(window.cells(), None);
+        assert_eq!(
+            window.iter().collect::<Vec<_>>(),
+            [(999_999, 0), (1_000_000, 9), (1_000_001, 0)]
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use crate::engine::Engine;
+use crate::tape::Tape;
+
+use alloc::vec::Vec;
+
+/// A step changing a cell, found by comparing the tape before and after it
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CellWrite {
+    /// The step that wrote the cell, counting from 0, so `goto_step` with
+    /// one more goes to just after it
+    pub step: usize,
+    pub cell: usize,
+    pub old: u8,
+    pub new: u8,
+}
+
+impl Engine {
+    /// Keep a log of every change to every cell, by the steps and recorded
+    /// tape edits that made them. Changes made before logging was turned on
+    /// aren't in it.
+    pub fn set_log_cell_writes(&mut self, log: bool) {
+        self.cell_writes = log.then(Vec::new);
+    }
+
+    pub fn logs_cell_writes(&self) -> bool {
+        self.cell_writes.is_some()
+    }
+
+    /// Every logged change to a cell, oldest first
+    pub fn cell_writes(&self, cell: usize) -> Vec<CellWrite> {
+        let writes = self.cell_writes.iter().flatten();
+        writes.filter(|write| write.cell == cell).copied().collect()
+    }
+
+    /// When a cell last changed, if it's been logged
+    pub fn last_cell_write(&self, cell: usize) -> Option<CellWrite> {
+        let writes = self.cell_writes.iter().flatten();
+        writes.rev().find(|write| write.cell == cell).copied()
+    }
+
+    /// The tape before a step, for comparing with after it, if writes are
+    /// being logged
+    pub(crate) fn tape_for_cell_writes(&self) -> Option<Tape> {
+        self.cell_writes.is_some().then(|| self.tape.clone())
+    }
+
+    /// Log the changes a step made from the tape before it
+    pub(crate) fn record_cell_writes(&mut self, step: usize, before: Option<Tape>) {
+        let (Some(writes), Some(before)) = (&mut self.cell_writes, before) else {
+            return;
+        };
+        let changes = before.diff(&self.tape).into_iter();
+        writes.extend(changes.map(|(cell, old, new)| CellWrite {
+            step,
+            cell,
+            old,
+            new,
+        }));
+    }
+
+    /// Drop the changes made by steps that have been undone
+    pub(crate) fn forget_cell_writes(&mut self) {
+        let step = self.history.len();
+        if let Some(writes) = &mut self.cell_writes {
+            let kept = writes.partition_point(|write| write.step < step);
+            writes.truncate(kept);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn writes_are_logged_per_cell() {
+        let mut engine = Engine::builder()
+            .code("++>+[<->-]")
+            .log_cell_writes(true)
+            .build()
+            .unwrap();
+        while engine.step().is_ok() {}
+        engine.write_tape(1, &[7], true).unwrap();
+
+        let olds = |writes: Vec<CellWrite>| writes.iter().map(|w| w.old).collect::<Vec<_>>();
+        assert_eq!(olds(engine.cell_writes(0)), vec![0, 1, 2]);
+        assert_eq!(
+            engine.last_cell_write(1),
+            Some(CellWrite {
+                step: 10,
+                cell: 1,
+                old: 0,
+                new: 7
+            })
+        );
+        assert_eq!(engine.cell_writes(2), vec![]);
+
+        engine.goto_step(3).unwrap();
+        assert_eq!(olds(engine.cell_writes(0)), vec![0, 1]);
+        assert_eq!(engine.last_cell_write(1), None);
+    }
+
+    #[test]
+    fn nothing_is_logged_unless_asked() {
+        let mut engine = Engine::builder().code("+").build().unwrap();
+        engine.step().unwrap();
+        engine.step().unwrap();
+        assert!(!engine.logs_cell_writes());
+        assert_eq!(engine.last_cell_write(0), None);
+    }
+}
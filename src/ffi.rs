@@ -0,0 +1,392 @@
+//! A C API for embedding the engine in programs not written in Rust, such
+//! as Python through `ctypes` or a C GUI. `include/plaque.h` declares it,
+//! generated with `cbindgen --config cbindgen.toml -o include/plaque.h`.
+//!
+//! Engines are created with `plaque_engine_new` and must be freed with
+//! `plaque_engine_free`. Every other function takes an engine from
+//! `plaque_engine_new` that hasn't been freed, and isn't safe to call on one
+//! engine from two threads at once.
+
+use crate::engine::run::{StopReason, Stops};
+use crate::engine::{Engine, EngineError, EngineResult, Exception, InstructionPointer};
+use crate::flavor::{overflow, Eof};
+
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+/// The instruction pointer before the first instruction
+pub const PLAQUE_AT_START: isize = -1;
+/// The instruction pointer after the last instruction
+pub const PLAQUE_AT_END: isize = -2;
+
+/// What a call did, or why it stopped
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PlaqueStatus {
+    /// It did what it was asked and the program can go on
+    PlaqueOk = 0,
+    /// The program stopped at a breakpoint
+    PlaqueBreakpoint = 1,
+    /// The program needs input, given with `plaque_push_input`
+    PlaqueNeedsInput = 2,
+    /// The program ran past its last instruction
+    PlaqueFinished = 3,
+    /// A run took as many steps as it was allowed
+    PlaqueOutOfSteps = 4,
+    /// It failed, with a message from `plaque_last_error`
+    PlaqueError = 5,
+}
+
+/// An engine running the overflow flavor, with breakpoints at instruction
+/// indexes and the last error, opaque to C
+#[derive(Debug)]
+pub struct PlaqueEngine {
+    engine: Engine,
+    stops: Stops,
+    error: Option<CString>,
+}
+
+impl PlaqueEngine {
+    fn status(&mut self, result: EngineResult) -> PlaqueStatus {
+        self.error = None;
+        match result {
+            Ok(()) if self.engine.instruction_pointer == InstructionPointer::End => {
+                PlaqueStatus::PlaqueFinished
+            }
+            Ok(()) => PlaqueStatus::PlaqueOk,
+            Err(Exception::Breakpoint) => PlaqueStatus::PlaqueBreakpoint,
+            Err(Exception::RequestingInput) => PlaqueStatus::PlaqueNeedsInput,
+            Err(Exception::Error(error)) => self.fail(error),
+        }
+    }
+
+    fn fail(&mut self, error: EngineError) -> PlaqueStatus {
+        // messages don't have nul bytes, but they could come from anywhere
+        let message = error.message().replace('\0', " ");
+        self.error = CString::new(message).ok();
+        PlaqueStatus::PlaqueError
+    }
+}
+
+/// Parse a program, with input instructions storing zero at the end of
+/// input rather than asking for more if `zero_eof` isn't 0. Returns null if
+/// `code` isn't UTF-8 or has a bracket nothing matches.
+///
+/// # Safety
+///
+/// `code` must be a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn plaque_engine_new(
+    code: *const c_char,
+    zero_eof: i32,
+) -> *mut PlaqueEngine {
+    if code.is_null() {
+        return ptr::null_mut();
+    }
+    let Ok(code) = unsafe { CStr::from_ptr(code) }.to_str() else {
+        return ptr::null_mut();
+    };
+    let eof = match zero_eof {
+        0 => Eof::default(),
+        _ => Eof::Zero,
+    };
+    let mut engine = Engine::new(vec![]);
+    let instructions = overflow::instruction_set_with(eof).parse(code);
+    if engine.load_instructions(instructions).is_err() {
+        return ptr::null_mut();
+    }
+    engine.next_instruction().ok();
+    Box::into_raw(Box::new(PlaqueEngine {
+        engine,
+        stops: Stops::default(),
+        error: None,
+    }))
+}
+
+/// Free an engine. Null is ignored.
+///
+/// # Safety
+///
+/// `engine` must be null or from `plaque_engine_new`, and not used again.
+#[no_mangle]
+pub unsafe extern "C" fn plaque_engine_free(engine: *mut PlaqueEngine) {
+    if !engine.is_null() {
+        drop(unsafe { Box::from_raw(engine) });
+    }
+}
+
+/// Run one instruction
+///
+/// # Safety
+///
+/// `engine` must be from `plaque_engine_new` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn plaque_step(engine: *mut PlaqueEngine) -> PlaqueStatus {
+    let engine = unsafe { &mut *engine };
+    if engine.engine.instruction_pointer == InstructionPointer::End {
+        return PlaqueStatus::PlaqueFinished;
+    }
+    let result = engine.engine.step();
+    engine.status(result)
+}
+
+/// Undo the last step
+///
+/// # Safety
+///
+/// `engine` must be from `plaque_engine_new` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn plaque_undo(engine: *mut PlaqueEngine) -> PlaqueStatus {
+    let engine = unsafe { &mut *engine };
+    let result = engine.engine.undo();
+    match engine.status(result) {
+        // undoing back from the end isn't finishing
+        PlaqueStatus::PlaqueFinished => PlaqueStatus::PlaqueOk,
+        status => status,
+    }
+}
+
+/// Step until the program finishes, stops or has taken `max_steps` steps,
+/// or with no limit if `max_steps` is 0. Getting past a breakpoint it's
+/// stopped at first.
+///
+/// # Safety
+///
+/// `engine` must be from `plaque_engine_new` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn plaque_run(engine: *mut PlaqueEngine, max_steps: usize) -> PlaqueStatus {
+    let engine = unsafe { &mut *engine };
+    engine.stops.fuel = (max_steps > 0).then_some(max_steps);
+    engine.error = None;
+    match engine.engine.continue_(&engine.stops) {
+        StopReason::Completed => PlaqueStatus::PlaqueFinished,
+        StopReason::Breakpoint(_) | StopReason::Watchpoint(_) => PlaqueStatus::PlaqueBreakpoint,
+        StopReason::InputRequested => PlaqueStatus::PlaqueNeedsInput,
+        StopReason::FuelExhausted | StopReason::TimedOut | StopReason::InfiniteLoopDetected => {
+            PlaqueStatus::PlaqueOutOfSteps
+        }
+        StopReason::Error(error) => engine.fail(error),
+    }
+}
+
+/// Start the program over, keeping the breakpoints
+///
+/// # Safety
+///
+/// `engine` must be from `plaque_engine_new` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn plaque_reset(engine: *mut PlaqueEngine) {
+    let engine = unsafe { &mut *engine };
+    engine.engine.reset();
+    engine.engine.next_instruction().ok();
+    engine.error = None;
+}
+
+/// The value of a cell, with cells the program hasn't reached yet 0
+///
+/// # Safety
+///
+/// `engine` must be from `plaque_engine_new` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn plaque_cell_get(engine: *const PlaqueEngine, index: usize) -> u8 {
+    let engine = unsafe { &*engine };
+    engine.engine.tape.get(index).copied().unwrap_or(0)
+}
+
+/// Change a cell, which can be undone like a step
+///
+/// # Safety
+///
+/// `engine` must be from `plaque_engine_new` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn plaque_cell_set(
+    engine: *mut PlaqueEngine,
+    index: usize,
+    value: u8,
+) -> PlaqueStatus {
+    let engine = unsafe { &mut *engine };
+    let result = engine.engine.write_tape(index, &[value], true);
+    match engine.status(result) {
+        PlaqueStatus::PlaqueFinished => PlaqueStatus::PlaqueOk,
+        status => status,
+    }
+}
+
+/// # Safety
+///
+/// `engine` must be from `plaque_engine_new` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn plaque_tape_pointer(engine: *const PlaqueEngine) -> usize {
+    unsafe { &*engine }.engine.tape_pointer
+}
+
+/// The index of the next instruction to run, or `PLAQUE_AT_START` or
+/// `PLAQUE_AT_END`
+///
+/// # Safety
+///
+/// `engine` must be from `plaque_engine_new` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn plaque_instruction_pointer(engine: *const PlaqueEngine) -> isize {
+    match unsafe { &*engine }.engine.instruction_pointer {
+        InstructionPointer::Start => PLAQUE_AT_START,
+        InstructionPointer::End => PLAQUE_AT_END,
+        InstructionPointer::Index(i) => i as isize,
+    }
+}
+
+/// How many steps the program has taken
+///
+/// # Safety
+///
+/// `engine` must be from `plaque_engine_new` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn plaque_steps(engine: *const PlaqueEngine) -> usize {
+    unsafe { &*engine }.engine.history.len()
+}
+
+/// The output so far, with its length stored in `len`. The bytes are only
+/// valid until the engine is next changed.
+///
+/// # Safety
+///
+/// `engine` must be from `plaque_engine_new` and not yet freed, and `len`
+/// must point to somewhere to store a `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn plaque_output(engine: *const PlaqueEngine, len: *mut usize) -> *const u8 {
+    let output = &unsafe { &*engine }.engine.output;
+    unsafe { *len = output.len() };
+    output.as_ptr()
+}
+
+/// Give the program input, after any it has yet to read
+///
+/// # Safety
+///
+/// `engine` must be from `plaque_engine_new` and not yet freed, and `bytes`
+/// must point to `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn plaque_push_input(
+    engine: *mut PlaqueEngine,
+    bytes: *const u8,
+    len: usize,
+) {
+    if len == 0 {
+        return;
+    }
+    let input = unsafe { std::slice::from_raw_parts(bytes, len) };
+    unsafe { &mut *engine }
+        .engine
+        .input
+        .extend_from_slice(input);
+}
+
+/// # Safety
+///
+/// `engine` must be from `plaque_engine_new` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn plaque_set_breakpoint(engine: *mut PlaqueEngine, index: usize) {
+    unsafe { &mut *engine }.stops.breakpoints.insert(index);
+}
+
+/// # Safety
+///
+/// `engine` must be from `plaque_engine_new` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn plaque_clear_breakpoint(engine: *mut PlaqueEngine, index: usize) {
+    unsafe { &mut *engine }.stops.breakpoints.remove(&index);
+}
+
+/// Why the last call returning `PlaqueError` failed, or null if the last
+/// call didn't. The message is only valid until the engine is next changed.
+///
+/// # Safety
+///
+/// `engine` must be from `plaque_engine_new` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn plaque_last_error(engine: *const PlaqueEngine) -> *const c_char {
+    match &unsafe { &*engine }.error {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// The version of the crate, as a static nul-terminated string
+#[no_mangle]
+pub extern "C" fn plaque_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine(code: &str) -> *mut PlaqueEngine {
+        let code = CString::new(code).unwrap();
+        unsafe { plaque_engine_new(code.as_ptr(), 1) }
+    }
+
+    #[test]
+    fn engines_run_through_the_c_api() {
+        let engine = engine(",+.>++");
+        unsafe {
+            plaque_push_input(engine, b"a".as_ptr(), 1);
+            plaque_set_breakpoint(engine, 3);
+            assert_eq!(plaque_run(engine, 0), PlaqueStatus::PlaqueBreakpoint);
+            assert_eq!(plaque_instruction_pointer(engine), 3);
+            let mut len = 0;
+            let output = plaque_output(engine, &mut len);
+            assert_eq!(std::slice::from_raw_parts(output, len), b"b");
+
+            assert_eq!(plaque_step(engine), PlaqueStatus::PlaqueOk);
+            assert_eq!(plaque_tape_pointer(engine), 1);
+            assert_eq!(plaque_run(engine, 0), PlaqueStatus::PlaqueFinished);
+            assert_eq!(plaque_instruction_pointer(engine), PLAQUE_AT_END);
+            assert_eq!(plaque_cell_get(engine, 1), 2);
+            assert_eq!(plaque_cell_get(engine, 100), 0);
+
+            assert_eq!(plaque_undo(engine), PlaqueStatus::PlaqueOk);
+            assert_eq!(plaque_cell_get(engine, 1), 1);
+            assert_eq!(plaque_cell_set(engine, 0, 7), PlaqueStatus::PlaqueOk);
+            assert_eq!(plaque_cell_get(engine, 0), 7);
+
+            plaque_reset(engine);
+            assert_eq!(plaque_steps(engine), 0);
+            assert_eq!(plaque_instruction_pointer(engine), 0);
+            plaque_engine_free(engine);
+        }
+    }
+
+    #[test]
+    fn failures_leave_a_message() {
+        let engine = engine("<");
+        unsafe {
+            assert!(plaque_last_error(engine).is_null());
+            assert_eq!(plaque_step(engine), PlaqueStatus::PlaqueError);
+            let message = CStr::from_ptr(plaque_last_error(engine));
+            assert_eq!(
+                message.to_str(),
+                Ok("can't move tape pointer before the first cell")
+            );
+            plaque_engine_free(engine);
+        }
+        assert!(self::engine("[").is_null());
+        assert!(unsafe { plaque_engine_new(ptr::null(), 0) }.is_null());
+    }
+
+    #[test]
+    fn the_header_declares_everything() {
+        let header = include_str!("../include/plaque.h");
+        let source = include_str!("ffi.rs");
+        let after = |prefix| source.split(prefix).skip(1);
+        let exported = after("pub unsafe extern \"C\" fn ")
+            .chain(after("pub extern \"C\" fn "))
+            .filter_map(|rest| rest.split('(').next());
+        for function in exported {
+            assert!(
+                header.contains(&format!("{function}(")),
+                "{function} isn't in plaque.h"
+            );
+        }
+    }
+}
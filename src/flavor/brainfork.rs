@@ -0,0 +1,36 @@
+use crate::engine::EngineError;
+use crate::flavor::overflow;
+use crate::instruction::{Instruction, InstructionSet};
+
+pub fn fork() -> Instruction {
+    Instruction::new(
+        'Y',
+        // the parent's cell is cleared, while the child moves one cell to the
+        // right and sets it to 1, so each thread can tell which one it is
+        |program| {
+            let mut child = program.fork();
+            child.next_cell()?;
+            child.set_cell(1);
+            child.next_instruction()?;
+            program.spawned.push(child);
+
+            program.fork_cell_history.push(program.try_cell()?);
+            program.set_cell(0);
+            program.next_instruction()
+        },
+        |program| match program.fork_cell_history.pop() {
+            None => Err(EngineError::UndoStateMissing("fork").into()),
+            Some(cell) => {
+                program.spawned.pop();
+                program.try_set_cell(cell)?;
+                program.prev_instruction()
+            }
+        },
+    )
+}
+
+pub fn instruction_set() -> InstructionSet {
+    let mut instruction_set = overflow::instruction_set();
+    instruction_set.insert(fork());
+    instruction_set
+}
@@ -1 +1,65 @@
+pub mod brainfork;
 pub mod overflow;
+pub mod random;
+
+use crate::instruction::InstructionSet;
+
+use alloc::format;
+use alloc::string::String;
+
+/// What an input instruction does when there's no input left to read
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Eof {
+    /// Stop and ask for more input, for interactive sessions
+    #[default]
+    RequestInput,
+    Zero,
+    Unchanged,
+    Max,
+}
+
+impl core::str::FromStr for Eof {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Eof, String> {
+        match name {
+            "request" => Ok(Eof::RequestInput),
+            "zero" => Ok(Eof::Zero),
+            "unchanged" => Ok(Eof::Unchanged),
+            "max" => Ok(Eof::Max),
+            _ => Err(format!("unknown EOF policy {name}")),
+        }
+    }
+}
+
+/// One of the built-in instruction sets
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Dialect {
+    #[default]
+    Overflow,
+    /// Overflow with `Y` for forking threads
+    Brainfork,
+}
+
+impl Dialect {
+    pub fn instruction_set(self) -> InstructionSet {
+        match self {
+            Dialect::Overflow => overflow::instruction_set(),
+            Dialect::Brainfork => brainfork::instruction_set(),
+        }
+    }
+}
+
+impl core::str::FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Dialect, String> {
+        match name {
+            "overflow" => Ok(Dialect::Overflow),
+            "brainfork" => Ok(Dialect::Brainfork),
+            _ => Err(format!(
+                "unknown dialect {name}, expected overflow or brainfork"
+            )),
+        }
+    }
+}
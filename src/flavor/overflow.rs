@@ -1,154 +1,225 @@
-use crate::engine::Exception;
-use crate::instruction::Instruction;
-
-pub const INCREMENT_POINTER: Instruction = Instruction {
-    symbol: '>',
-
-    exec: |program| {
-        program.next_cell()?;
-        program.next_instruction()
-    },
-
-    unexec: |program| {
-        program.prev_cell()?;
-        program.prev_instruction()
-    },
-};
-
-pub const DECREMENT_POINTER: Instruction = Instruction {
-    symbol: '<',
-
-    exec: |program| {
-        program.prev_cell()?;
-        program.next_instruction()
-    },
-
-    unexec: |program| {
-        program.next_cell()?;
-        program.prev_instruction()
-    },
-};
-
-pub const INCREMENT_CELL: Instruction = Instruction {
-    symbol: '+',
-
-    exec: |program| {
-        program.map_cell(|cell| cell.wrapping_add(1));
-        program.next_instruction()
-    },
-
-    unexec: |program| {
-        program.map_cell(|cell| cell.wrapping_sub(1));
-        program.prev_instruction()
-    },
-};
-
-pub const DECREMENT_CELL: Instruction = Instruction {
-    symbol: '-',
-
-    exec: |program| {
-        program.map_cell(|cell| cell.wrapping_sub(1));
-        program.next_instruction()
-    },
-
-    unexec: |program| {
-        program.map_cell(|cell| cell.wrapping_add(1));
-        program.prev_instruction()
-    },
-};
-
-pub const OUTPUT: Instruction = Instruction {
-    symbol: '.',
-
-    exec: |program| {
-        program.output.push(program.cell());
-        program.next_instruction()
-    },
-
-    unexec: |program| {
-        program.output.pop();
-        program.prev_instruction()
-    },
-};
-
-pub const INPUT: Instruction = Instruction {
-    symbol: ',',
-
-    exec: |program| match program.pop_input() {
-        None => Exception::RequestingInput.result(),
-        Some(input) => {
-            let cell = program.cell();
-            program.set_cell(input);
-            program.input_cell_history.push(cell);
+use crate::engine::{EngineError, Exception};
+use crate::flavor::Eof;
+use crate::instruction::InstructionKind::{CloseLoop, OpenLoop};
+use crate::instruction::{Instruction, InstructionSet};
+
+pub fn increment_pointer() -> Instruction {
+    Instruction::new(
+        '>',
+        |program| {
+            program.next_cell()?;
             program.next_instruction()
-        }
-    },
-
-    unexec: |program| match program.input_cell_history.pop() {
-        None => Exception::error("no input to undo").result(),
-        Some(cell) => {
-            let input = program.cell();
-            program.set_cell(cell);
-            program.push_input(input);
+        },
+        |program| {
+            program.try_prev_cell()?;
             program.prev_instruction()
-        }
-    },
-};
-
-pub const JUMP_FORWARD: Instruction = Instruction {
-    symbol: '[',
-
-    exec: |program| {
-        if program.cell() == 0 {
-            program.goto_next(JUMP_BACKWARD, JUMP_FORWARD)?;
-        }
-        program.next_instruction()
-    },
-
-    unexec: |program| match program.cell() {
-        0 => program.goto_prev(JUMP_FORWARD, JUMP_BACKWARD),
-        _ => program.prev_instruction(),
-    },
-};
-
-pub const JUMP_BACKWARD: Instruction = Instruction {
-    symbol: ']',
-
-    exec: |program| {
-        if program.cell() != 0 {
-            program.goto_prev(JUMP_FORWARD, JUMP_BACKWARD)?;
-        }
-        program.next_instruction()
-    },
-
-    unexec: |program| match program.cell() {
-        0 => program.prev_instruction(),
-        _ => program.goto_next(JUMP_BACKWARD, JUMP_FORWARD),
-    },
-};
-
-pub const BREAKPOINT: Instruction = Instruction {
-    symbol: '$',
-
-    exec: |program| {
-        program.next_instruction()?;
-        Exception::Breakpoint.result()
-    },
-
-    unexec: |program| {
-        program.prev_instruction()?;
-        Exception::Breakpoint.result()
-    },
-};
-
-pub const INSTRUCTION_SET: [Instruction; 9] = [
-    INCREMENT_POINTER,
-    DECREMENT_POINTER,
-    INCREMENT_CELL,
-    DECREMENT_CELL,
-    OUTPUT,
-    INPUT,
-    JUMP_FORWARD,
-    JUMP_BACKWARD,
-    BREAKPOINT,
-];
+        },
+    )
+}
+
+pub fn decrement_pointer() -> Instruction {
+    Instruction::new(
+        '<',
+        |program| {
+            program.try_prev_cell()?;
+            program.next_instruction()
+        },
+        |program| {
+            program.next_cell()?;
+            program.prev_instruction()
+        },
+    )
+}
+
+pub fn increment_cell() -> Instruction {
+    Instruction::new(
+        '+',
+        |program| {
+            program.try_map_cell(|cell| cell.wrapping_add(1))?;
+            program.next_instruction()
+        },
+        |program| {
+            program.try_map_cell(|cell| cell.wrapping_sub(1))?;
+            program.prev_instruction()
+        },
+    )
+}
+
+pub fn decrement_cell() -> Instruction {
+    Instruction::new(
+        '-',
+        |program| {
+            program.try_map_cell(|cell| cell.wrapping_sub(1))?;
+            program.next_instruction()
+        },
+        |program| {
+            program.try_map_cell(|cell| cell.wrapping_add(1))?;
+            program.prev_instruction()
+        },
+    )
+}
+
+pub fn output() -> Instruction {
+    Instruction::new(
+        '.',
+        |program| {
+            let cell = program.try_cell()?;
+            program.output.push(cell);
+            program.next_instruction()
+        },
+        |program| {
+            program.output.pop();
+            program.prev_instruction()
+        },
+    )
+}
+
+pub fn input(eof: Eof) -> Instruction {
+    Instruction::new(
+        ',',
+        move |program| {
+            let cell = program.try_cell()?;
+            let input = program.pop_input();
+            let value = match (input, eof) {
+                (Some(input), _) => input,
+                (None, Eof::RequestInput) => return Exception::RequestingInput.result(),
+                (None, Eof::Zero) => 0,
+                (None, Eof::Unchanged) => cell,
+                (None, Eof::Max) => u8::MAX,
+            };
+            program.try_set_cell(value)?;
+            program.input_cell_history.push((cell, input));
+            program.next_instruction()
+        },
+        |program| match program.input_cell_history.pop() {
+            None => Err(EngineError::UndoStateMissing("input").into()),
+            Some((cell, input)) => {
+                program.try_set_cell(cell)?;
+                if let Some(input) = input {
+                    program.push_input(input);
+                }
+                program.prev_instruction()
+            }
+        },
+    )
+}
+
+pub fn jump_forward() -> Instruction {
+    Instruction::new(
+        '[',
+        |program| {
+            if program.try_cell()? == 0 {
+                program.goto_next(CloseLoop, OpenLoop)?;
+            }
+            program.next_instruction()
+        },
+        // skipping the loop left the pointer just past its `]`
+        |program| match program.try_cell()? {
+            0 => {
+                program.prev_instruction()?;
+                program.goto_prev(OpenLoop, CloseLoop)
+            }
+            _ => program.prev_instruction(),
+        },
+    )
+}
+
+pub fn jump_backward() -> Instruction {
+    Instruction::new(
+        ']',
+        |program| {
+            if program.try_cell()? != 0 {
+                program.goto_prev(OpenLoop, CloseLoop)?;
+            }
+            program.next_instruction()
+        },
+        // going round again left the pointer just past the `[`
+        |program| match program.try_cell()? {
+            0 => program.prev_instruction(),
+            _ => {
+                program.prev_instruction()?;
+                program.goto_next(CloseLoop, OpenLoop)
+            }
+        },
+    )
+}
+
+pub fn breakpoint() -> Instruction {
+    Instruction::new(
+        '$',
+        |program| {
+            program.next_instruction()?;
+            Exception::Breakpoint.result()
+        },
+        |program| {
+            program.prev_instruction()?;
+            Exception::Breakpoint.result()
+        },
+    )
+}
+
+pub fn instruction_set() -> InstructionSet {
+    instruction_set_with(Eof::default())
+}
+
+pub fn instruction_set_with(eof: Eof) -> InstructionSet {
+    InstructionSet::from_iter([
+        increment_pointer(),
+        decrement_pointer(),
+        increment_cell(),
+        decrement_cell(),
+        output(),
+        input(eof),
+        jump_forward(),
+        jump_backward(),
+        breakpoint(),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+
+    fn run(eof: Eof, code: &str) -> Engine {
+        let mut engine = Engine::new(instruction_set_with(eof).parse(code));
+        while engine.step().is_ok() {}
+        engine
+    }
+
+    #[test]
+    fn eof_policy_is_captured_by_input() {
+        assert_eq!(run(Eof::Zero, "+++,").tape, vec![0]);
+        assert_eq!(run(Eof::Unchanged, "+++,").tape, vec![3]);
+        assert_eq!(run(Eof::Max, "+++,").tape, vec![255]);
+    }
+
+    #[test]
+    fn eof_waits_for_input_by_default() {
+        let engine = run(Eof::RequestInput, "+++,");
+        assert_eq!(engine.tape, vec![3]);
+        assert_eq!(engine.current_instruction().map(|i| i.symbol), Some(','));
+    }
+
+    #[test]
+    fn skipped_loops_undo() {
+        let mut engine = run(Eof::Zero, "[+]>++[[>+<-]]");
+        assert_eq!(engine.tape, vec![0, 0, 2]);
+
+        while engine.undo().is_ok() {}
+        assert_eq!(engine.history.len(), 0);
+        assert_eq!(engine.tape_pointer, 0);
+        assert!(engine.tape.iter().all(|&cell| cell == 0));
+    }
+
+    #[test]
+    fn undoing_eof_read_consumes_no_input() {
+        let mut engine = run(Eof::Zero, "+++,");
+        engine.input = vec![7];
+
+        engine.undo().unwrap();
+
+        assert_eq!(engine.tape, vec![3]);
+        assert_eq!(engine.input, vec![7]);
+    }
+}
@@ -0,0 +1,62 @@
+use crate::engine::EngineError;
+use crate::instruction::Instruction;
+
+/// `?`, setting the cell to a random byte from the engine's seeded random
+/// numbers. It's in no dialect by default, so add it to one to use it.
+pub fn random() -> Instruction {
+    Instruction::new(
+        '?',
+        |program| {
+            let cell = program.try_cell()?;
+            let state = program.random_state;
+            let byte = (program.next_random() >> 56) as u8;
+            program.try_set_cell(byte)?;
+            program.random_history.push((cell, state));
+            program.next_instruction()
+        },
+        |program| match program.random_history.pop() {
+            None => Err(EngineError::UndoStateMissing("random number").into()),
+            Some((cell, state)) => {
+                program.try_set_cell(cell)?;
+                program.random_state = state;
+                program.prev_instruction()
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::flavor::overflow;
+
+    fn engine(code: &str) -> Engine {
+        let mut instruction_set = overflow::instruction_set();
+        instruction_set.insert(random());
+        Engine::new(instruction_set.parse(code))
+    }
+
+    #[test]
+    fn random_bytes_undo_and_repeat() {
+        let mut engine = engine("?.?.?.");
+        engine.set_seed(42);
+        while engine.step().is_ok() {}
+        let output = engine.output.clone();
+        assert_ne!(output[0], output[1]);
+
+        engine.goto_step(2).unwrap();
+        assert_eq!(engine.output, output[..1]);
+        while engine.step().is_ok() {}
+        assert_eq!(engine.output, output);
+
+        engine.reset();
+        while engine.step().is_ok() {}
+        assert_eq!(engine.output, output);
+
+        engine.set_seed(7);
+        engine.reset();
+        while engine.step().is_ok() {}
+        assert_ne!(engine.output, output);
+    }
+}
@@ -0,0 +1,181 @@
+use crate::instruction::InstructionSet;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Marks a spot in the source for tools, such as where to dump the tape
+const MARKER: char = '#';
+/// Everything after it is input for the program, not code
+const INPUT_SECTION: char = '!';
+
+/// How formatted source is laid out. Comments are stripped in every style.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Style {
+    /// Everything on one line
+    Minified,
+    /// Lines of at most `width` characters
+    Reflowed { width: usize },
+    /// Each loop's brackets on lines of their own, with its body indented
+    /// by `indent` spaces more than them, and runs of code between brackets
+    /// wrapped at `width`
+    Indented { width: usize, indent: usize },
+}
+
+impl Default for Style {
+    fn default() -> Style {
+        Style::Indented {
+            width: 80,
+            indent: 2,
+        }
+    }
+}
+
+/// Parses `minify`, `reflow` or `indent`, with the default width and indent
+impl core::str::FromStr for Style {
+    type Err = String;
+
+    fn from_str(style: &str) -> Result<Style, String> {
+        match style {
+            "minify" => Ok(Style::Minified),
+            "reflow" => Ok(Style::Reflowed { width: 80 }),
+            "indent" => Ok(Style::default()),
+            _ => Err(format!(
+                "invalid style {style}, expected minify, reflow or indent"
+            )),
+        }
+    }
+}
+
+/// Lay out a program's code in a style, dropping everything that isn't an
+/// instruction or a `#` marker. An input section from a `!` on is kept as
+/// it is, on a line of its own unless minifying.
+pub fn format(source: &str, instruction_set: &InstructionSet, style: Style) -> String {
+    let (code, input) = match instruction_set.contains(INPUT_SECTION) {
+        true => (source, None),
+        false => match source.find(INPUT_SECTION) {
+            Some(split) => (&source[..split], Some(&source[split..])),
+            None => (source, None),
+        },
+    };
+    let code = code
+        .chars()
+        .filter(|&symbol| symbol == MARKER || instruction_set.contains(symbol))
+        .collect::<Vec<_>>();
+
+    let mut formatted = match style {
+        Style::Minified => code.iter().collect(),
+        Style::Reflowed { width } => wrap(&code, width.max(1), "").join("\n"),
+        Style::Indented { width, indent } => indented(&code, width, indent).join("\n"),
+    };
+    if let Some(input) = input {
+        if style != Style::Minified && !formatted.is_empty() {
+            formatted.push('\n');
+        }
+        formatted.push_str(input);
+    }
+    formatted
+}
+
+/// Split code into lines of at most `width` characters, counting a prefix
+/// each line starts with
+fn wrap(code: &[char], width: usize, prefix: &str) -> Vec<String> {
+    let room = width.saturating_sub(prefix.len()).max(1);
+    code.chunks(room)
+        .map(|chunk| prefix.chars().chain(chunk.iter().copied()).collect())
+        .collect()
+}
+
+fn indented(code: &[char], width: usize, indent: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut depth = 0;
+    let mut run = vec![];
+    let mut index = 0;
+    while index < code.len() {
+        let prefix = " ".repeat(depth * indent);
+        match code[index] {
+            '[' if code.get(index + 1) == Some(&']') => {
+                // an empty loop has nothing to indent, so stays with the code
+                // around it
+                run.extend(['[', ']']);
+                index += 1;
+            }
+            '[' => {
+                lines.extend(wrap(&core::mem::take(&mut run), width, &prefix));
+                lines.push(format!("{prefix}["));
+                depth += 1;
+            }
+            ']' => {
+                lines.extend(wrap(&core::mem::take(&mut run), width, &prefix));
+                // a bracket with nothing to match it stays at the left
+                depth = depth.saturating_sub(1);
+                lines.push(format!("{}]", " ".repeat(depth * indent)));
+            }
+            symbol => run.push(symbol),
+        }
+        index += 1;
+    }
+    lines.extend(wrap(&run, width, &" ".repeat(depth * indent)));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    fn format(source: &str, style: Style) -> String {
+        super::format(source, &overflow::instruction_set(), style)
+    }
+
+    #[test]
+    fn minified_code_keeps_markers_and_input() {
+        assert_eq!(
+            format(
+                "add two: ++ \n then # print it .!input stays\n",
+                Style::Minified
+            ),
+            "++#.!input stays\n"
+        );
+    }
+
+    #[test]
+    fn reflowed_code_fits_the_width() {
+        assert_eq!(
+            format("+++ +++ +++ +", Style::Reflowed { width: 4 }),
+            "++++\n++++\n++"
+        );
+    }
+
+    #[test]
+    fn indented_code_follows_loops() {
+        let source = "++[>++[>+<-]<-] >[] .!ab";
+        assert_eq!(
+            format(
+                source,
+                Style::Indented {
+                    width: 80,
+                    indent: 2
+                }
+            ),
+            "++\n[\n  >++\n  [\n    >+<-\n  ]\n  <-\n]\n>[].\n!ab"
+        );
+    }
+
+    #[test]
+    fn formatting_keeps_the_program() {
+        let source = "comment +[->++<]>. ]";
+        for style in [
+            Style::Minified,
+            Style::Reflowed { width: 3 },
+            Style::default(),
+        ] {
+            let formatted = format(source, style);
+            assert_eq!(
+                overflow::instruction_set().parse(&formatted),
+                overflow::instruction_set().parse(source)
+            );
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Helpers for stressing the engine with generated programs, from property
+//! tests or cargo-fuzz targets, looking for panics and steps that don't undo
+//! to where they started or redo to where they ended.
+
+use crate::engine::run::StopReason;
+use crate::engine::{Engine, Exception, InstructionPointer};
+use crate::flavor::overflow;
+use crate::instruction::Instruction;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// The symbols generated programs are made of, which are every overflow
+/// instruction but the breakpoint
+const SYMBOLS: &[char] = &['+', '-', '<', '>', '.', ',', '[', ']'];
+
+/// A source of randomness, so the helpers don't need any particular crate's
+pub trait Rng {
+    fn next_u64(&mut self) -> u64;
+}
+
+impl<F: FnMut() -> u64> Rng for F {
+    fn next_u64(&mut self) -> u64 {
+        self()
+    }
+}
+
+/// A small, fast generator, good enough for picking instructions
+#[derive(Clone, Debug)]
+pub struct XorShift(u64);
+
+impl XorShift {
+    pub fn new(seed: u64) -> XorShift {
+        XorShift(seed.max(1))
+    }
+}
+
+impl Rng for XorShift {
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// A program of `len` overflow instructions with matching brackets, as
+/// unmatched ones only make programs fail sooner
+pub fn arbitrary_program<R: Rng + ?Sized>(rng: &mut R, len: usize) -> Vec<Instruction> {
+    let instruction_set = overflow::instruction_set();
+    let mut symbols = vec![];
+    let mut open = 0;
+    for i in 0..len {
+        let left = len - i;
+        let symbol = match SYMBOLS[(rng.next_u64() % SYMBOLS.len() as u64) as usize] {
+            // every loop still open needs room for its `]`
+            _ if open == left => ']',
+            '[' if open + 1 == left => '+',
+            ']' if open == 0 => '-',
+            symbol => symbol,
+        };
+        match symbol {
+            '[' => open += 1,
+            ']' => open -= 1,
+            _ => {}
+        }
+        symbols.push(symbol);
+    }
+    symbols
+        .into_iter()
+        .filter_map(|symbol| instruction_set.get(symbol).cloned())
+        .collect()
+}
+
+/// What running a program for a while did
+#[derive(Debug)]
+pub struct Outcome {
+    pub engine: Engine,
+    pub stop: StopReason,
+    /// How many steps were taken, and then undone and redone
+    pub steps: usize,
+}
+
+/// A step that didn't undo or redo to the state it should have
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mismatch {
+    /// Undoing the step failed or left a different state than before it
+    Undo { step: usize },
+    /// Running the step again after undoing it left a different state
+    Redo { step: usize },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Undo { step } => write!(fmt, "undoing step {step} didn't restore the state"),
+            Mismatch::Redo { step } => write!(fmt, "redoing step {step} didn't repeat it"),
+        }
+    }
+}
+
+/// The parts of an engine a step changes
+#[derive(Debug, Eq, PartialEq)]
+struct State {
+    /// The tape up to its last nonzero cell, as undoing a move doesn't
+    /// shrink the tape back
+    tape: Vec<u8>,
+    tape_pointer: usize,
+    instruction_pointer: InstructionPointer,
+    output: Vec<u8>,
+}
+
+impl State {
+    fn of(engine: &Engine) -> State {
+        State {
+            tape: {
+                let mut tape = engine.tape.to_vec();
+                let used = tape
+                    .iter()
+                    .rposition(|&cell| cell != 0)
+                    .map_or(0, |i| i + 1);
+                tape.truncate(used);
+                tape
+            },
+            tape_pointer: engine.tape_pointer,
+            instruction_pointer: engine.instruction_pointer,
+            output: engine.output.clone(),
+        }
+    }
+}
+
+/// Run a program for at most `fuel` steps, with input that never runs out,
+/// then undo every step back to the first and run them all again, checking
+/// each lands on the state it did the first time
+pub fn run_bounded(program: Vec<Instruction>, fuel: usize) -> Result<Outcome, Mismatch> {
+    let mut engine = Engine::new(program);
+    engine.input_cycle = (0..=255).collect();
+
+    // the state after each step, from before the first
+    let mut states = vec![];
+    let stop = loop {
+        if engine.instruction_pointer == InstructionPointer::End {
+            break StopReason::Completed;
+        }
+        if engine.history.len() >= fuel {
+            break StopReason::FuelExhausted;
+        }
+        let result = engine.step();
+        if engine.history.len() == states.len() {
+            states.push(State::of(&engine));
+        }
+        match result {
+            Ok(()) | Err(Exception::Breakpoint) => {}
+            Err(Exception::RequestingInput) => break StopReason::InputRequested,
+            Err(Exception::Error(error)) => break StopReason::Error(error),
+        }
+    };
+
+    let steps = engine.history.len();
+    for step in (1..=steps).rev() {
+        let undone = engine.undo_step();
+        if undone.is_err() || State::of(&engine) != states[step - 1] {
+            return Err(Mismatch::Undo { step });
+        }
+    }
+    for (step, state) in states.iter().enumerate().skip(1) {
+        let _ = engine.step();
+        if State::of(&engine) != *state {
+            return Err(Mismatch::Redo { step });
+        }
+    }
+
+    Ok(Outcome {
+        engine,
+        stop,
+        steps,
+    })
+}
+
+/// A program for cargo-fuzz to generate, with matching brackets
+#[cfg(feature = "arbitrary")]
+#[derive(Clone, Debug)]
+pub struct Program(pub Vec<Instruction>);
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Program {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Program> {
+        let len = u.arbitrary_len::<u8>()?;
+        // a byte of input for each choice, so mutating one changes one
+        // instruction
+        let mut next = || u.arbitrary::<u8>().map_or(0, u64::from);
+        Ok(Program(arbitrary_program(&mut next, len)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+
+    fn symbols(program: &[Instruction]) -> String {
+        program
+            .iter()
+            .map(|instruction| instruction.symbol)
+            .collect()
+    }
+
+    #[test]
+    fn generated_programs_have_matching_brackets() {
+        let mut rng = XorShift::new(7);
+        for len in 0..50 {
+            let program = arbitrary_program(&mut rng, len);
+            assert_eq!(program.len(), len);
+            assert!(crate::analysis::unmatched_brackets(&program).is_empty());
+        }
+
+        let mut counter = 0;
+        let mut next = || {
+            counter += 1;
+            counter
+        };
+        assert_eq!(symbols(&arbitrary_program(&mut next, 4)), "-<>.");
+    }
+
+    #[test]
+    fn bounded_runs_undo_and_redo_every_step() {
+        let program = overflow::instruction_set().parse("+[>,.<]");
+        let outcome = run_bounded(program, 20).unwrap();
+        assert_eq!(outcome.stop, StopReason::FuelExhausted);
+        assert_eq!(outcome.steps, 20);
+        assert_eq!(outcome.engine.output, vec![0, 1, 2, 3]);
+
+        let outcome = run_bounded(overflow::instruction_set().parse("+<"), 20).unwrap();
+        assert!(matches!(outcome.stop, StopReason::Error(_)));
+        assert_eq!(outcome.steps, 1);
+
+        let mut rng = XorShift::new(1);
+        for _ in 0..200 {
+            let program = arbitrary_program(&mut rng, 40);
+            if let Err(mismatch) = run_bounded(program.clone(), 200) {
+                panic!("{mismatch} of {}", symbols(&program));
+            }
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use crate::config::parse_value;
+use crate::engine::expect::Mismatch;
+use crate::engine::run::{StopReason, Stops};
+use crate::engine::{Engine, EngineError};
+use crate::flavor::Eof;
+use crate::output::json_string;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::time::Duration;
+
+/// The most steps a case can take when neither it nor its suite says
+pub const DEFAULT_STEPS: usize = 10_000_000;
+
+/// How often a case's run is checked for going round in circles
+const LOOP_CHECK: usize = 1000;
+
+/// One run of a program, with the input it's given and the output it
+/// should write
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Case {
+    pub name: String,
+    pub input: Vec<u8>,
+    pub output: Vec<u8>,
+    /// The most steps the run can take, if not the suite's
+    pub steps: Option<usize>,
+}
+
+/// Cases to grade programs against, read from the `key = value` lines of
+/// a file like `plaque.toml`, with a `[[case]]` line starting each case:
+///
+/// ```toml
+/// program = "echo.bf"  # what to grade, unless told otherwise
+/// steps = 100_000      # the most steps for any case
+/// timeout = 2000       # the most milliseconds for any case
+/// eof = "zero"         # what `,` reads once the input's used up
+///
+/// [[case]]
+/// name = "two letters"
+/// input = "hi"
+/// output = "hi"
+/// steps = 50
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Suite {
+    pub program: Option<String>,
+    pub steps: Option<usize>,
+    /// How long any case can take, if it's limited. Without `std` there's
+    /// no clock, so it's ignored.
+    pub timeout: Option<Duration>,
+    /// What input instructions do once a case's input runs out, if not to
+    /// ask for more, failing the case
+    pub eof: Option<Eof>,
+    pub cases: Vec<Case>,
+}
+
+impl core::str::FromStr for Suite {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Suite, String> {
+        let mut suite = Suite::default();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| format!("line {}: {message}", line_number + 1);
+
+            if line.split('#').next().unwrap_or_default().trim() == "[[case]]" {
+                suite.cases.push(Case {
+                    name: format!("case {}", suite.cases.len() + 1),
+                    ..Case::default()
+                });
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("expected key = value, found {line}")))?;
+            let key = key.trim();
+            let value = parse_value(value.trim()).map_err(error)?;
+            match (suite.cases.last_mut(), key) {
+                (None, "program") => suite.program = Some(value.string(key).map_err(error)?),
+                (None, "steps") => suite.steps = Some(value.integer(key).map_err(error)?),
+                (None, "timeout") => {
+                    let millis = value.integer(key).map_err(error)?;
+                    suite.timeout = Some(Duration::from_millis(millis as u64));
+                }
+                (None, "eof") => {
+                    suite.eof = Some(value.string(key).map_err(error)?.parse().map_err(error)?)
+                }
+                (Some(case), "name") => case.name = value.string(key).map_err(error)?,
+                (Some(case), "input") => case.input = value.string(key).map_err(error)?.into(),
+                (Some(case), "output") => case.output = value.string(key).map_err(error)?.into(),
+                (Some(case), "steps") => case.steps = Some(value.integer(key).map_err(error)?),
+                _ => return Err(error(format!("unknown key {key}"))),
+            }
+        }
+        Ok(suite)
+    }
+}
+
+/// How a case went
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Verdict {
+    Passed,
+    WrongOutput(Mismatch),
+    /// The run took as many steps as the case allows without finishing
+    OutOfSteps(usize),
+    /// The run took as long as the suite allows without finishing
+    TimedOut(Duration),
+    /// The run came back to where it had been, so would never finish
+    InfiniteLoop,
+    /// The program wanted more input than the case gives it
+    NeedsInput,
+    Error(EngineError),
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Passed => write!(fmt, "passed"),
+            Verdict::WrongOutput(mismatch) => write!(fmt, "{mismatch}"),
+            Verdict::OutOfSteps(steps) => write!(fmt, "didn't finish in {steps} steps"),
+            Verdict::TimedOut(timeout) => {
+                write!(fmt, "didn't finish in {} ms", timeout.as_millis())
+            }
+            Verdict::InfiniteLoop => write!(fmt, "stuck in a loop that never ends"),
+            Verdict::NeedsInput => write!(fmt, "read past the end of the input"),
+            Verdict::Error(error) => write!(fmt, "{error}"),
+        }
+    }
+}
+
+impl Verdict {
+    /// A name for the kind of verdict, for reports read by other programs
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Verdict::Passed => "passed",
+            Verdict::WrongOutput(_) => "wrong-output",
+            Verdict::OutOfSteps(_) => "out-of-steps",
+            Verdict::TimedOut(_) => "timed-out",
+            Verdict::InfiniteLoop => "infinite-loop",
+            Verdict::NeedsInput => "needs-input",
+            Verdict::Error(_) => "error",
+        }
+    }
+}
+
+/// A graded case
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Outcome {
+    pub name: String,
+    pub verdict: Verdict,
+    pub steps: usize,
+    pub output: Vec<u8>,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.verdict == Verdict::Passed
+    }
+}
+
+impl Suite {
+    /// Run a program through every case, each from a copy of how `engine`
+    /// is now
+    pub fn grade(&self, engine: &Engine) -> Vec<Outcome> {
+        self.cases
+            .iter()
+            .map(|case| self.grade_case(engine, case))
+            .collect()
+    }
+
+    fn grade_case(&self, engine: &Engine, case: &Case) -> Outcome {
+        let mut engine = engine.clone();
+        engine.input = case.input.clone();
+        let limit = case.steps.or(self.steps).unwrap_or(DEFAULT_STEPS);
+        let mut stops = Stops {
+            loop_check: Some(LOOP_CHECK),
+            timeout: self.timeout,
+            ..Stops::default()
+        };
+
+        let verdict = loop {
+            let steps = engine.history.len();
+            stops.fuel = Some(limit.saturating_sub(steps));
+            match engine.continue_(&stops) {
+                StopReason::Completed => break output_verdict(&engine, &case.output),
+                // breakpoints are for debugging, not grading
+                StopReason::Breakpoint(_) | StopReason::Watchpoint(_) => {}
+                StopReason::FuelExhausted => break Verdict::OutOfSteps(limit),
+                StopReason::TimedOut => break Verdict::TimedOut(self.timeout.unwrap_or_default()),
+                StopReason::InfiniteLoopDetected => break Verdict::InfiniteLoop,
+                StopReason::InputRequested => break Verdict::NeedsInput,
+                StopReason::Error(error) => break Verdict::Error(error),
+            }
+        };
+        Outcome {
+            name: case.name.clone(),
+            verdict,
+            steps: engine.history.len(),
+            output: engine.output,
+        }
+    }
+}
+
+/// Whether a finished run wrote what it should have, and if not, where it
+/// went wrong
+fn output_verdict(engine: &Engine, expected: &[u8]) -> Verdict {
+    let offset = (engine.output.iter())
+        .zip(expected)
+        .take_while(|(actual, expected)| actual == expected)
+        .count();
+    if offset == engine.output.len() && offset == expected.len() {
+        return Verdict::Passed;
+    }
+    let source = engine.output_source(offset);
+    Verdict::WrongOutput(Mismatch {
+        offset,
+        expected: expected.get(offset).copied(),
+        actual: engine.output.get(offset).copied(),
+        step: source.map_or(engine.history.len(), |source| source.step),
+        index: source.and_then(|source| source.index),
+    })
+}
+
+/// A report on each program graded, as a JSON array of objects like
+/// `{"program": "echo.bf", "passed": 1, "failed": 0, "cases": [...]}`
+pub fn json_report(programs: &[(String, Vec<Outcome>)]) -> String {
+    let mut json = String::from("[");
+    for (i, (program, outcomes)) in programs.iter().enumerate() {
+        let passed = outcomes.iter().filter(|outcome| outcome.passed()).count();
+        let _ = write!(
+            json,
+            "{}\n{{\"program\":{},\"passed\":{passed},\"failed\":{},\"cases\":[",
+            if i == 0 { "" } else { "," },
+            json_string(program.as_bytes()),
+            outcomes.len() - passed
+        );
+        for (j, outcome) in outcomes.iter().enumerate() {
+            let _ = write!(
+                json,
+                "{}\n{{\"name\":{},\"passed\":{},\"verdict\":{},",
+                if j == 0 { "" } else { "," },
+                json_string(outcome.name.as_bytes()),
+                outcome.passed(),
+                json_string(outcome.verdict.kind().as_bytes()),
+            );
+            let _ = write!(
+                json,
+                "\"message\":{},\"steps\":{},\"output\":{}}}",
+                json_string(format!("{}", outcome.verdict).as_bytes()),
+                outcome.steps,
+                json_string(&outcome.output)
+            );
+        }
+        json.push_str("]}");
+    }
+    json.push_str("\n]\n");
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+    use alloc::vec;
+
+    const SUITE: &str = "\
+        steps = 5000\n\
+        eof = \"zero\"\n\
+        \n\
+        [[case]]\n\
+        name = \"echo\"\n\
+        input = \"hi\"\n\
+        output = \"hi\"\n\
+        \n\
+        [[case]]  # no name\n\
+        input = \"ok\\n\"\n\
+        output = \"no\"\n\
+        steps = 20\n";
+
+    fn engine(code: &str) -> Engine {
+        Engine::builder().code(code).eof(Eof::Zero).build().unwrap()
+    }
+
+    #[test]
+    fn suites_read_their_cases() {
+        let suite = SUITE.parse::<Suite>().unwrap();
+        assert_eq!(suite.steps, Some(5000));
+        assert_eq!(suite.eof, Some(Eof::Zero));
+        assert_eq!(suite.cases.len(), 2);
+        assert_eq!(suite.cases[1].name, "case 2");
+        assert_eq!(suite.cases[1].input, b"ok\n");
+        assert_eq!(suite.cases[1].steps, Some(20));
+
+        let error = |suite: &str| suite.parse::<Suite>().unwrap_err();
+        assert_eq!(error("name = \"x\""), "line 1: unknown key name");
+        assert_eq!(
+            error("[[case]]\nsteps = \"9\""),
+            "line 2: steps should be a number"
+        );
+    }
+
+    #[test]
+    fn cases_are_graded() {
+        let suite = SUITE.parse::<Suite>().unwrap();
+        let outcomes = suite.grade(&engine(",[.,]"));
+        assert!(outcomes[0].passed());
+        assert_eq!(
+            outcomes[1].verdict,
+            Verdict::WrongOutput(Mismatch {
+                offset: 0,
+                expected: Some(b'n'),
+                actual: Some(b'o'),
+                step: 2,
+                index: Some(2),
+            })
+        );
+
+        let verdicts = |code| {
+            (suite.grade(&engine(code)).into_iter())
+                .map(|outcome| outcome.verdict)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(verdicts("+[]")[0], Verdict::InfiniteLoop);
+        assert_eq!(verdicts("+[>+]")[1], Verdict::OutOfSteps(20));
+        let asking = Engine::new(overflow::instruction_set().parse(",,,,"));
+        assert_eq!(suite.grade(&asking)[0].verdict, Verdict::NeedsInput);
+        assert_eq!(verdicts("<")[0], Verdict::Error(EngineError::TapeUnderflow));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn slow_cases_time_out() {
+        let suite = "timeout = 10\n[[case]]\n".parse::<Suite>().unwrap();
+        assert_eq!(suite.timeout, Some(Duration::from_millis(10)));
+        let outcome = &suite.grade(&engine("+[>+]"))[0];
+        assert_eq!(
+            outcome.verdict,
+            Verdict::TimedOut(Duration::from_millis(10))
+        );
+        let report = json_report(&[(String::from("slow.bf"), vec![outcome.clone()])]);
+        assert!(report.contains("\"verdict\":\"timed-out\",\"message\":\"didn't finish in 10 ms\""));
+    }
+
+    #[test]
+    fn reports_are_json() {
+        let outcome = Outcome {
+            name: String::from("say \"hi\""),
+            verdict: Verdict::Passed,
+            steps: 4,
+            output: b"hi\n".to_vec(),
+        };
+        assert_eq!(
+            json_report(&[(String::from("hi.bf"), vec![outcome])]),
+            "[\n{\"program\":\"hi.bf\",\"passed\":1,\"failed\":0,\"cases\":[\n\
+             {\"name\":\"say \\\"hi\\\"\",\"passed\":true,\"verdict\":\"passed\",\
+             \"message\":\"passed\",\"steps\":4,\"output\":\"hi\\n\"}]}\n]\n"
+        );
+    }
+}
@@ -1,33 +1,475 @@
-use crate::engine::{Engine, EngineResult};
+use crate::engine::{Engine, EngineError, EngineResult};
+use crate::ir::Composite;
 
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+pub type InstructionFn = Arc<dyn Fn(&mut Engine) -> EngineResult + Send + Sync>;
+
+/// What an instruction is, as far as features that work with any dialect,
+/// such as loop tracking and analysis, need to know
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum InstructionKind {
+    #[default]
+    Plain,
+    /// Skips past its matching `CloseLoop` when the cell is zero
+    OpenLoop,
+    /// Goes back to its matching `OpenLoop` when the cell is nonzero
+    CloseLoop,
+    /// Reads input or writes output
+    Io,
+    /// Only there for debugging, such as a breakpoint or an assertion
+    Debug,
+    /// Built by the optimizer to do the work of several instructions in one
+    /// step, as its `composite` says
+    Composite,
+}
+
+impl InstructionKind {
+    /// The kind of the standard instruction with a symbol, which
+    /// `Instruction::new` assumes
+    pub fn of(symbol: char) -> InstructionKind {
+        match symbol {
+            '[' => InstructionKind::OpenLoop,
+            ']' => InstructionKind::CloseLoop,
+            ',' | '.' => InstructionKind::Io,
+            '$' | '#' => InstructionKind::Debug,
+            _ => InstructionKind::Plain,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Instruction {
     pub symbol: char,
-    pub exec: fn(&mut Engine) -> EngineResult,
-    pub unexec: fn(&mut Engine) -> EngineResult,
+    pub kind: InstructionKind,
+    pub exec: InstructionFn,
+    pub unexec: InstructionFn,
+    /// What the instruction does, if the optimizer built it, in which case
+    /// its symbol is only for show
+    pub composite: Option<Composite>,
 }
 
-impl Clone for Instruction {
-    fn clone(&self) -> Instruction {
+impl Instruction {
+    /// An instruction of the kind its symbol has in the standard dialects,
+    /// which `with_kind` changes
+    pub fn new<E, U>(symbol: char, exec: E, unexec: U) -> Instruction
+    where
+        E: Fn(&mut Engine) -> EngineResult + Send + Sync + 'static,
+        U: Fn(&mut Engine) -> EngineResult + Send + Sync + 'static,
+    {
         Instruction {
-            symbol: self.symbol,
-            exec: self.exec,
-            unexec: self.unexec,
+            symbol,
+            kind: InstructionKind::of(symbol),
+            exec: Arc::new(exec),
+            unexec: Arc::new(unexec),
+            composite: None,
         }
     }
-}
 
-impl Copy for Instruction {}
+    pub fn with_kind(mut self, kind: InstructionKind) -> Instruction {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_composite(mut self, composite: Composite) -> Instruction {
+        self.kind = InstructionKind::Composite;
+        self.composite = Some(composite);
+        self
+    }
+
+    /// The symbol, unless the optimizer built the instruction, when the
+    /// symbol doesn't say what it does
+    pub fn written_symbol(&self) -> Option<char> {
+        self.composite.is_none().then_some(self.symbol)
+    }
+
+    pub fn opens_loop(&self) -> bool {
+        self.kind == InstructionKind::OpenLoop
+    }
 
-impl std::cmp::PartialEq for Instruction {
+    pub fn closes_loop(&self) -> bool {
+        self.kind == InstructionKind::CloseLoop
+    }
+}
+
+impl core::cmp::PartialEq for Instruction {
     fn eq(&self, other: &Instruction) -> bool {
-        self.symbol == other.symbol
+        self.symbol == other.symbol && self.composite == other.composite
     }
 }
 
-impl std::cmp::Eq for Instruction {}
+impl core::cmp::Eq for Instruction {}
+
+impl core::fmt::Debug for Instruction {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        match &self.composite {
+            Some(composite) => write!(fmt, "{composite:?}"),
+            None => write!(fmt, "{}", self.symbol),
+        }
+    }
+}
+
+/// Instructions serialize as their symbol alone, so only the instruction set
+/// they came from can tell what they did. Deserializing one on its own gives
+/// an instruction that refuses to run until it's bound to a set, which
+/// `EngineSeed` does as it reads an engine. Instructions the optimizer built
+/// serialize as what they do instead, and come back ready to run.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Instruction {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.composite {
+            Some(composite) => composite.serialize(serializer),
+            None => serializer.serialize_char(self.symbol),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Instruction {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Instruction, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Serialized {
+            Symbol(char),
+            Composite(Composite),
+        }
+
+        let symbol = match Serialized::deserialize(deserializer)? {
+            Serialized::Symbol(symbol) => symbol,
+            Serialized::Composite(composite) => return Ok(composite.instruction()),
+        };
+        let unbound = move |_: &mut Engine| -> EngineResult {
+            Err(EngineError::UnboundInstruction { symbol }.into())
+        };
+        Ok(Instruction::new(symbol, unbound, unbound))
+    }
+}
+
+/// Reads an engine back with its instructions taken from an instruction set,
+/// so engines of any dialect or EOF policy come back as they were saved
+#[cfg(feature = "serde")]
+#[derive(Clone, Copy)]
+pub struct EngineSeed<'a>(pub &'a InstructionSet);
+
+#[cfg(feature = "serde")]
+impl<'de> serde::de::DeserializeSeed<'de> for EngineSeed<'_> {
+    type Value = Engine;
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<Engine, D::Error> {
+        use serde::de::Error;
+        use serde::Deserialize;
+
+        let mut engine = Engine::deserialize(deserializer)?;
+        engine.bind_instructions(self.0).map_err(D::Error::custom)?;
+        Ok(engine)
+    }
+}
+
+/// A registry of instructions keyed by their symbol, which library users can
+/// extend with their own symbols to prototype new dialects.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct InstructionSet {
+    instructions: BTreeMap<char, Instruction>,
+}
+
+impl InstructionSet {
+    pub fn new() -> InstructionSet {
+        InstructionSet::default()
+    }
+
+    /// Register an instruction built from a pair of closures, unless its
+    /// symbol is taken or is whitespace, as with `try_insert`
+    pub fn register<E, U>(
+        &mut self,
+        symbol: char,
+        exec: E,
+        unexec: U,
+    ) -> Result<&mut InstructionSet, EngineError>
+    where
+        E: Fn(&mut Engine) -> EngineResult + Send + Sync + 'static,
+        U: Fn(&mut Engine) -> EngineResult + Send + Sync + 'static,
+    {
+        self.try_insert(Instruction::new(symbol, exec, unexec))?;
+        Ok(self)
+    }
+
+    /// Add an instruction, replacing any already registered under the same
+    /// symbol
+    pub fn insert(&mut self, instruction: Instruction) -> Option<Instruction> {
+        self.instructions.insert(instruction.symbol, instruction)
+    }
+
+    /// Add an instruction, unless its symbol is taken or is whitespace,
+    /// which programs use for layout
+    pub fn try_insert(&mut self, instruction: Instruction) -> Result<(), EngineError> {
+        let symbol = instruction.symbol;
+        if symbol.is_whitespace() {
+            return Err(EngineError::WhitespaceInstruction { symbol });
+        }
+        if self.contains(symbol) {
+            return Err(EngineError::DuplicateInstruction { symbol });
+        }
+        self.insert(instruction);
+        Ok(())
+    }
+
+    /// Check the set hangs together, with no whitespace symbols and loops
+    /// that can both open and close. Every instruction has an undo, as
+    /// `Instruction::new` needs one, and a symbol of its own, as the set is
+    /// keyed by them.
+    pub fn validate(&self) -> Result<(), EngineError> {
+        if let Some(&symbol) = self
+            .instructions
+            .keys()
+            .find(|symbol| symbol.is_whitespace())
+        {
+            return Err(EngineError::WhitespaceInstruction { symbol });
+        }
+        let open = self.iter().find(|instruction| instruction.opens_loop());
+        let close = self.iter().find(|instruction| instruction.closes_loop());
+        match (open, close) {
+            (Some(open), None) => Err(EngineError::UnpairedLoop {
+                symbol: open.symbol,
+                opens: true,
+            }),
+            (None, Some(close)) => Err(EngineError::UnpairedLoop {
+                symbol: close.symbol,
+                opens: false,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn remove(&mut self, symbol: char) -> Option<Instruction> {
+        self.instructions.remove(&symbol)
+    }
+
+    pub fn get(&self, symbol: char) -> Option<&Instruction> {
+        self.instructions.get(&symbol)
+    }
+
+    pub fn contains(&self, symbol: char) -> bool {
+        self.instructions.contains_key(&symbol)
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Instruction> {
+        self.instructions.values()
+    }
+
+    /// The instructions for each recognised symbol in some source code, with
+    /// every other character treated as a comment.
+    pub fn parse(&self, code: &str) -> Vec<Instruction> {
+        code.chars()
+            .filter_map(|symbol| self.get(symbol).cloned())
+            .collect()
+    }
+
+    /// Like `parse`, but checking the set with `validate` first, and that
+    /// every bracket in the code is matched
+    pub fn try_parse(&self, code: &str) -> Result<Vec<Instruction>, EngineError> {
+        self.validate()?;
+        let instructions = self.parse(code);
+        match crate::analysis::unmatched_brackets(&instructions).first() {
+            Some(&index) => Err(EngineError::UnmatchedBracket { index }),
+            None => Ok(instructions),
+        }
+    }
+}
+
+impl FromIterator<Instruction> for InstructionSet {
+    fn from_iter<I: IntoIterator<Item = Instruction>>(instructions: I) -> InstructionSet {
+        let mut instruction_set = InstructionSet::new();
+        instruction_set.extend(instructions);
+        instruction_set
+    }
+}
+
+impl Extend<Instruction> for InstructionSet {
+    fn extend<I: IntoIterator<Item = Instruction>>(&mut self, instructions: I) {
+        for instruction in instructions {
+            self.insert(instruction);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::InstructionPointer;
+
+    #[test]
+    fn registered_closures_capture_configuration() {
+        let step_size = 5;
+        let mut instruction_set = InstructionSet::new();
+        instruction_set
+            .register(
+                '*',
+                move |engine| {
+                    engine.map_cell(|cell| cell.wrapping_add(step_size));
+                    engine.next_instruction()
+                },
+                move |engine| {
+                    engine.map_cell(|cell| cell.wrapping_sub(step_size));
+                    engine.prev_instruction()
+                },
+            )
+            .unwrap();
+
+        let mut engine = Engine::new(instruction_set.parse("a * b *"));
+        engine.instruction_pointer = InstructionPointer::Index(0);
+        engine.step().unwrap();
+        engine.step().unwrap();
+        assert_eq!(engine.cell(), 10);
+
+        engine.undo().unwrap();
+        assert_eq!(engine.cell(), 5);
+    }
+
+    #[test]
+    fn register_refuses_a_taken_symbol() {
+        let mut instruction_set = InstructionSet::new();
+        let registered = instruction_set
+            .register('!', |_| Ok(()), |_| Ok(()))
+            .unwrap()
+            .register(
+                '!',
+                |engine| engine.next_cell(),
+                |engine| engine.prev_cell(),
+            );
+
+        assert_eq!(
+            registered.map(|_| ()),
+            Err(EngineError::DuplicateInstruction { symbol: '!' })
+        );
+        assert_eq!(instruction_set.len(), 1);
+        assert!(instruction_set.contains('!'));
+    }
+
+    #[test]
+    fn kinds_follow_the_standard_symbols() {
+        let instruction_set = crate::flavor::overflow::instruction_set();
+        let kind = |symbol| instruction_set.get(symbol).unwrap().kind;
+        assert_eq!(kind('['), InstructionKind::OpenLoop);
+        assert_eq!(kind(']'), InstructionKind::CloseLoop);
+        assert_eq!(kind(','), InstructionKind::Io);
+        assert_eq!(kind('$'), InstructionKind::Debug);
+        assert_eq!(kind('+'), InstructionKind::Plain);
+
+        let open =
+            Instruction::new('(', |_| Ok(()), |_| Ok(())).with_kind(InstructionKind::OpenLoop);
+        let mut instruction_set = InstructionSet::new();
+        instruction_set.insert(open);
+        assert_eq!(
+            instruction_set.validate(),
+            Err(EngineError::UnpairedLoop {
+                symbol: '(',
+                opens: true
+            })
+        );
+    }
+
+    #[test]
+    fn renamed_loops_work_everywhere() {
+        let mut instruction_set = crate::flavor::overflow::instruction_set();
+        for (from, to) in [('[', '('), (']', ')')] {
+            let instruction = instruction_set.remove(from).unwrap();
+            instruction_set.insert(Instruction {
+                symbol: to,
+                ..instruction
+            });
+        }
+        let instructions = instruction_set.parse("++(->+<)>(-)(<)");
+
+        let mut engine = Engine::new(instructions.clone());
+        while engine.step().is_ok() {}
+        assert_eq!(engine.tape, vec![0, 0]);
+        assert_eq!(engine.instruction_pointer, InstructionPointer::End);
+
+        let optimized = crate::ir::compile(&instructions).instructions;
+        assert_eq!(optimized.len(), 5);
+        let warnings = crate::analysis::sanity_warnings(&instruction_set.parse("+()(-)"), None);
+        let kinds = warnings
+            .iter()
+            .map(|warning| warning.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                crate::analysis::WarningKind::EmptyLoop,
+                crate::analysis::WarningKind::DeadLoop
+            ]
+        );
+        assert!(crate::analysis::unmatched_brackets(&instructions).is_empty());
+    }
+
+    #[test]
+    fn inconsistent_sets_are_refused() {
+        let mut instruction_set = crate::flavor::overflow::instruction_set();
+        assert_eq!(instruction_set.validate(), Ok(()));
+        assert_eq!(
+            instruction_set.try_insert(Instruction::new('+', |_| Ok(()), |_| Ok(()))),
+            Err(EngineError::DuplicateInstruction { symbol: '+' })
+        );
+        assert!(instruction_set
+            .try_insert(Instruction::new(' ', |_| Ok(()), |_| Ok(())))
+            .is_err());
+
+        instruction_set.remove(']');
+        assert_eq!(
+            instruction_set.validate().map_err(|error| error.message()),
+            Err(String::from("[ opens loops, but nothing closes them"))
+        );
+        assert_eq!(
+            instruction_set.try_parse("+[-"),
+            Err(EngineError::UnpairedLoop {
+                symbol: '[',
+                opens: true
+            })
+        );
+        instruction_set.insert(Instruction::new('\t', |_| Ok(()), |_| Ok(())));
+        assert_eq!(
+            instruction_set.validate().map_err(|error| error.message()),
+            Err(String::from(
+                "'\\t' is whitespace, so can't be an instruction"
+            ))
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn engines_deserialize_with_their_own_instructions() {
+        use crate::flavor::{overflow, Eof};
+        use serde::de::DeserializeSeed;
+
+        let instruction_set = overflow::instruction_set_with(Eof::Max);
+        let engine = Engine::new(instruction_set.parse(","));
+        let json = serde_json::to_string(&engine).unwrap();
+
+        let mut resumed = EngineSeed(&instruction_set)
+            .deserialize(&mut serde_json::Deserializer::from_str(&json))
+            .unwrap();
+        resumed.instruction_pointer = InstructionPointer::Index(0);
+        resumed.step().unwrap();
+        assert_eq!(resumed.cell(), 255);
 
-impl std::fmt::Debug for Instruction {
-    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        write!(fmt, "{}", self.symbol)
+        let mut unbound: Engine = serde_json::from_str(&json).unwrap();
+        unbound.instruction_pointer = InstructionPointer::Index(0);
+        assert_eq!(
+            unbound.step(),
+            Err(EngineError::UnboundInstruction { symbol: ',' }.into())
+        );
+        assert!(EngineSeed(&InstructionSet::new())
+            .deserialize(&mut serde_json::Deserializer::from_str(&json))
+            .is_err());
     }
 }
@@ -0,0 +1,484 @@
+use crate::engine::EngineError;
+use crate::instruction::Instruction;
+use crate::optimize::{self, Optimized};
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Op {
+    /// Add to the current cell. Amounts here and in `Transfer` are exact,
+    /// for backends to wrap at their own cell width.
+    Add(isize),
+    Move(isize),
+    /// A `[-]` loop, which sets the current cell to zero
+    Clear,
+    /// A loop like `[->+<]`, which adds the current cell times each factor to
+    /// the cell at each offset, then clears the current cell. `low` and
+    /// `high` are the furthest offsets the loop body reaches.
+    Transfer {
+        targets: Vec<(isize, isize)>,
+        low: isize,
+        high: isize,
+    },
+    /// A loop like `[>]`, which moves by `stride` until it finds a zero cell
+    Scan(isize),
+    Instruction(Instruction),
+}
+
+/// What an instruction the optimizer built does, which is all there is to
+/// tell it apart from others, as its symbol is only for show
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Composite {
+    /// Add to the current cell, wrapping
+    Add(u8),
+    Move(isize),
+    Clear,
+    Transfer {
+        targets: Vec<(isize, isize)>,
+        low: isize,
+        high: isize,
+    },
+    Scan(isize),
+}
+
+impl Composite {
+    /// The instruction that does it
+    pub fn instruction(&self) -> Instruction {
+        match self {
+            Composite::Add(amount) => optimize::add(*amount),
+            Composite::Move(offset) => optimize::move_pointer(*offset),
+            Composite::Clear => clear(),
+            Composite::Transfer { targets, low, high } => transfer(targets.clone(), *low, *high),
+            Composite::Scan(stride) => scan(*stride),
+        }
+    }
+}
+
+impl From<&Composite> for Op {
+    fn from(composite: &Composite) -> Op {
+        match composite {
+            Composite::Add(amount) => Op::Add(*amount as i8 as isize),
+            Composite::Move(offset) => Op::Move(*offset),
+            Composite::Clear => Op::Clear,
+            Composite::Transfer { targets, low, high } => Op::Transfer {
+                targets: targets.clone(),
+                low: *low,
+                high: *high,
+            },
+            Composite::Scan(stride) => Op::Scan(*stride),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Node {
+    pub op: Op,
+    /// The index of the first original instruction this node covers
+    pub origin: usize,
+    /// The index just past the last original instruction it covers
+    pub end: usize,
+}
+
+/// Recognize runs and common loop idioms in a program. Anything that isn't
+/// recognized is kept as the original instruction, and anything the
+/// optimizer already built becomes the op it stands for.
+pub fn build(instructions: &[Instruction]) -> Vec<Node> {
+    let mut nodes = vec![];
+
+    let mut index = 0;
+    while index < instructions.len() {
+        let origin = index;
+        let instruction = &instructions[index];
+        let op = match instruction.written_symbol() {
+            _ if instruction.opens_loop() => match recognize_loop(instructions, index) {
+                Some((op, close)) => {
+                    index = close + 1;
+                    Some(op)
+                }
+                None => {
+                    index += 1;
+                    Some(Op::Instruction(instruction.clone()))
+                }
+            },
+            None => {
+                index += 1;
+                instruction.composite.as_ref().map(Op::from)
+            }
+            Some('+' | '-') => {
+                let mut amount = 0isize;
+                while let Some(symbol) = instructions
+                    .get(index)
+                    .and_then(Instruction::written_symbol)
+                {
+                    match symbol {
+                        '+' => amount += 1,
+                        '-' => amount -= 1,
+                        _ => break,
+                    }
+                    index += 1;
+                }
+                (amount != 0).then_some(Op::Add(amount))
+            }
+            Some('>' | '<') => {
+                let mut offset = 0isize;
+                while let Some(symbol) = instructions
+                    .get(index)
+                    .and_then(Instruction::written_symbol)
+                {
+                    match symbol {
+                        '>' => offset += 1,
+                        '<' => offset -= 1,
+                        _ => break,
+                    }
+                    index += 1;
+                }
+                (offset != 0).then_some(Op::Move(offset))
+            }
+            Some(_) => {
+                index += 1;
+                Some(Op::Instruction(instruction.clone()))
+            }
+        };
+
+        if let Some(op) = op {
+            nodes.push(Node {
+                op,
+                origin,
+                end: index,
+            });
+        }
+    }
+
+    nodes
+}
+
+/// Recognize an innermost loop made up only of `+-<>`, returning what it
+/// does and the index of its closing bracket.
+fn recognize_loop(instructions: &[Instruction], open: usize) -> Option<(Op, usize)> {
+    let mut deltas = BTreeMap::new();
+    let (mut offset, mut low, mut high) = (0isize, 0isize, 0isize);
+    let mut moves = (false, false);
+
+    let mut index = open + 1;
+    loop {
+        let instruction = instructions.get(index)?;
+        match instruction.written_symbol()? {
+            _ if instruction.closes_loop() => break,
+            '+' => add_delta(&mut deltas, offset, 1),
+            '-' => add_delta(&mut deltas, offset, -1),
+            '>' => {
+                offset += 1;
+                high = core::cmp::max(high, offset);
+                moves.0 = true;
+            }
+            '<' => {
+                offset -= 1;
+                low = core::cmp::min(low, offset);
+                moves.1 = true;
+            }
+            _ => return None,
+        }
+        index += 1;
+    }
+
+    let step = deltas.remove(&0).unwrap_or(0);
+    deltas.retain(|_, delta| *delta != 0);
+
+    let op = match (offset, step) {
+        // the loop runs `cell` times when counting down, or `256 - cell`
+        // times when counting up, which is the same as negating each factor
+        (0, 1 | -1) if deltas.is_empty() => Op::Clear,
+        (0, 1 | -1) => Op::Transfer {
+            targets: deltas
+                .into_iter()
+                .map(|(offset, delta)| match step {
+                    1 => (offset, -delta),
+                    _ => (offset, delta),
+                })
+                .collect(),
+            low,
+            high,
+        },
+        // only loops moving in one direction, or it could step off the tape
+        // partway through an iteration
+        (stride, 0) if stride != 0 && deltas.is_empty() && moves != (true, true) => {
+            Op::Scan(stride)
+        }
+        _ => return None,
+    };
+
+    Some((op, index))
+}
+
+fn add_delta(deltas: &mut BTreeMap<isize, isize>, offset: isize, delta: isize) {
+    *deltas.entry(offset).or_insert(0) += delta;
+}
+
+/// Set the current cell to zero in one step
+pub fn clear() -> Instruction {
+    Instruction::new(
+        '[',
+        |program| {
+            program.cleared_cell_history.push(program.try_cell()?);
+            program.set_cell(0);
+            program.next_instruction()
+        },
+        |program| match program.cleared_cell_history.pop() {
+            None => Err(EngineError::UndoStateMissing("cleared cell").into()),
+            Some(cell) => {
+                program.try_set_cell(cell)?;
+                program.prev_instruction()
+            }
+        },
+    )
+    .with_composite(Composite::Clear)
+}
+
+/// Run a whole `[->+<]`-style loop in one step
+pub fn transfer(targets: Vec<(isize, isize)>, low: isize, high: isize) -> Instruction {
+    let composite = Composite::Transfer {
+        targets: targets.clone(),
+        low,
+        high,
+    };
+    let undo_targets = targets.clone();
+    Instruction::new(
+        '[',
+        move |program| {
+            let cell = program.try_cell()?;
+            if cell != 0 {
+                let pointer = program.tape_pointer;
+                if pointer.checked_add_signed(low).is_none() {
+                    return Err(EngineError::TapeUnderflow.into());
+                }
+                program.reach(pointer.wrapping_add_signed(high))?;
+                for &(offset, factor) in &targets {
+                    let target = pointer.wrapping_add_signed(offset);
+                    let value = program.tape[target].wrapping_add(cell.wrapping_mul(factor as u8));
+                    program.tape[target] = value;
+                    program.write_mapped(target, value);
+                }
+                program.set_cell(0);
+            }
+            program.cleared_cell_history.push(cell);
+            program.next_instruction()
+        },
+        move |program| match program.cleared_cell_history.pop() {
+            None => Err(EngineError::UndoStateMissing("cleared cell").into()),
+            Some(cell) => {
+                if cell != 0 {
+                    let pointer = program.tape_pointer;
+                    for &(offset, factor) in &undo_targets {
+                        let target = program
+                            .tape
+                            .get_mut(pointer.wrapping_add_signed(offset))
+                            .ok_or(EngineError::OffTape)?;
+                        *target = target.wrapping_sub(cell.wrapping_mul(factor as u8));
+                    }
+                }
+                program.try_set_cell(cell)?;
+                program.prev_instruction()
+            }
+        },
+    )
+    .with_composite(composite)
+}
+
+/// Run a whole `[>]`-style loop in one step
+pub fn scan(stride: isize) -> Instruction {
+    Instruction::new(
+        '[',
+        move |program| {
+            let start = program.tape_pointer;
+            while program.try_cell()? != 0 {
+                if let Err(e) = program.move_pointer(stride) {
+                    program.tape_pointer = start;
+                    return Err(e);
+                }
+            }
+            program.scan_history.push(start);
+            program.next_instruction()
+        },
+        |program| match program.scan_history.pop() {
+            None => Err(EngineError::UndoStateMissing("scan").into()),
+            Some(start) => {
+                program.tape_pointer = start;
+                program.prev_instruction()
+            }
+        },
+    )
+    .with_composite(Composite::Scan(stride))
+}
+
+/// Turn each node into a single instruction, for running a program quickly.
+/// The original instructions are still the ones to step through when
+/// debugging, and `origins` and `ends` map between the two.
+pub fn lower(nodes: &[Node]) -> Optimized {
+    let instructions = nodes
+        .iter()
+        .map(|node| match &node.op {
+            Op::Add(amount) => optimize::add(*amount as u8),
+            Op::Move(offset) => optimize::move_pointer(*offset),
+            Op::Clear => clear(),
+            Op::Transfer { targets, low, high } => transfer(targets.clone(), *low, *high),
+            Op::Scan(stride) => scan(*stride),
+            Op::Instruction(instruction) => instruction.clone(),
+        })
+        .collect();
+    let origins = nodes.iter().map(|node| node.origin).collect();
+    let ends = nodes.iter().map(|node| node.end).collect();
+
+    Optimized {
+        instructions,
+        origins,
+        ends,
+    }
+}
+
+pub fn compile(instructions: &[Instruction]) -> Optimized {
+    lower(&build(instructions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::flavor::overflow;
+
+    const HELLO: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+    fn run(instructions: Vec<Instruction>) -> Engine {
+        let mut engine = Engine::new(instructions);
+        while engine.step().is_ok() {}
+        engine
+    }
+
+    fn symbols(optimized: &Optimized) -> String {
+        optimized.instructions.iter().map(|i| i.symbol).collect()
+    }
+
+    fn composites(optimized: &Optimized) -> Vec<Option<Composite>> {
+        (optimized.instructions.iter())
+            .map(|i| i.composite.clone())
+            .collect()
+    }
+
+    #[test]
+    fn recognizes_idioms() {
+        let instructions = overflow::instruction_set().parse("[-]>[->+>++<<]<[<]");
+        let optimized = compile(&instructions);
+
+        let transfer = Composite::Transfer {
+            targets: vec![(1, 1), (2, 2)],
+            low: 0,
+            high: 2,
+        };
+        assert_eq!(
+            composites(&optimized),
+            [
+                Composite::Clear,
+                Composite::Move(1),
+                transfer,
+                Composite::Move(-1),
+                Composite::Scan(-1)
+            ]
+            .map(Some)
+        );
+        assert_eq!(optimized.origins, vec![0, 3, 4, 14, 15]);
+
+        let nodes = build(&instructions);
+        assert_eq!(
+            nodes[2].op,
+            Op::Transfer {
+                targets: vec![(1, 1), (2, 2)],
+                low: 0,
+                high: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn leaves_other_loops_alone() {
+        let instructions = overflow::instruction_set().parse(",[.,]+[>-]+[<>]");
+        assert_eq!(symbols(&compile(&instructions)), ",[.,]+[>-]+[]");
+    }
+
+    #[test]
+    fn behaves_like_the_original() {
+        let instructions = overflow::instruction_set().parse(HELLO);
+        let original = run(instructions.clone());
+        let compiled = run(compile(&instructions).instructions);
+
+        assert_eq!(compiled.output, b"Hello World!\n".to_vec());
+        assert_eq!(compiled.output, original.output);
+        assert_eq!(compiled.tape, original.tape);
+        assert!(compiled.history.len() < original.history.len());
+    }
+
+    #[test]
+    fn undo_restores_the_start() {
+        let instructions = overflow::instruction_set().parse(HELLO);
+        let mut engine = run(compile(&instructions).instructions);
+
+        while engine.undo().is_ok() {}
+
+        assert!(engine.tape.iter().all(|&cell| cell == 0));
+        assert!(engine.output.is_empty());
+    }
+
+    #[test]
+    fn built_instructions_keep_their_meaning() {
+        let mut instruction_set = overflow::instruction_set();
+        instruction_set.insert(Instruction::new('0', |_| Ok(()), |_| Ok(())));
+        let instructions = instruction_set.parse("0[-]+++[>]");
+        let optimized = compile(&instructions);
+        assert_ne!(optimized.instructions[0], optimized.instructions[1]);
+        assert_ne!(optimized.instructions[2], optimize::add(1));
+
+        // optimizing again changes nothing
+        assert_eq!(
+            compile(&optimized.instructions).instructions,
+            optimized.instructions
+        );
+        assert_eq!(
+            build(&optimized.instructions)[2].op,
+            build(&instructions)[2].op
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn built_instructions_serialize_with_their_parameters() {
+        let instructions = overflow::instruction_set().parse("+++[->++<]");
+        let engine = Engine::new(compile(&instructions).instructions);
+        let json = serde_json::to_string(&engine).unwrap();
+
+        let mut resumed: Engine = serde_json::from_str(&json).unwrap();
+        assert_eq!(resumed.instructions, engine.instructions);
+        while resumed.step().is_ok() {}
+        assert_eq!(resumed.tape, vec![0, 6]);
+    }
+
+    #[test]
+    fn counting_up_negates_factors() {
+        let instructions = overflow::instruction_set().parse("++[+>+<]");
+        let engine = run(compile(&instructions).instructions);
+
+        assert_eq!(engine.tape, vec![0, 254]);
+    }
+
+    #[test]
+    fn transfer_off_the_tape_fails_without_changes() {
+        let instructions = overflow::instruction_set().parse("+[-<+>]");
+        let mut engine = Engine::new(compile(&instructions).instructions);
+        engine.next_instruction().unwrap();
+        engine.step().unwrap();
+
+        assert!(engine.step().is_err());
+        assert_eq!(engine.tape, vec![1]);
+        assert!(engine.cleared_cell_history.is_empty());
+    }
+}
@@ -0,0 +1,656 @@
+use crate::engine::{Engine, EngineError, EngineResult, Exception, InstructionPointer, TapeModel};
+use crate::flavor::Eof;
+use crate::instruction::Instruction;
+use crate::ir::{self, Node, Op};
+
+use cranelift_codegen::entity::EntityRef;
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{types, AbiParam, Block, FuncRef, InstBuilder, MemFlags, Type, Value};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Linkage, Module};
+use std::ffi::c_void;
+use std::fmt;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum JitError {
+    /// A symbol the JIT doesn't know how to compile, such as one registered
+    /// by a library user
+    Unsupported(char),
+    UnmatchedBracket(usize),
+    Codegen(String),
+}
+
+impl fmt::Display for JitError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JitError::Unsupported(symbol) => write!(fmt, "can't compile instruction {symbol}"),
+            JitError::UnmatchedBracket(index) => {
+                write!(fmt, "unmatched bracket at instruction {index}")
+            }
+            JitError::Codegen(message) => write!(fmt, "code generation failed: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for JitError {}
+
+fn codegen(error: impl fmt::Display) -> JitError {
+    JitError::Codegen(error.to_string())
+}
+
+/// What the compiled code can reach through its callbacks
+struct Runtime<'a> {
+    engine: &'a mut Engine,
+    eof: Eof,
+    /// Where and why the compiled code stopped early, if it did
+    stop: Option<(usize, Exception)>,
+}
+
+impl Runtime<'_> {
+    /// # Safety
+    ///
+    /// `runtime` must be the pointer passed into the compiled code by
+    /// `Compiled::run`.
+    unsafe fn from_raw<'a>(runtime: *mut c_void) -> &'a mut Runtime<'a> {
+        &mut *(runtime as *mut Runtime)
+    }
+}
+
+extern "C" fn output(runtime: *mut c_void, value: u32) {
+    let runtime = unsafe { Runtime::from_raw(runtime) };
+    runtime.engine.output.push(value as u8);
+}
+
+/// The byte to store in the current cell, or -1 to stop and wait for input
+extern "C" fn input(runtime: *mut c_void, cell: u32, origin: usize) -> i32 {
+    let runtime = unsafe { Runtime::from_raw(runtime) };
+    let value = match (runtime.engine.pop_input(), runtime.eof) {
+        (Some(input), _) => input,
+        (None, Eof::RequestInput) => {
+            runtime.stop = Some((origin, Exception::RequestingInput));
+            return -1;
+        }
+        (None, Eof::Zero) => 0,
+        (None, Eof::Unchanged) => cell as u8,
+        (None, Eof::Max) => u8::MAX,
+    };
+    value as i32
+}
+
+/// Grow the tape to include the cell at `index`, returning its new address,
+/// or null to stop if the tape is a fixed size that doesn't reach that far
+extern "C" fn grow(runtime: *mut c_void, index: usize, origin: usize) -> *mut u8 {
+    let runtime = unsafe { Runtime::from_raw(runtime) };
+    if let TapeModel::Fixed(len) = runtime.engine.tape_model {
+        if index >= len {
+            runtime.stop = Some((origin, EngineError::TapeOverflow.into()));
+            return std::ptr::null_mut();
+        }
+    }
+    let cells = runtime.engine.tape.make_dense();
+    cells.resize(index + 1, 0);
+    cells.as_mut_ptr()
+}
+
+extern "C" fn underflow(runtime: *mut c_void, origin: usize) {
+    let runtime = unsafe { Runtime::from_raw(runtime) };
+    runtime.stop = Some((origin, EngineError::TapeUnderflow.into()));
+}
+
+type Entry = unsafe extern "C" fn(*mut c_void, *mut u8, usize, *mut usize);
+
+/// A program compiled to native code, which can be run any number of times
+pub struct Compiled {
+    module: Option<JITModule>,
+    entry: Entry,
+}
+
+impl Compiled {
+    /// Run the whole program from the start at native speed. There's no
+    /// history to undo afterwards, but if the program stops early to wait
+    /// for input or on an error, the engine is left pointing at the
+    /// instruction it stopped on so that it can carry on being stepped.
+    pub fn run(&self, engine: &mut Engine, eof: Eof) -> EngineResult {
+        if !matches!(
+            engine.instruction_pointer,
+            InstructionPointer::Start | InstructionPointer::Index(0)
+        ) {
+            return Exception::error("compiled programs can only run from the start").result();
+        }
+
+        if engine.tape_model == TapeModel::Sparse {
+            return Exception::error("compiled programs can't run on sparse tapes").result();
+        }
+        let cells = engine.tape.make_dense();
+        let tape = cells.as_mut_ptr();
+        let length = cells.len();
+        let mut pointer = engine.tape_pointer;
+        let mut runtime = Runtime {
+            engine,
+            eof,
+            stop: None,
+        };
+
+        unsafe {
+            (self.entry)(
+                &mut runtime as *mut Runtime as *mut c_void,
+                tape,
+                length,
+                &mut pointer,
+            );
+        }
+
+        let Runtime { engine, stop, .. } = runtime;
+        engine.tape_pointer = pointer;
+        match stop {
+            None => {
+                engine.instruction_pointer = InstructionPointer::End;
+                Ok(())
+            }
+            Some((origin, exception)) => {
+                engine.instruction_pointer = InstructionPointer::Index(origin);
+                Err(exception)
+            }
+        }
+    }
+}
+
+impl Drop for Compiled {
+    fn drop(&mut self) {
+        if let Some(module) = self.module.take() {
+            // nothing can call into the code once its entry point is gone
+            unsafe { module.free_memory() }
+        }
+    }
+}
+
+impl fmt::Debug for Compiled {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Compiled").finish_non_exhaustive()
+    }
+}
+
+/// Compile a program using the overflow flavor's symbols to native code
+pub fn compile(instructions: &[Instruction]) -> Result<Compiled, JitError> {
+    let nodes = ir::build(instructions);
+
+    let mut flags = settings::builder();
+    flags.set("opt_level", "speed").map_err(codegen)?;
+    let isa = cranelift_native::builder()
+        .map_err(codegen)?
+        .finish(settings::Flags::new(flags))
+        .map_err(codegen)?;
+
+    let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+    builder.symbol("plaque_output", output as *const u8);
+    builder.symbol("plaque_input", input as *const u8);
+    builder.symbol("plaque_grow", grow as *const u8);
+    builder.symbol("plaque_underflow", underflow as *const u8);
+    let mut module = JITModule::new(builder);
+
+    let id = translate(&mut module, &nodes)?;
+    module.finalize_definitions().map_err(codegen)?;
+    let entry =
+        unsafe { std::mem::transmute::<*const u8, Entry>(module.get_finalized_function(id)) };
+
+    Ok(Compiled {
+        module: Some(module),
+        entry,
+    })
+}
+
+/// Run a program from the start, compiling it first
+pub fn run(engine: &mut Engine, eof: Eof) -> EngineResult {
+    compile(&engine.instructions)
+        .map_err(|e| Exception::error(e.to_string()))?
+        .run(engine, eof)
+}
+
+fn import(
+    module: &mut JITModule,
+    name: &str,
+    params: &[Type],
+    returns: &[Type],
+) -> Result<FuncId, JitError> {
+    let mut signature = module.make_signature();
+    signature
+        .params
+        .extend(params.iter().map(|&param| AbiParam::new(param)));
+    signature
+        .returns
+        .extend(returns.iter().map(|&ret| AbiParam::new(ret)));
+    module
+        .declare_function(name, Linkage::Import, &signature)
+        .map_err(codegen)
+}
+
+fn translate(module: &mut JITModule, nodes: &[Node]) -> Result<FuncId, JitError> {
+    let pointer_type = module.target_config().pointer_type();
+    let output = import(module, "plaque_output", &[pointer_type, types::I32], &[])?;
+    let input = import(
+        module,
+        "plaque_input",
+        &[pointer_type, types::I32, pointer_type],
+        &[types::I32],
+    )?;
+    let grow = import(
+        module,
+        "plaque_grow",
+        &[pointer_type, pointer_type, pointer_type],
+        &[pointer_type],
+    )?;
+    let underflow = import(
+        module,
+        "plaque_underflow",
+        &[pointer_type, pointer_type],
+        &[],
+    )?;
+
+    let mut context = module.make_context();
+    // runtime, tape, tape length and a pointer to the tape pointer
+    context
+        .func
+        .signature
+        .params
+        .extend([AbiParam::new(pointer_type); 4]);
+
+    let mut builder_context = FunctionBuilderContext::new();
+    let mut builder = FunctionBuilder::new(&mut context.func, &mut builder_context);
+    let imports = Imports {
+        output: module.declare_func_in_func(output, builder.func),
+        input: module.declare_func_in_func(input, builder.func),
+        grow: module.declare_func_in_func(grow, builder.func),
+        underflow: module.declare_func_in_func(underflow, builder.func),
+    };
+
+    let mut translator = Translator::new(&mut builder, pointer_type, imports);
+    for node in nodes {
+        translator.node(node)?;
+    }
+    translator.finish()?;
+    builder.finalize();
+
+    let id = module
+        .declare_anonymous_function(&context.func.signature)
+        .map_err(codegen)?;
+    module.define_function(id, &mut context).map_err(codegen)?;
+    module.clear_context(&mut context);
+
+    Ok(id)
+}
+
+struct Imports {
+    output: FuncRef,
+    input: FuncRef,
+    grow: FuncRef,
+    underflow: FuncRef,
+}
+
+struct Translator<'a, 'b> {
+    builder: &'a mut FunctionBuilder<'b>,
+    pointer_type: Type,
+    imports: Imports,
+    runtime: Value,
+    tape_pointer_address: Value,
+    tape: Variable,
+    length: Variable,
+    pointer: Variable,
+    /// Saves the tape pointer and returns, whether finished or stopped early
+    exit: Block,
+    /// The header and exit blocks of each open loop, and where it started
+    loops: Vec<(Block, Block, usize)>,
+}
+
+impl<'a, 'b> Translator<'a, 'b> {
+    fn new(
+        builder: &'a mut FunctionBuilder<'b>,
+        pointer_type: Type,
+        imports: Imports,
+    ) -> Translator<'a, 'b> {
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        builder.seal_block(entry);
+
+        let params = builder.block_params(entry).to_vec();
+        let (tape, length, pointer) = (Variable::new(0), Variable::new(1), Variable::new(2));
+        for variable in [tape, length, pointer] {
+            builder.declare_var(variable, pointer_type);
+        }
+        builder.def_var(tape, params[1]);
+        builder.def_var(length, params[2]);
+        let initial_pointer = builder
+            .ins()
+            .load(pointer_type, MemFlags::trusted(), params[3], 0);
+        builder.def_var(pointer, initial_pointer);
+
+        let exit = builder.create_block();
+
+        Translator {
+            builder,
+            pointer_type,
+            imports,
+            runtime: params[0],
+            tape_pointer_address: params[3],
+            tape,
+            length,
+            pointer,
+            exit,
+            loops: vec![],
+        }
+    }
+
+    fn node(&mut self, node: &Node) -> Result<(), JitError> {
+        match &node.op {
+            Op::Add(amount) => {
+                let cell = self.load_cell(0);
+                let cell = self
+                    .builder
+                    .ins()
+                    .iadd_imm(cell, *amount as u8 as i8 as i64);
+                self.store_cell(0, cell);
+            }
+            Op::Move(offset) => self.move_pointer(*offset, node.origin),
+            Op::Clear => {
+                let zero = self.builder.ins().iconst(types::I8, 0);
+                self.store_cell(0, zero);
+            }
+            Op::Transfer { targets, low, high } => {
+                let body = self.builder.create_block();
+                let after = self.builder.create_block();
+                let cell = self.load_cell(0);
+                self.builder.ins().brif(cell, body, &[], after, &[]);
+                self.builder.switch_to_block(body);
+                self.builder.seal_block(body);
+
+                self.reach(*low, *high, node.origin);
+                for &(offset, factor) in targets {
+                    let target = self.load_cell(offset);
+                    let product = self.builder.ins().imul_imm(cell, factor as u8 as i8 as i64);
+                    let target = self.builder.ins().iadd(target, product);
+                    self.store_cell(offset, target);
+                }
+                let zero = self.builder.ins().iconst(types::I8, 0);
+                self.store_cell(0, zero);
+
+                self.builder.ins().jump(after, &[]);
+                self.builder.switch_to_block(after);
+                self.builder.seal_block(after);
+            }
+            Op::Scan(stride) => {
+                let (header, body, after) = self.open_loop();
+                self.builder.switch_to_block(body);
+                self.builder.seal_block(body);
+                self.move_pointer(*stride, node.origin);
+                self.close_loop(header, after);
+            }
+            Op::Instruction(instruction) => match instruction.symbol {
+                _ if instruction.opens_loop() => {
+                    let (header, body, after) = self.open_loop();
+                    self.builder.switch_to_block(body);
+                    self.builder.seal_block(body);
+                    self.loops.push((header, after, node.origin));
+                }
+                _ if instruction.closes_loop() => {
+                    let (header, after, _) = self
+                        .loops
+                        .pop()
+                        .ok_or(JitError::UnmatchedBracket(node.origin))?;
+                    self.close_loop(header, after);
+                }
+                '.' => {
+                    let cell = self.load_cell(0);
+                    let cell = self.builder.ins().uextend(types::I32, cell);
+                    self.builder
+                        .ins()
+                        .call(self.imports.output, &[self.runtime, cell]);
+                }
+                ',' => {
+                    let cell = self.load_cell(0);
+                    let cell = self.builder.ins().uextend(types::I32, cell);
+                    let origin = self.origin(node.origin);
+                    let call = self
+                        .builder
+                        .ins()
+                        .call(self.imports.input, &[self.runtime, cell, origin]);
+                    let value = self.builder.inst_results(call)[0];
+
+                    let store = self.builder.create_block();
+                    let received =
+                        self.builder
+                            .ins()
+                            .icmp_imm(IntCC::SignedGreaterThanOrEqual, value, 0);
+                    self.builder
+                        .ins()
+                        .brif(received, store, &[], self.exit, &[]);
+                    self.builder.switch_to_block(store);
+                    self.builder.seal_block(store);
+
+                    let value = self.builder.ins().ireduce(types::I8, value);
+                    self.store_cell(0, value);
+                }
+                // there's nobody to hand control to in run mode
+                '$' => {}
+                symbol => return Err(JitError::Unsupported(symbol)),
+            },
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), JitError> {
+        if let Some(&(_, _, origin)) = self.loops.last() {
+            return Err(JitError::UnmatchedBracket(origin));
+        }
+
+        self.builder.ins().jump(self.exit, &[]);
+        self.builder.switch_to_block(self.exit);
+        self.builder.seal_block(self.exit);
+        let pointer = self.builder.use_var(self.pointer);
+        self.builder
+            .ins()
+            .store(MemFlags::trusted(), pointer, self.tape_pointer_address, 0);
+        self.builder.ins().return_(&[]);
+
+        Ok(())
+    }
+
+    /// Start a loop that runs while the current cell is non-zero, returning
+    /// its header, body and exit blocks
+    fn open_loop(&mut self) -> (Block, Block, Block) {
+        let header = self.builder.create_block();
+        let body = self.builder.create_block();
+        let after = self.builder.create_block();
+
+        self.builder.ins().jump(header, &[]);
+        self.builder.switch_to_block(header);
+        let cell = self.load_cell(0);
+        self.builder.ins().brif(cell, body, &[], after, &[]);
+
+        (header, body, after)
+    }
+
+    fn close_loop(&mut self, header: Block, after: Block) {
+        self.builder.ins().jump(header, &[]);
+        self.builder.seal_block(header);
+        self.builder.switch_to_block(after);
+        self.builder.seal_block(after);
+    }
+
+    fn origin(&mut self, origin: usize) -> Value {
+        self.builder.ins().iconst(self.pointer_type, origin as i64)
+    }
+
+    fn cell_address(&mut self) -> Value {
+        let tape = self.builder.use_var(self.tape);
+        let pointer = self.builder.use_var(self.pointer);
+        self.builder.ins().iadd(tape, pointer)
+    }
+
+    fn load_cell(&mut self, offset: isize) -> Value {
+        let address = self.cell_address();
+        self.builder
+            .ins()
+            .load(types::I8, MemFlags::trusted(), address, offset as i32)
+    }
+
+    fn store_cell(&mut self, offset: isize, value: Value) {
+        let address = self.cell_address();
+        self.builder
+            .ins()
+            .store(MemFlags::trusted(), value, address, offset as i32);
+    }
+
+    fn move_pointer(&mut self, offset: isize, origin: usize) {
+        self.reach(std::cmp::min(offset, 0), std::cmp::max(offset, 0), origin);
+        let pointer = self.builder.use_var(self.pointer);
+        let pointer = self.builder.ins().iadd_imm(pointer, offset as i64);
+        self.builder.def_var(self.pointer, pointer);
+    }
+
+    /// Stop if the cell `low` away from the pointer is before the start of
+    /// the tape, then grow the tape to include the cell `high` away, or stop
+    /// if it can't.
+    fn reach(&mut self, low: isize, high: isize, origin: usize) {
+        let pointer = self.builder.use_var(self.pointer);
+
+        if low < 0 {
+            let underflow = self.builder.create_block();
+            let rest = self.builder.create_block();
+            let underflows =
+                self.builder
+                    .ins()
+                    .icmp_imm(IntCC::UnsignedLessThan, pointer, -low as i64);
+            self.builder
+                .ins()
+                .brif(underflows, underflow, &[], rest, &[]);
+
+            self.builder.switch_to_block(underflow);
+            self.builder.seal_block(underflow);
+            let origin = self.origin(origin);
+            self.builder
+                .ins()
+                .call(self.imports.underflow, &[self.runtime, origin]);
+            self.builder.ins().jump(self.exit, &[]);
+
+            self.builder.switch_to_block(rest);
+            self.builder.seal_block(rest);
+        }
+
+        if high > 0 {
+            let grow = self.builder.create_block();
+            let rest = self.builder.create_block();
+            let end = self.builder.ins().iadd_imm(pointer, high as i64);
+            let length = self.builder.use_var(self.length);
+            let overflows = self
+                .builder
+                .ins()
+                .icmp(IntCC::UnsignedGreaterThanOrEqual, end, length);
+            self.builder.ins().brif(overflows, grow, &[], rest, &[]);
+
+            self.builder.switch_to_block(grow);
+            self.builder.seal_block(grow);
+            let origin = self.origin(origin);
+            let call = self
+                .builder
+                .ins()
+                .call(self.imports.grow, &[self.runtime, end, origin]);
+            let tape = self.builder.inst_results(call)[0];
+            let grown = self.builder.create_block();
+            self.builder.ins().brif(tape, grown, &[], self.exit, &[]);
+
+            self.builder.switch_to_block(grown);
+            self.builder.seal_block(grown);
+            self.builder.def_var(self.tape, tape);
+            let length = self.builder.ins().iadd_imm(end, 1);
+            self.builder.def_var(self.length, length);
+            self.builder.ins().jump(rest, &[]);
+
+            self.builder.switch_to_block(rest);
+            self.builder.seal_block(rest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::{brainfork, overflow};
+
+    const HELLO: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+    fn engine(code: &str) -> Engine {
+        Engine::new(overflow::instruction_set().parse(code))
+    }
+
+    #[test]
+    fn matches_the_interpreter() {
+        let mut interpreted = engine(HELLO);
+        while interpreted.step().is_ok() {}
+        let mut compiled = engine(HELLO);
+        run(&mut compiled, Eof::default()).unwrap();
+
+        assert_eq!(compiled.output, b"Hello World!\n".to_vec());
+        assert_eq!(compiled.tape, interpreted.tape);
+        assert_eq!(compiled.tape_pointer, interpreted.tape_pointer);
+        assert_eq!(compiled.instruction_pointer, InstructionPointer::End);
+    }
+
+    #[test]
+    fn reads_input_with_eof_policy() {
+        let mut program = engine(",[.,]");
+        program.input = b"abc".to_vec();
+        run(&mut program, Eof::Zero).unwrap();
+
+        assert_eq!(program.output, b"abc".to_vec());
+    }
+
+    #[test]
+    fn stops_at_input_request_for_stepping_to_resume() {
+        let mut program = engine("+.,.");
+        assert_eq!(
+            run(&mut program, Eof::RequestInput),
+            Err(Exception::RequestingInput)
+        );
+        assert_eq!(program.instruction_pointer, InstructionPointer::Index(2));
+        assert_eq!(program.output, vec![1]);
+
+        program.input = vec![7];
+        while program.step().is_ok() {}
+        assert_eq!(program.output, vec![1, 7]);
+    }
+
+    #[test]
+    fn stops_before_moving_off_the_tape() {
+        let mut program = engine(">+<<");
+        assert!(run(&mut program, Eof::default()).is_err());
+        assert_eq!(program.instruction_pointer, InstructionPointer::Index(2));
+        assert_eq!(program.tape_pointer, 1);
+    }
+
+    #[test]
+    fn stops_at_the_end_of_a_fixed_tape() {
+        let mut program = Engine::builder()
+            .code("+>+>+")
+            .tape(TapeModel::Fixed(2))
+            .build()
+            .unwrap();
+        assert_eq!(
+            run(&mut program, Eof::default()),
+            Err(EngineError::TapeOverflow.into())
+        );
+        assert_eq!(program.instruction_pointer, InstructionPointer::Index(3));
+        assert_eq!(program.tape, vec![1, 1]);
+    }
+
+    #[test]
+    fn rejects_unknown_instructions() {
+        let instructions = brainfork::instruction_set().parse("+Y");
+        assert_eq!(
+            compile(&instructions).err(),
+            Some(JitError::Unsupported('Y'))
+        );
+    }
+}
@@ -0,0 +1,33 @@
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+extern crate alloc;
+
+pub mod analysis;
+pub mod assertions;
+pub mod bisect;
+pub mod codegen;
+pub mod config;
+pub mod engine;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod flavor;
+pub mod format;
+pub mod fuzz;
+pub mod grade;
+pub mod instruction;
+pub mod ir;
+#[cfg(feature = "jit")]
+pub mod jit;
+pub mod optimize;
+pub mod output;
+pub mod preprocess;
+pub mod profile;
+#[cfg(feature = "std")]
+pub mod script;
+pub mod tape;
+#[cfg(feature = "std")]
+pub mod transpile;
+pub mod watch;
+#[cfg(feature = "wasm-bindgen")]
+pub mod web;
+pub mod workspace;
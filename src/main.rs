@@ -1,28 +1,190 @@
-#![allow(dead_code, unstable_name_collisions)]
-#![feature(iter_intersperse)]
+#![allow(dead_code)]
 
+#[cfg(feature = "tui")]
 mod app;
+mod cli;
 mod editor;
-mod engine;
-mod flavor;
-mod instruction;
 mod program;
+#[cfg(feature = "session")]
+mod session;
+#[cfg(feature = "tui")]
+mod tabs;
+#[cfg(feature = "tui")]
 mod ui;
 
-use program::Program;
+#[cfg(feature = "tui")]
+use plaque::config;
+#[cfg(feature = "jit")]
+use plaque::jit;
+use plaque::{
+    analysis, assertions, bisect, codegen, engine, flavor, format, grade, instruction, ir, output,
+    preprocess, profile, script, tape, transpile, watch,
+};
 
 use anyhow::Result;
 
 fn main() -> Result<()> {
-    let input_filepath = std::env::args().nth(1);
-    let flavor = flavor::overflow::INSTRUCTION_SET.to_vec();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let flavor = flavor::overflow::instruction_set();
+    flavor.validate()?;
 
-    let mut program = match input_filepath {
-        Some(filepath) => Program::load(filepath, flavor)?,
-        None => Program::blank(flavor),
+    match args.first().map(String::as_str) {
+        #[cfg(feature = "server")]
+        Some("attach-run") => return cli::attach::run(&args[1..], flavor),
+        Some("bench") => return cli::bench::run(&args[1..], flavor),
+        Some("bisect") => return cli::bisect::run(&args[1..], flavor),
+        Some("cfg") => return cli::cfg::run(&args[1..], flavor),
+        Some("check") => return cli::check::run(&args[1..], flavor),
+        Some("fmt") => return cli::fmt::run(&args[1..], flavor),
+        #[cfg(feature = "server")]
+        Some("dap") => return cli::dap::run(&args[1..], flavor),
+        Some("debug") => return debug(&args[1..], flavor),
+        Some("dump") => return cli::dump::run(&args[1..], flavor),
+        Some("gen-text") => return cli::gen_text::run(&args[1..], flavor),
+        Some("inspect") => return cli::inspect::run(&args[1..], flavor),
+        #[cfg(feature = "server")]
+        Some("kernel") => return cli::kernel::run(&args[1..], flavor),
+        #[cfg(feature = "server")]
+        Some("gdb") => return cli::gdb::run(&args[1..], flavor),
+        #[cfg(feature = "server")]
+        Some("lsp") => return cli::lsp::run(&args[1..], flavor),
+        Some("record") => return cli::record::run(&args[1..], flavor),
+        Some("replay") => return cli::replay::run(&args[1..], flavor),
+        Some("run") => return cli::run::run(&args[1..], flavor),
+        Some("script") => return cli::script::run(&args[1..], flavor),
+        #[cfg(feature = "server")]
+        Some("serve") => return cli::serve::run(&args[1..], flavor),
+        Some("stat") => return cli::stat::run(&args[1..], flavor),
+        Some("test") => return cli::test::run(&args[1..], flavor),
+        Some("transpile") => return cli::transpile::run(&args[1..], flavor),
+        _ => {}
+    }
+
+    debug(&args, flavor)
+}
+
+#[cfg(feature = "tui")]
+fn debug(args: &[String], mut flavor: instruction::InstructionSet) -> Result<()> {
+    let args = cli::Args::parse(args, &["macros"])?;
+    // with nothing else to debug, a manifest in the current directory says
+    // what to
+    let manifest = std::path::Path::new(config::MANIFEST);
+    let config = match args.positional().is_empty() && args.value("resume").is_none() {
+        true if manifest.exists() => {
+            Some(config::Config::load(manifest).map_err(anyhow::Error::msg)?)
+        }
+        _ => None,
     };
+    if let Some(config) = &config {
+        if config.cell_width != 8 {
+            anyhow::bail!(
+                "the debugger only has 8-bit cells, not the {}-bit cells in {}",
+                config.cell_width,
+                config::MANIFEST
+            );
+        }
+        flavor = config.dialect.instruction_set();
+        flavor.validate()?;
+    }
+    let resumed = args
+        .value("resume")
+        .map(|path| session::load(path, flavor.clone()))
+        .transpose()?;
 
-    program.read_stdin();
+    let mut programs = args
+        .positional()
+        .iter()
+        .map(String::as_str)
+        .chain(config.as_ref().and_then(|config| config.entry.as_deref()))
+        .map(|filepath| program::Program::load(filepath, flavor.clone()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    if programs.is_empty() && resumed.is_none() {
+        programs.push(program::Program::blank(flavor));
+    }
+
+    // there's only one stdin, so every program gets a copy of it, except a
+    // resumed one, which already has the input it was given
+    if let Some((first, rest)) = programs.split_first_mut() {
+        first.read_stdin();
+        for program in rest {
+            program.set_stdin(first.stdin.clone());
+        }
+    }
+    if let Some(resumed) = resumed {
+        programs.insert(0, resumed);
+    }
+    let pins = args
+        .values("pin")
+        .map(|pin| {
+            pin.split_once("..")
+                .and_then(|(start, end)| Some(start.parse().ok()?..end.parse().ok()?))
+                .ok_or_else(|| anyhow::anyhow!("invalid value for --pin: {pin}"))
+        })
+        .collect::<Result<Vec<std::ops::Range<usize>>>>()?;
+    let labels = args
+        .values("label")
+        .map(|label| label.parse().map_err(anyhow::Error::msg))
+        .collect::<Result<Vec<engine::labels::Label>>>()?;
+    let history = args
+        .value("history")
+        .map(|history| history.parse::<engine::history::HistoryPolicy>())
+        .transpose()
+        .map_err(anyhow::Error::msg)?;
+    let decoder = args
+        .value("decode")
+        .map(|decoder| decoder.parse::<output::OutputDecoder>())
+        .transpose()
+        .map_err(anyhow::Error::msg)?;
+    let input = args
+        .value("input")
+        .map(|input| input.parse::<engine::input::InputSource>())
+        .transpose()
+        .map_err(anyhow::Error::msg)?
+        .or_else(|| {
+            let path = config.as_ref()?.input.as_ref()?;
+            Some(engine::input::InputSource::File(path.into()))
+        });
+    let mut watches = watch::Watches::default();
+    for expression in args.values("watch") {
+        watches.add(expression).map_err(anyhow::Error::msg)?;
+    }
+    for program in programs.iter_mut() {
+        if let Some(config) = &config {
+            program.engine.tape_model = config.tape;
+            program.engine.tape = tape::Tape::new(config.tape);
+        }
+        if args.switch("macros") {
+            program.macros = true;
+            program.index_instructions();
+        }
+        if let Some(input) = &input {
+            program.engine.set_input(input).map_err(anyhow::Error::msg)?;
+            // cycled input never runs out, so there's no end to warn about
+            program.stdin = match input {
+                engine::input::InputSource::Cycle(_) => None,
+                _ => Some(program.engine.input.clone()),
+            };
+        }
+        program.check();
+        for pin in &pins {
+            program.tape_view.pin(pin.clone());
+        }
+        for label in &labels {
+            program.engine.label_range(label.cells.clone(), label.name.clone());
+        }
+        program.watches = watches.clone();
+        if let Some(history) = history {
+            program.engine.history.set_policy(history);
+        }
+        if let Some(decoder) = decoder {
+            program.output_decoder = decoder;
+        }
+    }
+
+    app::run(tabs::Tabs::new(programs))
+}
 
-    app::run(program)
+#[cfg(not(feature = "tui"))]
+fn debug(_args: &[String], _flavor: instruction::InstructionSet) -> Result<()> {
+    anyhow::bail!("plaque was built without the interactive debugger (the `tui` feature)")
 }
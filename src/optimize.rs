@@ -0,0 +1,192 @@
+use crate::instruction::Instruction;
+use crate::ir::Composite;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// An optimized instruction stream, alongside the original instructions
+/// each optimized one covers, from `origins` up to but not including `ends`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Optimized {
+    pub instructions: Vec<Instruction>,
+    pub origins: Vec<usize>,
+    pub ends: Vec<usize>,
+}
+
+impl Optimized {
+    /// The optimized instruction that covers some original instruction, if it
+    /// wasn't optimized away entirely.
+    pub fn optimized_index(&self, original: usize) -> Option<usize> {
+        let index = self.origins.partition_point(|&origin| origin <= original);
+        let index = index.checked_sub(1)?;
+        (original < self.ends[index]).then_some(index)
+    }
+
+    fn push(&mut self, instruction: Instruction, origin: usize, end: usize) {
+        self.instructions.push(instruction);
+        self.origins.push(origin);
+        self.ends.push(end);
+    }
+}
+
+/// Add `amount` to the current cell in one step
+pub fn add(amount: u8) -> Instruction {
+    let symbol = if amount < 128 { '+' } else { '-' };
+    Instruction::new(
+        symbol,
+        move |program| {
+            program.try_map_cell(|cell| cell.wrapping_add(amount))?;
+            program.next_instruction()
+        },
+        move |program| {
+            program.try_map_cell(|cell| cell.wrapping_sub(amount))?;
+            program.prev_instruction()
+        },
+    )
+    .with_composite(Composite::Add(amount))
+}
+
+/// Move the tape pointer by `offset` cells in one step
+pub fn move_pointer(offset: isize) -> Instruction {
+    let symbol = if offset > 0 { '>' } else { '<' };
+    Instruction::new(
+        symbol,
+        move |program| {
+            program.move_pointer(offset)?;
+            program.next_instruction()
+        },
+        move |program| {
+            program.move_pointer(-offset)?;
+            program.prev_instruction()
+        },
+    )
+    .with_composite(Composite::Move(offset))
+}
+
+/// Collapse each run of `+`/`-` into a single `add` and each run of `>`/`<`
+/// into a single `move_pointer`, dropping runs that cancel out entirely.
+pub fn run_length_encode(instructions: &[Instruction]) -> Optimized {
+    let mut optimized = Optimized {
+        instructions: vec![],
+        origins: vec![],
+        ends: vec![],
+    };
+
+    let mut index = 0;
+    while index < instructions.len() {
+        let start = index;
+        match instructions[index].written_symbol() {
+            Some('+' | '-') => {
+                let mut amount = 0u8;
+                while let Some(symbol) = instructions
+                    .get(index)
+                    .and_then(Instruction::written_symbol)
+                {
+                    match symbol {
+                        '+' => amount = amount.wrapping_add(1),
+                        '-' => amount = amount.wrapping_sub(1),
+                        _ => break,
+                    }
+                    index += 1;
+                }
+                if amount != 0 {
+                    optimized.push(add(amount), start, index);
+                }
+            }
+            Some('>' | '<') => {
+                let mut offset = 0isize;
+                while let Some(symbol) = instructions
+                    .get(index)
+                    .and_then(Instruction::written_symbol)
+                {
+                    match symbol {
+                        '>' => offset += 1,
+                        '<' => offset -= 1,
+                        _ => break,
+                    }
+                    index += 1;
+                }
+                if offset != 0 {
+                    optimized.push(move_pointer(offset), start, index);
+                }
+            }
+            _ => {
+                optimized.push(instructions[index].clone(), start, index + 1);
+                index += 1;
+            }
+        }
+    }
+
+    optimized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::flavor::overflow;
+
+    const HELLO: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+    fn run(instructions: Vec<Instruction>) -> Engine {
+        let mut engine = Engine::new(instructions);
+        while engine.step().is_ok() {}
+        engine
+    }
+
+    #[test]
+    fn collapses_runs() {
+        let instructions = overflow::instruction_set().parse("+++-->>><[-]<<");
+        let optimized = run_length_encode(&instructions);
+
+        let symbols = optimized
+            .instructions
+            .iter()
+            .map(|i| i.symbol)
+            .collect::<String>();
+        assert_eq!(symbols, "+>[-]<");
+        assert_eq!(optimized.origins, vec![0, 5, 9, 10, 11, 12]);
+        assert_eq!(optimized.instructions[0], add(1));
+        assert_ne!(optimized.instructions[0], add(3));
+        assert_eq!(optimized.instructions[5], move_pointer(-2));
+    }
+
+    #[test]
+    fn cancelling_runs_are_dropped() {
+        let instructions = overflow::instruction_set().parse("+-><.");
+        let optimized = run_length_encode(&instructions);
+
+        assert_eq!(optimized.instructions.len(), 1);
+        assert_eq!(optimized.optimized_index(4), Some(0));
+        assert_eq!(optimized.optimized_index(0), None);
+
+        let instructions = overflow::instruction_set().parse("+++.+-");
+        let optimized = run_length_encode(&instructions);
+        assert_eq!(optimized.optimized_index(2), Some(0));
+        assert_eq!(optimized.optimized_index(3), Some(1));
+        assert_eq!(optimized.optimized_index(5), None);
+    }
+
+    #[test]
+    fn behaves_like_the_original() {
+        let instructions = overflow::instruction_set().parse(HELLO);
+        let original = run(instructions.clone());
+        let optimized = run(run_length_encode(&instructions).instructions);
+
+        assert_eq!(optimized.output, b"Hello World!\n".to_vec());
+        assert_eq!(optimized.output, original.output);
+        assert_eq!(optimized.tape, original.tape);
+        assert!(optimized.history.len() < original.history.len());
+    }
+
+    #[test]
+    fn undo_restores_the_start() {
+        let instructions = overflow::instruction_set().parse(HELLO);
+        let mut engine = run(run_length_encode(&instructions).instructions);
+
+        while engine.undo().is_ok() {}
+
+        assert!(engine.tape.iter().all(|&cell| cell == 0));
+        assert!(engine.output.is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+use alloc::format;
+use alloc::string::String;
+use core::fmt::Write;
+
+/// How many bytes a hex dump puts on each line
+const HEX_LINE: usize = 16;
+
+/// How a program's output bytes are shown as text
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OutputDecoder {
+    /// UTF-8, with invalid bytes replaced by U+FFFD
+    #[default]
+    Utf8,
+    /// Each byte as the character with that code point, so every byte is
+    /// shown as exactly one character
+    Latin1,
+    /// Each byte as two hex digits
+    Hex,
+}
+
+/// Parses `utf8`, `latin1` or `hex`
+impl core::str::FromStr for OutputDecoder {
+    type Err = String;
+
+    fn from_str(decoder: &str) -> Result<OutputDecoder, String> {
+        match decoder {
+            "utf8" | "utf-8" => Ok(OutputDecoder::Utf8),
+            "latin1" | "latin-1" => Ok(OutputDecoder::Latin1),
+            "hex" => Ok(OutputDecoder::Hex),
+            _ => Err(format!(
+                "invalid output decoder {decoder}, expected utf8, latin1 or hex"
+            )),
+        }
+    }
+}
+
+/// Decoded output, with how many bytes at the end are the start of a
+/// character still being written
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Decoded {
+    pub text: String,
+    pub pending: usize,
+}
+
+impl OutputDecoder {
+    /// Decode output written so far. A multi-byte character cut off at the
+    /// end is left pending rather than replaced, since the rest of it may
+    /// be yet to come.
+    pub fn decode(self, bytes: &[u8]) -> Decoded {
+        match self {
+            OutputDecoder::Utf8 => decode_utf8(bytes),
+            OutputDecoder::Latin1 => Decoded {
+                text: bytes.iter().map(|&byte| byte as char).collect(),
+                pending: 0,
+            },
+            OutputDecoder::Hex => {
+                let mut text = String::new();
+                for (i, byte) in bytes.iter().enumerate() {
+                    let separator = match i {
+                        0 => "",
+                        _ if i % HEX_LINE == 0 => "\n",
+                        _ => " ",
+                    };
+                    let _ = write!(text, "{separator}{byte:02X}");
+                }
+                Decoded { text, pending: 0 }
+            }
+        }
+    }
+
+    /// Decode everything that's finished, for showing output that's already
+    /// all been written
+    pub fn decode_all(self, bytes: &[u8]) -> String {
+        let decoded = self.decode(bytes);
+        match decoded.pending {
+            0 => decoded.text,
+            _ => decoded.text + "\u{FFFD}",
+        }
+    }
+}
+
+fn decode_utf8(mut bytes: &[u8]) -> Decoded {
+    let mut text = String::new();
+    loop {
+        match core::str::from_utf8(bytes) {
+            Ok(valid) => {
+                text.push_str(valid);
+                return Decoded { text, pending: 0 };
+            }
+            Err(error) => {
+                let (valid, rest) = bytes.split_at(error.valid_up_to());
+                // only ever valid UTF-8 by here
+                text.push_str(core::str::from_utf8(valid).unwrap_or_default());
+                match error.error_len() {
+                    Some(invalid) => {
+                        text.push('\u{FFFD}');
+                        bytes = &rest[invalid..];
+                    }
+                    None => {
+                        return Decoded {
+                            text,
+                            pending: rest.len(),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Bytes as a JSON string, with anything that isn't UTF-8 replaced
+pub fn json_string(bytes: &[u8]) -> String {
+    let mut json = String::from("\"");
+    for character in OutputDecoder::Utf8.decode_all(bytes).chars() {
+        match character {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            control if control < ' ' => {
+                let _ = write!(json, "\\u{:04x}", control as u32);
+            }
+            character => json.push(character),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf8_leaves_cut_off_characters_pending() {
+        let snowman = "☃".as_bytes();
+        let mut output = b"a".to_vec();
+        output.extend(&snowman[..2]);
+        assert_eq!(
+            OutputDecoder::Utf8.decode(&output),
+            Decoded {
+                text: String::from("a"),
+                pending: 2
+            }
+        );
+        assert_eq!(OutputDecoder::Utf8.decode_all(&output), "a\u{FFFD}");
+
+        output.push(snowman[2]);
+        output.extend([0xFF, b'b']);
+        assert_eq!(
+            OutputDecoder::Utf8.decode(&output),
+            Decoded {
+                text: String::from("a☃\u{FFFD}b"),
+                pending: 0
+            }
+        );
+    }
+
+    #[test]
+    fn other_decoders_show_every_byte() {
+        let output = (0..20).chain([0xE9]).collect::<alloc::vec::Vec<u8>>();
+        assert_eq!(OutputDecoder::Latin1.decode(&[b'c', 0xE9]).text, "cé");
+        let hex = OutputDecoder::Hex.decode(&output).text;
+        assert!(hex.starts_with("00 01 02"));
+        assert!(hex.ends_with("0F\n10 11 12 13 E9"));
+
+        assert_eq!("latin1".parse(), Ok(OutputDecoder::Latin1));
+        assert!("ebcdic".parse::<OutputDecoder>().is_err());
+    }
+}
@@ -0,0 +1,412 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// How long expanded source may get, so a few nested repetitions can't use
+/// up all the memory there is
+const EXPANSION_LIMIT: usize = 1 << 24;
+
+/// Macros every program can use, each the instruction it's named after
+const BUILTINS: &[(&str, &str)] = &[("inc", "+"), ("dec", "-"), ("left", "<"), ("right", ">")];
+
+/// Which of a [`SourceManager`]'s files something is in
+pub type FileId = usize;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SourceFile {
+    /// The path the file was loaded from, which files it includes are found
+    /// relative to
+    pub name: String,
+    pub text: String,
+}
+
+/// Every file a program is made of, with the program's own first, and the
+/// files it includes added as they're found
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SourceManager {
+    files: Vec<SourceFile>,
+}
+
+/// Where a character of expanded source came from
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Origin {
+    pub file: FileId,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Source with its macros and includes expanded, and where each character
+/// of it came from
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Expanded {
+    pub text: String,
+    /// Where each character of `text` came from, which for anything a macro
+    /// expanded to is where the macro was invoked
+    pub origins: Vec<Origin>,
+    /// The line and column of each character of `text` in the file that was
+    /// expanded, which for anything from another file is where it was
+    /// included
+    pub positions: Vec<(usize, usize)>,
+}
+
+impl SourceManager {
+    pub fn new() -> SourceManager {
+        SourceManager::default()
+    }
+
+    /// Add a file, or replace the text of one that's already been added
+    pub fn add<N: Into<String>, T: Into<String>>(&mut self, name: N, text: T) -> FileId {
+        let (name, text) = (name.into(), text.into());
+        match self.find(&name) {
+            Some(file) => {
+                self.files[file].text = text;
+                file
+            }
+            None => {
+                self.files.push(SourceFile { name, text });
+                self.files.len() - 1
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn load(&mut self, path: &str) -> std::io::Result<FileId> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(self.add(path, text))
+    }
+
+    pub fn find(&self, name: &str) -> Option<FileId> {
+        self.files.iter().position(|file| file.name == name)
+    }
+
+    pub fn get(&self, file: FileId) -> Option<&SourceFile> {
+        self.files.get(file)
+    }
+
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    /// Expand a file's macros and includes. A line starting `@def name`
+    /// defines a macro as the rest of the line, which can use macros defined
+    /// before it, and `@name` anywhere after it is replaced by that, or
+    /// `@name(n)` by that repeated `n` times. A line starting
+    /// `@include "path"` is replaced by the file at that path, relative to
+    /// the one including it, whose macros can be used after it. An `@` not
+    /// followed by a name is left as it is.
+    pub fn expand(&mut self, file: FileId) -> Result<Expanded, String> {
+        let mut expander = Expander {
+            sources: self,
+            macros: BUILTINS
+                .iter()
+                .map(|&(name, body)| (String::from(name), String::from(body)))
+                .collect(),
+            including: Vec::new(),
+        };
+        let mut expanded = Expanded::default();
+        expander.expand_file(file, None, &mut expanded)?;
+        Ok(expanded)
+    }
+
+    /// Find an included file, relative to the one including it
+    fn resolve(&mut self, from: FileId, name: &str) -> Option<FileId> {
+        let path = match self.files[from].name.rfind('/') {
+            Some(end) if !name.starts_with('/') => {
+                format!("{}/{name}", &self.files[from].name[..end])
+            }
+            _ => String::from(name),
+        };
+        #[cfg(feature = "std")]
+        if self.find(&path).is_none() {
+            return self.load(&path).ok();
+        }
+        self.find(&path)
+    }
+}
+
+/// Expand the macros in source that isn't in a file
+pub fn expand(source: &str) -> Result<Expanded, String> {
+    let mut sources = SourceManager::new();
+    let file = sources.add("", source);
+    sources.expand(file)
+}
+
+struct Expander<'a> {
+    sources: &'a mut SourceManager,
+    macros: BTreeMap<String, String>,
+    /// The files being expanded, each included by the one before it
+    including: Vec<FileId>,
+}
+
+impl Expander<'_> {
+    /// Where in which file something is, for error messages
+    fn locate(&self, origin: Origin) -> String {
+        match self.sources.files[origin.file].name.as_str() {
+            "" => format!("{}:{}", origin.line + 1, origin.column + 1),
+            name => format!("{name}:{}:{}", origin.line + 1, origin.column + 1),
+        }
+    }
+
+    fn push(
+        &self,
+        expanded: &mut Expanded,
+        text: &str,
+        origin: Origin,
+        site: Option<(usize, usize)>,
+    ) -> Result<(), String> {
+        if expanded.text.len() + text.len() > EXPANSION_LIMIT {
+            return Err(format!(
+                "{}: macros expand to more than {EXPANSION_LIMIT} characters",
+                self.locate(origin)
+            ));
+        }
+        expanded.text.push_str(text);
+        for _ in text.chars() {
+            expanded.origins.push(origin);
+            expanded
+                .positions
+                .push(site.unwrap_or((origin.line, origin.column)));
+        }
+        Ok(())
+    }
+
+    /// Expand a file, with everything in it at `site` in the file being
+    /// expanded if it's been included
+    fn expand_file(
+        &mut self,
+        file: FileId,
+        site: Option<(usize, usize)>,
+        expanded: &mut Expanded,
+    ) -> Result<(), String> {
+        self.including.push(file);
+        let text = self.sources.files[file].text.clone();
+        let lines = text.split('\n').collect::<Vec<_>>();
+        for (line_number, line) in lines.iter().enumerate() {
+            let line = line.chars().collect::<Vec<_>>();
+            let indent = line
+                .iter()
+                .take_while(|symbol| symbol.is_whitespace())
+                .count();
+            let origin = Origin {
+                file,
+                line: line_number,
+                column: indent,
+            };
+            match directive(&line[indent..]) {
+                Some(("def", name, _)) if name.is_empty() => {
+                    return Err(format!("{}: @def needs a name", self.locate(origin)));
+                }
+                Some(("def", name, body)) => {
+                    let mut definition = Expanded::default();
+                    let column = line.len() - body.len();
+                    self.expand_line(body, (file, line_number, column), None, &mut definition)?;
+                    self.macros.insert(name, definition.text);
+                }
+                Some(("include", _, path)) => {
+                    let path = path.iter().collect::<String>();
+                    let name = path
+                        .trim()
+                        .strip_prefix('"')
+                        .and_then(|path| path.strip_suffix('"'))
+                        .ok_or_else(|| {
+                            format!("{}: expected a quoted path to include", self.locate(origin))
+                        })?;
+                    let included = self.sources.resolve(file, name).ok_or_else(|| {
+                        format!("{}: can't find {name} to include", self.locate(origin))
+                    })?;
+                    if self.including.contains(&included) {
+                        return Err(format!(
+                            "{}: {name} ends up including itself",
+                            self.locate(origin)
+                        ));
+                    }
+                    let site = site.or(Some((line_number, indent)));
+                    self.expand_file(included, site, expanded)?;
+                }
+                _ => self.expand_line(&line, (file, line_number, 0), site, expanded)?,
+            }
+            if line_number + 1 < lines.len() {
+                let origin = Origin {
+                    file,
+                    line: line_number,
+                    column: line.len(),
+                };
+                self.push(expanded, "\n", origin, site)?;
+            }
+        }
+        self.including.pop();
+        Ok(())
+    }
+
+    /// Expand the macros in a line, or the part of one from `start`
+    fn expand_line(
+        &self,
+        line: &[char],
+        (file, line_number, start): (FileId, usize, usize),
+        site: Option<(usize, usize)>,
+        expanded: &mut Expanded,
+    ) -> Result<(), String> {
+        let mut column = 0;
+        while column < line.len() {
+            let origin = Origin {
+                file,
+                line: line_number,
+                column: start + column,
+            };
+            let symbol = line[column];
+            let length = name_length(&line[column + 1..]);
+            if symbol != '@' || length == 0 {
+                self.push(expanded, symbol.encode_utf8(&mut [0; 4]), origin, site)?;
+                column += 1;
+                continue;
+            }
+
+            let error = |message: String| format!("{}: {message}", self.locate(origin));
+            let name = line[column + 1..column + 1 + length]
+                .iter()
+                .collect::<String>();
+            let mut end = column + 1 + length;
+            let mut count = 1;
+            if line.get(end) == Some(&'(') {
+                let close = line[end..]
+                    .iter()
+                    .position(|&symbol| symbol == ')')
+                    .ok_or_else(|| error(format!("unclosed count for @{name}")))?;
+                let argument = line[end + 1..end + close].iter().collect::<String>();
+                count = argument
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| error(format!("invalid count {argument} for @{name}")))?;
+                end += close + 1;
+            }
+            let body = match name.as_str() {
+                "def" | "include" => return Err(error(format!("@{name} must start a line"))),
+                _ => self
+                    .macros
+                    .get(&name)
+                    .ok_or_else(|| error(format!("unknown macro @{name}")))?,
+            };
+            if body.len().saturating_mul(count) > EXPANSION_LIMIT {
+                return Err(error(format!(
+                    "macros expand to more than {EXPANSION_LIMIT} characters"
+                )));
+            }
+            for _ in 0..count {
+                self.push(expanded, body, origin, site)?;
+            }
+            column = end;
+        }
+        Ok(())
+    }
+}
+
+/// The kind, name and rest of a `@def` or `@include` line
+fn directive(line: &[char]) -> Option<(&'static str, String, &[char])> {
+    let skip_space = |text: &[char]| {
+        text.iter()
+            .take_while(|symbol| symbol.is_whitespace())
+            .count()
+    };
+    let kind = ["def", "include"].into_iter().find(|kind| {
+        line.first() == Some(&'@')
+            && line[1..].iter().copied().take(kind.len()).eq(kind.chars())
+            && line
+                .get(kind.len() + 1)
+                .is_none_or(|symbol| symbol.is_whitespace())
+    })?;
+    let rest = &line[kind.len() + 1..];
+    let rest = &rest[skip_space(rest)..];
+    if kind == "include" {
+        return Some((kind, String::new(), rest));
+    }
+    let length = name_length(rest);
+    let body = &rest[length..];
+    Some((
+        kind,
+        rest[..length].iter().collect(),
+        &body[skip_space(body)..],
+    ))
+}
+
+/// How many characters at the start make up a macro name
+fn name_length(text: &[char]) -> usize {
+    match text.first() {
+        Some(first) if first.is_ascii_alphabetic() || *first == '_' => text
+            .iter()
+            .take_while(|symbol| symbol.is_ascii_alphanumeric() || **symbol == '_')
+            .count(),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn macros_expand_in_place() {
+        let expanded = expand("@def zero [-]\n@def two @inc(2)\n@two@zero @right(3). @ x").unwrap();
+        assert_eq!(expanded.text, "\n\n++[-] >>>. @ x");
+        assert_eq!(expanded.positions[2..4], [(2, 0), (2, 0)]);
+        assert_eq!(expanded.positions[4], (2, 4));
+        assert_eq!(expanded.positions[8], (2, 10));
+        assert_eq!(expanded.positions[11], (2, 19));
+    }
+
+    #[test]
+    fn bad_macros_are_errors() {
+        assert_eq!(
+            expand("+\n +@nope"),
+            Err(String::from("2:3: unknown macro @nope"))
+        );
+        assert_eq!(
+            expand("@inc(x)"),
+            Err(String::from("1:1: invalid count x for @inc"))
+        );
+        // a macro can't use itself, since it's not defined until its line ends
+        assert!(expand("@def loop [@loop]").is_err());
+        assert_eq!(
+            expand("  @def [-]"),
+            Err(String::from("1:3: @def needs a name"))
+        );
+        assert!(expand("@def big @inc(65536)\n@big(65536)").is_err());
+    }
+
+    #[test]
+    fn included_files_keep_their_own_origins() {
+        let mut sources = SourceManager::new();
+        let main = sources.add("src/main.bf", "+\n  @include \"lib/zero.bf\"\n@zero.");
+        let lib = sources.add("src/lib/zero.bf", "@def zero [-]\n>@zero");
+        let expanded = sources.expand(main).unwrap();
+
+        assert_eq!(expanded.text, "+\n\n>[-]\n[-].");
+        let origin = |line, column| Origin {
+            file: lib,
+            line,
+            column,
+        };
+        assert_eq!(expanded.origins[3..5], [origin(1, 0), origin(1, 1)]);
+        assert_eq!(expanded.positions[3..5], [(1, 2), (1, 2)]);
+        assert_eq!(expanded.positions[8], (2, 0));
+    }
+
+    #[test]
+    fn bad_includes_are_errors() {
+        let mut sources = SourceManager::new();
+        let main = sources.add("main.bf", "@include \"lib.bf\"");
+        sources.add("lib.bf", "+\n @include \"main.bf\"");
+        assert_eq!(
+            sources.expand(main),
+            Err(String::from("lib.bf:2:2: main.bf ends up including itself"))
+        );
+
+        let lib = sources.add("lib.bf", "@include missing.bf");
+        assert_eq!(
+            sources.expand(lib),
+            Err(String::from(
+                "lib.bf:1:1: expected a quoted path to include"
+            ))
+        );
+    }
+}
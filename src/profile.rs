@@ -0,0 +1,171 @@
+use crate::engine::trace::Trace;
+use crate::instruction::InstructionSet;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// One time a loop ran, from reaching its `[` to leaving past its `]`, with
+/// steps standing in for time
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LoopSpan {
+    pub open_idx: usize,
+    pub close_idx: usize,
+    /// The step that ran the `[`
+    pub start: usize,
+    /// How many steps the loop took, including the brackets
+    pub steps: usize,
+    /// How many times its body finished
+    pub iterations: usize,
+    /// How many loops it's inside
+    pub depth: usize,
+}
+
+/// Where a recorded run spent its steps, loop by loop
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Profile {
+    /// Every loop run, in the order they started
+    pub loops: Vec<LoopSpan>,
+    /// The step each output byte was written at
+    pub output: Vec<(usize, u8)>,
+    pub steps: usize,
+}
+
+impl Profile {
+    /// Find the loops in a trace. As nothing but a loop jumps, a loop runs
+    /// for exactly as long as the steps stay between its brackets.
+    pub fn from_trace(trace: &Trace, instruction_set: &InstructionSet) -> Profile {
+        let matching = crate::analysis::match_brackets(&instruction_set.parse(&trace.source));
+
+        let mut loops = Vec::new();
+        // indexes into `loops` of the ones still running, innermost last
+        let mut open: Vec<usize> = vec![];
+        for (step, &index) in trace.steps.iter().enumerate() {
+            while let Some(&running) = open.last() {
+                let span: &mut LoopSpan = &mut loops[running];
+                if (span.open_idx..=span.close_idx).contains(&index) {
+                    break;
+                }
+                span.steps = step - span.start;
+                open.pop();
+            }
+            let close_idx = (matching.get(index).copied().flatten()).filter(|&close| close > index);
+            match close_idx {
+                Some(close_idx) if open.last().is_none_or(|&i| loops[i].open_idx != index) => {
+                    open.push(loops.len());
+                    loops.push(LoopSpan {
+                        open_idx: index,
+                        close_idx,
+                        start: step,
+                        steps: 0,
+                        iterations: 0,
+                        depth: open.len() - 1,
+                    });
+                }
+                _ => {}
+            }
+            if let Some(&running) = open.last() {
+                if loops[running].close_idx == index {
+                    loops[running].iterations += 1;
+                }
+            }
+        }
+        for running in open {
+            loops[running].steps = trace.steps.len() - loops[running].start;
+        }
+
+        Profile {
+            loops,
+            output: trace.output.clone(),
+            steps: trace.steps.len(),
+        }
+    }
+
+    /// The profile as Chrome `trace_event` JSON, for Perfetto or
+    /// `chrome://tracing`, with a microsecond for each step. Loops are
+    /// complete events nested by depth, and output bytes are instants.
+    pub fn chrome_trace(&self) -> String {
+        let mut events = vec![format!(
+            r#"{{"name":"program","ph":"X","ts":0,"dur":{},"pid":1,"tid":1}}"#,
+            self.steps
+        )];
+        for span in &self.loops {
+            events.push(format!(
+                r#"{{"name":"loop {}..{}","cat":"loop","ph":"X","ts":{},"dur":{},"pid":1,"tid":1,"args":{{"iterations":{},"depth":{}}}}}"#,
+                span.open_idx,
+                span.close_idx,
+                span.start,
+                span.steps,
+                span.iterations,
+                span.depth
+            ));
+        }
+        for &(step, byte) in &self.output {
+            events.push(format!(
+                r#"{{"name":"output","cat":"io","ph":"i","s":"t","ts":{step},"pid":1,"tid":1,"args":{{"byte":{byte}}}}}"#
+            ));
+        }
+
+        let mut json = String::from("{\"traceEvents\":[\n");
+        for (i, event) in events.iter().enumerate() {
+            let separator = if i + 1 < events.len() { "," } else { "" };
+            let _ = writeln!(json, "{event}{separator}");
+        }
+        json.push_str("]}\n");
+        json
+    }
+}
+
+/// Write a profile as Chrome `trace_event` JSON
+#[cfg(feature = "std")]
+pub fn export_chrome_trace<P: AsRef<std::path::Path>>(
+    profile: &Profile,
+    path: P,
+) -> std::io::Result<()> {
+    std::fs::write(path, profile.chrome_trace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::flavor::overflow;
+
+    fn profile(code: &str) -> Profile {
+        let instruction_set = overflow::instruction_set();
+        let mut engine = Engine::new(instruction_set.parse(code));
+        let (trace, result) = engine.record(code);
+        assert_eq!(result, Ok(()));
+        Profile::from_trace(&trace, &instruction_set)
+    }
+
+    #[test]
+    fn profiles_find_nested_loops() {
+        let profile = profile("++[>++[-]<-].[]");
+        let spans = profile
+            .loops
+            .iter()
+            .map(|span| (span.open_idx, span.start, span.iterations, span.depth))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            [(2, 2, 2, 0), (6, 6, 2, 1), (6, 17, 2, 1), (13, 26, 0, 0)]
+        );
+        let outer = profile.loops[0];
+        assert_eq!(outer.start + outer.steps, 25);
+        assert_eq!(profile.output, vec![(25, 0)]);
+    }
+
+    #[test]
+    fn profiles_export_as_trace_events() {
+        let json = profile("+[-].").chrome_trace();
+        assert!(json.starts_with("{\"traceEvents\":["));
+        assert!(json.contains(
+            r#"{"name":"loop 1..3","cat":"loop","ph":"X","ts":1,"dur":3,"pid":1,"tid":1,"args":{"iterations":1,"depth":0}},"#
+        ));
+        assert!(json.contains(r#""ph":"i","s":"t","ts":4"#));
+        assert!(!json.contains(",\n]"));
+    }
+}
@@ -1,9 +1,14 @@
+use crate::analysis::{self, Severity, Warning};
 use crate::editor::Editor;
 use crate::engine::{Engine, EngineResult, Exception, InstructionPointer};
-use crate::instruction::Instruction;
+use crate::instruction::{Instruction, InstructionSet};
+use crate::output::OutputDecoder;
+use crate::preprocess::{Origin, SourceManager};
+use crate::tape::CellFormat;
+use crate::watch::Watches;
 
-use std::collections::HashMap;
 use std::io::{self, Read};
+use std::ops::Range;
 use std::path::PathBuf;
 use tap::prelude::*;
 
@@ -12,37 +17,107 @@ pub enum Mode {
     Interactive,
     Editor,
     Input,
+    Search,
+}
+
+/// How the tape pane shows the tape: either kept centred on the pointer or
+/// turned a page at a time when the pointer leaves it, with any pinned
+/// ranges of cells shown on a row of their own.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TapeView {
+    pub paged: bool,
+    pub pinned: Vec<Range<usize>>,
+    pub format: CellFormat,
 }
 
 #[derive(Debug)]
 pub struct Program {
     pub engine: Engine,
-    pub instruction_set: HashMap<char, Instruction>,
+    pub instruction_set: InstructionSet,
     pub editor: Editor,
     pub instruction_positions: Vec<(usize, usize)>,
     pub mode: Mode,
     pub input_buffer: Vec<u8>,
+    pub search_buffer: String,
     pub stdin: Option<Vec<u8>>,
     pub debug_messages: Vec<String>,
+    pub warnings: Vec<Warning>,
+    pub furthest_step: usize,
+    pub playing: bool,
+    pub tape_view: TapeView,
+    pub watches: Watches,
+    pub output_decoder: OutputDecoder,
+    /// Whether macros and includes are expanded before the code is parsed
+    pub macros: bool,
+    /// The files included while expanding
+    pub sources: SourceManager,
+    /// Which file each instruction came from, and where in it
+    pub instruction_origins: Vec<Origin>,
+}
+
+impl TapeView {
+    /// Pin a range of cells, merging it with any pinned ranges it touches
+    pub fn pin(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let mut merged = range;
+        self.pinned.retain(|pinned| {
+            let touches = pinned.start <= merged.end && merged.start <= pinned.end;
+            if touches {
+                merged = merged.start.min(pinned.start)..merged.end.max(pinned.end);
+            }
+            !touches
+        });
+        let index = self
+            .pinned
+            .partition_point(|pinned| pinned.start < merged.start);
+        self.pinned.insert(index, merged);
+    }
+
+    /// Pin a single cell, or unpin it if it's already pinned, splitting the
+    /// range it was part of
+    pub fn toggle_pin(&mut self, cell: usize) {
+        let Some(index) = self.pinned.iter().position(|pinned| pinned.contains(&cell)) else {
+            self.pin(cell..cell + 1);
+            return;
+        };
+        let pinned = self.pinned.remove(index);
+        for part in [pinned.start..cell, cell + 1..pinned.end] {
+            if !part.is_empty() {
+                self.pin(part);
+            }
+        }
+    }
 }
 
 impl Program {
     pub fn new() -> Program {
         Program {
             engine: Engine::new(vec![]),
-            instruction_set: HashMap::new(),
+            instruction_set: InstructionSet::new(),
             editor: Editor::new(),
             instruction_positions: vec![],
             mode: Mode::Interactive,
             input_buffer: vec![],
+            search_buffer: String::new(),
             stdin: None,
             debug_messages: vec![],
+            warnings: vec![],
+            furthest_step: 0,
+            playing: false,
+            tape_view: TapeView::default(),
+            watches: Watches::default(),
+            output_decoder: OutputDecoder::default(),
+            macros: false,
+            sources: SourceManager::new(),
+            instruction_origins: vec![],
         }
     }
 
     pub fn load<S: Into<String>>(
         filename: S,
-        instruction_set: Vec<Instruction>,
+        instruction_set: InstructionSet,
     ) -> io::Result<Program> {
         let mut program = Program::new();
 
@@ -54,7 +129,7 @@ impl Program {
         Ok(program)
     }
 
-    pub fn blank(instruction_set: Vec<Instruction>) -> Program {
+    pub fn blank(instruction_set: InstructionSet) -> Program {
         let mut program = Program::new();
 
         program.set_instructions(instruction_set);
@@ -63,15 +138,12 @@ impl Program {
         program
     }
 
-    pub fn set_instructions(&mut self, instruction_set: Vec<Instruction>) {
-        self.instruction_set = HashMap::new();
-        for instruction in instruction_set {
-            self.instruction_set.insert(instruction.symbol, instruction);
-        }
+    pub fn set_instructions(&mut self, instruction_set: InstructionSet) {
+        self.instruction_set = instruction_set;
     }
 
     pub fn read_instruction(&self, character: char) -> Option<Instruction> {
-        self.instruction_set.get(&character).copied()
+        self.instruction_set.get(character).cloned()
     }
 
     pub fn hotload(&mut self) -> io::Result<()> {
@@ -91,18 +163,61 @@ impl Program {
     }
 
     pub fn index_instructions(&mut self) {
-        self.engine.instructions = vec![];
+        let previous = std::mem::take(&mut self.engine.instructions);
         self.instruction_positions = vec![];
 
-        for (line_number, line) in self.editor.lines.iter().enumerate() {
-            for (column_number, character) in line.chars().enumerate() {
-                if let Some(instruction) = self.read_instruction(character) {
-                    self.engine.instructions.push(instruction);
-                    self.instruction_positions
-                        .push((line_number, column_number));
-                }
+        self.instruction_origins = vec![];
+
+        // expanded code is indexed by where each macro was invoked, or as
+        // it's written if the macros are wrong
+        let expanded = match self.macros {
+            true => {
+                let name = self
+                    .editor
+                    .filepath
+                    .as_ref()
+                    .map_or_else(String::new, |path| path.display().to_string());
+                self.sources = SourceManager::new();
+                let file = self.sources.add(name, self.editor.lines.join("\n"));
+                self.sources
+                    .expand(file)
+                    .map_err(|message| self.debug_messages.push(message))
+                    .ok()
+            }
+            false => None,
+        };
+        let characters = match expanded {
+            Some(expanded) => expanded
+                .text
+                .chars()
+                .zip(expanded.origins)
+                .zip(expanded.positions)
+                .collect(),
+            None => self
+                .editor
+                .lines
+                .iter()
+                .enumerate()
+                .flat_map(|(line_number, line)| {
+                    line.chars().enumerate().map(move |(column, character)| {
+                        let origin = Origin {
+                            file: 0,
+                            line: line_number,
+                            column,
+                        };
+                        ((character, origin), (line_number, column))
+                    })
+                })
+                .collect::<Vec<_>>(),
+        };
+        for ((character, origin), position) in characters {
+            if let Some(instruction) = self.read_instruction(character) {
+                self.engine.instructions.push(instruction);
+                self.instruction_origins.push(origin);
+                self.instruction_positions.push(position);
             }
         }
+        self.engine.rematch_brackets();
 
         if self.engine.instructions.is_empty() {
             self.engine.instruction_pointer = InstructionPointer::Start;
@@ -110,24 +225,109 @@ impl Program {
             self.engine.instruction_pointer =
                 InstructionPointer::Index(std::cmp::min(i, self.instruction_positions.len() - 1));
         }
+        if self.engine.instructions != previous {
+            self.diverged();
+        }
+    }
+
+    pub fn check(&mut self) {
+        let input_length = self.stdin.as_ref().map(|stdin| stdin.len());
+        self.warnings = analysis::sanity_warnings(&self.engine.instructions, input_length);
+        self.warnings.extend(analysis::bounds::tape_warnings(
+            &self.engine.instructions,
+            self.engine.tape_model,
+        ));
+        self.warnings.sort_by_key(|warning| warning.index);
+
+        for warning in self.warnings.clone() {
+            let origin = self.instruction_origins[warning.index];
+            // anything from an included file says which one
+            let file = match origin.file {
+                0 => String::new(),
+                file => format!("{}:", self.sources.files()[file].name),
+            };
+            self.debug_messages.push(format!(
+                "{} ({file}{}:{}): {}",
+                match warning.severity() {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                },
+                origin.line + 1,
+                origin.column + 1,
+                warning.message()
+            ));
+        }
     }
 
     pub fn step(&mut self) -> EngineResult {
-        self.engine.step().tap_err(|e| match e {
-            Exception::Error(message) => {
-                self.debug_messages.push(message.clone());
+        let result = self.engine.step().tap_err(|e| self.stopped(e));
+        self.furthest_step = std::cmp::max(self.furthest_step, self.engine.history.len());
+        result
+    }
+
+    /// Step until the program writes a byte of output or stops
+    pub fn step_until_output(&mut self) {
+        self.engine.begin_group();
+        let result = self.engine.run_until_output();
+        self.engine.end_group();
+        if let Err(e) = result {
+            self.stopped(&e);
+        }
+        self.furthest_step = std::cmp::max(self.furthest_step, self.engine.history.len());
+    }
+
+    /// Step until the value of a cell changes or the program stops
+    pub fn step_until_cell_changes(&mut self, index: usize) {
+        self.engine.begin_group();
+        let result = self.engine.run_until_cell_changes(index);
+        self.engine.end_group();
+        if let Err(e) = result {
+            self.stopped(&e);
+        }
+        self.furthest_step = std::cmp::max(self.furthest_step, self.engine.history.len());
+    }
+
+    /// Undo until the last byte of output is taken back or undoing stops
+    pub fn undo_until_output_removed(&mut self) {
+        if let Err(Exception::Error(error)) = self.engine.reverse_until_output_removed() {
+            self.debug_messages.push(error.message());
+        }
+    }
+
+    /// Undo until the value of a cell changes or undoing stops
+    pub fn undo_until_cell_changes(&mut self, index: usize) {
+        if let Err(Exception::Error(error)) = self.engine.reverse_until_cell_changes(index) {
+            self.debug_messages.push(error.message());
+        }
+    }
+
+    fn stopped(&mut self, exception: &Exception) {
+        match exception {
+            Exception::Error(error) => {
+                self.debug_messages.push(error.message());
             }
             Exception::RequestingInput => {
                 self.enter_input_mode();
             }
             Exception::Breakpoint => {}
-        })
+        }
+    }
+
+    /// How many steps back from the furthest point reached the engine is
+    pub fn rewound_steps(&self) -> usize {
+        self.furthest_step.saturating_sub(self.engine.history.len())
+    }
+
+    /// Forget the furthest point reached, once a change to the program or
+    /// its input means stepping on won't go back there
+    fn diverged(&mut self) {
+        self.furthest_step = self.engine.history.len();
     }
 
     pub fn undo(&mut self) -> EngineResult {
         self.engine.undo().tap_err(|e| {
-            if let Exception::Error(message) = e {
-                self.debug_messages.push(message.clone());
+            if let Exception::Error(error) = e {
+                self.debug_messages.push(error.message());
             }
         })
     }
@@ -140,6 +340,21 @@ impl Program {
         }
     }
 
+    /// Take one step of playing the program back, stopping at anything
+    /// that would stop a step
+    pub fn play(&mut self) {
+        if !self.playing {
+            return;
+        }
+        if self.engine.instruction_pointer == InstructionPointer::End || self.step().is_err() {
+            self.playing = false;
+        }
+    }
+
+    pub fn toggle_playing(&mut self) {
+        self.playing = !self.playing;
+    }
+
     pub fn undo_until_exception(&mut self) {
         loop {
             if self.undo().is_err() {
@@ -149,7 +364,9 @@ impl Program {
     }
 
     pub fn reset(&mut self) {
+        self.playing = false;
         self.engine.reset();
+        self.furthest_step = 0;
         if let Some(stdin) = &self.stdin {
             self.engine.input = stdin.clone();
         }
@@ -169,17 +386,25 @@ impl Program {
     }
 
     pub fn read_stdin(&mut self) {
-        self.stdin = if atty::isnt(atty::Stream::Stdin) {
+        let stdin = if atty::isnt(atty::Stream::Stdin) {
             let stdin = io::stdin()
                 .lock()
                 .bytes()
                 .map(|x| x.unwrap_or_default())
                 .collect::<Vec<_>>();
-            self.engine.input = stdin.clone();
             Some(stdin)
         } else {
             None
         };
+        self.set_stdin(stdin);
+    }
+
+    pub fn set_stdin(&mut self, stdin: Option<Vec<u8>>) {
+        if let Some(stdin) = &stdin {
+            self.engine.input = stdin.clone();
+            self.diverged();
+        }
+        self.stdin = stdin;
     }
 
     pub fn enter_input_mode(&mut self) {
@@ -189,8 +414,9 @@ impl Program {
 
     pub fn exit_input_mode(&mut self, commit: bool) {
         self.mode = Mode::Interactive;
-        if commit {
+        if commit && self.input_buffer != self.engine.input {
             self.engine.input = self.input_buffer.clone();
+            self.diverged();
         }
         self.input_buffer = vec![];
     }
@@ -201,6 +427,104 @@ impl Program {
         }
     }
 
+    pub fn enter_search_mode(&mut self) {
+        self.mode = Mode::Search;
+        self.search_buffer.clear();
+    }
+
+    pub fn exit_search_mode(&mut self, commit: bool) {
+        self.mode = Mode::Interactive;
+        if commit {
+            let pattern = std::mem::take(&mut self.search_buffer);
+            self.search_tape(&pattern);
+        }
+    }
+
+    /// Look for text on the tape, pinning the first match so it's in view
+    pub fn search_tape(&mut self, pattern: &str) {
+        let found = self.engine.find_in_tape(pattern.as_bytes());
+        let message = match found.as_slice() {
+            [] => format!("{pattern:?} isn't on the tape"),
+            [at] => format!("found {pattern:?} at {at}"),
+            [first, rest @ ..] => format!(
+                "found {pattern:?} at {first} and {} other place{}",
+                rest.len(),
+                if rest.len() == 1 { "" } else { "s" }
+            ),
+        };
+        if let Some(&first) = found.first() {
+            self.tape_view.pin(first..first + pattern.len());
+        }
+        self.debug_messages.push(message);
+    }
+
+    /// Put a `$` breakpoint just before the current instruction, or take
+    /// away the one that's already there, keeping the same instruction current
+    pub fn toggle_breakpoint(&mut self) {
+        let InstructionPointer::Index(i) = self.engine.instruction_pointer else {
+            return;
+        };
+        if !self.instruction_set.contains('$') {
+            return;
+        }
+        if self.macros {
+            // the instruction may have come from a macro, with nowhere in
+            // the source of its own to put the breakpoint
+            self.debug_messages
+                .push("breakpoints can't be toggled with macros expanded".to_string());
+            return;
+        }
+
+        let (line, column) = self.instruction_positions[i];
+        let previous = i.checked_sub(1).filter(|&previous| {
+            self.engine.instructions[previous].symbol == '$'
+                && self.instruction_positions[previous] == (line, column.wrapping_sub(1))
+        });
+        let text = &mut self.editor.lines[line];
+        let index = match previous {
+            Some(_) => column - 1,
+            None => column,
+        };
+        let byte = text
+            .char_indices()
+            .nth(index)
+            .map_or(text.len(), |(byte, _)| byte);
+        match previous {
+            Some(previous) => {
+                text.remove(byte);
+                self.engine.history.forget_instruction(previous);
+                self.engine.instruction_pointer = InstructionPointer::Index(previous);
+            }
+            None => {
+                text.insert(byte, '$');
+                self.engine.history.instruction_inserted(i);
+                self.engine.instruction_pointer = InstructionPointer::Index(i + 1);
+            }
+        }
+        self.editor.dirty = true;
+        self.index_instructions();
+    }
+
+    /// Move the editor's cursor to the start of the loop enclosing the one
+    /// it's on, beginning with the innermost loop around the current
+    /// instruction and wrapping back round to it from the outermost
+    pub fn jump_to_enclosing_loop(&mut self) {
+        let positions = self
+            .engine
+            .loop_stack()
+            .iter()
+            .rev()
+            .map(|frame| self.instruction_positions[frame.open_idx])
+            .collect::<Vec<_>>();
+        let next = positions
+            .iter()
+            .position(|&position| position == self.editor.cursor)
+            .map_or(0, |i| (i + 1) % positions.len());
+        if let Some(&(line, column)) = positions.get(next) {
+            self.editor.set_pinned_cursor(line, column);
+        }
+    }
+
     pub fn cursor(&self) -> Option<(usize, usize)> {
         match self.engine.instruction_pointer {
             InstructionPointer::Index(i) => Some(self.instruction_positions[i]),
@@ -208,3 +532,104 @@ impl Program {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    #[test]
+    fn pinned_ranges_merge_and_split() {
+        let mut view = TapeView::default();
+        view.pin(10..12);
+        view.pin(0..2);
+        view.toggle_pin(12);
+        assert_eq!(view.pinned, vec![0..2, 10..13]);
+
+        view.toggle_pin(11);
+        assert_eq!(view.pinned, vec![0..2, 10..11, 12..13]);
+    }
+
+    #[test]
+    fn rewinding_lasts_until_the_run_changes() {
+        let mut program = Program::blank(overflow::instruction_set());
+        program.editor.lines = vec![",.,.,.".to_string()];
+        program.index_instructions();
+        program.engine.input = b"abc".to_vec();
+        while program.step().is_ok() {}
+        assert_eq!(program.rewound_steps(), 0);
+
+        program.undo().unwrap();
+        program.undo().unwrap();
+        program.undo().unwrap();
+        assert_eq!(program.rewound_steps(), 3);
+        program.step().unwrap();
+        assert_eq!(program.rewound_steps(), 2);
+
+        program.enter_input_mode();
+        program.add_input('x');
+        program.exit_input_mode(true);
+        assert_eq!(program.rewound_steps(), 0);
+        program.step().unwrap();
+        assert_eq!(program.rewound_steps(), 0);
+    }
+
+    #[test]
+    fn toggling_a_breakpoint_keeps_the_current_instruction() {
+        let mut program = Program::blank(overflow::instruction_set());
+        program.editor.lines = vec!["+ >+".to_string()];
+        program.index_instructions();
+        program.step().unwrap();
+        program.step().unwrap();
+
+        program.toggle_breakpoint();
+        assert_eq!(program.editor.lines, vec!["+ $>+"]);
+        assert_eq!(
+            program.engine.current_instruction().map(|i| i.symbol),
+            Some('>')
+        );
+
+        // run through the breakpoint, so taking it away has to forget it ran
+        program.engine.instruction_pointer = InstructionPointer::Index(1);
+        assert_eq!(program.step(), Err(Exception::Breakpoint));
+
+        program.toggle_breakpoint();
+        assert_eq!(program.editor.lines, vec!["+ >+"]);
+        assert_eq!(
+            program.engine.current_instruction().map(|i| i.symbol),
+            Some('>')
+        );
+        program.undo().unwrap();
+        assert_eq!(program.engine.tape, vec![0]);
+    }
+
+    #[test]
+    fn searching_the_tape_pins_the_first_match() {
+        let mut program = Program::blank(overflow::instruction_set());
+        program.engine.tape = b"..Hi..Hi".to_vec().into();
+
+        program.search_tape("Hi");
+        assert_eq!(program.tape_view.pinned, vec![2..4]);
+        assert_eq!(
+            program.debug_messages.last().map(String::as_str),
+            Some("found \"Hi\" at 2 and 1 other place")
+        );
+    }
+
+    #[test]
+    fn jumping_to_enclosing_loops_wraps_round() {
+        let mut program = Program::blank(overflow::instruction_set());
+        program.editor.lines = vec!["+[".to_string(), " [-]".to_string(), "]".to_string()];
+        program.index_instructions();
+        for _ in 0..4 {
+            program.step().unwrap();
+        }
+
+        program.jump_to_enclosing_loop();
+        assert_eq!(program.editor.cursor, (1, 1));
+        program.jump_to_enclosing_loop();
+        assert_eq!(program.editor.cursor, (0, 1));
+        program.jump_to_enclosing_loop();
+        assert_eq!(program.editor.cursor, (1, 1));
+    }
+}
@@ -0,0 +1,449 @@
+use crate::engine::run::{StopReason, Stops};
+use crate::engine::{Engine, Exception, InstructionPointer};
+
+use std::fmt;
+use std::io::Write;
+
+/// Something to look at in the engine, for `print` and `expect`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Target {
+    /// The cells in a half-open range
+    Tape(usize, usize),
+    Cell,
+    Pointer,
+    Steps,
+    Output,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Value {
+    Cells(Vec<u8>),
+    Number(usize),
+    Text(Vec<u8>),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Command {
+    /// Stop `run` before the instruction at an index
+    Break(usize),
+    Unbreak(usize),
+    /// Step until a breakpoint, the end of the program or an error
+    Run,
+    Step(usize),
+    Undo(usize),
+    /// Move between a loop's brackets without running them
+    Match,
+    Input(Vec<u8>),
+    Print(Target),
+    Expect(Target, Value),
+    ExpectFinished,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// Commands for driving an engine without a person at the keyboard, such as
+///
+/// ```text
+/// break 120; run
+/// print tape[0..16]
+/// step 100; expect output "Hello"
+/// ```
+///
+/// Commands are separated by newlines or semicolons, and `#` starts a
+/// comment. Any failed expectation or engine error stops the script.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Script {
+    /// Each command with the line it's on
+    pub commands: Vec<(usize, Command)>,
+}
+
+impl Script {
+    pub fn parse(source: &str) -> Result<Script, ScriptError> {
+        let mut commands = vec![];
+        for (i, line) in source.lines().enumerate() {
+            for statement in statements(line) {
+                let words = words(&statement).map_err(|message| ScriptError {
+                    line: i + 1,
+                    message,
+                })?;
+                if !words.is_empty() {
+                    let command = parse_command(&words).map_err(|message| ScriptError {
+                        line: i + 1,
+                        message,
+                    })?;
+                    commands.push((i + 1, command));
+                }
+            }
+        }
+        Ok(Script { commands })
+    }
+
+    /// Run every command against an engine, writing whatever is printed
+    pub fn run<W: Write>(&self, engine: &mut Engine, out: &mut W) -> Result<(), ScriptError> {
+        let mut stops = Stops::default();
+
+        for (line, command) in &self.commands {
+            let fail = |message: String| ScriptError {
+                line: *line,
+                message,
+            };
+
+            match command {
+                Command::Break(index) => {
+                    stops.breakpoints.insert(*index);
+                }
+                Command::Unbreak(index) => {
+                    stops.breakpoints.remove(index);
+                }
+                Command::Run => run(engine, &stops).map_err(fail)?,
+                Command::Step(count) => {
+                    for _ in 0..*count {
+                        step(engine).map_err(fail)?;
+                    }
+                }
+                Command::Undo(count) => {
+                    for _ in 0..*count {
+                        match engine.undo() {
+                            Ok(()) | Err(Exception::Breakpoint) => {}
+                            Err(Exception::RequestingInput) => {}
+                            Err(Exception::Error(error)) => return Err(fail(error.message())),
+                        }
+                    }
+                }
+                Command::Match => engine
+                    .goto_matching_bracket()
+                    .map_err(|error| fail(error.to_string()))?,
+                Command::Input(bytes) => engine.input.extend(bytes),
+                Command::Print(target) => {
+                    let value = target.value(engine);
+                    writeln!(out, "{} = {}", target, value).map_err(|e| fail(e.to_string()))?;
+                }
+                Command::Expect(target, expected) => {
+                    let value = target.value(engine);
+                    if value != *expected {
+                        return Err(fail(format!(
+                            "expected {target} to be {expected}, but it was {value}"
+                        )));
+                    }
+                }
+                Command::ExpectFinished => {
+                    if engine.instruction_pointer != InstructionPointer::End {
+                        return Err(fail("expected the program to have finished".to_string()));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn step(engine: &mut Engine) -> Result<(), String> {
+    match engine.step() {
+        Ok(()) | Err(Exception::Breakpoint) => Ok(()),
+        Err(Exception::RequestingInput) => Err("the program needs more input".to_string()),
+        Err(Exception::Error(error)) => Err(error.message()),
+    }
+}
+
+fn run(engine: &mut Engine, stops: &Stops) -> Result<(), String> {
+    match engine.continue_(stops) {
+        StopReason::InputRequested => Err("the program needs more input".to_string()),
+        StopReason::Error(error) => Err(error.message()),
+        _ => Ok(()),
+    }
+}
+
+/// A line split at the semicolons outside of strings, without its comment
+fn statements(line: &str) -> Vec<String> {
+    let mut statements = vec![String::new()];
+    let mut quoted = false;
+    let mut escaped = false;
+    for character in line.chars() {
+        match character {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                statements.push(String::new());
+                continue;
+            }
+            '#' if !quoted => break,
+            _ => {}
+        }
+        statements.last_mut().unwrap().push(character);
+    }
+    statements
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Word {
+    Bare(String),
+    Quoted(Vec<u8>),
+}
+
+fn words(statement: &str) -> Result<Vec<Word>, String> {
+    let mut words = vec![];
+    let mut characters = statement.chars().peekable();
+    while let Some(&character) = characters.peek() {
+        if character.is_whitespace() {
+            characters.next();
+        } else if character == '"' {
+            characters.next();
+            words.push(Word::Quoted(string(&mut characters)?));
+        } else {
+            let mut word = String::new();
+            while let Some(character) = characters.next_if(|c| !c.is_whitespace() && *c != '"') {
+                word.push(character);
+            }
+            words.push(Word::Bare(word));
+        }
+    }
+    Ok(words)
+}
+
+/// The bytes of a string up to its closing quote, with `\n`, `\t`, `\0`,
+/// `\xNN`, `\"` and `\\` escapes
+fn string(characters: &mut impl Iterator<Item = char>) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+    while let Some(character) = characters.next() {
+        match character {
+            '"' => return Ok(bytes),
+            '\\' => {
+                let byte = match characters.next() {
+                    Some('n') => b'\n',
+                    Some('t') => b'\t',
+                    Some('0') => 0,
+                    Some('x') => {
+                        let hex = characters.by_ref().take(2).collect::<String>();
+                        u8::from_str_radix(&hex, 16)
+                            .map_err(|_| format!("invalid escape \\x{hex}"))?
+                    }
+                    Some(c @ ('"' | '\\')) => c as u8,
+                    Some(c) => return Err(format!("invalid escape \\{c}")),
+                    None => break,
+                };
+                bytes.push(byte);
+            }
+            c => bytes.extend(c.to_string().as_bytes()),
+        }
+    }
+    Err("unterminated string".to_string())
+}
+
+fn number(word: &Word) -> Result<usize, String> {
+    match word {
+        Word::Bare(word) => word
+            .parse()
+            .map_err(|_| format!("expected a number, not {word}")),
+        Word::Quoted(_) => Err("expected a number, not a string".to_string()),
+    }
+}
+
+fn parse_target(word: &Word) -> Result<Target, String> {
+    let Word::Bare(word) = word else {
+        return Err("expected tape[a..b], cell, pointer, steps or output".to_string());
+    };
+    match word.as_str() {
+        "cell" => Ok(Target::Cell),
+        "pointer" => Ok(Target::Pointer),
+        "steps" => Ok(Target::Steps),
+        "output" => Ok(Target::Output),
+        _ => {
+            let range = word
+                .strip_prefix("tape[")
+                .and_then(|range| range.strip_suffix(']'))
+                .ok_or_else(|| format!("unknown target {word}"))?;
+            let (start, end) = match range.split_once("..") {
+                Some((start, end)) => (start, end),
+                None => (range, ""),
+            };
+            let start = start
+                .parse::<usize>()
+                .map_err(|_| format!("invalid tape range {range}"))?;
+            let end = match end {
+                "" if !range.contains("..") => start + 1,
+                end => end
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid tape range {range}"))?,
+            };
+            if end < start {
+                return Err(format!("invalid tape range {range}"));
+            }
+            Ok(Target::Tape(start, end))
+        }
+    }
+}
+
+fn parse_command(words: &[Word]) -> Result<Command, String> {
+    let Word::Bare(name) = &words[0] else {
+        return Err("expected a command".to_string());
+    };
+    let arguments = &words[1..];
+
+    let command = match (name.as_str(), arguments) {
+        ("break", [index]) => Command::Break(number(index)?),
+        ("unbreak", [index]) => Command::Unbreak(number(index)?),
+        ("run", []) => Command::Run,
+        ("step", []) => Command::Step(1),
+        ("step", [count]) => Command::Step(number(count)?),
+        ("undo", []) => Command::Undo(1),
+        ("undo", [count]) => Command::Undo(number(count)?),
+        ("match", []) => Command::Match,
+        ("input", [Word::Quoted(bytes)]) => Command::Input(bytes.clone()),
+        ("print", [target]) => Command::Print(parse_target(target)?),
+        ("expect", [Word::Bare(finished)]) if finished == "finished" => Command::ExpectFinished,
+        ("expect", [target, expected @ ..]) if !expected.is_empty() => {
+            let target = parse_target(target)?;
+            let value = match (target, expected) {
+                (Target::Output, [Word::Quoted(bytes)]) => Value::Text(bytes.clone()),
+                (Target::Tape(start, end), cells) if cells.len() == end - start => Value::Cells(
+                    cells
+                        .iter()
+                        .map(|cell| {
+                            let cell = number(cell)?;
+                            u8::try_from(cell).map_err(|_| format!("{cell} doesn't fit in a cell"))
+                        })
+                        .collect::<Result<_, _>>()?,
+                ),
+                (Target::Cell | Target::Pointer | Target::Steps, [value]) => {
+                    Value::Number(number(value)?)
+                }
+                (target, _) => return Err(format!("wrong kind of value to expect of {target}")),
+            };
+            Command::Expect(target, value)
+        }
+        ("break" | "unbreak" | "run" | "step" | "undo" | "input" | "print" | "expect", _) => {
+            return Err(format!("wrong arguments for {name}"))
+        }
+        _ => return Err(format!("unknown command {name}")),
+    };
+    Ok(command)
+}
+
+impl Target {
+    fn value(&self, engine: &Engine) -> Value {
+        match *self {
+            Target::Tape(start, end) => Value::Cells(
+                (start..end)
+                    .map(|i| engine.tape.get(i).copied().unwrap_or(0))
+                    .collect(),
+            ),
+            Target::Cell => Value::Number(engine.peek() as usize),
+            Target::Pointer => Value::Number(engine.tape_pointer),
+            Target::Steps => Value::Number(engine.history.len()),
+            Target::Output => Value::Text(engine.output.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Tape(start, end) => write!(fmt, "tape[{start}..{end}]"),
+            Target::Cell => write!(fmt, "cell"),
+            Target::Pointer => write!(fmt, "pointer"),
+            Target::Steps => write!(fmt, "steps"),
+            Target::Output => write!(fmt, "output"),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Cells(cells) => {
+                let cells = cells.iter().map(u8::to_string).collect::<Vec<_>>();
+                write!(fmt, "{}", cells.join(" "))
+            }
+            Value::Number(number) => write!(fmt, "{number}"),
+            Value::Text(bytes) => write!(fmt, "\"{}\"", bytes.escape_ascii()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    fn engine(code: &str) -> Engine {
+        Engine::new(overflow::instruction_set().parse(code))
+    }
+
+    fn run(code: &str, script: &str) -> Result<String, ScriptError> {
+        let mut out = vec![];
+        Script::parse(script)?.run(&mut engine(code), &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn parses_statements_and_strings() {
+        let script = Script::parse("break 3; run # to the loop\nexpect output \"a;b\\n\"").unwrap();
+        assert_eq!(
+            script.commands,
+            vec![
+                (1, Command::Break(3)),
+                (1, Command::Run),
+                (
+                    2,
+                    Command::Expect(Target::Output, Value::Text(b"a;b\n".to_vec()))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn runs_to_breakpoints_and_prints() {
+        let out = run(
+            "++>+++<[->+<]>.",
+            "break 7; run; print tape[0..2]; run; print output",
+        )
+        .unwrap();
+        assert_eq!(out, "tape[0..2] = 2 3\noutput = \"\\x05\"\n");
+    }
+
+    #[test]
+    fn reports_failed_expectations_with_their_line() {
+        let error = run("+++", "run\nexpect cell 2").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert_eq!(error.message, "expected cell to be 2, but it was 3");
+    }
+
+    #[test]
+    fn matches_brackets_without_running_them() {
+        assert_eq!(
+            run("+[-]", "step 2; match; step 2; expect steps 3; expect cell 0"),
+            Ok(String::new())
+        );
+        let error = run("+[-]", "match").unwrap_err();
+        assert_eq!(
+            error.message,
+            "already at the start of the instruction list"
+        );
+    }
+
+    #[test]
+    fn feeds_input() {
+        assert!(run(",.", "run").is_err());
+        assert_eq!(
+            run(
+                ",.",
+                "input \"hi\"; run; expect output \"h\"; expect finished"
+            ),
+            Ok(String::new())
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use crate::engine::Engine;
+use crate::instruction::InstructionSet;
+use crate::program::Program;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever the format changes in a way older versions can't read
+pub const VERSION: u64 = 1;
+
+/// Everything needed to pick a debugging session back up where it was left.
+/// Breakpoints are `$` instructions, so they're saved as part of the source.
+#[derive(Debug, Serialize, Deserialize)]
+struct Session {
+    version: u64,
+    filepath: Option<PathBuf>,
+    source: Vec<String>,
+    engine: Engine,
+    stdin: Option<Vec<u8>>,
+    input_buffer: Vec<u8>,
+    furthest_step: usize,
+}
+
+/// Where a program's session is saved to, next to the program itself
+pub fn default_path(program: &Program) -> PathBuf {
+    match &program.editor.filepath {
+        Some(filepath) => {
+            let mut path = filepath.clone().into_os_string();
+            path.push(".session.json");
+            PathBuf::from(path)
+        }
+        None => PathBuf::from("plaque.session.json"),
+    }
+}
+
+pub fn save<P: AsRef<Path>>(program: &Program, path: P) -> Result<()> {
+    let session = Session {
+        version: VERSION,
+        filepath: program.editor.filepath.clone(),
+        source: program.editor.lines.clone(),
+        engine: program.engine.clone(),
+        stdin: program.stdin.clone(),
+        input_buffer: program.input_buffer.clone(),
+        furthest_step: program.furthest_step,
+    };
+    std::fs::write(path, serde_json::to_string(&session)?)?;
+    Ok(())
+}
+
+pub fn load<P: AsRef<Path>>(path: P, instruction_set: InstructionSet) -> Result<Program> {
+    let session: serde_json::Value = serde_json::from_slice(&std::fs::read(path)?)?;
+    match session["version"].as_u64() {
+        Some(VERSION) => {}
+        Some(version) => return Err(anyhow!("unsupported session version {version}")),
+        None => return Err(anyhow!("not a session file")),
+    }
+    let mut session: Session = serde_json::from_value(session)?;
+    session.engine.bind_instructions(&instruction_set)?;
+
+    let mut program = Program::new();
+    program.set_instructions(instruction_set);
+    program.editor.filepath = session.filepath;
+    program.editor.lines = session.source;
+    program.engine = session.engine;
+    program.stdin = session.stdin;
+    program.input_buffer = session.input_buffer;
+    program.furthest_step = session.furthest_step;
+
+    // the saved instructions only record their symbols, so take them from
+    // the source again, as the instruction set defines them
+    program.index_instructions();
+
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    #[test]
+    fn saved_session_resumes_where_it_left_off() {
+        let mut program = Program::blank(overflow::instruction_set());
+        program.editor.lines = vec!["++".to_string(), ">+$,".to_string()];
+        program.index_instructions();
+        program.set_stdin(Some(b"x".to_vec()));
+        program.step_until_exception();
+
+        let path = std::env::temp_dir().join(format!("plaque-{}.session.json", std::process::id()));
+        save(&program, &path).unwrap();
+        let mut resumed = load(&path, overflow::instruction_set()).unwrap();
+
+        assert_eq!(resumed.editor.lines, program.editor.lines);
+        assert_eq!(resumed.engine, program.engine);
+        assert_eq!(resumed.stdin, Some(b"x".to_vec()));
+
+        resumed.step_until_exception();
+        assert_eq!(resumed.engine.tape, vec![2, b'x']);
+        resumed.undo().unwrap();
+        assert_eq!(resumed.engine.tape, vec![2, 1]);
+    }
+}
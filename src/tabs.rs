@@ -0,0 +1,53 @@
+use crate::program::Program;
+
+/// Several programs open in one TUI session, each with its own engine and
+/// editor, of which one is active at a time.
+#[derive(Debug)]
+pub struct Tabs {
+    pub programs: Vec<Program>,
+    pub active: usize,
+    pub overview: bool,
+}
+
+impl Tabs {
+    pub fn new(programs: Vec<Program>) -> Tabs {
+        Tabs {
+            programs,
+            active: 0,
+            overview: false,
+        }
+    }
+
+    pub fn active(&self) -> &Program {
+        &self.programs[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut Program {
+        &mut self.programs[self.active]
+    }
+
+    pub fn select(&mut self, index: usize) {
+        if index >= self.programs.len() || index == self.active {
+            return;
+        }
+
+        // the clipboard follows the user between tabs
+        let clipboard = self.active().editor.clipboard.clone();
+        self.active = index;
+        if clipboard.is_some() {
+            self.active_mut().editor.clipboard = clipboard;
+        }
+    }
+
+    pub fn next(&mut self) {
+        self.select((self.active + 1) % self.programs.len());
+    }
+
+    pub fn prev(&mut self) {
+        self.select((self.active + self.programs.len() - 1) % self.programs.len());
+    }
+
+    pub fn toggle_overview(&mut self) {
+        self.overview = !self.overview;
+    }
+}
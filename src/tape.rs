@@ -0,0 +1,536 @@
+use crate::engine::TapeModel;
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::{Index, IndexMut, Range};
+
+/// Cells in each page of a paged tape
+const PAGE: usize = 4096;
+
+/// What a page a paged tape hasn't written to reads as
+static ZEROES: [u8; PAGE] = [0; PAGE];
+
+/// With `cow-tape`, copies of a paged tape share the pages neither has
+/// changed since, so copying one costs a pointer per page
+#[cfg(feature = "cow-tape")]
+type Page = alloc::sync::Arc<Vec<u8>>;
+#[cfg(not(feature = "cow-tape"))]
+type Page = Vec<u8>;
+
+#[cfg(feature = "cow-tape")]
+fn page_mut(page: &mut Page) -> &mut Vec<u8> {
+    alloc::sync::Arc::make_mut(page)
+}
+
+#[cfg(not(feature = "cow-tape"))]
+fn page_mut(page: &mut Page) -> &mut Vec<u8> {
+    page
+}
+
+/// Whether two tapes share a page, so it can't differ between them
+#[cfg(feature = "cow-tape")]
+fn shared(a: &Page, b: &Page) -> bool {
+    alloc::sync::Arc::ptr_eq(a, b)
+}
+
+#[cfg(not(feature = "cow-tape"))]
+fn shared(_: &Page, _: &Page) -> bool {
+    false
+}
+
+/// The cells of an engine's tape, up to the furthest one reached.
+///
+/// Cells are in one block by default, but a paged tape only stores pages
+/// of cells that have been written to, for programs that reach cells
+/// millions apart. Either way cells read the same, with ones never written
+/// to as zero.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Tape {
+    Dense(Vec<u8>),
+    Paged {
+        /// Pages by their number, counting from the first cell
+        pages: BTreeMap<usize, Page>,
+        len: usize,
+    },
+}
+
+impl Default for Tape {
+    fn default() -> Tape {
+        Tape::new(TapeModel::default())
+    }
+}
+
+impl Tape {
+    /// A tape of one zero cell, stored as suits a tape model. Every tape
+    /// is paged with `cow-tape`, so that engines are cheap to copy.
+    pub fn new(model: TapeModel) -> Tape {
+        match model {
+            TapeModel::Unbounded | TapeModel::Fixed(_) if !cfg!(feature = "cow-tape") => {
+                Tape::Dense(vec![0])
+            }
+            _ => Tape::Paged {
+                pages: BTreeMap::new(),
+                len: 1,
+            },
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Tape::Dense(cells) => cells.len(),
+            Tape::Paged { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<&u8> {
+        match self {
+            Tape::Dense(cells) => cells.get(index),
+            Tape::Paged { len, .. } if index >= *len => None,
+            Tape::Paged { pages, .. } => Some(
+                pages
+                    .get(&(index / PAGE))
+                    .map_or(&0, |page| &page[index % PAGE]),
+            ),
+        }
+    }
+
+    /// A cell to change, adding the page it's on to a paged tape, or copying
+    /// it if it's shared
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut u8> {
+        match self {
+            Tape::Dense(cells) => cells.get_mut(index),
+            Tape::Paged { len, .. } if index >= *len => None,
+            Tape::Paged { pages, .. } => {
+                let page = pages
+                    .entry(index / PAGE)
+                    .or_insert_with(|| Page::from(vec![0; PAGE]));
+                Some(&mut page_mut(page)[index % PAGE])
+            }
+        }
+    }
+
+    /// Grow the tape with zeroes, or cut it short
+    pub fn resize(&mut self, new_len: usize) {
+        match self {
+            Tape::Dense(cells) => cells.resize(new_len, 0),
+            Tape::Paged { pages, len } => {
+                if new_len < *len {
+                    // zero what's cut off, so it reads as zero if reached again
+                    pages.retain(|&number, _| number * PAGE < new_len);
+                    if let Some(page) = pages.get_mut(&(new_len / PAGE)) {
+                        page_mut(page)[new_len % PAGE..].fill(0);
+                    }
+                }
+                *len = new_len;
+            }
+        }
+    }
+
+    /// Every cell, including the zeroes a paged tape doesn't store
+    pub fn iter(&self) -> impl Iterator<Item = &u8> + '_ {
+        self.pages().flatten()
+    }
+
+    /// The cells of a page's worth of the tape, as far as the tape goes,
+    /// with a page a paged tape hasn't written to as zeroes
+    fn page(&self, number: usize) -> &[u8] {
+        let range = number * PAGE..self.len().min((number + 1) * PAGE);
+        match self {
+            Tape::Dense(cells) => &cells[range],
+            Tape::Paged { pages, .. } => match pages.get(&number) {
+                Some(page) => &page[..range.len()],
+                None => &ZEROES[..range.len()],
+            },
+        }
+    }
+
+    fn pages(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        (0..self.len().div_ceil(PAGE)).map(|number| self.page(number))
+    }
+
+    /// The blocks of cells the tape stores, with the index of the first cell
+    /// of each, skipping over cells a paged tape has never written
+    pub fn regions(&self) -> impl Iterator<Item = (usize, &[u8])> + '_ {
+        let (dense, sparse) = match self {
+            Tape::Dense(cells) => (Some((0, cells.as_slice())), None),
+            Tape::Paged { pages, len } => {
+                let pages = pages.iter().map(move |(&number, page)| {
+                    let start = number * PAGE;
+                    (start, &page[..PAGE.min(len - start)])
+                });
+                (None, Some(pages))
+            }
+        };
+        dense.into_iter().chain(sparse.into_iter().flatten())
+    }
+
+    /// The cells as one block, if that's how they're stored
+    pub fn as_slice(&self) -> Option<&[u8]> {
+        match self {
+            Tape::Dense(cells) => Some(cells),
+            Tape::Paged { .. } => None,
+        }
+    }
+
+    /// The cells as one block, moving a paged tape's into one first
+    pub fn make_dense(&mut self) -> &mut Vec<u8> {
+        if let Tape::Paged { .. } = self {
+            *self = Tape::Dense(self.to_vec());
+        }
+        match self {
+            Tape::Dense(cells) => cells,
+            Tape::Paged { .. } => unreachable!(),
+        }
+    }
+
+    /// A copy of the cells in a range, which must be on the tape
+    pub fn read(&self, range: Range<usize>) -> Vec<u8> {
+        match self {
+            Tape::Dense(cells) => cells[range].to_vec(),
+            Tape::Paged { .. } => range.map(|index| self[index]).collect(),
+        }
+    }
+
+    /// Overwrite cells from an offset on, which must all be on the tape
+    pub fn write(&mut self, offset: usize, bytes: &[u8]) {
+        match self {
+            Tape::Dense(cells) => cells[offset..offset + bytes.len()].copy_from_slice(bytes),
+            Tape::Paged { .. } => {
+                for (index, &byte) in (offset..).zip(bytes) {
+                    self[index] = byte;
+                }
+            }
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        match self {
+            Tape::Dense(cells) => cells.clone(),
+            Tape::Paged { len, .. } => {
+                let mut cells = vec![0; *len];
+                for (start, region) in self.regions() {
+                    cells[start..start + region.len()].copy_from_slice(region);
+                }
+                cells
+            }
+        }
+    }
+
+    /// Each cell that differs from another tape, with its value on each and
+    /// cells past the end of either as zero. Pages two paged tapes share,
+    /// or that neither has written to, aren't looked through.
+    pub fn diff(&self, other: &Tape) -> Vec<(usize, u8, u8)> {
+        let len = self.len().max(other.len());
+        let ranges: Vec<Range<usize>> = match (self, other) {
+            (Tape::Paged { pages: before, .. }, Tape::Paged { pages: after, .. }) => {
+                let numbers = before.keys().chain(after.keys()).collect::<BTreeSet<_>>();
+                numbers
+                    .into_iter()
+                    .filter(|number| match (before.get(number), after.get(number)) {
+                        (Some(before), Some(after)) => !shared(before, after),
+                        _ => true,
+                    })
+                    .map(|number| number * PAGE..len.min((number + 1) * PAGE))
+                    .collect()
+            }
+            _ => core::iter::once(0..len).collect(),
+        };
+
+        let cell = |tape: &Tape, i: usize| tape.get(i).copied().unwrap_or(0);
+        ranges
+            .into_iter()
+            .flatten()
+            .map(|i| (i, cell(self, i), cell(other, i)))
+            .filter(|&(_, before, after)| before != after)
+            .collect()
+    }
+
+    /// Where a sequence of bytes starts on the tape, including overlapping
+    /// matches. Unless the bytes are all zero, a paged tape is only searched
+    /// around the pages it's written to, as a match can't be anywhere else.
+    pub fn find(&self, pattern: &[u8]) -> Vec<usize> {
+        if pattern.is_empty() {
+            return vec![];
+        }
+        let matches = |cells: &[u8], start: usize| {
+            cells
+                .windows(pattern.len())
+                .enumerate()
+                .filter(|(_, window)| *window == pattern)
+                .map(|(i, _)| start + i)
+                .collect::<Vec<_>>()
+        };
+        let pages = match self {
+            Tape::Paged { pages, .. } if pattern.iter().any(|&byte| byte != 0) => pages,
+            _ => return matches(&self.to_vec(), 0),
+        };
+
+        // each run of pages next to each other, reaching far enough either
+        // side to take in matches that only start or end on the run
+        let mut runs: Vec<Range<usize>> = vec![];
+        for &number in pages.keys() {
+            match runs.last_mut() {
+                Some(run) if run.end == number => run.end += 1,
+                _ => runs.push(number..number + 1),
+            }
+        }
+        let reach = pattern.len() - 1;
+        let mut found = runs
+            .into_iter()
+            .flat_map(|run| {
+                let start = (run.start * PAGE).saturating_sub(reach);
+                let end = self.len().min(run.end * PAGE + reach);
+                matches(&self.read(start..end), start)
+            })
+            .collect::<Vec<_>>();
+        // runs reaching into each other can find the same match
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+}
+
+impl Index<usize> for Tape {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &u8 {
+        let len = self.len();
+        self.get(index)
+            .unwrap_or_else(|| panic!("cell {index} is past the end of a tape of {len}"))
+    }
+}
+
+impl IndexMut<usize> for Tape {
+    fn index_mut(&mut self, index: usize) -> &mut u8 {
+        let len = self.len();
+        self.get_mut(index)
+            .unwrap_or_else(|| panic!("cell {index} is past the end of a tape of {len}"))
+    }
+}
+
+impl From<Vec<u8>> for Tape {
+    fn from(cells: Vec<u8>) -> Tape {
+        Tape::Dense(cells)
+    }
+}
+
+/// Tapes are equal with the same cells, however they're stored. Two paged
+/// tapes are only compared on the pages either has written to.
+impl PartialEq for Tape {
+    fn eq(&self, other: &Tape) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+        match (self, other) {
+            (Tape::Paged { pages: a, .. }, Tape::Paged { pages: b, .. }) => (a.keys())
+                .chain(b.keys())
+                .all(|&number| self.page(number) == other.page(number)),
+            _ => self.pages().eq(other.pages()),
+        }
+    }
+}
+
+impl Eq for Tape {}
+
+impl PartialEq<Vec<u8>> for Tape {
+    fn eq(&self, other: &Vec<u8>) -> bool {
+        self.len() == other.len() && self.pages().eq(other.chunks(PAGE))
+    }
+}
+
+/// How a cell's value is written out, each at a fixed width so cells line
+/// up however they're shown.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CellFormat {
+    #[default]
+    Decimal,
+    Hex,
+    /// Printable characters as themselves, common control characters as
+    /// escapes and anything else in hex
+    Ascii,
+    Binary,
+}
+
+impl CellFormat {
+    pub fn width(self) -> usize {
+        match self {
+            CellFormat::Decimal => 3,
+            CellFormat::Hex => 2,
+            CellFormat::Ascii => 3,
+            CellFormat::Binary => 8,
+        }
+    }
+
+    pub fn format(self, cell: u8) -> String {
+        match self {
+            CellFormat::Decimal => format!("{cell:0>3}"),
+            CellFormat::Hex => format!("{cell:02X}"),
+            CellFormat::Ascii => match cell {
+                b' '..=b'~' => format!(" {} ", cell as char),
+                b'\0' => String::from(" \\0"),
+                b'\t' => String::from(" \\t"),
+                b'\n' => String::from(" \\n"),
+                b'\r' => String::from(" \\r"),
+                _ => format!("x{cell:02X}"),
+            },
+            CellFormat::Binary => format!("{cell:08b}"),
+        }
+    }
+
+    /// The format after this one, for cycling through them
+    pub fn next(self) -> CellFormat {
+        match self {
+            CellFormat::Decimal => CellFormat::Hex,
+            CellFormat::Hex => CellFormat::Ascii,
+            CellFormat::Ascii => CellFormat::Binary,
+            CellFormat::Binary => CellFormat::Decimal,
+        }
+    }
+}
+
+impl core::str::FromStr for CellFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<CellFormat, String> {
+        match format {
+            "dec" | "decimal" => Ok(CellFormat::Decimal),
+            "hex" => Ok(CellFormat::Hex),
+            "ascii" => Ok(CellFormat::Ascii),
+            "bin" | "binary" => Ok(CellFormat::Binary),
+            _ => Err(format!(
+                "unknown cell format {format}, expected dec, hex, ascii or bin"
+            )),
+        }
+    }
+}
+
+/// Write out a range of cells separated by `|`, with cells past the end of
+/// the tape as the zeroes they'd be once reached.
+pub fn render(tape: &[u8], range: Range<usize>, format: CellFormat) -> String {
+    range
+        .map(|i| format.format(tape.get(i).copied().unwrap_or(0)))
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_keep_their_width() {
+        for format in [
+            CellFormat::Decimal,
+            CellFormat::Hex,
+            CellFormat::Ascii,
+            CellFormat::Binary,
+        ] {
+            for cell in 0..=u8::MAX {
+                assert_eq!(
+                    format.format(cell).len(),
+                    format.width(),
+                    "{format:?} {cell}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sparse_tapes_read_like_dense_ones() {
+        let mut sparse = Tape::new(TapeModel::Sparse);
+        let mut dense = Tape::default();
+        for tape in [&mut sparse, &mut dense] {
+            tape.resize(3 * PAGE);
+            tape[1] = 1;
+            tape[2 * PAGE + 5] = 7;
+            tape.write(PAGE - 1, &[2, 3]);
+        }
+        assert_eq!(sparse, dense);
+        assert_eq!(sparse.get(3 * PAGE), None);
+        assert_eq!(sparse.find(&[2, 3]), vec![PAGE - 1]);
+        assert_eq!(sparse.read(PAGE - 1..PAGE + 1), vec![2, 3]);
+
+        let regions = sparse.regions().map(|(start, cells)| (start, cells.len()));
+        assert_eq!(
+            regions.collect::<Vec<_>>(),
+            vec![(0, PAGE), (PAGE, PAGE), (2 * PAGE, PAGE)]
+        );
+
+        sparse.resize(2 * PAGE + 1);
+        assert_eq!(sparse.regions().last(), Some((2 * PAGE, &[0][..])));
+        sparse.resize(3 * PAGE);
+        assert_eq!(sparse[2 * PAGE + 5], 0);
+    }
+
+    #[test]
+    fn untouched_sparse_pages_take_no_room() {
+        let mut tape = Tape::new(TapeModel::Sparse);
+        tape.resize(10_000_000);
+        tape[9_999_999] = 1;
+        assert_eq!(tape.regions().count(), 1);
+        assert_eq!(tape.iter().filter(|&&cell| cell != 0).count(), 1);
+
+        tape[5 * PAGE - 1] = 1;
+        assert_eq!(tape.find(&[0, 1]), vec![5 * PAGE - 2, 9_999_998]);
+        assert_eq!(tape.find(&[1, 0]), vec![5 * PAGE - 1]);
+        let mut copy = tape.clone();
+        assert_eq!(copy, tape);
+        copy[5 * PAGE] = 1;
+        assert_ne!(copy, tape);
+    }
+
+    #[test]
+    fn diffs_skip_pages_neither_tape_has() {
+        let mut before = Tape::new(TapeModel::Sparse);
+        before.resize(100 * PAGE);
+        before[3] = 1;
+        let mut after = before.clone();
+        after[3] = 2;
+        after[50 * PAGE] = 5;
+        after.resize(100 * PAGE + 1);
+        after[100 * PAGE] = 6;
+
+        let expected = vec![(3, 1, 2), (50 * PAGE, 0, 5), (100 * PAGE, 0, 6)];
+        assert_eq!(before.diff(&after), expected);
+        assert_eq!(before.diff(&Tape::from(after.to_vec())), expected);
+    }
+
+    #[cfg(feature = "cow-tape")]
+    #[test]
+    fn copies_share_pages_until_written() {
+        let mut tape = Tape::new(TapeModel::Unbounded);
+        tape.resize(2 * PAGE);
+        tape[0] = 1;
+        tape[PAGE] = 2;
+        let mut copy = tape.clone();
+        copy[PAGE] = 3;
+
+        let (
+            Tape::Paged {
+                pages: original, ..
+            },
+            Tape::Paged { pages: copied, .. },
+        ) = (&tape, &copy)
+        else {
+            panic!("expected paged tapes");
+        };
+        assert!(shared(&original[&0], &copied[&0]));
+        assert!(!shared(&original[&1], &copied[&1]));
+        assert_eq!(tape.diff(&copy), vec![(PAGE, 2, 3)]);
+    }
+
+    #[test]
+    fn render_pads_past_the_end() {
+        let tape = [b'A', 10, 255];
+        assert_eq!(render(&tape, 0..4, CellFormat::Ascii), " A | \\n|xFF| \\0");
+        assert_eq!(render(&tape, 1..3, CellFormat::Hex), "0A|FF");
+    }
+}
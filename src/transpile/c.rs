@@ -0,0 +1,250 @@
+use super::{nodes, Emitter, TranspileError};
+use crate::flavor::Eof;
+use crate::instruction::Instruction;
+use crate::ir::Op;
+
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CellWidth {
+    #[default]
+    Eight,
+    Sixteen,
+    ThirtyTwo,
+}
+
+impl CellWidth {
+    pub fn bits(self) -> u32 {
+        match self {
+            CellWidth::Eight => 8,
+            CellWidth::Sixteen => 16,
+            CellWidth::ThirtyTwo => 32,
+        }
+    }
+
+    /// The smallest C89 type guaranteed to hold a cell
+    fn c_type(self) -> &'static str {
+        match self {
+            CellWidth::Eight => "unsigned char",
+            CellWidth::Sixteen => "unsigned short",
+            CellWidth::ThirtyTwo => "unsigned long",
+        }
+    }
+
+    fn mask(self) -> u64 {
+        (1 << self.bits()) - 1
+    }
+
+    /// An amount as an unsigned long literal, wrapped to this width
+    fn literal(self, amount: isize) -> String {
+        format!("{}UL", (amount as i64).rem_euclid(1 << self.bits()))
+    }
+}
+
+impl FromStr for CellWidth {
+    type Err = String;
+
+    fn from_str(bits: &str) -> Result<CellWidth, String> {
+        match bits {
+            "8" => Ok(CellWidth::Eight),
+            "16" => Ok(CellWidth::Sixteen),
+            "32" => Ok(CellWidth::ThirtyTwo),
+            _ => Err(format!("unsupported cell width {bits}")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct COptions {
+    pub cell_width: CellWidth,
+    /// The number of cells, past which the program stops with an error
+    pub tape_size: usize,
+    pub eof: Eof,
+}
+
+impl Default for COptions {
+    fn default() -> COptions {
+        COptions {
+            cell_width: CellWidth::default(),
+            tape_size: 30_000,
+            eof: Eof::default(),
+        }
+    }
+}
+
+const PRELUDE: &str = r#"static cell tape[TAPE_SIZE];
+static size_t pointer = 0;
+
+void fail(const char *message)
+{
+    fflush(stdout);
+    fprintf(stderr, "%s\n", message);
+    exit(1);
+}
+
+void reach(size_t low, size_t high)
+{
+    if (pointer < low) {
+        fail("can't move tape pointer before the first cell");
+    }
+    if (pointer + high >= TAPE_SIZE) {
+        fail("can't move tape pointer past the end of the tape");
+    }
+}
+
+void left(size_t offset)
+{
+    reach(offset, 0);
+    pointer -= offset;
+}
+
+void right(size_t offset)
+{
+    reach(0, offset);
+    pointer += offset;
+}
+
+void add(long offset, unsigned long value)
+{
+    size_t target = pointer + offset;
+    tape[target] = (cell)((tape[target] + value) & MASK);
+}
+
+void write_cell(void)
+{
+    putchar((int)(tape[pointer] & 0xFF));
+}
+"#;
+
+/// A portable C89 program that behaves like the given one does in the
+/// interpreter, except with the given cell width and a fixed size tape.
+pub fn to_c(instructions: &[Instruction], options: &COptions) -> Result<String, TranspileError> {
+    let nodes = nodes(instructions)?;
+    let width = options.cell_width;
+
+    let mut emitter = Emitter::new();
+    emitter.line("#include <stdio.h>");
+    emitter.line("#include <stdlib.h>");
+    emitter.line("");
+    emitter.line(format!("#define TAPE_SIZE {}", options.tape_size));
+    emitter.line(format!("#define MASK {:#X}UL", width.mask()));
+    emitter.line("");
+    emitter.line(format!("typedef {} cell;", width.c_type()));
+    emitter.line("");
+    emitter.code.push_str(PRELUDE);
+    emitter.line("");
+
+    emitter.line("void read_cell(void)");
+    emitter.open("{");
+    emitter.line("int byte = getchar();");
+    emitter.open("if (byte != EOF) {");
+    emitter.line("tape[pointer] = (cell)byte;");
+    let eof = match options.eof {
+        Eof::RequestInput => Some("fail(\"the program needs more input\");"),
+        Eof::Zero => Some("tape[pointer] = 0;"),
+        Eof::Unchanged => None,
+        Eof::Max => Some("tape[pointer] = (cell)MASK;"),
+    };
+    if let Some(eof) = eof {
+        emitter.close("} else {");
+        emitter.depth += 1;
+        emitter.line(eof);
+    }
+    emitter.close("}");
+    emitter.close("}");
+    emitter.line("");
+
+    emitter.line("int main(void)");
+    emitter.open("{");
+    for node in &nodes {
+        match &node.op {
+            Op::Add(amount) => emitter.line(format!(
+                "tape[pointer] = (cell)((tape[pointer] + {}) & MASK);",
+                width.literal(*amount)
+            )),
+            Op::Move(offset) if *offset < 0 => emitter.line(format!("left({});", -offset)),
+            Op::Move(offset) => emitter.line(format!("right({offset});")),
+            Op::Clear => emitter.line("tape[pointer] = 0;"),
+            Op::Transfer { targets, low, high } => {
+                emitter.open("if (tape[pointer] != 0) {");
+                emitter.line("unsigned long value = tape[pointer];");
+                emitter.line(format!("reach({}, {});", -low, high));
+                for (offset, factor) in targets {
+                    emitter.line(format!(
+                        "add({offset}L, value * {});",
+                        width.literal(*factor)
+                    ));
+                }
+                emitter.line("tape[pointer] = 0;");
+                emitter.close("}");
+            }
+            Op::Scan(stride) => {
+                emitter.open("while (tape[pointer] != 0) {");
+                if *stride < 0 {
+                    emitter.line(format!("left({});", -stride));
+                } else {
+                    emitter.line(format!("right({stride});"));
+                }
+                emitter.close("}");
+            }
+            Op::Instruction(instruction) => match instruction.symbol {
+                _ if instruction.opens_loop() => emitter.open("while (tape[pointer] != 0) {"),
+                _ if instruction.closes_loop() => emitter.close("}"),
+                '.' => emitter.line("write_cell();"),
+                ',' => emitter.line("read_cell();"),
+                _ => {}
+            },
+        }
+    }
+    emitter.line("fflush(stdout);");
+    emitter.line("return 0;");
+    emitter.close("}");
+
+    Ok(emitter.code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    fn c(code: &str, options: &COptions) -> String {
+        to_c(&overflow::instruction_set().parse(code), options).unwrap()
+    }
+
+    #[test]
+    fn wraps_amounts_at_the_cell_width() {
+        let eight = c("-[+>+<]", &COptions::default());
+        assert!(eight.contains("typedef unsigned char cell;"));
+        assert!(eight.contains("(tape[pointer] + 255UL) & MASK"));
+        assert!(eight.contains("add(1L, value * 255UL);"));
+
+        let options = COptions {
+            cell_width: CellWidth::Sixteen,
+            ..COptions::default()
+        };
+        let sixteen = c("-[+>+<]", &options);
+        assert!(sixteen.contains("#define MASK 0xFFFFUL"));
+        assert!(sixteen.contains("(tape[pointer] + 65535UL) & MASK"));
+        assert!(sixteen.contains("add(1L, value * 65535UL);"));
+    }
+
+    #[test]
+    fn uses_only_c89_comments_and_declarations() {
+        let code = c("+[->+<]>,.", &COptions::default());
+        assert!(!code.contains("//"));
+        assert!(!code.contains("stdint"));
+    }
+
+    #[test]
+    fn sizes_the_tape() {
+        let options = COptions {
+            tape_size: 64,
+            eof: Eof::Zero,
+            ..COptions::default()
+        };
+        let code = c(",.", &options);
+        assert!(code.contains("#define TAPE_SIZE 64"));
+        assert!(code.contains("tape[pointer] = 0;"));
+    }
+}
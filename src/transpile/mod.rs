@@ -0,0 +1,91 @@
+mod c;
+mod rust;
+mod wasm;
+
+pub use c::{to_c, COptions, CellWidth};
+pub use rust::to_rust;
+pub use wasm::{to_wasm, WasmOptions};
+
+use crate::instruction::Instruction;
+use crate::ir::{self, Node, Op};
+
+use std::fmt;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TranspileError {
+    Unsupported(char),
+    UnmatchedBracket(usize),
+}
+
+impl fmt::Display for TranspileError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranspileError::Unsupported(symbol) => {
+                write!(fmt, "can't transpile instruction {symbol}")
+            }
+            TranspileError::UnmatchedBracket(index) => {
+                write!(fmt, "unmatched bracket at instruction {index}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TranspileError {}
+
+/// Source code under construction, indented by loop depth
+struct Emitter {
+    code: String,
+    depth: usize,
+}
+
+impl Emitter {
+    fn new() -> Emitter {
+        Emitter {
+            code: String::new(),
+            depth: 0,
+        }
+    }
+
+    fn line<S: AsRef<str>>(&mut self, line: S) {
+        for _ in 0..self.depth {
+            self.code.push_str("    ");
+        }
+        self.code.push_str(line.as_ref());
+        self.code.push('\n');
+    }
+
+    fn open<S: AsRef<str>>(&mut self, line: S) {
+        self.line(line);
+        self.depth += 1;
+    }
+
+    fn close<S: AsRef<str>>(&mut self, line: S) {
+        self.depth -= 1;
+        self.line(line);
+    }
+}
+
+/// The IR for a program, checked for anything a transpiler can't express
+fn nodes(instructions: &[Instruction]) -> Result<Vec<Node>, TranspileError> {
+    let nodes = ir::build(instructions);
+
+    let mut open = vec![];
+    for node in &nodes {
+        if let Op::Instruction(instruction) = &node.op {
+            match instruction.symbol {
+                _ if instruction.opens_loop() => open.push(node.origin),
+                _ if instruction.closes_loop() => {
+                    open.pop()
+                        .ok_or(TranspileError::UnmatchedBracket(node.origin))?;
+                }
+                '+' | '-' | '<' | '>' | '.' | ',' | '$' => {}
+                symbol => return Err(TranspileError::Unsupported(symbol)),
+            }
+        }
+    }
+    if let Some(&origin) = open.last() {
+        return Err(TranspileError::UnmatchedBracket(origin));
+    }
+
+    Ok(nodes)
+}
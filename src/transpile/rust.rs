@@ -0,0 +1,209 @@
+use super::{nodes, Emitter, TranspileError};
+use crate::engine::TapeModel;
+use crate::flavor::Eof;
+use crate::instruction::Instruction;
+use crate::ir::Op;
+
+const PRELUDE: &str = r#"#![allow(dead_code)]
+
+use std::io::{Bytes, BufWriter, Read, StdinLock, StdoutLock, Write};
+
+struct Machine {
+    tape: Vec<u8>,
+    /// The number of cells the tape can grow to
+    limit: usize,
+    pointer: usize,
+    input: Bytes<StdinLock<'static>>,
+    output: BufWriter<StdoutLock<'static>>,
+}
+
+impl Machine {
+    fn fail(&mut self, message: &str) -> ! {
+        self.output.flush().ok();
+        eprintln!("{}", message);
+        std::process::exit(1)
+    }
+
+    fn reach(&mut self, low: usize, high: usize) {
+        if self.pointer < low {
+            self.fail("can't move tape pointer before the first cell");
+        }
+        if self.pointer + high >= self.limit {
+            self.fail("can't move tape pointer past the end of the tape");
+        }
+        if self.pointer + high >= self.tape.len() {
+            self.tape.resize(self.pointer + high + 1, 0);
+        }
+    }
+
+    fn left(&mut self, offset: usize) {
+        self.reach(offset, 0);
+        self.pointer -= offset;
+    }
+
+    fn right(&mut self, offset: usize) {
+        self.reach(0, offset);
+        self.pointer += offset;
+    }
+
+    fn cell(&mut self) -> &mut u8 {
+        &mut self.tape[self.pointer]
+    }
+
+    fn add(&mut self, offset: isize, value: u8) {
+        let cell = &mut self.tape[self.pointer.wrapping_add_signed(offset)];
+        *cell = cell.wrapping_add(value);
+    }
+
+    fn write(&mut self) {
+        let cell = *self.cell();
+        if self.output.write_all(&[cell]).is_err() {
+            std::process::exit(1);
+        }
+    }
+"#;
+
+/// A standalone Rust program that behaves exactly like the given one does
+/// in the interpreter, with cells wrapping on overflow and the given EOF
+/// policy and tape. Any error the interpreter would stop on ends the program
+/// instead. Sparse tapes grow like unbounded ones, as only how the cells are
+/// stored differs.
+pub fn to_rust(
+    instructions: &[Instruction],
+    eof: Eof,
+    tape: TapeModel,
+) -> Result<String, TranspileError> {
+    let nodes = nodes(instructions)?;
+
+    let mut emitter = Emitter::new();
+    emitter.code.push_str(PRELUDE);
+    emitter.depth = 1;
+    emitter.line("");
+    emitter.open("fn read(&mut self) {");
+    emitter.open("match self.input.next() {");
+    emitter.line("Some(Ok(byte)) => *self.cell() = byte,");
+    emitter.line(match eof {
+        Eof::RequestInput => "_ => self.fail(\"the program needs more input\"),",
+        Eof::Zero => "_ => *self.cell() = 0,",
+        Eof::Unchanged => "_ => {}",
+        Eof::Max => "_ => *self.cell() = u8::MAX,",
+    });
+    emitter.close("}");
+    emitter.close("}");
+    emitter.close("}");
+    emitter.line("");
+
+    emitter.open("fn main() {");
+    emitter.open("let mut m = Machine {");
+    emitter.line("tape: vec![0],");
+    emitter.line(match tape {
+        TapeModel::Fixed(cells) => format!("limit: {cells},"),
+        TapeModel::Unbounded | TapeModel::Sparse => String::from("limit: usize::MAX,"),
+    });
+    emitter.line("pointer: 0,");
+    emitter.line("input: std::io::stdin().lock().bytes(),");
+    emitter.line("output: BufWriter::new(std::io::stdout().lock()),");
+    emitter.close("};");
+    emitter.line("");
+
+    for node in &nodes {
+        match &node.op {
+            Op::Add(amount) => emitter.line(format!(
+                "*m.cell() = m.cell().wrapping_add({});",
+                *amount as u8
+            )),
+            Op::Move(offset) if *offset < 0 => emitter.line(format!("m.left({});", -offset)),
+            Op::Move(offset) => emitter.line(format!("m.right({offset});")),
+            Op::Clear => emitter.line("*m.cell() = 0;"),
+            Op::Transfer { targets, low, high } => {
+                emitter.open("if *m.cell() != 0 {");
+                emitter.line(format!("m.reach({}, {});", -low, high));
+                emitter.line("let cell = *m.cell();");
+                for (offset, factor) in targets {
+                    emitter.line(format!(
+                        "m.add({offset}, cell.wrapping_mul({}));",
+                        *factor as u8
+                    ));
+                }
+                emitter.line("*m.cell() = 0;");
+                emitter.close("}");
+            }
+            Op::Scan(stride) => {
+                emitter.open("while *m.cell() != 0 {");
+                if *stride < 0 {
+                    emitter.line(format!("m.left({});", -stride));
+                } else {
+                    emitter.line(format!("m.right({stride});"));
+                }
+                emitter.close("}");
+            }
+            Op::Instruction(instruction) => match instruction.symbol {
+                _ if instruction.opens_loop() => emitter.open("while *m.cell() != 0 {"),
+                _ if instruction.closes_loop() => emitter.close("}"),
+                '.' => emitter.line("m.write();"),
+                ',' => emitter.line("m.read();"),
+                _ => {}
+            },
+        }
+    }
+
+    emitter.line("");
+    emitter.line("m.output.flush().ok();");
+    emitter.close("}");
+
+    Ok(emitter.code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::{brainfork, overflow};
+
+    fn rust(code: &str, eof: Eof) -> Result<String, TranspileError> {
+        to_rust(
+            &overflow::instruction_set().parse(code),
+            eof,
+            TapeModel::Unbounded,
+        )
+    }
+
+    #[test]
+    fn emits_idioms_and_loops() {
+        let code = rust("+++[->++<]>[.-]<<", Eof::Zero).unwrap();
+
+        assert!(code.contains("*m.cell() = m.cell().wrapping_add(3);"));
+        assert!(code.contains("m.add(1, cell.wrapping_mul(2));"));
+        assert!(code.contains("    while *m.cell() != 0 {\n        m.write();"));
+        assert!(code.contains("m.left(2);"));
+        assert!(code.contains("_ => *self.cell() = 0,"));
+        assert!(code.contains("limit: usize::MAX,"));
+    }
+
+    #[test]
+    fn fixed_tapes_keep_their_size() {
+        let instructions = overflow::instruction_set().parse(">+");
+        let code = to_rust(&instructions, Eof::Zero, TapeModel::Fixed(2)).unwrap();
+        assert!(code.contains("limit: 2,"));
+    }
+
+    #[test]
+    fn rejects_unmatched_brackets() {
+        assert_eq!(
+            rust("+[[-]", Eof::Zero),
+            Err(TranspileError::UnmatchedBracket(1))
+        );
+        assert_eq!(
+            rust("+]", Eof::Zero),
+            Err(TranspileError::UnmatchedBracket(1))
+        );
+    }
+
+    #[test]
+    fn rejects_other_flavors() {
+        let instructions = brainfork::instruction_set().parse("Y");
+        assert_eq!(
+            to_rust(&instructions, Eof::Zero, TapeModel::Unbounded),
+            Err(TranspileError::Unsupported('Y'))
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use super::{nodes, Emitter, TranspileError};
+use crate::flavor::Eof;
+use crate::instruction::Instruction;
+use crate::ir::Op;
+
+const PAGE_SIZE: usize = 65_536;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct WasmOptions {
+    /// The number of cells, past which the program traps
+    pub tape_size: usize,
+    pub eof: Eof,
+}
+
+impl Default for WasmOptions {
+    fn default() -> WasmOptions {
+        WasmOptions {
+            tape_size: 30_000,
+            eof: Eof::default(),
+        }
+    }
+}
+
+/// A WebAssembly module in text format that behaves like the given program
+/// does in the interpreter, with a fixed size tape. The host provides
+/// `env.read`, returning the next input byte or -1 at the end of input, and
+/// `env.write`, then calls the exported `run`. Anything the interpreter
+/// would stop on with an error traps instead, as does `Eof::RequestInput`
+/// running out of input.
+pub fn to_wasm(
+    instructions: &[Instruction],
+    options: &WasmOptions,
+) -> Result<String, TranspileError> {
+    let nodes = nodes(instructions)?;
+    let pages = std::cmp::max(options.tape_size.div_ceil(PAGE_SIZE), 1);
+
+    let mut wasm = Wasm {
+        emitter: Emitter::new(),
+        labels: 0,
+    };
+    wasm.emitter.open("(module");
+    wasm.emitter.code.push_str(&format!(
+        r#"    (import "env" "read" (func $read (result i32)))
+    (import "env" "write" (func $write (param i32)))
+    (memory (export "memory") {pages})
+
+    (func $reach (param $p i32) (param $low i32) (param $high i32)
+        local.get $p
+        local.get $low
+        i32.lt_u
+        if
+            unreachable
+        end
+        local.get $p
+        local.get $high
+        i32.add
+        i32.const {tape_size}
+        i32.ge_u
+        if
+            unreachable
+        end
+    )
+
+"#,
+        tape_size = options.tape_size
+    ));
+
+    wasm.emitter
+        .open(r#"(func (export "run") (local $p i32) (local $v i32)"#);
+    let mut loops = vec![];
+    for node in &nodes {
+        match &node.op {
+            Op::Add(amount) => {
+                wasm.lines(&["local.get $p", "local.get $p", "i32.load8_u"]);
+                wasm.emitter.line(format!("i32.const {}", *amount as u8));
+                wasm.lines(&["i32.add", "i32.store8"]);
+            }
+            Op::Move(offset) => wasm.move_pointer(*offset),
+            Op::Clear => wasm.lines(&["local.get $p", "i32.const 0", "i32.store8"]),
+            Op::Transfer { targets, low, high } => {
+                wasm.lines(&["local.get $p", "i32.load8_u", "local.tee $v"]);
+                wasm.emitter.open("if");
+                wasm.reach(*low, *high);
+                for &(offset, factor) in targets {
+                    wasm.address(offset);
+                    wasm.address(offset);
+                    wasm.lines(&["i32.load8_u", "local.get $v"]);
+                    wasm.emitter.line(format!("i32.const {}", factor as u8));
+                    wasm.lines(&["i32.mul", "i32.add", "i32.store8"]);
+                }
+                wasm.lines(&["local.get $p", "i32.const 0", "i32.store8"]);
+                wasm.emitter.close("end");
+            }
+            Op::Scan(stride) => {
+                let label = wasm.open_loop();
+                wasm.move_pointer(*stride);
+                wasm.close_loop(label);
+            }
+            Op::Instruction(instruction) => match instruction.symbol {
+                _ if instruction.opens_loop() => loops.push(wasm.open_loop()),
+                _ if instruction.closes_loop() => {
+                    let label = loops.pop().expect("brackets are checked by nodes()");
+                    wasm.close_loop(label);
+                }
+                '.' => wasm.lines(&["local.get $p", "i32.load8_u", "call $write"]),
+                ',' => {
+                    wasm.lines(&["call $read", "local.tee $v", "i32.const 0", "i32.ge_s"]);
+                    wasm.emitter.open("if");
+                    wasm.lines(&["local.get $p", "local.get $v", "i32.store8"]);
+                    let eof: &[&str] = match options.eof {
+                        Eof::RequestInput => &["unreachable"],
+                        Eof::Zero => &["local.get $p", "i32.const 0", "i32.store8"],
+                        Eof::Unchanged => &[],
+                        Eof::Max => &["local.get $p", "i32.const 255", "i32.store8"],
+                    };
+                    if !eof.is_empty() {
+                        wasm.emitter.close("else");
+                        wasm.emitter.depth += 1;
+                        wasm.lines(eof);
+                    }
+                    wasm.emitter.close("end");
+                }
+                _ => {}
+            },
+        }
+    }
+    wasm.emitter.close(")");
+    wasm.emitter.close(")");
+
+    Ok(wasm.emitter.code)
+}
+
+struct Wasm {
+    emitter: Emitter,
+    labels: usize,
+}
+
+impl Wasm {
+    fn lines(&mut self, lines: &[&str]) {
+        for line in lines {
+            self.emitter.line(line);
+        }
+    }
+
+    fn address(&mut self, offset: isize) {
+        self.emitter.line("local.get $p");
+        self.emitter.line(format!("i32.const {offset}"));
+        self.emitter.line("i32.add");
+    }
+
+    fn reach(&mut self, low: isize, high: isize) {
+        self.emitter.line("local.get $p");
+        self.emitter.line(format!("i32.const {}", -low));
+        self.emitter.line(format!("i32.const {high}"));
+        self.emitter.line("call $reach");
+    }
+
+    fn move_pointer(&mut self, offset: isize) {
+        self.reach(std::cmp::min(offset, 0), std::cmp::max(offset, 0));
+        self.address(offset);
+        self.emitter.line("local.set $p");
+    }
+
+    /// Start a loop that runs while the current cell is non-zero
+    fn open_loop(&mut self) -> usize {
+        self.labels += 1;
+        let label = self.labels;
+        self.emitter.open(format!("block $break{label}"));
+        self.emitter.open(format!("loop $continue{label}"));
+        self.lines(&["local.get $p", "i32.load8_u", "i32.eqz"]);
+        self.emitter.line(format!("br_if $break{label}"));
+        label
+    }
+
+    fn close_loop(&mut self, label: usize) {
+        self.emitter.line(format!("br $continue{label}"));
+        self.emitter.close("end");
+        self.emitter.close("end");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+
+    fn wasm(code: &str, options: &WasmOptions) -> String {
+        to_wasm(&overflow::instruction_set().parse(code), options).unwrap()
+    }
+
+    #[test]
+    fn emits_balanced_blocks() {
+        let code = wasm("+[->+<]>[[-]<]>,.", &WasmOptions::default());
+
+        let opened = code.lines().filter(|line| {
+            let line = line.trim();
+            line.starts_with("block") || line.starts_with("loop") || line == "if"
+        });
+        let closed = code.lines().filter(|line| line.trim() == "end");
+        assert_eq!(opened.count(), closed.count());
+        assert_eq!(code.matches('(').count(), code.matches(')').count());
+    }
+
+    #[test]
+    fn sizes_memory_to_the_tape() {
+        let options = WasmOptions {
+            tape_size: 100_000,
+            eof: Eof::Zero,
+        };
+        let code = wasm(",.", &options);
+
+        assert!(code.contains(r#"(memory (export "memory") 2)"#));
+        assert!(code.contains("i32.const 100000"));
+        assert!(code.contains("else\n"));
+    }
+}
@@ -29,14 +29,13 @@ impl HelpItem<'_> {
 }
 
 pub fn render<B: Backend>(frame: &mut Frame<B>, area: Rect, mode: Mode) {
-    let height = 3;
-
     let title = format!(
         "Help ({})",
         match mode {
             Mode::Interactive => "interactive mode",
             Mode::Editor => "editor mode",
             Mode::Input => "input mode",
+            Mode::Search => "tape search",
         }
     );
 
@@ -47,8 +46,22 @@ pub fn render<B: Backend>(frame: &mut Frame<B>, area: Rect, mode: Mode) {
             HelpItem::new("space", "Play/Pause"),
             HelpItem::new("↓", "Step to Breakpoint"),
             HelpItem::new("↑", "Undo to Breakpoint"),
+            HelpItem::new(".", "Step to Output"),
+            HelpItem::new(",", "Undo to Output"),
+            HelpItem::new("w", "Step to Cell Change"),
+            HelpItem::new("W", "Undo to Cell Change"),
+            HelpItem::new("b", "Toggle Breakpoint"),
+            HelpItem::new("f", "Follow/Page Tape"),
+            HelpItem::new("p", "Pin/Unpin Cell"),
+            HelpItem::new("c", "Cell Format"),
+            HelpItem::new("l", "Enclosing Loop"),
+            HelpItem::new("%", "Matching Bracket"),
+            HelpItem::new("/", "Search Tape"),
             HelpItem::new("e", "Editor Mode"),
             HelpItem::new("x", "Reset"),
+            HelpItem::new("s", "Save Session"),
+            HelpItem::new("tab", "Next Program"),
+            HelpItem::new("o", "Overview"),
             HelpItem::new("esc/q", "Quit"),
         ],
         Mode::Editor => vec![
@@ -66,8 +79,33 @@ pub fn render<B: Backend>(frame: &mut Frame<B>, area: Rect, mode: Mode) {
             HelpItem::new("enter", "Submit"),
             HelpItem::new("shift+enter", "Newline"),
         ],
+        Mode::Search => vec![
+            HelpItem::new("enter", "Search"),
+            HelpItem::new("esc", "Cancel"),
+        ],
     };
 
+    render_items(frame, area, title, help_items);
+}
+
+pub fn render_overview<B: Backend>(frame: &mut Frame<B>, area: Rect) {
+    let help_items = vec![
+        HelpItem::new("↑↓", "Select Program"),
+        HelpItem::new("enter", "Open"),
+        HelpItem::new("q", "Quit"),
+    ];
+
+    render_items(frame, area, "Help (overview)".to_string(), help_items);
+}
+
+fn render_items<B: Backend>(
+    frame: &mut Frame<B>,
+    area: Rect,
+    title: String,
+    help_items: Vec<HelpItem>,
+) {
+    let height = 3;
+
     let (columns, widths): (Vec<_>, Vec<_>) = help_items
         .as_slice()
         .chunks(height)
@@ -1,18 +1,31 @@
 use tui::{
     backend::Backend,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     terminal::Frame,
     text::{Span, Spans, Text},
     widgets::{Block, Borders, Paragraph, Wrap},
 };
 
+use crate::output::OutputDecoder;
 use crate::program::{Mode, Program};
 
 const NEWLINE_COLOR: Color = Color::Rgb(80, 80, 80);
+const WATCH_ERROR_COLOR: Color = Color::Rgb(200, 80, 80);
+
+/// How many of the innermost enclosing loops the loop pane shows
+const SHOWN_LOOPS: usize = 4;
 
 pub fn render<B: Backend>(frame: &mut Frame<B>, area: Rect, program: &Program) {
-    let input_output_height = area.height - 6;
+    let loop_height = match program.engine.loop_stack().len().min(SHOWN_LOOPS) {
+        0 => 0,
+        n => n as u16 + 2,
+    };
+    let watch_height = match program.watches.len() {
+        0 => 0,
+        n => n as u16 + 2,
+    };
+    let input_output_height = area.height.saturating_sub(6 + loop_height + watch_height);
     let output_height = input_output_height / 2;
     let input_height = input_output_height - output_height;
 
@@ -23,6 +36,8 @@ pub fn render<B: Backend>(frame: &mut Frame<B>, area: Rect, program: &Program) {
             [
                 Constraint::Length(input_height),
                 Constraint::Length(output_height),
+                Constraint::Length(loop_height),
+                Constraint::Length(watch_height),
                 Constraint::Length(6),
             ]
             .as_ref(),
@@ -31,12 +46,17 @@ pub fn render<B: Backend>(frame: &mut Frame<B>, area: Rect, program: &Program) {
 
     render_output(frame, panel[0], program);
     render_input(frame, panel[1], program);
-    render_debug(frame, panel[2], program);
+    if loop_height > 0 {
+        render_loops(frame, panel[2], program);
+    }
+    if !program.watches.is_empty() {
+        render_watches(frame, panel[3], program);
+    }
+    render_debug(frame, panel[4], program);
 }
 
 /// Display Input/Output text
-fn io_text(buffer: &[u8]) -> Text {
-    let text = std::str::from_utf8(buffer).unwrap();
+fn io_text(text: &str) -> Text<'_> {
     let newlines = text.matches('\n').count();
     let lines = text
         .split('\n')
@@ -62,7 +82,8 @@ pub fn render_input<B: Backend>(frame: &mut Frame<B>, area: Rect, program: &Prog
         Mode::Input => &program.input_buffer,
         _ => &program.engine.input,
     };
-    let input = Paragraph::new(io_text(text))
+    let text = OutputDecoder::Utf8.decode_all(text);
+    let input = Paragraph::new(io_text(&text))
         .block(Block::default().title("Input").borders(Borders::ALL))
         .wrap(Wrap { trim: false });
 
@@ -70,13 +91,73 @@ pub fn render_input<B: Backend>(frame: &mut Frame<B>, area: Rect, program: &Prog
 }
 
 pub fn render_output<B: Backend>(frame: &mut Frame<B>, area: Rect, program: &Program) {
-    let output = Paragraph::new(io_text(&program.engine.output))
-        .block(Block::default().title("Output").borders(Borders::ALL))
+    // the output always reflects the current step, so make it obvious when
+    // that's in the past and later output is hidden
+    let title = match program.rewound_steps() {
+        0 => "Output".to_string(),
+        1 => "Output (1 step back)".to_string(),
+        n => format!("Output ({n} steps back)"),
+    };
+    // a character still being written shows once it's finished
+    let output = program.output_decoder.decode(&program.engine.output);
+    let output = Paragraph::new(io_text(&output.text))
+        .block(Block::default().title(title).borders(Borders::ALL))
         .wrap(Wrap { trim: false });
 
     frame.render_widget(output, area);
 }
 
+/// The loops around the current instruction, innermost first like a stack
+/// trace, with the one the editor's cursor is on highlighted
+fn render_loops<B: Backend>(frame: &mut Frame<B>, area: Rect, program: &Program) {
+    let lines = program
+        .engine
+        .loop_stack()
+        .iter()
+        .rev()
+        .take(SHOWN_LOOPS)
+        .map(|entered| {
+            let position = program.instruction_positions[entered.open_idx];
+            let text = format!(
+                "[ at {}:{} #{} of {}",
+                position.0 + 1,
+                position.1 + 1,
+                entered.iteration_count,
+                program.engine.loop_iterations(entered.open_idx)
+            );
+            let style = if position == program.editor.cursor {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            Spans::from(vec![Span::styled(text, style)])
+        })
+        .collect::<Vec<_>>();
+
+    let loops = Paragraph::new(lines).block(Block::default().title("Loops").borders(Borders::ALL));
+
+    frame.render_widget(loops, area);
+}
+
+fn render_watches<B: Backend>(frame: &mut Frame<B>, area: Rect, program: &Program) {
+    let lines = program
+        .watches
+        .evaluate(&program.engine)
+        .map(|(source, value)| match value {
+            Ok(value) => Spans::from(vec![Span::raw(format!("{source} = {value}"))]),
+            Err(message) => Spans::from(vec![Span::styled(
+                format!("{source}: {message}"),
+                Style::default().fg(WATCH_ERROR_COLOR),
+            )]),
+        })
+        .collect::<Vec<_>>();
+
+    let watches =
+        Paragraph::new(lines).block(Block::default().title("Watch").borders(Borders::ALL));
+
+    frame.render_widget(watches, area);
+}
+
 fn render_debug<B: Backend>(frame: &mut Frame<B>, area: Rect, program: &Program) {
     let lines = program
         .debug_messages
@@ -1,6 +1,7 @@
 mod editor;
 mod help;
 mod io;
+mod overview;
 mod tape;
 
 use tui::{
@@ -8,13 +9,17 @@ use tui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     terminal::Frame,
+    text::{Span, Spans},
     widgets::Paragraph,
 };
 
-use crate::program::Program;
+use crate::engine::InstructionPointer;
+use crate::program::{Mode, Program};
+use crate::tabs::Tabs;
 
-pub fn draw<B: Backend>(program: &Program, frame: &mut Frame<B>) {
+pub fn draw<B: Backend>(tabs: &Tabs, frame: &mut Frame<B>) {
     let size = frame.size();
+    let program = tabs.active();
 
     let window = Layout::default()
         .direction(Direction::Vertical)
@@ -23,8 +28,9 @@ pub fn draw<B: Backend>(program: &Program, frame: &mut Frame<B>) {
             [
                 Constraint::Length(1),
                 Constraint::Min(6),
+                Constraint::Length(tape::height(program)),
                 Constraint::Length(5),
-                Constraint::Length(5),
+                Constraint::Length(1),
             ]
             .as_ref(),
         )
@@ -36,14 +42,94 @@ pub fn draw<B: Backend>(program: &Program, frame: &mut Frame<B>) {
         .constraints([Constraint::Min(10), Constraint::Length(30)].as_ref())
         .split(window[1]);
 
-    render_filename(frame, window[0], program);
-    editor::render(frame, top_panel[0], program);
-    io::render(frame, top_panel[1], program);
-    tape::render(frame, window[2], program);
-    help::render(frame, window[3], program.mode);
+    render_tabs(frame, window[0], tabs);
+    if tabs.overview {
+        overview::render(frame, window[1], tabs);
+        tape::render(frame, window[2], program);
+        help::render_overview(frame, window[3]);
+    } else {
+        editor::render(frame, top_panel[0], program);
+        io::render(frame, top_panel[1], program);
+        tape::render(frame, window[2], program);
+        help::render(frame, window[3], program.mode);
+    }
+    render_status(frame, window[4], program);
+}
+
+fn render_tabs<B: Backend>(frame: &mut Frame<B>, area: Rect, tabs: &Tabs) {
+    let active_style = Style::default()
+        .bg(Color::Rgb(200, 200, 200))
+        .fg(Color::Rgb(50, 50, 50))
+        .add_modifier(Modifier::BOLD);
+    let inactive_style = Style::default()
+        .bg(Color::Rgb(100, 100, 100))
+        .fg(Color::Rgb(200, 200, 200));
+
+    let titles = tabs.programs.iter().enumerate().map(|(i, program)| {
+        let style = if i == tabs.active {
+            active_style
+        } else {
+            inactive_style
+        };
+        Span::styled(format!(" {} ", title(program)), style)
+    });
+    let titles = join(titles, Span::styled(" ", inactive_style));
+
+    let paragraph = Paragraph::new(Spans::from(titles))
+        .alignment(tui::layout::Alignment::Center)
+        .style(inactive_style);
+
+    frame.render_widget(paragraph, area);
+}
+
+/// The items with the separator between each pair of them
+fn join<T: Clone>(items: impl IntoIterator<Item = T>, separator: T) -> Vec<T> {
+    let mut joined = vec![];
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            joined.push(separator.clone());
+        }
+        joined.push(item);
+    }
+    joined
 }
 
-fn render_filename<B: Backend>(frame: &mut Frame<B>, area: Rect, program: &Program) {
+fn render_status<B: Backend>(frame: &mut Frame<B>, area: Rect, program: &Program) {
+    let engine = &program.engine;
+    let state = if program.playing {
+        "playing"
+    } else if program.is_input_mode() {
+        "waiting for input"
+    } else if engine.instruction_pointer == InstructionPointer::End {
+        "finished"
+    } else {
+        "paused"
+    };
+    let position = match program.cursor() {
+        Some((line, column)) => format!("{}:{}", line + 1, column + 1),
+        None => "-".to_string(),
+    };
+
+    let status = if program.mode == Mode::Search {
+        format!(" search tape: {}", program.search_buffer)
+    } else {
+        format!(
+            " {state} | step {} | at {position} | pointer {} | cell {}",
+            engine.history.len(),
+            engine.tape_pointer,
+            engine.peek()
+        )
+    };
+    let paragraph = Paragraph::new(Spans::from(status)).style(
+        Style::default()
+            .bg(Color::Rgb(100, 100, 100))
+            .fg(Color::Rgb(200, 200, 200)),
+    );
+
+    frame.render_widget(paragraph, area);
+}
+
+pub fn title(program: &Program) -> String {
     let filename = program
         .editor
         .filepath
@@ -57,14 +143,5 @@ fn render_filename<B: Backend>(frame: &mut Frame<B>, area: Rect, program: &Progr
         title.push('*');
     }
 
-    let paragraph = Paragraph::new(title)
-        .alignment(tui::layout::Alignment::Center)
-        .style(
-            Style::default()
-                .bg(Color::Rgb(200, 200, 200))
-                .fg(Color::Rgb(50, 50, 50))
-                .add_modifier(Modifier::BOLD),
-        );
-
-    frame.render_widget(paragraph, area);
+    title
 }
@@ -0,0 +1,78 @@
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Rect},
+    style::{Color, Modifier, Style},
+    terminal::Frame,
+    widgets::{Block, Borders, Cell, Row, Table},
+};
+
+use crate::engine::InstructionPointer;
+use crate::program::Mode;
+use crate::tabs::Tabs;
+use crate::ui::title;
+
+pub fn render<B: Backend>(frame: &mut Frame<B>, area: Rect, tabs: &Tabs) {
+    let header_style = Style::default().fg(Color::Rgb(150, 150, 150));
+    let active_style = Style::default()
+        .bg(Color::Rgb(75, 75, 75))
+        .add_modifier(Modifier::BOLD);
+
+    let header = Row::new(
+        [
+            "#", "Program", "Position", "Steps", "Pointer", "Output", "Mode",
+        ]
+        .into_iter()
+        .map(Cell::from),
+    )
+    .style(header_style);
+
+    let rows = tabs.programs.iter().enumerate().map(|(i, program)| {
+        let engine = &program.engine;
+        let position = match (engine.instruction_pointer, program.cursor()) {
+            (InstructionPointer::Start, _) => "start".to_string(),
+            (InstructionPointer::End, _) => "end".to_string(),
+            (_, Some((line, column))) => format!("{}:{}", line + 1, column + 1),
+            (_, None) => "-".to_string(),
+        };
+        let mode = match program.mode {
+            Mode::Interactive => "interactive",
+            Mode::Editor => "editor",
+            Mode::Input => "awaiting input",
+            Mode::Search => "searching tape",
+        };
+
+        let row = Row::new(vec![
+            Cell::from((i + 1).to_string()),
+            Cell::from(title(program)),
+            Cell::from(position),
+            Cell::from(engine.history.len().to_string()),
+            Cell::from(engine.tape_pointer.to_string()),
+            Cell::from(format!("{} bytes", engine.output.len())),
+            Cell::from(mode),
+        ]);
+
+        if i == tabs.active {
+            row.style(active_style)
+        } else {
+            row
+        }
+    });
+
+    let widths = [
+        Constraint::Length(3),
+        Constraint::Min(16),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(8),
+        Constraint::Length(12),
+        Constraint::Length(14),
+    ];
+
+    let table = Table::new(rows)
+        .header(header)
+        .block(Block::default().title("Overview").borders(Borders::ALL))
+        .widths(&widths)
+        .column_spacing(2);
+
+    frame.render_widget(table, area);
+}
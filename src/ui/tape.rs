@@ -1,66 +1,156 @@
 use num_integer::Integer;
+use std::cmp::max;
 use tui::{
     backend::Backend,
     layout::{Alignment, Rect},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     terminal::Frame,
     text::{Span, Spans},
     widgets::{Block, Borders, Paragraph},
 };
 
+use super::join;
+use crate::engine::Engine;
 use crate::program::Program;
+use crate::tape::CellFormat;
 
 const CELL_COLOR: Color = Color::Rgb(255, 255, 255);
 const INDEX_COLOR: Color = Color::Rgb(150, 150, 150);
 const EMPTY_COLOR: Color = Color::Rgb(80, 80, 80);
+const LABEL_COLOR: Color = Color::Rgb(120, 180, 255);
+
+/// How tall the tape pane needs to be, with room for any pinned cells
+pub fn height(program: &Program) -> u16 {
+    if program.tape_view.pinned.is_empty() {
+        5
+    } else {
+        7
+    }
+}
 
 pub fn render<B: Backend>(frame: &mut Frame<B>, area: Rect, program: &Program) {
+    let tape = &program.engine.tape;
     let tape_pointer = program.engine.tape_pointer;
-    let tape_length = program.engine.tape.len();
-    let tape_space = TapeSpace::new(frame.size().width as usize - 2, tape_pointer, tape_length);
+    let width = frame.size().width as usize - 2;
+    let format = program.tape_view.format;
+    let cell_width = format.width();
+
+    // a paged view only moves when the pointer leaves it, a page at a time
+    let center = if program.tape_view.paged {
+        let (left, right) = TapeSpace::halves(width, cell_width);
+        let (left_slots, right_slots) = TapeSpace::slots(left, right, cell_width + 1);
+        let page = left_slots + 1 + right_slots;
+        tape_pointer / page * page + left_slots
+    } else {
+        tape_pointer
+    };
+    let tape_space =
+        TapeSpace::with_cell_width(width, center, max(tape.len(), center + 1), cell_width);
     let right_slots = tape_space.used_right_slots + tape_space.unused_right_slots;
 
     let cell_style = Style::default().fg(CELL_COLOR);
+    let pointer_style = Style::default()
+        .fg(CELL_COLOR)
+        .add_modifier(Modifier::REVERSED);
     let index_style = Style::default().fg(INDEX_COLOR);
+    let label_style = Style::default().fg(LABEL_COLOR);
     let empty_style = Style::default().fg(EMPTY_COLOR);
 
-    let tape_iter = program.engine.tape.iter();
-    let mut cells = ["---"]
-        .repeat(tape_space.unused_left_slots)
-        .into_iter()
+    let window = center - tape_space.used_left_slots..center + 1 + right_slots;
+    let cell = |i: usize| {
+        let style = if i == tape_pointer {
+            pointer_style
+        } else {
+            cell_style
+        };
+        Span::styled(format.format(tape.get(i).copied().unwrap_or(0)), style)
+    };
+    // indexes are cut down to fit under their cells, and labelled cells
+    // show their label instead
+    let index = |i: usize| match label_text(&program.engine, i, cell_width) {
+        Some(text) => Span::styled(text, label_style),
+        None => {
+            let index = i % 10usize.pow(cell_width as u32);
+            Span::styled(format!("{index:0>cell_width$}"), index_style)
+        }
+    };
+    let empty = "-".repeat(cell_width);
+
+    let mut cells = std::iter::repeat_n(empty.clone(), tape_space.unused_left_slots)
         .map(|blob| Span::styled(blob, empty_style))
-        .chain(
-            tape_iter
-                .chain([&0u8].repeat(tape_space.unused_right_slots))
-                .skip(tape_pointer - tape_space.used_left_slots)
-                .take(tape_space.used_left_slots + 1 + right_slots)
-                .map(|cell| Span::styled(format!("{cell:0>3}"), cell_style)),
-        )
+        .chain(window.clone().map(cell))
         .collect::<Vec<Span>>();
 
-    let mut indexes = ["---"]
-        .repeat(tape_space.unused_left_slots)
-        .into_iter()
+    let mut indexes = std::iter::repeat_n(empty.clone(), tape_space.unused_left_slots)
         .map(|blob| Span::styled(blob, empty_style))
-        .chain(
-            (tape_pointer - tape_space.used_left_slots..tape_pointer + 1 + right_slots)
-                .map(|i| Span::styled(format!("{:0>3}", i % 1000), index_style)),
-        )
+        .chain(window.map(index))
         .collect::<Vec<Span>>();
 
-    let text = vec![
-        Spans::from("\u{25BC}"),
+    // the marker only lines up with the pointer when it's in the centre
+    let marker = if center == tape_pointer {
+        Spans::from("\u{25BC}")
+    } else {
+        Spans::from("")
+    };
+    let mut text = vec![
+        marker,
         join_tape_spans(cells.as_mut(), &tape_space),
         join_tape_spans(indexes.as_mut(), &tape_space),
     ];
 
+    if !program.tape_view.pinned.is_empty() {
+        let separator = Span::styled(" ... ", Style::default().fg(EMPTY_COLOR));
+        let pinned_row = |span: &dyn Fn(usize) -> Span<'static>| {
+            let ranges = program.tape_view.pinned.iter().map(|range| {
+                join(
+                    range.clone().map(span),
+                    Span::styled("|", Style::default().fg(EMPTY_COLOR)),
+                )
+            });
+            Spans::from(join(ranges, vec![separator.clone()]).concat())
+        };
+        text.push(pinned_row(&cell));
+        text.push(pinned_row(&index));
+    }
+
+    let title = match (program.tape_view.paged, format) {
+        (false, CellFormat::Decimal) => "Tape".to_string(),
+        (true, CellFormat::Decimal) => "Tape (paged)".to_string(),
+        (false, format) => format!("Tape ({})", format_name(format)),
+        (true, format) => format!("Tape (paged, {})", format_name(format)),
+    };
     let tape = Paragraph::new(text)
-        .block(Block::default().title("Tape").borders(Borders::ALL))
+        .block(Block::default().title(title).borders(Borders::ALL))
         .alignment(Alignment::Center);
 
     frame.render_widget(tape, area);
 }
 
+/// A label cut down to fit under a cell: the name under its first cell and
+/// the offset into it under the rest
+fn label_text(engine: &Engine, index: usize, width: usize) -> Option<String> {
+    let label = engine.label_at(index)?;
+    let text = match index - label.cells.start {
+        0 => label.name.clone(),
+        offset => format!("+{offset}"),
+    };
+    // the tape is cut down by bytes at its edges, so keep to ASCII
+    let text = text
+        .chars()
+        .map(|c| if c.is_ascii() { c } else { '?' })
+        .collect::<String>();
+    Some(format!("{text:<width$.width$}"))
+}
+
+fn format_name(format: CellFormat) -> &'static str {
+    match format {
+        CellFormat::Decimal => "decimal",
+        CellFormat::Hex => "hex",
+        CellFormat::Ascii => "ascii",
+        CellFormat::Binary => "binary",
+    }
+}
+
 struct TapeSpace {
     used_left_slots: usize,
     unused_left_slots: usize,
@@ -72,11 +162,37 @@ struct TapeSpace {
 }
 
 impl TapeSpace {
-    fn new(width: usize, tape_pointer: usize, tape_length: usize) -> TapeSpace {
-        let available = width - 3;
+    /// The widths either side of the centre cell
+    fn halves(width: usize, cell_width: usize) -> (usize, usize) {
+        let available = width - cell_width;
         let (half, remainder) = (available / 2, available % 2);
-        let (left, right) = (half + remainder, half);
-        let (left_slots, right_slots) = (left.div_ceil(&4), right.div_ceil(&4));
+        (half + remainder, half)
+    }
+
+    /// How many cells fit in each half, counting any cut off at the edge
+    fn slots(left: usize, right: usize, slot_width: usize) -> (usize, usize) {
+        // called as a function, as method syntax picks `usize`'s own
+        // `div_ceil`, which takes its divisor by value
+        (
+            Integer::div_ceil(&left, &slot_width),
+            Integer::div_ceil(&right, &slot_width),
+        )
+    }
+
+    #[cfg(test)]
+    fn new(width: usize, tape_pointer: usize, tape_length: usize) -> TapeSpace {
+        TapeSpace::with_cell_width(width, tape_pointer, tape_length, 3)
+    }
+
+    fn with_cell_width(
+        width: usize,
+        tape_pointer: usize,
+        tape_length: usize,
+        cell_width: usize,
+    ) -> TapeSpace {
+        let (left, right) = Self::halves(width, cell_width);
+        let slot_width = cell_width + 1;
+        let (left_slots, right_slots) = Self::slots(left, right, slot_width);
 
         let used_left_slots = std::cmp::min(left_slots, tape_pointer);
         let unused_left_slots = left_slots - used_left_slots;
@@ -84,7 +200,10 @@ impl TapeSpace {
         let used_right_slots = std::cmp::min(right_slots, tape_length - tape_pointer - 1);
         let unused_right_slots = right_slots - used_right_slots;
 
-        let (left_overflow, right_overflow) = ((left_slots * 4) - left, (right_slots * 4) - right);
+        let (left_overflow, right_overflow) = (
+            (left_slots * slot_width) - left,
+            (right_slots * slot_width) - right,
+        );
 
         TapeSpace {
             used_left_slots,
@@ -98,24 +217,21 @@ impl TapeSpace {
     }
 }
 
-fn join_tape_spans<'a>(spans: &mut Vec<Span<'a>>, tape_space: &TapeSpace) -> Spans<'a> {
+fn join_tape_spans<'a>(spans: &mut [Span<'a>], tape_space: &TapeSpace) -> Spans<'a> {
     let len = spans.len();
 
     // remove any overflow from the first and last elements
-    spans[0].content = spans[0].content[..3 - tape_space.left_overflow]
+    spans[0].content = spans[0].content[..spans[0].content.len() - tape_space.left_overflow]
         .to_string()
         .into();
     spans[len - 1].content = spans[len - 1].content[tape_space.right_overflow..]
         .to_string()
         .into();
 
-    let joined = spans
-        .clone()
-        .into_iter()
-        .intersperse(Span::styled("|", Style::default().fg(EMPTY_COLOR)))
-        .collect::<Vec<Span>>();
-
-    Spans::from(joined)
+    Spans::from(join(
+        spans.iter().cloned(),
+        Span::styled("|", Style::default().fg(EMPTY_COLOR)),
+    ))
 }
 
 #[cfg(test)]
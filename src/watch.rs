@@ -0,0 +1,346 @@
+use crate::engine::Engine;
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A small expression over an engine's state, such as `tape[3]*256 + tape[4]`.
+///
+/// Names are `ptr`, `cell`, `steps`, `out.len` and `in.len`, along with any
+/// labelled cells, and `tape[i]` or `label[i]` index into the tape or a
+/// labelled range. Comparisons are 1 when true and 0 when false.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Expr {
+    Number(i64),
+    Name(String),
+    Index(String, Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 15] = [
+    "==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "/", "%", "(", ")", "[", "]",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut rest = source.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let number = rest[..end]
+                .parse()
+                .map_err(|_| format!("number too large: {}", &rest[..end]))?;
+            tokens.push(Token::Number(number));
+            rest = &rest[end..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Name(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else {
+            let symbol = SYMBOLS
+                .into_iter()
+                .find(|symbol| rest.starts_with(symbol))
+                .ok_or_else(|| format!("unexpected {c}"))?;
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        let matches = matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(format!("expected {symbol}"))
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.sum()?;
+        let ops = [
+            ("==", BinaryOp::Eq),
+            ("!=", BinaryOp::Ne),
+            ("<=", BinaryOp::Le),
+            (">=", BinaryOp::Ge),
+            ("<", BinaryOp::Lt),
+            (">", BinaryOp::Gt),
+        ];
+        for (symbol, op) in ops {
+            if self.eat(symbol) {
+                let right = self.sum()?;
+                return Ok(Expr::Binary(op, Box::new(left), Box::new(right)));
+            }
+        }
+        Ok(left)
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut left = self.product()?;
+        loop {
+            let op = if self.eat("+") {
+                BinaryOp::Add
+            } else if self.eat("-") {
+                BinaryOp::Sub
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Binary(op, Box::new(left), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        loop {
+            let op = if self.eat("*") {
+                BinaryOp::Mul
+            } else if self.eat("/") {
+                BinaryOp::Div
+            } else if self.eat("%") {
+                BinaryOp::Rem
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(Expr::Number(number)),
+            Some(Token::Name(name)) => {
+                if self.eat("[") {
+                    let index = self.comparison()?;
+                    self.expect("]")?;
+                    Ok(Expr::Index(name, Box::new(index)))
+                } else {
+                    Ok(Expr::Name(name))
+                }
+            }
+            Some(Token::Symbol("(")) => {
+                let expr = self.comparison()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Symbol(symbol)) => Err(format!("unexpected {symbol}")),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Expr, String> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+        };
+        let expr = parser.comparison()?;
+        match parser.next() {
+            None => Ok(expr),
+            Some(Token::Number(number)) => Err(format!("unexpected {number}")),
+            Some(Token::Name(name)) => Err(format!("unexpected {name}")),
+            Some(Token::Symbol(symbol)) => Err(format!("unexpected {symbol}")),
+        }
+    }
+
+    pub fn eval(&self, engine: &Engine) -> Result<i64, String> {
+        let cell = |index: i64| -> Result<i64, String> {
+            let index = usize::try_from(index).map_err(|_| format!("no cell {index}"))?;
+            Ok(engine.tape.get(index).copied().unwrap_or(0).into())
+        };
+
+        match self {
+            Expr::Number(number) => Ok(*number),
+            Expr::Name(name) => match name.as_str() {
+                "ptr" => Ok(engine.tape_pointer as i64),
+                "cell" => Ok(engine.peek().into()),
+                "steps" => Ok(engine.history.len() as i64),
+                "out.len" => Ok(engine.output.len() as i64),
+                "in.len" => Ok(engine.input.len() as i64),
+                _ => match engine.labelled(name) {
+                    Some(cells) => cell(cells.start as i64),
+                    None => Err(format!("unknown name {name}")),
+                },
+            },
+            Expr::Index(name, index) => {
+                let index = index.eval(engine)?;
+                if name == "tape" {
+                    return cell(index);
+                }
+                let cells = engine
+                    .labelled(name)
+                    .ok_or_else(|| format!("unknown name {name}"))?;
+                match usize::try_from(index) {
+                    Ok(offset) if offset < cells.len() => cell((cells.start + offset) as i64),
+                    _ => Err(format!("{name}[{index}] is outside {name}")),
+                }
+            }
+            Expr::Negate(expr) => expr
+                .eval(engine)?
+                .checked_neg()
+                .ok_or_else(|| "overflow".to_string()),
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (left.eval(engine)?, right.eval(engine)?);
+                let result = match op {
+                    BinaryOp::Add => left.checked_add(right),
+                    BinaryOp::Sub => left.checked_sub(right),
+                    BinaryOp::Mul => left.checked_mul(right),
+                    BinaryOp::Div if right == 0 => return Err("division by zero".to_string()),
+                    BinaryOp::Div => left.checked_div(right),
+                    BinaryOp::Rem if right == 0 => return Err("division by zero".to_string()),
+                    BinaryOp::Rem => left.checked_rem(right),
+                    BinaryOp::Eq => Some((left == right).into()),
+                    BinaryOp::Ne => Some((left != right).into()),
+                    BinaryOp::Lt => Some((left < right).into()),
+                    BinaryOp::Le => Some((left <= right).into()),
+                    BinaryOp::Gt => Some((left > right).into()),
+                    BinaryOp::Ge => Some((left >= right).into()),
+                };
+                result.ok_or_else(|| "overflow".to_string())
+            }
+        }
+    }
+}
+
+/// Expressions to evaluate each time the engine stops, kept with the source
+/// they were written as
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Watches {
+    pub watches: Vec<(String, Expr)>,
+}
+
+impl Watches {
+    pub fn add(&mut self, source: &str) -> Result<(), String> {
+        let expr = Expr::parse(source)?;
+        self.watches.push((source.trim().to_string(), expr));
+        Ok(())
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.watches.len() {
+            self.watches.remove(index);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.watches.len()
+    }
+
+    /// Each watch's source with its value, or why it has none
+    pub fn evaluate<'a>(
+        &'a self,
+        engine: &'a Engine,
+    ) -> impl Iterator<Item = (&'a str, Result<i64, String>)> + 'a {
+        self.watches
+            .iter()
+            .map(move |(source, expr)| (source.as_str(), expr.eval(engine)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine() -> Engine {
+        let mut engine = Engine::new(vec![]);
+        engine.tape = vec![0, 0, 0, 1, 2, 7].into();
+        engine.tape_pointer = 5;
+        engine.output = b"hi".to_vec();
+        engine.label_cell(5, "counter");
+        engine.label_range(3..5, "pair");
+        engine
+    }
+
+    fn eval(source: &str) -> Result<i64, String> {
+        Expr::parse(source)?.eval(&engine())
+    }
+
+    #[test]
+    fn expressions_follow_precedence() {
+        assert_eq!(eval("tape[3]*256 + tape[4]"), Ok(258));
+        assert_eq!(eval("-(1 + 2) * 3 % 4"), Ok(-1));
+        assert_eq!(eval("ptr == 5"), Ok(1));
+        assert_eq!(eval("out.len + cell"), Ok(9));
+    }
+
+    #[test]
+    fn labels_name_cells() {
+        assert_eq!(eval("counter"), Ok(7));
+        assert_eq!(eval("pair[1] - pair"), Ok(1));
+        assert_eq!(eval("pair[2]"), Err("pair[2] is outside pair".to_string()));
+    }
+
+    #[test]
+    fn bad_expressions_are_errors() {
+        assert!(Expr::parse("1 +").is_err());
+        assert!(Expr::parse("tape[1").is_err());
+        assert!(Expr::parse("1 2").is_err());
+        assert_eq!(eval("1 / (ptr - 5)"), Err("division by zero".to_string()));
+        assert_eq!(eval("nothing"), Err("unknown name nothing".to_string()));
+    }
+}
@@ -0,0 +1,205 @@
+use crate::engine::run::{StopReason, Stops};
+use crate::engine::{Engine, Exception, InstructionPointer};
+use crate::flavor::{overflow, Eof};
+
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Status {
+    Running,
+    Breakpoint,
+    RequestingInput,
+    Finished,
+}
+
+/// An engine running the overflow flavor, with breakpoints at instruction
+/// indexes, for driving from JavaScript.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct Debugger {
+    engine: Engine,
+    stops: Stops,
+}
+
+#[wasm_bindgen]
+impl Debugger {
+    #[wasm_bindgen(constructor)]
+    pub fn new(code: &str) -> Debugger {
+        Debugger::with_eof(code, Eof::default())
+    }
+
+    /// Like the constructor, but with input instructions storing zero at the
+    /// end of input instead of waiting for more
+    #[wasm_bindgen(js_name = withZeroEof)]
+    pub fn with_zero_eof(code: &str) -> Debugger {
+        Debugger::with_eof(code, Eof::Zero)
+    }
+
+    pub fn step(&mut self) -> Result<Status, JsError> {
+        self.advance().map_err(|message| JsError::new(&message))
+    }
+
+    pub fn undo(&mut self) -> Result<(), JsError> {
+        match self.engine.undo() {
+            Ok(()) | Err(Exception::Breakpoint) => Ok(()),
+            Err(Exception::RequestingInput) => Ok(()),
+            Err(Exception::Error(error)) => Err(JsError::new(&error.message())),
+        }
+    }
+
+    /// Step until the program finishes, stops or has taken `max_steps` steps
+    pub fn run(&mut self, max_steps: usize) -> Result<Status, JsError> {
+        self.run_steps(max_steps)
+            .map_err(|message| JsError::new(&message))
+    }
+
+    /// Start over, at the first instruction as when the debugger was made
+    pub fn reset(&mut self) {
+        self.engine.reset();
+        self.engine.next_instruction().ok();
+    }
+
+    /// Start over with new input, keeping the breakpoints
+    #[wasm_bindgen(js_name = restartWithInput)]
+    pub fn restart_with_input(&mut self, input: &[u8]) {
+        self.engine.restart_with_input(input.to_vec());
+        self.engine.next_instruction().ok();
+    }
+
+    #[wasm_bindgen(js_name = setBreakpoint)]
+    pub fn set_breakpoint(&mut self, index: usize) {
+        self.stops.breakpoints.insert(index);
+    }
+
+    #[wasm_bindgen(js_name = clearBreakpoint)]
+    pub fn clear_breakpoint(&mut self, index: usize) {
+        self.stops.breakpoints.remove(&index);
+    }
+
+    pub fn breakpoints(&self) -> Vec<u32> {
+        self.stops.breakpoints.iter().map(|&i| i as u32).collect()
+    }
+
+    pub fn tape(&self) -> Vec<u8> {
+        self.engine.tape.to_vec()
+    }
+
+    /// The cells up to `radius` either side of `center`, for drawing part of
+    /// a long tape without copying all of it
+    #[wasm_bindgen(js_name = tapeWindow)]
+    pub fn tape_window(&self, center: usize, radius: usize) -> Vec<u8> {
+        let window = self.engine.tape_window(center, radius);
+        window.iter().map(|(_, cell)| cell).collect()
+    }
+
+    #[wasm_bindgen(js_name = tapePointer)]
+    pub fn tape_pointer(&self) -> usize {
+        self.engine.tape_pointer
+    }
+
+    /// The index of the next instruction to run, if there is one
+    #[wasm_bindgen(js_name = instructionPointer)]
+    pub fn instruction_pointer(&self) -> Option<usize> {
+        match self.engine.instruction_pointer {
+            InstructionPointer::Index(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    pub fn steps(&self) -> usize {
+        self.engine.history.len()
+    }
+
+    pub fn output(&self) -> Vec<u8> {
+        self.engine.output.clone()
+    }
+
+    #[wasm_bindgen(js_name = pushInput)]
+    pub fn push_input(&mut self, input: &[u8]) {
+        self.engine.input.extend_from_slice(input);
+    }
+}
+
+impl Debugger {
+    fn with_eof(code: &str, eof: Eof) -> Debugger {
+        let mut engine = Engine::new(overflow::instruction_set_with(eof).parse(code));
+        engine.next_instruction().ok();
+        Debugger {
+            engine,
+            stops: Stops::default(),
+        }
+    }
+
+    fn advance(&mut self) -> Result<Status, String> {
+        if self.engine.instruction_pointer == InstructionPointer::End {
+            return Ok(Status::Finished);
+        }
+
+        match self.engine.step() {
+            Ok(()) if self.engine.instruction_pointer == InstructionPointer::End => {
+                Ok(Status::Finished)
+            }
+            Ok(()) => Ok(Status::Running),
+            Err(Exception::Breakpoint) => Ok(Status::Breakpoint),
+            Err(Exception::RequestingInput) => Ok(Status::RequestingInput),
+            Err(Exception::Error(error)) => Err(error.message()),
+        }
+    }
+
+    fn run_steps(&mut self, max_steps: usize) -> Result<Status, String> {
+        self.stops.fuel = Some(max_steps);
+        match self.engine.continue_(&self.stops) {
+            StopReason::Completed => Ok(Status::Finished),
+            StopReason::Breakpoint(_) => Ok(Status::Breakpoint),
+            StopReason::InputRequested => Ok(Status::RequestingInput),
+            StopReason::Watchpoint(_) | StopReason::FuelExhausted | StopReason::TimedOut => {
+                Ok(Status::Running)
+            }
+            StopReason::InfiniteLoopDetected => Err("the program is stuck in a loop".to_string()),
+            StopReason::Error(error) => Err(error.message()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_stops_at_breakpoints() {
+        let mut debugger = Debugger::new("+++.>+.");
+        debugger.set_breakpoint(4);
+
+        assert_eq!(debugger.run_steps(100), Ok(Status::Breakpoint));
+        assert_eq!(debugger.instruction_pointer(), Some(4));
+        assert_eq!(debugger.output(), vec![3]);
+
+        assert_eq!(debugger.run_steps(100), Ok(Status::Finished));
+        assert_eq!(debugger.output(), vec![3, 1]);
+    }
+
+    #[test]
+    fn reset_steps_from_the_first_instruction() {
+        let mut debugger = Debugger::new("+.");
+        assert_eq!(debugger.run_steps(100), Ok(Status::Finished));
+
+        debugger.reset();
+        assert_eq!(debugger.instruction_pointer(), Some(0));
+        assert_eq!(debugger.advance(), Ok(Status::Running));
+        assert_eq!(debugger.instruction_pointer(), Some(1));
+
+        debugger.restart_with_input(&[]);
+        assert_eq!(debugger.instruction_pointer(), Some(0));
+    }
+
+    #[test]
+    fn run_stops_for_input() {
+        let mut debugger = Debugger::new(",.");
+        assert_eq!(debugger.run_steps(100), Ok(Status::RequestingInput));
+
+        debugger.push_input(b"x");
+        assert_eq!(debugger.run_steps(100), Ok(Status::Finished));
+        assert_eq!(debugger.output(), b"x".to_vec());
+    }
+}
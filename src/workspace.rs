@@ -0,0 +1,320 @@
+use crate::engine::diff::EngineDiff;
+use crate::engine::run::{StopReason, Stops};
+use crate::engine::{Engine, EngineResult, Exception, InstructionPointer};
+
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Several engines under names, such as a program and an optimized rewrite
+/// of it, to be stepped together and compared. One of them is current, for
+/// frontends that show one at a time.
+#[derive(Clone, Debug, Default)]
+pub struct Workspace {
+    engines: Vec<(String, Engine)>,
+    current: usize,
+}
+
+impl Workspace {
+    pub fn new() -> Workspace {
+        Workspace::default()
+    }
+
+    /// Add an engine, replacing any with the same name
+    pub fn load<S: Into<String>>(&mut self, name: S, engine: Engine) {
+        let name = name.into();
+        match self.engines.iter_mut().find(|(loaded, _)| *loaded == name) {
+            Some((_, loaded)) => *loaded = engine,
+            None => self.engines.push((name, engine)),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Engine> {
+        let index = self.index(name).ok()?;
+        let (_, engine) = self.engines.remove(index);
+        if self.current > index || self.current == self.engines.len() {
+            self.current = self.current.saturating_sub(1);
+        }
+        Some(engine)
+    }
+
+    pub fn switch(&mut self, name: &str) -> Result<(), String> {
+        self.current = self.index(name)?;
+        Ok(())
+    }
+
+    /// The name and engine of the current one, if there are any
+    pub fn current(&self) -> Option<(&str, &Engine)> {
+        let (name, engine) = self.engines.get(self.current)?;
+        Some((name, engine))
+    }
+
+    pub fn current_mut(&mut self) -> Option<&mut Engine> {
+        let (_, engine) = self.engines.get_mut(self.current)?;
+        Some(engine)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Engine> {
+        let index = self.index(name).ok()?;
+        Some(&self.engines[index].1)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Engine> {
+        let index = self.index(name).ok()?;
+        Some(&mut self.engines[index].1)
+    }
+
+    /// The names of the engines, in the order they were loaded
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.engines.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Step every engine that hasn't finished once, with what each step did
+    /// by name
+    pub fn step_all(&mut self) -> Vec<(&str, EngineResult)> {
+        self.engines
+            .iter_mut()
+            .filter(|(_, engine)| engine.instruction_pointer != InstructionPointer::End)
+            .map(|(name, engine)| (name.as_str(), engine.step()))
+            .collect()
+    }
+
+    /// Run every engine until it stops, with why each stopped by name
+    pub fn run_all(&mut self, stops: &Stops) -> Vec<(&str, StopReason)> {
+        self.engines
+            .iter_mut()
+            .map(|(name, engine)| (name.as_str(), engine.run(stops)))
+            .collect()
+    }
+
+    /// What differs between two engines' tapes, pointers and output
+    pub fn compare(&self, a: &str, b: &str) -> Result<EngineDiff, String> {
+        let a = &self.engines[self.index(a)?].1;
+        let b = &self.engines[self.index(b)?].1;
+        Ok(a.diff(b))
+    }
+
+    /// Two engines to step together, looking for where they differ
+    pub fn lockstep(&mut self, a: &str, b: &str) -> Result<Lockstep<'_>, String> {
+        let (a_index, b_index) = (self.index(a)?, self.index(b)?);
+        if a_index == b_index {
+            return Err(format!("can't run {a} alongside itself"));
+        }
+        let (low, high) = self.engines.split_at_mut(a_index.max(b_index));
+        let (first, second) = (&mut low[a_index.min(b_index)], &mut high[0]);
+        let ((a_name, a), (b_name, b)) = if a_index < b_index {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        Ok(Lockstep {
+            names: (a_name, b_name),
+            engines: (a, b),
+            cells: BTreeSet::new(),
+            steps: 0,
+        })
+    }
+
+    fn index(&self, name: &str) -> Result<usize, String> {
+        self.engines
+            .iter()
+            .position(|(loaded, _)| loaded == name)
+            .ok_or_else(|| format!("no program named {name}"))
+    }
+}
+
+/// What made two engines run in lockstep differ
+#[derive(Debug, Eq, PartialEq)]
+pub enum DivergenceKind {
+    /// Their output differs from this offset on
+    Output { offset: usize },
+    /// One of the cells being compared differs
+    Cell(usize),
+    /// The named engine couldn't step, such as for an error or for input,
+    /// while the other could
+    Stopped { name: String, exception: Exception },
+}
+
+/// Where two engines run in lockstep first differed, with how the second's
+/// state differed from the first's then
+#[derive(Debug, Eq, PartialEq)]
+pub struct Divergence {
+    /// Steps taken together, counting the one they differed after
+    pub step: usize,
+    pub kind: DivergenceKind,
+    pub diff: EngineDiff,
+}
+
+/// Two engines from a workspace stepped together, as a program and a
+/// rewrite of it should keep writing the same output. A rewrite needn't take
+/// the same steps, so output only differs once the bytes both have written
+/// do, and the engine that finishes first waits for the other.
+#[derive(Debug)]
+pub struct Lockstep<'a> {
+    names: (&'a str, &'a str),
+    engines: (&'a mut Engine, &'a mut Engine),
+    cells: BTreeSet<usize>,
+    steps: usize,
+}
+
+impl Lockstep<'_> {
+    /// Also stop when these cells differ between the two after a step
+    pub fn compare_cells<I: IntoIterator<Item = usize>>(mut self, cells: I) -> Self {
+        self.cells.extend(cells);
+        self
+    }
+
+    pub fn engines(&self) -> (&Engine, &Engine) {
+        (self.engines.0, self.engines.1)
+    }
+
+    /// Step each engine that hasn't finished once, and whether that made
+    /// them differ. Once both have finished they're compared one last time,
+    /// when all of their output has to match.
+    pub fn step(&mut self) -> Option<Divergence> {
+        let (a, b) = (&mut *self.engines.0, &mut *self.engines.1);
+        let finished = |engine: &Engine| engine.instruction_pointer == InstructionPointer::End;
+        let stepped = [(&mut *a, self.names.0), (&mut *b, self.names.1)]
+            .into_iter()
+            .filter(|(engine, _)| !finished(engine))
+            .map(|(engine, name)| (engine.step(), name))
+            .find_map(|(result, name)| match result {
+                Ok(()) | Err(Exception::Breakpoint) => None,
+                Err(exception) => Some(DivergenceKind::Stopped {
+                    name: String::from(name),
+                    exception,
+                }),
+            });
+        self.steps += 1;
+
+        let common = a.output.len().min(b.output.len());
+        let output_offset = (a.output.iter().zip(&b.output))
+            .position(|(a, b)| a != b)
+            .or_else(|| {
+                let unmatched = finished(a) && finished(b) && a.output.len() != b.output.len();
+                unmatched.then_some(common)
+            });
+        let cell = |engine: &Engine, index: usize| engine.tape.get(index).copied().unwrap_or(0);
+
+        let kind = stepped
+            .or_else(|| output_offset.map(|offset| DivergenceKind::Output { offset }))
+            .or_else(|| {
+                (self.cells.iter())
+                    .find(|&&index| cell(a, index) != cell(b, index))
+                    .map(|&index| DivergenceKind::Cell(index))
+            })?;
+        Some(Divergence {
+            step: self.steps,
+            kind,
+            diff: a.diff(b),
+        })
+    }
+
+    /// Step both engines until they differ, or `None` if both finish alike
+    pub fn run_until_divergence(&mut self) -> Option<Divergence> {
+        let finished = |engine: &Engine| engine.instruction_pointer == InstructionPointer::End;
+        while !(finished(self.engines.0) && finished(self.engines.1)) {
+            if let Some(divergence) = self.step() {
+                return Some(divergence);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flavor::overflow;
+    use crate::ir;
+
+    fn engine(code: &str) -> Engine {
+        Engine::new(overflow::instruction_set().parse(code))
+    }
+
+    #[test]
+    fn engines_are_found_by_name() {
+        let mut workspace = Workspace::new();
+        workspace.load("first", engine("+"));
+        workspace.load("second", engine("++"));
+        workspace.load("first", engine("+++"));
+
+        assert_eq!(workspace.names().collect::<Vec<_>>(), ["first", "second"]);
+        assert_eq!(workspace.current().map(|(name, _)| name), Some("first"));
+        workspace.switch("second").unwrap();
+        assert_eq!(workspace.current_mut().unwrap().instructions.len(), 2);
+        assert_eq!(
+            workspace.switch("third"),
+            Err(String::from("no program named third"))
+        );
+
+        assert!(workspace.remove("first").is_some());
+        assert_eq!(workspace.current().map(|(name, _)| name), Some("second"));
+        assert_eq!(workspace.get("first"), None);
+    }
+
+    #[test]
+    fn engines_run_side_by_side() {
+        let mut workspace = Workspace::new();
+        let slow = engine("++[->+<]");
+        let fast = Engine::new(ir::compile(&slow.instructions).instructions);
+        workspace.load("slow", slow);
+        workspace.load("fast", fast);
+
+        assert_eq!(workspace.step_all().len(), 2);
+        let stops = workspace.run_all(&Stops::default());
+        assert!(stops.iter().all(|(_, stop)| *stop == StopReason::Completed));
+        assert!(workspace.compare("slow", "fast").unwrap().is_empty());
+    }
+
+    #[test]
+    fn lockstep_runs_stop_where_output_differs() {
+        let mut workspace = Workspace::new();
+        workspace.load("reference", engine("++[->+<]>.+."));
+        workspace.load("rewrite", engine("++[->+<]>.$++."));
+        let rewrite = &workspace.get("rewrite").unwrap().instructions;
+        workspace.load("compiled", Engine::new(ir::compile(rewrite).instructions));
+
+        let divergence = workspace
+            .lockstep("reference", "compiled")
+            .unwrap()
+            .run_until_divergence()
+            .unwrap();
+        assert_eq!(divergence.kind, DivergenceKind::Output { offset: 1 });
+        assert_eq!(divergence.diff.output, vec![4]);
+        assert_eq!(divergence.diff.output_removed, 1);
+
+        workspace.load("reference", engine("++[->+<]>.+."));
+        workspace.load("copy", engine("++[->+<]>.+."));
+        let mut lockstep = workspace.lockstep("copy", "reference").unwrap();
+        assert_eq!(lockstep.run_until_divergence(), None);
+        assert_eq!(lockstep.engines().0.output, vec![2, 3]);
+        assert!(workspace.lockstep("copy", "copy").is_err());
+    }
+
+    #[test]
+    fn lockstep_runs_compare_chosen_cells() {
+        let mut workspace = Workspace::new();
+        workspace.load("a", engine(">+>++"));
+        workspace.load("b", engine(">+>+"));
+        workspace.load("c", engine(",+"));
+
+        let mut lockstep = workspace.lockstep("a", "b").unwrap().compare_cells([2]);
+        let divergence = lockstep.run_until_divergence().unwrap();
+        assert_eq!(divergence.kind, DivergenceKind::Cell(2));
+        assert_eq!(divergence.step, 6);
+        assert_eq!(divergence.diff.cells, vec![(2, 2, 1)]);
+
+        let divergence = workspace.lockstep("a", "c").unwrap().step();
+        assert_eq!(divergence, None);
+        let divergence = workspace.lockstep("a", "c").unwrap().step().unwrap();
+        assert_eq!(
+            divergence.kind,
+            DivergenceKind::Stopped {
+                name: String::from("c"),
+                exception: Exception::RequestingInput
+            }
+        );
+    }
+}
@@ -0,0 +1,470 @@
+//! The command line subcommands available whenever the binary is built
+
+#![cfg(feature = "cli")]
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn program(name: &str, code: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("plaque-{}-{name}", std::process::id()));
+    std::fs::write(&path, code).unwrap();
+    path
+}
+
+fn plaque(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_plaque"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn check_reports_warnings() {
+    let path = program("check.bf", "+\n[]");
+    let output = plaque(&["check", path.to_str().unwrap()], b"");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(":2:1: warning: empty loop"), "{stdout}");
+}
+
+#[test]
+fn deep_check_finds_dead_loops_after_loops() {
+    let path = program("deep.bf", "++[>+<-]\n>>[.]");
+    let quick = plaque(&["check", path.to_str().unwrap()], b"");
+    let deep = plaque(&["check", "--deep", path.to_str().unwrap()], b"");
+
+    let quick = String::from_utf8(quick.stdout).unwrap();
+    let deep = String::from_utf8(deep.stdout).unwrap();
+    assert!(!quick.contains("always zero"), "{quick}");
+    assert!(deep.contains(":2:3: warning: loop starts"), "{deep}");
+}
+
+#[test]
+fn bench_reports_each_backend() {
+    let path = program("bench.bf", "++++++++[>++++++++<-]>[-]");
+    let output = plaque(&["bench", path.to_str().unwrap(), "--runs", "2"], b"");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("interpret      2"), "{stdout}");
+    assert!(stdout.contains("optimized      2"), "{stdout}");
+
+    let output = plaque(
+        &[
+            "bench",
+            path.to_str().unwrap(),
+            "--backend",
+            "interpret",
+            "--steps",
+            "5",
+        ],
+        b"",
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("(step budget reached)"), "{stdout}");
+    assert!(!stdout.contains("optimized"), "{stdout}");
+}
+
+#[test]
+fn fmt_rewrites_the_program() {
+    let path = program("fmt.bf", "print an exclamation mark\n+++++[>++++++<-]>+++.");
+    let output = plaque(&["fmt", path.to_str().unwrap(), "--style", "minify"], b"");
+    assert_eq!(output.stdout, b"+++++[>++++++<-]>+++.\n".to_vec());
+
+    let output = plaque(&["fmt", path.to_str().unwrap(), "--write"], b"");
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "+++++\n[\n  >++++++<-\n]\n>+++.\n"
+    );
+}
+
+#[test]
+fn stat_describes_the_program() {
+    let path = program("stat.bf", "++[>+++[-]<-]");
+    let output = plaque(&["stat", path.to_str().unwrap()], b"");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("loops: 2 (nested up to 2 deep)"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("+           5            3"), "{stdout}");
+}
+
+#[test]
+fn gen_text_prints_a_program_printing_the_text() {
+    let output = plaque(&["gen-text", "--width", "20"], b"Hello!\n");
+    assert!(output.status.success());
+    let code = String::from_utf8(output.stdout).unwrap();
+    assert!(code.lines().all(|line| line.len() <= 20), "{code}");
+
+    let path = program("gen-text.bf", &code);
+    let output = plaque(&["run", path.to_str().unwrap()], b"");
+    assert_eq!(output.stdout, b"Hello!\n".to_vec());
+}
+
+#[test]
+fn macros_expand_and_point_back_to_their_invocation() {
+    let path = program(
+        "macros.bf",
+        "@def zero [-]\n@inc(3)[>@inc(11)<-]>.\n@zero@zero",
+    );
+    let output = plaque(&["run", path.to_str().unwrap(), "--macros"], b"");
+    assert_eq!(output.stdout, b"!".to_vec());
+
+    let output = plaque(&["check", "--macros", path.to_str().unwrap()], b"");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains(":3:6: warning: loop starts at a cell that is always zero"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn includes_report_warnings_in_the_included_file() {
+    let lib = program("include-lib.bf", "@def zero [-]\n@zero@zero");
+    let name = lib.file_name().unwrap().to_str().unwrap();
+    let path = program(
+        "include.bf",
+        &format!("@include \"{name}\"\n@inc(33).@zero"),
+    );
+    let output = plaque(&["run", path.to_str().unwrap(), "--macros"], b"");
+    assert_eq!(output.stdout, b"!".to_vec());
+
+    let output = plaque(&["check", "--macros", path.to_str().unwrap()], b"");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains(&format!("{name}:2:6: warning: loop starts at")),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn bisect_shrinks_failing_input() {
+    let path = program("bisect.bf", ">>>,[<,]");
+    let failing = program("bisect.fail", "abcdef");
+    let passing = program("bisect.pass", "abc");
+    let output = plaque(
+        &[
+            "bisect",
+            path.to_str().unwrap(),
+            "--fail",
+            failing.to_str().unwrap(),
+            "--pass",
+            passing.to_str().unwrap(),
+        ],
+        b"",
+    );
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("minimal failing input (4 bytes)"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn cfg_prints_dot() {
+    let path = program("cfg.bf", "+[-]");
+    let output = plaque(&["cfg", path.to_str().unwrap()], b"");
+
+    assert!(output.status.success());
+    let dot = String::from_utf8(output.stdout).unwrap();
+    assert!(dot.starts_with("digraph cfg {"));
+    assert!(dot.contains("n1 -> n2 [label=\"not 0\"];"));
+}
+
+#[test]
+fn recorded_runs_replay_to_any_step() {
+    let path = program("record.bf", ",[.-]");
+    let trace = path.with_extension("plq");
+    let output = plaque(
+        &[
+            "record",
+            path.to_str().unwrap(),
+            "-o",
+            trace.to_str().unwrap(),
+        ],
+        b"\x02",
+    );
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"\x02\x01".to_vec());
+
+    let output = plaque(&["replay", trace.to_str().unwrap(), "--step", "3"], b"");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("step 3 of 8"));
+    assert!(stdout.contains("next instruction: 3 -"));
+    assert!(stdout.contains("output: \"\\u{2}\""));
+
+    let output = plaque(&["replay", trace.to_str().unwrap(), "--step", "9"], b"");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn replays_export_loops_as_chrome_traces() {
+    let path = program("profile.bf", "++[>+<-]");
+    let trace = path.with_extension("plq");
+    let json = path.with_extension("json");
+    plaque(
+        &[
+            "record",
+            path.to_str().unwrap(),
+            "-o",
+            trace.to_str().unwrap(),
+        ],
+        b"",
+    );
+
+    let output = plaque(
+        &[
+            "replay",
+            trace.to_str().unwrap(),
+            "--chrome-trace",
+            json.to_str().unwrap(),
+        ],
+        b"",
+    );
+    assert!(output.status.success());
+    let json = std::fs::read_to_string(json).unwrap();
+    assert!(json.contains(r#""name":"loop 2..7""#));
+}
+
+#[test]
+fn run_executes_to_completion() {
+    let path = program("run.bf", "+++++++[>++++++++++<-]>++.,.");
+    let output = plaque(&["run", path.to_str().unwrap()], b"i");
+
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"Hi".to_vec());
+}
+
+#[test]
+fn run_takes_input_from_a_source() {
+    let path = program("input.bf", ",.,.,.");
+    let output = plaque(
+        &["run", path.to_str().unwrap(), "--input", "hex:48 69"],
+        b"",
+    );
+    assert_eq!(output.stdout, b"Hi".to_vec());
+
+    let output = plaque(&["run", path.to_str().unwrap(), "--input", "cycle:ab"], b"");
+    assert_eq!(output.stdout, b"aba".to_vec());
+}
+
+#[test]
+fn run_reports_coverage() {
+    let path = program("coverage.bf", "+.-\n[->+<]");
+    let output = plaque(&["run", path.to_str().unwrap(), "--coverage"], b"");
+
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"\x01".to_vec());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("+.-\n[->+<]\n ^^^^^\n"));
+    assert!(stderr.contains("coverage: 4 of 9 instructions ran"));
+}
+
+#[test]
+fn run_checks_assertions() {
+    let path = program("assert.bf", "++ {assert cell==2} > {assert cell[0]>1} .");
+    let output = plaque(&["run", path.to_str().unwrap(), "--assertions"], b"");
+    assert!(output.status.success());
+
+    let path = program("assert_fails.bf", "+ {assert cell == 'A'}");
+    let output = plaque(&["run", path.to_str().unwrap(), "--assertions"], b"");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("assertion cell == 'A' failed, it was 1"));
+
+    let output = plaque(&["run", path.to_str().unwrap()], b"");
+    assert!(output.status.success());
+}
+
+#[test]
+fn run_starts_with_a_tape_image() {
+    let path = program("image.bf", "[.>]");
+    let image = program("image.bin", "hi!");
+    let output = plaque(
+        &[
+            "run",
+            path.to_str().unwrap(),
+            "--tape",
+            image.to_str().unwrap(),
+        ],
+        b"",
+    );
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"hi!");
+}
+
+#[test]
+fn run_stops_at_unexpected_output() {
+    let path = program("expect.bf", "+++++++[>++++++++++<-]>++.+.");
+    let right = program("expect.right", "HI");
+    let wrong = program("expect.wrong", "Hi");
+
+    let output = plaque(
+        &[
+            "run",
+            path.to_str().unwrap(),
+            "--expect-output",
+            right.to_str().unwrap(),
+        ],
+        b"",
+    );
+    assert!(output.status.success());
+
+    let output = plaque(
+        &[
+            "run",
+            path.to_str().unwrap(),
+            "--expect-output",
+            wrong.to_str().unwrap(),
+        ],
+        b"",
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("byte 1 of output is 'I', expected 'i', written at step"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("by instruction 27"), "{stderr}");
+}
+
+#[test]
+fn script_fails_on_unmet_expectations() {
+    let path = program("script.bf", ",.,.");
+    let passing = program("script.pass", "run\nexpect output \"ok\"; print steps");
+    let failing = program("script.fail", "run\nexpect output \"no\"");
+
+    let output = plaque(
+        &["script", path.to_str().unwrap(), passing.to_str().unwrap()],
+        b"ok",
+    );
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"steps = 4\n".to_vec());
+
+    let output = plaque(
+        &["script", path.to_str().unwrap(), failing.to_str().unwrap()],
+        b"ok",
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("line 2: expected output"), "{stderr}");
+}
+
+#[cfg(not(feature = "tui"))]
+#[test]
+fn debugger_needs_the_tui_feature() {
+    let output = plaque(&[], b"");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("`tui` feature"), "{stderr}");
+}
+
+#[test]
+fn dump_prints_the_tape_like_xxd() {
+    let path = program("dump.bf", ",>,>+++");
+    let output = plaque(&["dump", path.to_str().unwrap()], b"Hi");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("00000000: 48 69 03 "));
+    assert!(stdout.ends_with("  Hi.\n"));
+
+    let raw = std::env::temp_dir().join(format!("plaque-{}-dump.bin", std::process::id()));
+    let output = plaque(
+        &[
+            "dump",
+            path.to_str().unwrap(),
+            "--range",
+            "1..4",
+            "--raw",
+            raw.to_str().unwrap(),
+        ],
+        b"Hi",
+    );
+    assert!(output.status.success());
+    assert_eq!(std::fs::read(&raw).unwrap(), [b'i', 3, 0]);
+}
+
+#[test]
+fn test_grades_programs_against_a_suite() {
+    let echo = program("echo.bf", ",[.,]");
+    let shout = program("shout.bf", ",[--------------------------------.,]");
+    let suite = program(
+        "suite.toml",
+        &format!(
+            "program = \"plaque-{}-echo.bf\"\neof = \"zero\"\n\n\
+             [[case]]\nname = \"word\"\ninput = \"hi\"\noutput = \"hi\"\n",
+            std::process::id()
+        ),
+    );
+    let output = plaque(&["test", suite.to_str().unwrap()], b"");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("pass  word"), "{stdout}");
+    assert!(stdout.ends_with("1 of 1 passed\n"), "{stdout}");
+
+    let suite = suite.to_str().unwrap();
+    let programs = [echo.to_str().unwrap(), shout.to_str().unwrap()];
+    let output = plaque(&["test", suite, programs[0], programs[1], "--json"], b"");
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\"passed\":1,\"failed\":0"), "{stdout}");
+    assert!(stdout.contains("\"verdict\":\"wrong-output\""), "{stdout}");
+    assert!(stdout.contains("\"output\":\"HI\""), "{stdout}");
+}
+
+#[test]
+fn inspect_shows_where_a_program_stopped() {
+    let path = program("inspect.bf", "++.>+$-");
+    let output = plaque(&["inspect", path.to_str().unwrap()], b"");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.starts_with("stopped at the breakpoint at 5, at instruction 6 (-)"),
+        "{stdout}"
+    );
+
+    let output = plaque(&["inspect", path.to_str().unwrap(), "--json"], b"");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\"tape_pointer\":1,"), "{stdout}");
+    assert!(stdout.ends_with("\"stop\":{\"reason\":\"breakpoint\",\"instruction\":5}}\n"));
+}
+
+#[test]
+fn run_streams_events_as_ndjson() {
+    let path = program("events.bf", ",.");
+    let events = std::env::temp_dir().join(format!("plaque-{}-events", std::process::id()));
+    let output = plaque(
+        &[
+            "run",
+            path.to_str().unwrap(),
+            "--events",
+            "ndjson",
+            "--events-to",
+            events.to_str().unwrap(),
+        ],
+        b"A",
+    );
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"A");
+    let events = std::fs::read_to_string(&events).unwrap();
+    let kinds = events
+        .lines()
+        .map(|line| line.split('"').nth(3).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(kinds, ["input", "step", "step", "output", "finished"]);
+}
@@ -0,0 +1,66 @@
+//! The Debug Adapter Protocol server of `plaque dap`
+
+#![cfg(feature = "server")]
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn message(seq: usize, command: &str, arguments: &str) -> String {
+    let body = format!(
+        r#"{{"seq": {seq}, "type": "request", "command": "{command}", "arguments": {arguments}}}"#
+    );
+    format!("Content-Length: {}\r\n\r\n{body}", body.len())
+}
+
+#[test]
+fn dap_stops_on_breakpoints_inside_loops() {
+    let path = std::env::temp_dir().join(format!("plaque-{}-dap.bf", std::process::id()));
+    std::fs::write(&path, "++[\n->+<\n]>.").unwrap();
+    let program = path.to_str().unwrap();
+
+    let requests = [
+        message(1, "initialize", "{}"),
+        message(2, "launch", &format!(r#"{{"program": "{program}"}}"#)),
+        message(
+            3,
+            "setBreakpoints",
+            &format!(r#"{{"source": {{"path": "{program}"}}, "breakpoints": [{{"line": 2}}]}}"#),
+        ),
+        message(4, "configurationDone", "{}"),
+        message(5, "stackTrace", r#"{"threadId": 1}"#),
+        message(6, "continue", r#"{"threadId": 1}"#),
+        message(7, "variables", r#"{"variablesReference": 1}"#),
+        message(8, "stepBack", r#"{"threadId": 1}"#),
+        message(9, "disconnect", "{}"),
+    ];
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_plaque"))
+        .arg("dap")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(requests.concat().as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("Content-Length: "), "{stdout}");
+    assert_eq!(
+        stdout.matches(r#""reason":"breakpoint""#).count(),
+        2,
+        "{stdout}"
+    );
+    assert!(stdout.contains(r#""name":"loop at 1:3""#), "{stdout}");
+    assert!(
+        stdout.contains(r#"{"name":"[1]","value":"1","variablesReference":0}"#),
+        "{stdout}"
+    );
+    assert!(stdout.contains(r#""reason":"step""#), "{stdout}");
+    assert!(!stdout.contains(r#""success":false"#), "{stdout}");
+}
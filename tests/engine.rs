@@ -0,0 +1,97 @@
+//! The engine-only public API, which builds under every feature combination
+
+use plaque::analysis::{sanity_warnings, WarningKind};
+use plaque::bisect::{bisect, Budget};
+use plaque::engine::multi::MultiEngine;
+use plaque::engine::{Engine, Exception, InstructionPointer};
+use plaque::flavor::{brainfork, overflow, Eof};
+use plaque::instruction::InstructionSet;
+use plaque::optimize::run_length_encode;
+
+const HELLO: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+fn run(engine: &mut Engine) {
+    while engine.instruction_pointer != InstructionPointer::End {
+        engine.step().unwrap();
+    }
+}
+
+#[test]
+fn runs_and_rewinds_a_program() {
+    let mut engine = Engine::new(overflow::instruction_set().parse(HELLO));
+    run(&mut engine);
+    assert_eq!(engine.output, b"Hello World!\n".to_vec());
+
+    while engine.undo().is_ok() {}
+    assert!(engine.output.is_empty());
+    assert!(engine.tape.iter().all(|&cell| cell == 0));
+}
+
+#[test]
+fn custom_instructions_extend_a_flavor() {
+    let mut instruction_set = overflow::instruction_set();
+    instruction_set.register(
+        '~',
+        |engine| {
+            engine.map_cell(|cell| !cell);
+            engine.next_instruction()
+        },
+        |engine| {
+            engine.map_cell(|cell| !cell);
+            engine.prev_instruction()
+        },
+    )
+    .unwrap();
+
+    let mut engine = Engine::new(instruction_set.parse("+~."));
+    run(&mut engine);
+    assert_eq!(engine.output, vec![254]);
+}
+
+#[test]
+fn eof_policy_is_configurable() {
+    let mut engine = Engine::new(overflow::instruction_set_with(Eof::Max).parse(",."));
+    run(&mut engine);
+    assert_eq!(engine.output, vec![255]);
+
+    let mut engine = Engine::new(overflow::instruction_set().parse(",."));
+    engine.next_instruction().unwrap();
+    assert_eq!(engine.step(), Err(Exception::RequestingInput));
+}
+
+#[test]
+fn optimized_programs_match_the_original() {
+    let instructions = overflow::instruction_set().parse(HELLO);
+    let mut engine = Engine::new(run_length_encode(&instructions).instructions);
+    run(&mut engine);
+    assert_eq!(engine.output, b"Hello World!\n".to_vec());
+}
+
+#[test]
+fn forked_threads_share_output() {
+    let engine = Engine::new(brainfork::instruction_set().parse("Y+."));
+    let mut multi = MultiEngine::new(engine);
+    multi.run().unwrap();
+    assert_eq!(multi.threads.len(), 2);
+    assert_eq!(multi.output.len(), 2);
+}
+
+#[test]
+fn analyses_run_on_parsed_programs() {
+    let instructions = overflow::instruction_set().parse("[-]+[]");
+    let kinds = sanity_warnings(&instructions, None)
+        .into_iter()
+        .map(|warning| warning.kind)
+        .collect::<Vec<_>>();
+    assert_eq!(kinds, vec![WarningKind::DeadLoop, WarningKind::EmptyLoop]);
+
+    let instructions = overflow::instruction_set().parse(">>>,[<,]");
+    let bisection = bisect(&instructions, b"abcdef", b"abc", Budget::default()).unwrap();
+    assert_eq!(bisection.input.len(), 4);
+}
+
+#[test]
+fn instruction_sets_can_start_empty() {
+    let instruction_set = InstructionSet::new();
+    assert!(instruction_set.parse(HELLO).is_empty());
+}
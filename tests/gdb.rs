@@ -0,0 +1,72 @@
+//! The gdb remote serial protocol stub of `plaque gdb`
+
+#![cfg(feature = "server")]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Command, Stdio};
+
+fn exchange(stream: &mut TcpStream, packet: &str) -> String {
+    let checksum = packet.bytes().fold(0u8, u8::wrapping_add);
+    write!(stream, "${packet}#{checksum:02x}").unwrap();
+
+    let mut acknowledgement = [0];
+    stream.read_exact(&mut acknowledgement).unwrap();
+    assert_eq!(acknowledgement[0], b'+');
+
+    let mut reply = vec![];
+    let mut byte = [0];
+    loop {
+        stream.read_exact(&mut byte).unwrap();
+        if byte[0] == b'#' {
+            break;
+        }
+        reply.push(byte[0]);
+    }
+    stream.read_exact(&mut [0; 2]).unwrap();
+    stream.write_all(b"+").unwrap();
+
+    String::from_utf8(reply)
+        .unwrap()
+        .trim_start_matches('$')
+        .to_string()
+}
+
+#[test]
+fn gdb_stops_on_breakpoints_and_reads_the_tape() {
+    let path = std::env::temp_dir().join(format!("plaque-{}-gdb.bf", std::process::id()));
+    std::fs::write(&path, "++>+++.<.").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_plaque"))
+        .args(["gdb", path.to_str().unwrap(), "--listen", "127.0.0.1:0"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // the stub's address is announced on stderr
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut announcement = String::new();
+    stderr.read_line(&mut announcement).unwrap();
+    let address = announcement.trim().rsplit(' ').next().unwrap().to_string();
+    let mut stream = TcpStream::connect(address).unwrap();
+
+    assert_eq!(exchange(&mut stream, "?"), "S05");
+    assert_eq!(exchange(&mut stream, "Z0,6,1"), "OK");
+    assert_eq!(exchange(&mut stream, "c"), "S05");
+    assert_eq!(
+        exchange(&mut stream, "g"),
+        "06000000000000000100000000000000"
+    );
+    assert_eq!(exchange(&mut stream, "m0,3"), "020300");
+    assert_eq!(exchange(&mut stream, "bs"), "S05");
+    assert_eq!(exchange(&mut stream, "p0"), "0500000000000000");
+    assert_eq!(exchange(&mut stream, "c"), "S05");
+    assert_eq!(exchange(&mut stream, "c"), "W00");
+    drop(stream);
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, vec![3, 2]);
+}
@@ -0,0 +1,70 @@
+//! The Jupyter kernel of `plaque kernel`
+
+#![cfg(feature = "server")]
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn message(body: &str) -> String {
+    format!("Content-Length: {}\r\n\r\n{body}", body.len())
+}
+
+#[test]
+fn kernels_run_cells_on_one_tape() {
+    let messages = [
+        message(r#"{"id": 1, "code": "%input text:A\n,+."}"#),
+        message(r#"{"id": 2, "code": "%step 2\n>+<"}"#),
+        message(r#"{"id": 3, "code": ",."}"#),
+        message(r#"{"input": "z"}"#),
+        message(r#"{"id": 4, "code": "%tape 1"}"#),
+        message(r#"{"id": 5, "code": "+["}"#),
+    ];
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_plaque"))
+        .args(["kernel", "serve"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(messages.concat().as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(r#""stream":"B""#), "{stdout}");
+    assert!(
+        stdout.contains("at instruction 2 (<), step 2, tape pointer 1"),
+        "{stdout}"
+    );
+    assert!(stdout.contains(r#""input_requested":true"#), "{stdout}");
+    assert!(stdout.contains(r#""stream":"z""#), "{stdout}");
+    assert!(stdout.contains("42 7A 00"), "{stdout}");
+    assert!(
+        stdout.contains("no bracket matching the one at instruction 1"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn install_writes_a_kernel_spec() {
+    let prefix = std::env::temp_dir().join(format!("plaque-{}-jupyter", std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_plaque"))
+        .args(["kernel", "install", "--prefix"])
+        .arg(&prefix)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let kernel = prefix.join("share/jupyter/kernels/plaque");
+    let spec: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(kernel.join("kernel.json")).unwrap()).unwrap();
+    assert_eq!(spec["language"].as_str(), Some("brainfuck"));
+    assert_eq!(spec["argv"][5].as_str(), Some("{connection_file}"));
+    assert!(kernel.join("plaque_kernel.py").exists());
+    std::fs::remove_dir_all(prefix).unwrap();
+}
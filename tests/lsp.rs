@@ -0,0 +1,67 @@
+//! The Language Server Protocol server of `plaque lsp`
+
+#![cfg(feature = "server")]
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn message(id: Option<usize>, method: &str, params: &str) -> String {
+    let id = id.map(|id| format!(r#""id": {id}, "#)).unwrap_or_default();
+    let body = format!(r#"{{"jsonrpc": "2.0", {id}"method": "{method}", "params": {params}}}"#);
+    format!("Content-Length: {}\r\n\r\n{body}", body.len())
+}
+
+#[test]
+fn lsp_reports_brackets_and_loops() {
+    let document = r#"{"uri": "file:///loops.bf"}"#;
+    let messages = [
+        message(Some(1), "initialize", "{}"),
+        message(
+            None,
+            "textDocument/didOpen",
+            r#"{"textDocument": {"uri": "file:///loops.bf", "text": "[-]+[\n>]]"}}"#,
+        ),
+        message(
+            Some(2),
+            "textDocument/hover",
+            &format!(
+                r#"{{"textDocument": {document}, "position": {{"line": 1, "character": 0}}}}"#
+            ),
+        ),
+        message(
+            Some(3),
+            "textDocument/definition",
+            &format!(
+                r#"{{"textDocument": {document}, "position": {{"line": 1, "character": 1}}}}"#
+            ),
+        ),
+        message(Some(4), "shutdown", "null"),
+        message(None, "exit", "null"),
+    ];
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_plaque"))
+        .arg("lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(messages.concat().as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(r#""message":"unmatched ]""#), "{stdout}");
+    assert!(stdout.contains(r#""severity":4"#), "{stdout}");
+    assert!(stdout.contains("loop depth 1"), "{stdout}");
+    assert!(
+        stdout.contains(
+            r#""range":{"end":{"character":5,"line":0},"start":{"character":4,"line":0}},"uri":"file:///loops.bf""#
+        ),
+        "{stdout}"
+    );
+}
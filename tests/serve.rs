@@ -0,0 +1,52 @@
+//! The HTTP playground API of `plaque serve`
+
+#![cfg(feature = "server")]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Command, Stdio};
+
+fn request(address: &str, method: &str, path: &str, body: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: {address}\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )
+    .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+    response
+}
+
+#[test]
+fn serve_steps_and_runs_loaded_programs() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_plaque"))
+        .args(["serve", "--listen", "127.0.0.1:0"])
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // the address is announced on stderr
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut announcement = String::new();
+    stderr.read_line(&mut announcement).unwrap();
+    let address = announcement.trim().rsplit("//").next().unwrap().to_string();
+
+    request(&address, "POST", "/load", "+,.");
+    let response = request(&address, "POST", "/step?count=2", "");
+    assert!(response.contains(r#""tape":[1]"#), "{response}");
+
+    let response = request(&address, "POST", "/run", "");
+    assert!(response.contains(r#""stopped":"input""#), "{response}");
+
+    request(&address, "POST", "/input", "A");
+    let response = request(&address, "POST", "/run", "");
+    assert!(response.contains(r#""output":"A""#), "{response}");
+    assert!(response.contains(r#""finished":true"#), "{response}");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
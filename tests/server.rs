@@ -0,0 +1,48 @@
+//! The JSON-RPC control socket of `plaque attach-run`
+
+#![cfg(feature = "server")]
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::process::{Command, Stdio};
+
+#[test]
+fn attach_run_filters_stdin_to_stdout() {
+    let path = std::env::temp_dir().join(format!("plaque-{}-cat.bf", std::process::id()));
+    std::fs::write(&path, ",[.,]").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_plaque"))
+        .args(["attach-run", path.to_str().unwrap()])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // the control socket's address is announced on stderr
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut announcement = String::new();
+    stderr.read_line(&mut announcement).unwrap();
+    let address = announcement.trim().rsplit(' ').next().unwrap().to_string();
+
+    let stream = TcpStream::connect(address).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+    writeln!(
+        writer,
+        r#"{{"jsonrpc": "2.0", "id": 1, "method": "state"}}"#
+    )
+    .unwrap();
+    let mut response = String::new();
+    reader.read_line(&mut response).unwrap();
+    assert!(response.contains(r#""id":1"#), "{response}");
+    assert!(response.contains(r#""paused":false"#), "{response}");
+    drop(writer);
+    drop(reader);
+
+    child.stdin.take().unwrap().write_all(b"hello").unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"hello".to_vec());
+}